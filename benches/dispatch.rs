@@ -0,0 +1,193 @@
+// Compares the cost of decoding and executing instructions through the precomputed
+// `OpObjectFactory` table against the previous dispatch path, which for every executed byte
+// decoded the mode flags, scanned the list of operations for the one matching the byte and
+// boxed the result as a `dyn Instruction`.
+//
+// Run with `cargo bench --bench dispatch`
+
+use std::hint;
+use std::time::{Duration, Instant};
+
+use rusty_uxn::instruction::{Instruction, InstructionFactory};
+use rusty_uxn::ops::{OpObject, OpObjectFactory};
+use rusty_uxn::uxninterface::{Uxn, UxnError, UxnWithDevices};
+
+// minimal machine with no devices, enough to execute the benchmark program
+struct BenchUxn {
+    ram: Vec<u8>,
+    program_counter: u16,
+    working_stack: Vec<u8>,
+    return_stack: Vec<u8>,
+}
+
+impl BenchUxn {
+    fn new(program: &[u8]) -> Self {
+        let mut ram = vec![0; 0x10000];
+        ram[0x100..0x100 + program.len()].copy_from_slice(program);
+        BenchUxn {
+            ram,
+            program_counter: 0x100,
+            working_stack: Vec::with_capacity(0x100),
+            return_stack: Vec::with_capacity(0x100),
+        }
+    }
+}
+
+impl Uxn for BenchUxn {
+    fn read_next_byte_from_ram(&mut self) -> Result<u8, UxnError> {
+        let ret = self.ram[usize::from(self.program_counter)];
+        self.program_counter = self.program_counter.wrapping_add(1);
+        return Ok(ret);
+    }
+
+    fn read_from_ram(&self, addr: u16) -> u8 {
+        return self.ram[usize::from(addr)];
+    }
+
+    fn write_to_ram(&mut self, addr: u16, val: u8) {
+        self.ram[usize::from(addr)] = val;
+    }
+
+    fn get_program_counter(&self) -> Result<u16, UxnError> {
+        return Ok(self.program_counter);
+    }
+
+    fn set_program_counter(&mut self, addr: u16) {
+        self.program_counter = addr;
+    }
+
+    fn push_to_return_stack(&mut self, byte: u8) -> Result<(), UxnError> {
+        self.return_stack.push(byte);
+        return Ok(());
+    }
+
+    fn push_to_working_stack(&mut self, byte: u8) -> Result<(), UxnError> {
+        self.working_stack.push(byte);
+        return Ok(());
+    }
+
+    fn pop_from_working_stack(&mut self) -> Result<u8, UxnError> {
+        return self.working_stack.pop().ok_or(UxnError::StackUnderflow);
+    }
+
+    fn pop_from_return_stack(&mut self) -> Result<u8, UxnError> {
+        return self.return_stack.pop().ok_or(UxnError::StackUnderflow);
+    }
+}
+
+impl UxnWithDevices for BenchUxn {
+    fn read_from_device(&mut self, _device_address: u8) -> Result<u8, UxnError> {
+        return Ok(0);
+    }
+
+    fn write_to_device(&mut self, _device_address: u8, _val: u8) -> Result<(), UxnError> {
+        return Ok(());
+    }
+}
+
+// the bytes of the operations, in the order of the list the previous dispatch path scanned
+const LEGACY_OP_BYTES: [u8; 32] = [
+    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07,
+    0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f,
+    0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17,
+    0x18, 0x19, 0x1a, 0x1b, 0x1c, 0x1d, 0x1e, 0x1f,
+];
+
+// decodes every byte into a heap allocated instruction, as the previous dispatch path did
+struct BoxedInstruction(Box<dyn Instruction>);
+
+impl Instruction for BoxedInstruction {
+    fn execute(&self, uxn: &mut dyn UxnWithDevices) -> Result<(), UxnError> {
+        return self.0.execute(uxn);
+    }
+}
+
+struct BoxedOpObjectFactory {}
+
+impl InstructionFactory for BoxedOpObjectFactory {
+    type Instr = BoxedInstruction;
+
+    fn from_byte(&self, byte: u8) -> BoxedInstruction {
+        let keep = byte & 0b10000000 > 0;
+        let ret = byte & 0b01000000 > 0;
+        let short = byte & 0b00100000 > 0;
+
+        let index = LEGACY_OP_BYTES.iter().position(
+            |b| *b == byte & 0x1f)
+            .expect("No matching operation for byte");
+
+        // the fields of an `OpObject` are private, so the decoded instruction is rebuilt from
+        // what was found, which costs a copy out of the table on top of the previous path
+        let byte = LEGACY_OP_BYTES[hint::black_box(index)]
+            | if keep { 0b10000000 } else { 0 }
+            | if ret { 0b01000000 } else { 0 }
+            | if short { 0b00100000 } else { 0 };
+
+        return BoxedInstruction(Box::new(OpObject::from_byte(byte)));
+    }
+}
+
+// counts from 0000 to ffff, stashing a copy of the counter on the return stack on every
+// iteration:
+//
+// |100
+// #0000
+// &loop
+//     INC2 DUP2 STH2 POP2r
+//     DUP2 #ffff NEQ2 ,&loop JCN
+// BRK
+const PROGRAM: [u8; 15] = [
+    0xa0, 0x00, 0x00,
+    0x21, 0x26, 0x2f, 0x62,
+    0x26, 0xa0, 0xff, 0xff, 0x29, 0x80, 0xf4, 0x0d,
+];
+
+// executes PROGRAM to completion, returning how long it took and how many
+// instructions were executed
+fn run_program<J: InstructionFactory>(instruction_factory: &J) -> (Duration, u64) {
+    let mut uxn = BenchUxn::new(&PROGRAM);
+    let mut instruction_count = 0u64;
+
+    let now = Instant::now();
+    loop {
+        let instr = uxn.read_next_byte_from_ram().unwrap();
+        if instr == 0x0 {
+            break;
+        }
+
+        let op = instruction_factory.from_byte(instr);
+        op.execute(&mut uxn).unwrap();
+        instruction_count += 1;
+    }
+    let elapsed = now.elapsed();
+
+    assert_eq!(uxn.working_stack, vec![0xff, 0xff]);
+    return (elapsed, instruction_count);
+}
+
+fn bench<J: InstructionFactory>(name: &str, instruction_factory: &J, iterations: u32) -> f64 {
+    // warm up
+    run_program(instruction_factory);
+
+    let mut total_time = Duration::default();
+    let mut total_instructions = 0;
+    for _ in 0..iterations {
+        let (time, instructions) = run_program(instruction_factory);
+        total_time += time;
+        total_instructions += instructions;
+    }
+
+    let ns_per_instruction = total_time.as_nanos() as f64 / total_instructions as f64;
+    println!("{:<10} {:>10} instructions in {:>10.2?} ({:.2} ns/instruction)",
+        name, total_instructions, total_time, ns_per_instruction);
+    return ns_per_instruction;
+}
+
+fn main() {
+    let iterations = 20;
+
+    let boxed = bench("boxed", &BoxedOpObjectFactory{}, iterations);
+    let table = bench("table", &OpObjectFactory{}, iterations);
+
+    println!("speedup: {:.2}x", boxed / table);
+}
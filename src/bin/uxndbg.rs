@@ -0,0 +1,17 @@
+use clap::Parser;
+use std::io;
+
+fn main() {
+    let args = rusty_uxn::emulators::uxndbglib::Cli::parse();
+    let other_config = rusty_uxn::emulators::uxndbglib::Config{
+        stdout_writer: io::stdout(),
+        command_reader: io::stdin().lock(),
+        stderr_writer: io::stderr(),
+        debug_writer: io::stderr(),
+        output_writer: io::stdout()};
+
+    if let Err(e) = rusty_uxn::emulators::uxndbglib::run(args, other_config) {
+        println!("{}", e);
+        std::process::exit(1);
+    }
+}
@@ -1,8 +1,15 @@
 pub mod uxnemulib;
 pub mod uxnclilib;
+pub mod uxndbglib;
 
 mod uxn;
-mod devices;
+pub mod devices;
+mod snapshot;
+mod replay;
+pub mod observer;
+pub mod machine;
+pub mod gdbstub;
 
 mod romreaderror;
 use romreaderror::RomReadError as RomReadError;
+use romreaderror::RomTooLargeError as RomTooLargeError;
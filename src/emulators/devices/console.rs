@@ -1,5 +1,6 @@
-use crate::emulators::uxn::device::{Device, MainRamInterface};
+use crate::emulators::uxn::device::{Device, DeviceError, MainRamInterface};
 use std::io;
+use crate::emulators::snapshot::{Snapshot, SnapshotWriter, SnapshotReader, SnapshotError};
 
 pub struct Console<J, K>
     where J: io::Write, 
@@ -34,9 +35,9 @@ impl<J, K> Device for Console<J, K>
     where J: io::Write,
           K: io::Write,
 {
-    fn write(&mut self, port: u8, val: u8, _main_ram: &mut dyn MainRamInterface) {
+    fn write(&mut self, port: u8, val: u8, _main_ram: &mut dyn MainRamInterface) -> Result<(), DeviceError> {
         if port > 0xf {
-            panic!("attempting to write to port out of range");
+            return Err(DeviceError::PortOutOfRange(port));
         }
 
         match port {
@@ -47,34 +48,53 @@ impl<J, K> Device for Console<J, K>
                 self.vector[1] = val;
             },
             0x8 => {
-                write!(self.stdout_writer, "{}", val as char)
-                    .expect("error writing to stdout");
-                self.stdout_writer.flush()
-                    .expect("error flushing stdout");
+                write!(self.stdout_writer, "{}", val as char)?;
+                self.stdout_writer.flush()?;
             },
             0x9 => {
-                write!(self.stderr_writer, "{}", val as char)
-                    .expect("error writing to stderr");
+                write!(self.stderr_writer, "{}", val as char)?;
             },
             _ => {}
         }
+
+        return Ok(());
     }
 
-    fn read(&mut self, port: u8) -> u8 {
+    fn read(&mut self, port: u8) -> Result<u8, DeviceError> {
+        if port > 0xf {
+            return Err(DeviceError::PortOutOfRange(port));
+        }
+
         match port {
             0x0 => {
-                return self.vector[0];
+                return Ok(self.vector[0]);
             },
             0x1 => {
-                return self.vector[1];
+                return Ok(self.vector[1]);
             },
             0x2 => {
-                return self.received_input;
+                return Ok(self.received_input);
             },
             _ => {},
         }
 
-        return 0;
+        return Ok(0);
+    }
+}
+
+impl<J, K> Snapshot for Console<J, K>
+    where J: io::Write,
+          K: io::Write,
+{
+    fn save_state(&self, writer: &mut SnapshotWriter) {
+        writer.put_bytes(&self.vector);
+        writer.put_u8(self.received_input);
+    }
+
+    fn load_state(&mut self, reader: &mut SnapshotReader) -> Result<(), SnapshotError> {
+        self.vector = reader.get_array()?;
+        self.received_input = reader.get_u8()?;
+        return Ok(());
     }
 }
 
@@ -101,14 +121,14 @@ mod tests {
         let initial_vector = console.read_vector();
         assert_eq!(initial_vector, 0);
 
-        console.write(0x0, 0xab, &mut MockMainRamInterface{});
-        console.write(0x1, 0xcd, &mut MockMainRamInterface{});
+        console.write(0x0, 0xab, &mut MockMainRamInterface{}).unwrap();
+        console.write(0x1, 0xcd, &mut MockMainRamInterface{}).unwrap();
 
         let vector = console.read_vector();
         assert_eq!(vector, 0xabcd);
 
-        assert_eq!(console.read(0x0), 0xab);
-        assert_eq!(console.read(0x1), 0xcd);
+        assert_eq!(console.read(0x0).unwrap(), 0xab);
+        assert_eq!(console.read(0x1).unwrap(), 0xcd);
     }
 
     #[test]
@@ -116,20 +136,20 @@ mod tests {
         let mut console = Console::new(Vec::new(), Vec::new());
 
         // initial read should return 0x00
-        assert_eq!(console.read(0x2), 0x00);
+        assert_eq!(console.read(0x2).unwrap(), 0x00);
 
         // provide some inputted text
         console.provide_input(0x8a);
 
         // read should return what was inputted
-        assert_eq!(console.read(0x2), 0x8a);
+        assert_eq!(console.read(0x2).unwrap(), 0x8a);
 
         // and again
-        assert_eq!(console.read(0x2), 0x8a);
+        assert_eq!(console.read(0x2).unwrap(), 0x8a);
 
         // provide some different inputted text
         console.provide_input(0x7b);
-        assert_eq!(console.read(0x2), 0x7b);
+        assert_eq!(console.read(0x2).unwrap(), 0x7b);
     }
 
     #[test]
@@ -139,12 +159,12 @@ mod tests {
 
         let mut console = Console::new(&mut stdout_writer, &mut stderr_writer);
 
-        console.write(0x8, 0x01, &mut MockMainRamInterface{});
-        console.write(0x8, 0x02, &mut MockMainRamInterface{});
-        console.write(0x9, 0x04, &mut MockMainRamInterface{});
-        console.write(0x8, 0x03, &mut MockMainRamInterface{});
-        console.write(0x9, 0x05, &mut MockMainRamInterface{});
-        console.write(0x9, 0x06, &mut MockMainRamInterface{});
+        console.write(0x8, 0x01, &mut MockMainRamInterface{}).unwrap();
+        console.write(0x8, 0x02, &mut MockMainRamInterface{}).unwrap();
+        console.write(0x9, 0x04, &mut MockMainRamInterface{}).unwrap();
+        console.write(0x8, 0x03, &mut MockMainRamInterface{}).unwrap();
+        console.write(0x9, 0x05, &mut MockMainRamInterface{}).unwrap();
+        console.write(0x9, 0x06, &mut MockMainRamInterface{}).unwrap();
 
         assert_eq!(stdout_writer, vec![0x01, 0x02, 0x03]);
         assert_eq!(stderr_writer, vec![0x04, 0x05, 0x06]);
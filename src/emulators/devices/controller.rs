@@ -1,5 +1,7 @@
-use crate::emulators::uxn::device::{Device, MainRamInterface};
+use crate::emulators::uxn::device::{Device, DeviceError, MainRamInterface};
+use crate::emulators::snapshot::{Snapshot, SnapshotWriter, SnapshotReader, SnapshotError};
 
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum Button {
     A,
     B,
@@ -12,7 +14,7 @@ pub enum Button {
 }
 
 impl Button {
-    fn to_code(&self) -> u8 {
+    fn to_code(self) -> u8 {
         match self {
             Button::A => 1,
             Button::B => 1<<1,
@@ -32,6 +34,12 @@ pub struct ControllerDevice {
     key: u8,
 }
 
+impl Default for ControllerDevice {
+    fn default() -> Self {
+        return ControllerDevice::new();
+    }
+}
+
 impl ControllerDevice {
     pub fn new() -> Self {
         ControllerDevice {
@@ -64,9 +72,9 @@ impl ControllerDevice {
 }
 
 impl Device for ControllerDevice {
-    fn write(&mut self, port: u8, val: u8, _main_ram: &mut dyn MainRamInterface) {
+    fn write(&mut self, port: u8, val: u8, _main_ram: &mut dyn MainRamInterface) -> Result<(), DeviceError> {
         if port > 0xf {
-            panic!("attempting to write to port out of range");
+            return Err(DeviceError::PortOutOfRange(port));
         }
 
         match port {
@@ -78,25 +86,42 @@ impl Device for ControllerDevice {
             },
             _ => {}
         }
+
+        return Ok(());
     }
 
-    fn read(&mut self, port: u8) -> u8 {
+    fn read(&mut self, port: u8) -> Result<u8, DeviceError> {
         if port > 0xf {
-            panic!("attempting to read from port out of range");
+            return Err(DeviceError::PortOutOfRange(port));
         }
 
         match port {
-            0x0 => return self.vector[0],
-            0x1 => return self.vector[1],
-            0x2 => return self.button_state,
-            0x3 => return self.key,
+            0x0 => return Ok(self.vector[0]),
+            0x1 => return Ok(self.vector[1]),
+            0x2 => return Ok(self.button_state),
+            0x3 => return Ok(self.key),
             _ => {
-                return 0x0;
+                return Ok(0x0);
             }
         }
     }
 }
 
+impl Snapshot for ControllerDevice {
+    fn save_state(&self, writer: &mut SnapshotWriter) {
+        writer.put_bytes(&self.vector);
+        writer.put_u8(self.button_state);
+        writer.put_u8(self.key);
+    }
+
+    fn load_state(&mut self, reader: &mut SnapshotReader) -> Result<(), SnapshotError> {
+        self.vector = reader.get_array()?;
+        self.button_state = reader.get_u8()?;
+        self.key = reader.get_u8()?;
+        return Ok(());
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -120,14 +145,14 @@ mod tests {
         let initial_vector = controller_device.read_vector();
         assert_eq!(initial_vector, 0);
 
-        controller_device.write(0x0, 0xab, &mut MockMainRamInterface{});
-        controller_device.write(0x1, 0xcd, &mut MockMainRamInterface{});
+        controller_device.write(0x0, 0xab, &mut MockMainRamInterface{}).unwrap();
+        controller_device.write(0x1, 0xcd, &mut MockMainRamInterface{}).unwrap();
 
         let vector = controller_device.read_vector();
         assert_eq!(vector, 0xabcd);
 
-        assert_eq!(controller_device.read(0x0), 0xab);
-        assert_eq!(controller_device.read(0x1), 0xcd);
+        assert_eq!(controller_device.read(0x0).unwrap(), 0xab);
+        assert_eq!(controller_device.read(0x1).unwrap(), 0xcd);
     }
 
     #[test]
@@ -139,13 +164,13 @@ mod tests {
         let changed = controller_device.notify_button_down(Button::Down);
         assert_eq!(changed, true);
 
-        assert_eq!(controller_device.read(0x2), (1<<2) | (1<<5));
+        assert_eq!(controller_device.read(0x2).unwrap(), (1<<2) | (1<<5));
 
         let changed = controller_device.notify_button_down(Button::Down);
         assert_eq!(changed, false);
 
         controller_device.notify_button_up(Button::Down);
-        assert_eq!(controller_device.read(0x2), (1<<2));
+        assert_eq!(controller_device.read(0x2).unwrap(), (1<<2));
     }
 
     #[test]
@@ -153,9 +178,9 @@ mod tests {
         let mut controller_device = ControllerDevice::new();
 
         controller_device.notify_key_press('h' as u8);
-        assert_eq!(controller_device.read(0x3), 'h' as u8);
+        assert_eq!(controller_device.read(0x3).unwrap(), 'h' as u8);
 
         controller_device.notify_key_press('e' as u8);
-        assert_eq!(controller_device.read(0x3), 'e' as u8);
+        assert_eq!(controller_device.read(0x3).unwrap(), 'e' as u8);
     }
 }
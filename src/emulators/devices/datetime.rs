@@ -1,10 +1,16 @@
-use crate::emulators::uxn::device::{Device, MainRamInterface};
+use crate::emulators::uxn::device::{Device, DeviceError, MainRamInterface};
 use chrono::{Local, Datelike, Timelike, DateTime};
 
 pub struct DateTimeDevice {
     now_fn: fn() -> DateTime<Local>,    
 }
 
+impl Default for DateTimeDevice {
+    fn default() -> Self {
+        return DateTimeDevice::new();
+    }
+}
+
 impl DateTimeDevice {
     pub fn new() -> Self {
         DateTimeDevice{now_fn: Local::now}
@@ -12,13 +18,18 @@ impl DateTimeDevice {
 }
 
 impl Device for DateTimeDevice {
-    fn write(&mut self, _port: u8, _val: u8, _main_ram: &mut dyn MainRamInterface) {
+    fn write(&mut self, port: u8, _val: u8, _main_ram: &mut dyn MainRamInterface) -> Result<(), DeviceError> {
+        if port > 0xf {
+            return Err(DeviceError::PortOutOfRange(port));
+        }
+
         // writing to date time device is a no-op
+        return Ok(());
     }
 
-    fn read(&mut self, port: u8) -> u8 {
+    fn read(&mut self, port: u8) -> Result<u8, DeviceError> {
         if port > 0xf {
-            panic!("attempting to read from port out of range");
+            return Err(DeviceError::PortOutOfRange(port));
         }
 
         let dt = (self.now_fn)();
@@ -26,45 +37,45 @@ impl Device for DateTimeDevice {
         match port {
             0x0 => {
                 let year = u16::try_from(dt.year()).unwrap();
-                return year.to_be_bytes()[0];
+                return Ok(year.to_be_bytes()[0]);
             },
             0x1 => {
                 let year = u16::try_from(dt.year()).unwrap();
-                return year.to_be_bytes()[1];
+                return Ok(year.to_be_bytes()[1]);
             },
             0x2 => {
-                return u8::try_from(dt.month0()).unwrap();
+                return Ok(u8::try_from(dt.month0()).unwrap());
             },
             0x3 => {
-                return u8::try_from(dt.day()).unwrap();
+                return Ok(u8::try_from(dt.day()).unwrap());
             },
             0x4 => {
-                return u8::try_from(dt.hour()).unwrap();
+                return Ok(u8::try_from(dt.hour()).unwrap());
             },
             0x5 => {
-                return u8::try_from(dt.minute()).unwrap();
+                return Ok(u8::try_from(dt.minute()).unwrap());
             },
             0x6 => {
-                return u8::try_from(dt.second()).unwrap();
+                return Ok(u8::try_from(dt.second()).unwrap());
             },
             0x7 => {
-                return u8::try_from(dt.weekday().num_days_from_sunday()).unwrap();
+                return Ok(u8::try_from(dt.weekday().num_days_from_sunday()).unwrap());
             },
             0x8 => {
                 let year_day = u16::try_from(dt.ordinal0()).unwrap();
-                return year_day.to_be_bytes()[0];
+                return Ok(year_day.to_be_bytes()[0]);
             },
             0x9 => {
                 let year_day = u16::try_from(dt.ordinal0()).unwrap();
-                return year_day.to_be_bytes()[1];
+                return Ok(year_day.to_be_bytes()[1]);
             },
             0xa => {
                 // 'is daylight saving time', just return -1 (not known) in this
                 // case
-                return (-1_i8).to_be_bytes()[0];
+                return Ok((-1_i8).to_be_bytes()[0]);
             },
             _ => {
-                return 0x0;
+                return Ok(0x0);
             },
         }
     }
@@ -95,46 +106,46 @@ mod tests {
 
         // test that the year returned matches that that the 'now_fn' returned
         let year_received = u16::from_be_bytes([
-            datetime_device.read(0x0),
-            datetime_device.read(0x1),
+            datetime_device.read(0x0).unwrap(),
+            datetime_device.read(0x1).unwrap(),
         ]);
         assert_eq!(1986, year_received);
 
         // test the month
-        let month_received = datetime_device.read(0x2);
+        let month_received = datetime_device.read(0x2).unwrap();
         // nb, month starts from 0
         assert_eq!(8, month_received);
 
         // test the day of the month
-        let day_of_month_received = datetime_device.read(0x3);
+        let day_of_month_received = datetime_device.read(0x3).unwrap();
         assert_eq!(16, day_of_month_received);
 
         // test the hour
-        let hour_received = datetime_device.read(0x4);
+        let hour_received = datetime_device.read(0x4).unwrap();
         assert_eq!(17, hour_received);
 
         // test the minute
-        let minute_received = datetime_device.read(0x5);
+        let minute_received = datetime_device.read(0x5).unwrap();
         assert_eq!(08, minute_received);
 
         // test the second
-        let second_received = datetime_device.read(0x6);
+        let second_received = datetime_device.read(0x6).unwrap();
         assert_eq!(20, second_received);
 
         // test the days since Sunday
-        let week_day_received = datetime_device.read(0x7);
+        let week_day_received = datetime_device.read(0x7).unwrap();
         assert_eq!(02, week_day_received);
 
         // test the days since January 1st
         let year_day_received = u16::from_be_bytes([
-            datetime_device.read(0x8),
-            datetime_device.read(0x9),
+            datetime_device.read(0x8).unwrap(),
+            datetime_device.read(0x9).unwrap(),
         ]);
         assert_eq!(258, year_day_received);
 
         // test whether dst (this is always reported -1 -- not awailable --
         // in this implementation)
-        let is_dst_received = datetime_device.read(0xa);
+        let is_dst_received = datetime_device.read(0xa).unwrap();
         assert_eq!(255, is_dst_received);
     }
 }
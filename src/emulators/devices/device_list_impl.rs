@@ -47,11 +47,13 @@ impl<'a, J> DeviceList for DeviceListImpl<'a, J>
             },
 
             // device not found under this index
-            None => return DeviceWriteReturnCode::Success, // TODO return unrecognised device error?
+            None => return DeviceWriteReturnCode::UnrecognisedDevice,
         };
 
         // pass port and value through to device
-        device.write(device_port, val, main_ram);
+        if let Err(err) = device.write(device_port, val, main_ram) {
+            return DeviceWriteReturnCode::Failure(err);
+        }
 
         return DeviceWriteReturnCode::Success;
     }
@@ -77,7 +79,7 @@ impl<'a, J> DeviceList for DeviceListImpl<'a, J>
             None => return DeviceReadReturnCode::Success(Err(UxnError::UnrecognisedDevice)),
         };
 
-        return DeviceReadReturnCode::Success(Ok(device.read(device_port)));
+        return DeviceReadReturnCode::Success(device.read(device_port).map_err(UxnError::from));
     }
 }
 
@@ -86,7 +88,7 @@ mod tests {
     use super::*;
     use std::cell::RefCell;
     use std::collections::VecDeque;
-    use crate::emulators::uxn::device::MainRamInterfaceError;
+    use crate::emulators::uxn::device::{MainRamInterfaceError, DeviceError};
 
     struct MockDeviceA {
         pub write_arguments_received: RefCell<VecDeque<(u8, u8)>>,
@@ -107,21 +109,22 @@ mod tests {
     }
 
     impl Device for MockDeviceA {
-        fn write(&mut self, port: u8, val: u8, _main_ram: &mut dyn MainRamInterface) {
+        fn write(&mut self, port: u8, val: u8, _main_ram: &mut dyn MainRamInterface) -> Result<(), DeviceError> {
             self.write_arguments_received
                 .borrow_mut()
                 .push_back((port, val));
+            return Ok(());
         }
-        fn read(&mut self, port: u8) -> u8 {
+        fn read(&mut self, port: u8) -> Result<u8, DeviceError> {
             self.read_arguments_received
                 .borrow_mut()
                 .push_back((port,));
 
-            return self
+            return Ok(self
                 .read_values_to_return
                 .borrow_mut()
                 .pop_front()
-                .unwrap();
+                .unwrap());
         }
     }
 
@@ -129,7 +132,7 @@ mod tests {
         pub write_arguments_received: RefCell<VecDeque<(u8, u8)>>,
 
         pub read_arguments_received: RefCell<VecDeque<(u8,)>>,
-        pub read_values_to_return: RefCell<VecDeque<u8>>,
+        pub read_values_to_return: RefCell<VecDeque<Result<u8, DeviceError>>>,
     }
 
     impl MockDeviceB {
@@ -144,12 +147,13 @@ mod tests {
     }
 
     impl Device for MockDeviceB {
-        fn write(&mut self, port: u8, val: u8, _main_ram: &mut dyn MainRamInterface) {
+        fn write(&mut self, port: u8, val: u8, _main_ram: &mut dyn MainRamInterface) -> Result<(), DeviceError> {
             self.write_arguments_received
                 .borrow_mut()
                 .push_back((port, val));
+            return Ok(());
         }
-        fn read(&mut self, port: u8) -> u8 {
+        fn read(&mut self, port: u8) -> Result<u8, DeviceError> {
             self.read_arguments_received
                 .borrow_mut()
                 .push_back((port,));
@@ -194,6 +198,10 @@ mod tests {
         let ret = device_list.write_to_device(0x24, 60, &mut MockMainRamInterface{});
         assert_eq!(ret, DeviceWriteReturnCode::Success);
 
+        // write to device 0x5, which doesn't exist
+        let ret = device_list.write_to_device(0x51, 60, &mut MockMainRamInterface{});
+        assert_eq!(ret, DeviceWriteReturnCode::UnrecognisedDevice);
+
         // write 77 to device 0x3, port 0x9
         let ret = device_list.write_to_device(0x39, 77, &mut MockMainRamInterface{});
 
@@ -238,7 +246,8 @@ mod tests {
         ]));
         let mut mock_device_b = MockDeviceB::new();
         mock_device_b.read_values_to_return = RefCell::new(VecDeque::from([
-            0x34,
+            Ok(0x34),
+            Err(DeviceError::PortOutOfRange(0x10)),
         ]));
 
 
@@ -257,6 +266,11 @@ mod tests {
         let ret = device_list.read_from_device(0x24);
         assert_eq!(ret, DeviceReadReturnCode::Success(Ok(0x34)));
 
+        // read from device 0x2, port 0x5, which fails
+        let ret = device_list.read_from_device(0x25);
+        assert_eq!(ret, DeviceReadReturnCode::Success(
+            Err(UxnError::DeviceError(DeviceError::PortOutOfRange(0x10)))));
+
         // read from device 0x3, port 0x9
         let ret = device_list.read_from_device(0x39);
         assert_eq!(ret, DeviceReadReturnCode::ReadFromSystemDevice(0x9));
@@ -277,7 +291,7 @@ mod tests {
             mock_device_b
                 .read_arguments_received
                 .into_inner(),
-            VecDeque::from([(0x4,),])
+            VecDeque::from([(0x4,), (0x5,),])
         );
     }
 }
@@ -1,6 +1,7 @@
-use crate::emulators::uxn::device::{Device, MainRamInterface};
+use crate::emulators::uxn::device::{Device, DeviceError, MainRamInterface};
+use crate::emulators::snapshot::{Snapshot, SnapshotWriter, SnapshotReader, SnapshotError};
 use std::fs::{File, ReadDir};
-use std::io::{Read, Write};
+use std::io::{Read, Write, Seek, SeekFrom};
 use std::io;
 use std::fs;
 use std::path::Path;
@@ -36,7 +37,7 @@ impl Iterator for DirEntryProducer {
                 Ok(metadata) => metadata,
                 Err(err) => return Some(Err(err)),
             };
-            let file_name = entry.file_name().into_string().expect("unsupported file");
+            let file_name = entry.file_name().to_string_lossy().into_owned();
             return Some(Ok(produce_dir_entry_string(&file_name, metadata).into_bytes()));
         } else {
             return None;
@@ -65,6 +66,7 @@ pub struct FileDevice {
     stat_target_address: [u8; 2],
     write_target_address: [u8; 2],
     subject: FsObject,
+    dir_entries_read: u64, // how far through the directory the subject is, if it is one
     append: u8,
 }
 
@@ -83,37 +85,45 @@ fn open_fs_object(fs_name: &str) -> FsObject {
     return FsObject::None;
 }
 
+impl Default for FileDevice {
+    fn default() -> Self {
+        return FileDevice::new();
+    }
+}
+
 impl FileDevice {
     pub fn new() -> Self {
         FileDevice{file_name_address: [0, 0], file_name: "".to_string(), success: 0,
         fetch_length: [0, 0], target_address: [0, 0], stat_target_address: [0, 0],
-        write_target_address: [0, 0], subject: FsObject::None, append: 0,}
+        write_target_address: [0, 0], subject: FsObject::None, dir_entries_read: 0, append: 0,}
     }
 
-    fn refresh_file_name(&mut self, main_ram: &mut dyn MainRamInterface) {
+    fn refresh_file_name(&mut self, main_ram: &mut dyn MainRamInterface) -> Result<(), DeviceError> {
+        self.subject = FsObject::None;
+        self.dir_entries_read = 0;
+        self.file_name = "".to_string();
+        self.success = 0;
+
         let mut file_name = Vec::new();
         let mut file_name_address = u16::from_be_bytes(self.file_name_address);
         loop {
-            let byte = match main_ram.read(file_name_address, 1) {
-                Ok(bytes) => bytes[0],
-                Err(err) => panic!("Failed to read file name: {}", err),
-            };
+            let byte = main_ram.read(file_name_address, 1)?[0];
             if byte == 0 {
                 break;
             }
             file_name.push(byte);
-            file_name_address += 1;
-        }
 
-        let file_name = String::from_utf8(file_name).expect(
-            "Non utf8 string encountered when reading file name");
+            // a name that reaches the end of memory without being terminated is invalid
+            file_name_address = file_name_address.checked_add(1)
+                .ok_or(DeviceError::InvalidFileName)?;
+        }
 
-        self.subject = FsObject::None;
-        self.file_name = file_name;
-        self.success = 0;
+        self.file_name = String::from_utf8(file_name)
+            .map_err(|_| DeviceError::InvalidFileName)?;
+        return Ok(());
     }
 
-    fn read_from_dir(&mut self, main_ram: &mut dyn MainRamInterface) {
+    fn read_from_dir(&mut self, main_ram: &mut dyn MainRamInterface) -> Result<(), DeviceError> {
         let dir = if let FsObject::Directory(dir) = &mut self.subject {
             dir
         } else {
@@ -136,23 +146,23 @@ impl FileDevice {
                 next_entry
             } else {
                 self.success = 0;
-                return;
+                return Ok(());
             };
 
             if buffer.len() + next_entry.len() > bytes_to_write {
                 break;
             }
             let next_entry = dir.next().unwrap().unwrap();
+            self.dir_entries_read += 1;
             buffer.extend(next_entry.into_iter());
         }
 
-        main_ram.write(u16::from_be_bytes(self.target_address),
-            &mut buffer)
-            .expect("had problem reading from file and writing to memory");
+        main_ram.write(u16::from_be_bytes(self.target_address), &buffer)?;
         self.success = u16::try_from(buffer.len()).unwrap();
+        return Ok(());
     }
 
-    fn read_from_file(&mut self, main_ram: &mut dyn MainRamInterface) {
+    fn read_from_file(&mut self, main_ram: &mut dyn MainRamInterface) -> Result<(), DeviceError> {
         let file = if let FsObject::File(file) = &mut self.subject {
             file
         } else {
@@ -164,38 +174,41 @@ impl FileDevice {
             num_butes_read
         } else {
             self.success = 0;
-            return;
+            return Ok(());
         };
 
-        main_ram.write(u16::from_be_bytes(self.target_address),
-            &mut buf[..num_bytes_read])
-            .expect("had problem reading from file and writing to memory");
+        main_ram.write(u16::from_be_bytes(self.target_address), &buf[..num_bytes_read])?;
         self.success = u16::try_from(num_bytes_read).unwrap();
+        return Ok(());
     }
 
-    fn stat_from_fs(&mut self, main_ram: &mut dyn MainRamInterface) {
+    fn stat_from_fs(&mut self, main_ram: &mut dyn MainRamInterface) -> Result<(), DeviceError> {
         let metadata = fs::metadata(&self.file_name);
         let metadata = if let Ok(metadata) = metadata {
             metadata
         } else {
             self.success = 0;
-            return;
+            return Ok(());
         };
 
-        let output = produce_dir_entry_string(
-            Path::new(&self.file_name).file_name().unwrap().to_str().unwrap(),
-            metadata)
-            .into_bytes();
+        // paths such as ".." have no final component to report
+        let file_name = if let Some(file_name) = Path::new(&self.file_name).file_name() {
+            file_name.to_string_lossy()
+        } else {
+            self.success = 0;
+            return Ok(());
+        };
+
+        let output = produce_dir_entry_string(&file_name, metadata).into_bytes();
 
         if output.len() > usize::from(u16::from_be_bytes(self.fetch_length)) {
             self.success = 0;
-            return;
+            return Ok(());
         }
 
-        main_ram.write(u16::from_be_bytes(self.stat_target_address),
-            &output)
-            .expect("had problem reading from file and writing to memory");
+        main_ram.write(u16::from_be_bytes(self.stat_target_address), &output)?;
         self.success = u16::try_from(output.len()).unwrap();
+        return Ok(());
     }
 
     fn delete_from_fs(&mut self) {
@@ -207,7 +220,7 @@ impl FileDevice {
         }
     }
 
-    fn read_from_fs(&mut self, main_ram: &mut dyn MainRamInterface) {
+    fn read_from_fs(&mut self, main_ram: &mut dyn MainRamInterface) -> Result<(), DeviceError> {
         match self.subject {
             FsObject::None => {
                 self.subject = open_fs_object(&self.file_name);
@@ -218,13 +231,13 @@ impl FileDevice {
         match &mut self.subject {
             FsObject::None => {
                 self.success = 0;
-                return;
+                return Ok(());
             },
             FsObject::File(_) => {
-                self.read_from_file(main_ram);
+                return self.read_from_file(main_ram);
             },
             FsObject::Directory(_) => {
-                self.read_from_dir(main_ram);
+                return self.read_from_dir(main_ram);
             },
         }
     }
@@ -259,14 +272,97 @@ impl FileDevice {
             return;
         };
 
-        f.write(&data_to_write).expect("Failed to write to file system");
+        if f.write_all(&data_to_write).is_ok() {
+            self.success = u16::from_be_bytes(self.fetch_length);
+        } else {
+            self.success = 0;
+        }
+    }
+}
+
+// how the subject of the file device is recorded in a save state
+const SUBJECT_NONE: u8 = 0;
+const SUBJECT_FILE: u8 = 1;
+const SUBJECT_DIRECTORY: u8 = 2;
+
+impl Snapshot for FileDevice {
+    fn save_state(&self, writer: &mut SnapshotWriter) {
+        writer.put_bytes(&self.file_name_address);
+        writer.put_sized_bytes(self.file_name.as_bytes());
+        writer.put_u16(self.success);
+        writer.put_bytes(&self.fetch_length);
+        writer.put_bytes(&self.target_address);
+        writer.put_bytes(&self.stat_target_address);
+        writer.put_bytes(&self.write_target_address);
+        writer.put_u8(self.append);
+
+        // open files and directories can't be saved directly, so record what was open and how
+        // far through it the device had read, so that it can be reopened at the same position
+        match &self.subject {
+            FsObject::None => {
+                writer.put_u8(SUBJECT_NONE);
+                writer.put_u64(0);
+            },
+            FsObject::File(file) => {
+                // `stream_position` requires a mutable handle, but seeking doesn't modify the
+                // file
+                let position = (&*file).stream_position().unwrap_or(0);
+                writer.put_u8(SUBJECT_FILE);
+                writer.put_u64(position);
+            },
+            FsObject::Directory(_) => {
+                writer.put_u8(SUBJECT_DIRECTORY);
+                writer.put_u64(self.dir_entries_read);
+            },
+        }
+    }
+
+    fn load_state(&mut self, reader: &mut SnapshotReader) -> Result<(), SnapshotError> {
+        self.file_name_address = reader.get_array()?;
+        self.file_name = String::from_utf8(reader.get_sized_bytes()?.to_vec())
+            .map_err(|_| SnapshotError::InvalidData("file name is not utf8"))?;
+        self.success = reader.get_u16()?;
+        self.fetch_length = reader.get_array()?;
+        self.target_address = reader.get_array()?;
+        self.stat_target_address = reader.get_array()?;
+        self.write_target_address = reader.get_array()?;
+        self.append = reader.get_u8()?;
+
+        let subject_type = reader.get_u8()?;
+        let position = reader.get_u64()?;
+
+        // if the file or directory can no longer be opened the subject is left unset, so the
+        // next read will try to open it again from the start
+        self.subject = FsObject::None;
+        self.dir_entries_read = 0;
+        match (subject_type, open_fs_object(&self.file_name)) {
+            (SUBJECT_NONE, _) => {},
+            (SUBJECT_FILE, FsObject::File(mut file)) => {
+                if file.seek(SeekFrom::Start(position)).is_ok() {
+                    self.subject = FsObject::File(file);
+                }
+            },
+            (SUBJECT_DIRECTORY, FsObject::Directory(mut dir)) => {
+                for _ in 0..position {
+                    dir.next();
+                }
+                self.dir_entries_read = position;
+                self.subject = FsObject::Directory(dir);
+            },
+            (SUBJECT_FILE, _) | (SUBJECT_DIRECTORY, _) => {},
+            _ => {
+                return Err(SnapshotError::InvalidData("invalid file device subject"));
+            },
+        }
+
+        return Ok(());
     }
 }
 
 impl Device for FileDevice {
-    fn write(&mut self, port: u8, val: u8, main_ram: &mut dyn MainRamInterface) {
+    fn write(&mut self, port: u8, val: u8, main_ram: &mut dyn MainRamInterface) -> Result<(), DeviceError> {
         if port > 0xf {
-            panic!("attempting to write to port out of range");
+            return Err(DeviceError::PortOutOfRange(port));
         }
 
         match port {
@@ -275,7 +371,7 @@ impl Device for FileDevice {
             },
             0x5 => {
                 self.stat_target_address[1] = val;
-                self.stat_from_fs(main_ram);
+                self.stat_from_fs(main_ram)?;
             },
             0x6 => {
                 self.delete_from_fs();
@@ -288,7 +384,7 @@ impl Device for FileDevice {
             },
             0x9 => {
                 self.file_name_address[1] = val;
-                self.refresh_file_name(main_ram);
+                self.refresh_file_name(main_ram)?;
             },
             0xa => {
                 self.fetch_length[0] = val;
@@ -301,7 +397,7 @@ impl Device for FileDevice {
             },
             0xd => {
                 self.target_address[1] = val;
-                self.read_from_fs(main_ram);
+                self.read_from_fs(main_ram)?;
             },
             0xe => {
                 self.write_target_address[0] = val;
@@ -312,22 +408,24 @@ impl Device for FileDevice {
             },
             _ => {}
         }
+
+        return Ok(());
     }
 
-    fn read(&mut self, port: u8) -> u8 {
+    fn read(&mut self, port: u8) -> Result<u8, DeviceError> {
         if port > 0xf {
-            panic!("attempting to read from port out of range");
+            return Err(DeviceError::PortOutOfRange(port));
         }
 
         match port {
             0x2 => {
-                return self.success.to_be_bytes()[0];
+                return Ok(self.success.to_be_bytes()[0]);
             },
             0x3 => {
-                return self.success.to_be_bytes()[1];
+                return Ok(self.success.to_be_bytes()[1]);
             },
             _ => {
-                return 0x0;
+                return Ok(0x0);
             },
         }
     }
@@ -413,8 +511,8 @@ mod tests {
 
         // write to the file device, setting the address that the
         // file name should be read from
-        file_device.write(0x8, 0xaa, &mut mock_ram_interface);
-        file_device.write(0x9, 0xbb, &mut mock_ram_interface);
+        file_device.write(0x8, 0xaa, &mut mock_ram_interface).unwrap();
+        file_device.write(0x9, 0xbb, &mut mock_ram_interface).unwrap();
 
         let mut expected_start_address = 0xaabb;
         let read_arguments_expected = tmp_file_path.bytes()
@@ -434,13 +532,13 @@ mod tests {
         // write to the file device, setting the length to 
         // be read
         let chunk_length = u16::try_from(contents.len() - 3).unwrap();
-        file_device.write(0xa, chunk_length.to_be_bytes()[0], &mut mock_ram_interface);
-        file_device.write(0xb, chunk_length.to_be_bytes()[1], &mut mock_ram_interface);
+        file_device.write(0xa, chunk_length.to_be_bytes()[0], &mut mock_ram_interface).unwrap();
+        file_device.write(0xb, chunk_length.to_be_bytes()[1], &mut mock_ram_interface).unwrap();
 
         // write to the file device, setting the address the read
         // data should be written to
-        file_device.write(0xc, 0xcc, &mut mock_ram_interface);
-        file_device.write(0xd, 0xdd, &mut mock_ram_interface);
+        file_device.write(0xc, 0xcc, &mut mock_ram_interface).unwrap();
+        file_device.write(0xd, 0xdd, &mut mock_ram_interface).unwrap();
 
         // assert that the contents of the file is written to the correct
         // address
@@ -452,14 +550,14 @@ mod tests {
 
         // assert that the success field is set to correct value
         let success = u16::from_be_bytes([
-            file_device.read(0x2),
-            file_device.read(0x3),
+            file_device.read(0x2).unwrap(),
+            file_device.read(0x3).unwrap(),
         ]);
         assert_eq!(success, chunk_length);
 
         // read the data again
-        file_device.write(0xc, 0xcc, &mut mock_ram_interface);
-        file_device.write(0xd, 0xdd, &mut mock_ram_interface);
+        file_device.write(0xc, 0xcc, &mut mock_ram_interface).unwrap();
+        file_device.write(0xd, 0xdd, &mut mock_ram_interface).unwrap();
 
         // assert that the remaining contents of the file is written
         // to the correct address
@@ -471,23 +569,102 @@ mod tests {
 
         // assert that the success field is set to correct value
         let success = u16::from_be_bytes([
-            file_device.read(0x2),
-            file_device.read(0x3),
+            file_device.read(0x2).unwrap(),
+            file_device.read(0x3).unwrap(),
         ]);
         assert_eq!(success, 3);
 
         // read the data again, now that the whole file has been read
-        file_device.write(0xc, 0xcc, &mut mock_ram_interface);
-        file_device.write(0xd, 0xdd, &mut mock_ram_interface);
+        file_device.write(0xc, 0xcc, &mut mock_ram_interface).unwrap();
+        file_device.write(0xd, 0xdd, &mut mock_ram_interface).unwrap();
 
         // assert that the success field is set to correct value
         let success = u16::from_be_bytes([
-            file_device.read(0x2),
-            file_device.read(0x3),
+            file_device.read(0x2).unwrap(),
+            file_device.read(0x3).unwrap(),
         ]);
         assert_eq!(success, 0);
     }
 
+    // a file name that isn't utf8, that runs off the end of memory or that can't be read from
+    // memory should give an error rather than changing the file device's subject
+    #[test]
+    fn test_invalid_file_name() {
+        let test_cases = [
+            (0x1000_u16, vec!(Ok(vec!(0x41)), Ok(vec!(0xff)), Ok(vec!(0x0))), DeviceError::InvalidFileName),
+            (0xfffe, vec!(Ok(vec!(0x41)), Ok(vec!(0x42))), DeviceError::InvalidFileName),
+            (0x1000, vec!(Err(MainRamInterfaceError::AddressOutOfBounds)), DeviceError::OutOfRangeMemoryAddress),
+        ];
+
+        for (address, read_values_to_return, expected_error) in test_cases {
+            let mut mock_ram_interface = MockMainRamInterface::new();
+            mock_ram_interface.read_values_to_return = RefCell::new(
+                VecDeque::from(read_values_to_return));
+
+            let mut file_device = FileDevice::new();
+            file_device.write(0x8, address.to_be_bytes()[0], &mut mock_ram_interface).unwrap();
+            assert_eq!(file_device.write(0x9, address.to_be_bytes()[1], &mut mock_ram_interface),
+                Err(expected_error));
+            assert_eq!(file_device.file_name, "");
+        }
+
+        let mut file_device = FileDevice::new();
+        assert_eq!(file_device.read(0x10), Err(DeviceError::PortOutOfRange(0x10)));
+    }
+
+    // read part of a file, then save the state of the file device and restore it into a new
+    // file device. Assert that the new file device carries on reading from where the first
+    // one stopped
+    #[test]
+    fn test_file_read_save_and_load_state() {
+        let mut mock_ram_interface = MockMainRamInterface::new();
+
+        let tmp_file_name = format!("test_file_read_save_and_load_state_{}", Uuid::new_v4());
+        let mut tmp_file_path = std::env::temp_dir();
+        tmp_file_path.push(tmp_file_name);
+        let contents = "file contents 1234";
+        fs::write(&tmp_file_path, &contents).expect("Failed to write test program");
+        let tmp_file_path = tmp_file_path.into_os_string().into_string()
+             .expect("could not convert file path into string");
+
+        let read_values_to_return = tmp_file_path.bytes()
+            .chain([0x0_u8,])
+            .map(|b| Ok(vec!(b)))
+            .collect::<VecDeque<_>>();
+        mock_ram_interface.read_values_to_return = RefCell::new(
+            read_values_to_return);
+        mock_ram_interface.write_values_to_return = RefCell::new(
+            VecDeque::from([Ok(5), Ok(5)]));
+
+        let mut file_device = FileDevice::new();
+
+        // set the file name, a read length of 5 and read the first chunk of the file
+        file_device.write(0x8, 0xaa, &mut mock_ram_interface).unwrap();
+        file_device.write(0x9, 0xbb, &mut mock_ram_interface).unwrap();
+        file_device.write(0xb, 5, &mut mock_ram_interface).unwrap();
+        file_device.write(0xc, 0xcc, &mut mock_ram_interface).unwrap();
+        file_device.write(0xd, 0xdd, &mut mock_ram_interface).unwrap();
+        assert_eq!(
+            mock_ram_interface.write_arguments_received.borrow_mut().pop_front().unwrap(),
+            (0xccdd_u16, "file ".bytes().collect::<Vec<_>>()));
+
+        let mut writer = SnapshotWriter::new();
+        file_device.save_state(&mut writer);
+        let state = writer.into_bytes();
+
+        let mut restored = FileDevice::new();
+        restored.load_state(&mut SnapshotReader::new(&state)).unwrap();
+
+        // the next read should continue from where the first device got to
+        restored.write(0xd, 0xdd, &mut mock_ram_interface).unwrap();
+        assert_eq!(
+            mock_ram_interface.write_arguments_received.borrow_mut().pop_front().unwrap(),
+            (0xccdd_u16, "conte".bytes().collect::<Vec<_>>()));
+        assert_eq!(restored.read(0x3).unwrap(), 5);
+
+        fs::remove_file(tmp_file_path).expect("could not remove test file");
+    }
+
     // try to read from a file where the file does not exist
     #[test]
     fn test_file_read_non_existent() {
@@ -510,8 +687,8 @@ mod tests {
 
         // write to the file device, setting the address that the
         // file name should be read from
-        file_device.write(0x8, 0xaa, &mut mock_ram_interface);
-        file_device.write(0x9, 0xbb, &mut mock_ram_interface);
+        file_device.write(0x8, 0xaa, &mut mock_ram_interface).unwrap();
+        file_device.write(0x9, 0xbb, &mut mock_ram_interface).unwrap();
 
         let mut expected_start_address = 0xaabb;
         let read_arguments_expected = tmp_file_path.bytes()
@@ -531,19 +708,19 @@ mod tests {
         // write to the file device, setting the length to 
         // be read (this is an arbitrary value of 5 bytes in this case)
         let chunk_length = 5_u16;
-        file_device.write(0xa, chunk_length.to_be_bytes()[0], &mut mock_ram_interface);
-        file_device.write(0xb, chunk_length.to_be_bytes()[1], &mut mock_ram_interface);
+        file_device.write(0xa, chunk_length.to_be_bytes()[0], &mut mock_ram_interface).unwrap();
+        file_device.write(0xb, chunk_length.to_be_bytes()[1], &mut mock_ram_interface).unwrap();
 
         // write to the file device, setting the address the read
         // data should be written to (not that it will be written,
         // since the file doesn't exist)
-        file_device.write(0xc, 0xcc, &mut mock_ram_interface);
-        file_device.write(0xd, 0xdd, &mut mock_ram_interface);
+        file_device.write(0xc, 0xcc, &mut mock_ram_interface).unwrap();
+        file_device.write(0xd, 0xdd, &mut mock_ram_interface).unwrap();
 
         // assert that the success field is set to correct value of 0
         let success = u16::from_be_bytes([
-            file_device.read(0x2),
-            file_device.read(0x3),
+            file_device.read(0x2).unwrap(),
+            file_device.read(0x3).unwrap(),
         ]);
         assert_eq!(success, 0);
 
@@ -616,8 +793,8 @@ mod tests {
 
         // write to the file device, setting the address that the
         // file name should be read from
-        file_device.write(0x8, 0xaa, &mut mock_ram_interface);
-        file_device.write(0x9, 0xbb, &mut mock_ram_interface);
+        file_device.write(0x8, 0xaa, &mut mock_ram_interface).unwrap();
+        file_device.write(0x9, 0xbb, &mut mock_ram_interface).unwrap();
 
         let mut expected_start_address = 0xaabb;
         let read_arguments_expected = tmp_dir_path.bytes()
@@ -637,13 +814,13 @@ mod tests {
         // write to the file device, setting the length to 
         // be read
         let chunk_len = u16::try_from(chunk_len).unwrap();
-        file_device.write(0xa, chunk_len.to_be_bytes()[0], &mut mock_ram_interface);
-        file_device.write(0xb, chunk_len.to_be_bytes()[1], &mut mock_ram_interface);
+        file_device.write(0xa, chunk_len.to_be_bytes()[0], &mut mock_ram_interface).unwrap();
+        file_device.write(0xb, chunk_len.to_be_bytes()[1], &mut mock_ram_interface).unwrap();
 
         // write to the file device, setting the address the read
         // data should be written to
-        file_device.write(0xc, 0xcc, &mut mock_ram_interface);
-        file_device.write(0xd, 0xdd, &mut mock_ram_interface);
+        file_device.write(0xc, 0xcc, &mut mock_ram_interface).unwrap();
+        file_device.write(0xd, 0xdd, &mut mock_ram_interface).unwrap();
 
         // assert that the contents of the directory is written to the correct
         // address
@@ -654,8 +831,8 @@ mod tests {
         // assert that the 'success' field has been written to with the 
         // expected number of bytes, 2*entry_len
         let success = u16::from_be_bytes([
-            file_device.read(0x2),
-            file_device.read(0x3),
+            file_device.read(0x2).unwrap(),
+            file_device.read(0x3).unwrap(),
         ]);
         assert_eq!(success, u16::try_from(2*entry_len).unwrap());
 
@@ -665,8 +842,8 @@ mod tests {
             .collect::<Vec<String>>();
 
         // do a second write to finish off the directory
-        file_device.write(0xc, 0xcc, &mut mock_ram_interface);
-        file_device.write(0xd, 0xdd, &mut mock_ram_interface);
+        file_device.write(0xc, 0xcc, &mut mock_ram_interface).unwrap();
+        file_device.write(0xd, 0xdd, &mut mock_ram_interface).unwrap();
 
         let write_arguments_received = mock_ram_interface.write_arguments_received.borrow_mut().pop_front().unwrap();
         assert_eq!(write_arguments_received.0, write_address_expected);
@@ -674,8 +851,8 @@ mod tests {
         // assert that the 'success' field has been written to with the 
         // expected number of bytes, entry_len
         let success = u16::from_be_bytes([
-            file_device.read(0x2),
-            file_device.read(0x3),
+            file_device.read(0x2).unwrap(),
+            file_device.read(0x3).unwrap(),
         ]);
         assert_eq!(success, u16::try_from(entry_len).unwrap());
 
@@ -696,11 +873,11 @@ mod tests {
         assert_eq!(expected_contents, received_directory_contents);
 
         // assert that one more attempt to write sets success to 0
-        file_device.write(0xc, 0xcc, &mut mock_ram_interface);
-        file_device.write(0xd, 0xdd, &mut mock_ram_interface);
+        file_device.write(0xc, 0xcc, &mut mock_ram_interface).unwrap();
+        file_device.write(0xd, 0xdd, &mut mock_ram_interface).unwrap();
         let success = u16::from_be_bytes([
-            file_device.read(0x2),
-            file_device.read(0x3),
+            file_device.read(0x2).unwrap(),
+            file_device.read(0x3).unwrap(),
         ]);
         assert_eq!(success, 0);
 
@@ -735,13 +912,13 @@ mod tests {
         // new line character
         let len = test_file_name.len() + 6;
         let len = u16::try_from(len).unwrap();
-        file_device.write(0xa, len.to_be_bytes()[0], &mut mock_ram_interface);
-        file_device.write(0xb, len.to_be_bytes()[1], &mut mock_ram_interface);
+        file_device.write(0xa, len.to_be_bytes()[0], &mut mock_ram_interface).unwrap();
+        file_device.write(0xb, len.to_be_bytes()[1], &mut mock_ram_interface).unwrap();
 
         // write to the file device, setting the address that the
         // file name should be read from
-        file_device.write(0x8, 0xcc, &mut mock_ram_interface);
-        file_device.write(0x9, 0xcd, &mut mock_ram_interface);
+        file_device.write(0x8, 0xcc, &mut mock_ram_interface).unwrap();
+        file_device.write(0x9, 0xcd, &mut mock_ram_interface).unwrap();
 
         let mut expected_start_address = 0xcccd;
         let read_arguments_expected = test_file_path.bytes()
@@ -767,8 +944,8 @@ mod tests {
         // write to the address(stat) port, and assert that the correct
         // expected string ('0a0b test_file_<guid>\n') has been written
         // to the correct location of the mock ram interface
-        file_device.write(0x4, 0x12, &mut mock_ram_interface);
-        file_device.write(0x5, 0x34, &mut mock_ram_interface);
+        file_device.write(0x4, 0x12, &mut mock_ram_interface).unwrap();
+        file_device.write(0x5, 0x34, &mut mock_ram_interface).unwrap();
 
         let expected_output = (0x1234, format!("0a0b {}\n", test_file_name));
         let write_arguments_received = mock_ram_interface.write_arguments_received.borrow_mut().pop_front().unwrap();
@@ -778,8 +955,8 @@ mod tests {
         // assert that the 'success' field has been written to with the 
         // expected number of bytes
         let success = u16::from_be_bytes([
-            file_device.read(0x2),
-            file_device.read(0x3),
+            file_device.read(0x2).unwrap(),
+            file_device.read(0x3).unwrap(),
         ]);
         assert_eq!(success, u16::try_from(len).unwrap());
 
@@ -812,13 +989,13 @@ mod tests {
         // '----'), plus a space, plus the new line character
         let len = test_dir_name.len() + 6;
         let len = u16::try_from(len).unwrap();
-        file_device.write(0xa, len.to_be_bytes()[0], &mut mock_ram_interface);
-        file_device.write(0xb, len.to_be_bytes()[1], &mut mock_ram_interface);
+        file_device.write(0xa, len.to_be_bytes()[0], &mut mock_ram_interface).unwrap();
+        file_device.write(0xb, len.to_be_bytes()[1], &mut mock_ram_interface).unwrap();
 
         // write to the file device, setting the address that the
         // file name should be read from
-        file_device.write(0x8, 0xcc, &mut mock_ram_interface);
-        file_device.write(0x9, 0xcd, &mut mock_ram_interface);
+        file_device.write(0x8, 0xcc, &mut mock_ram_interface).unwrap();
+        file_device.write(0x9, 0xcd, &mut mock_ram_interface).unwrap();
 
         let mut expected_start_address = 0xcccd;
         let read_arguments_expected = test_dir_path.bytes()
@@ -843,8 +1020,8 @@ mod tests {
         // write to the address(stat) port, and assert that the correct
         // expected string ('---- test_dir_<guid>\n') has been written
         // to the correct location of the mock ram interface
-        file_device.write(0x4, 0x12, &mut mock_ram_interface);
-        file_device.write(0x5, 0x34, &mut mock_ram_interface);
+        file_device.write(0x4, 0x12, &mut mock_ram_interface).unwrap();
+        file_device.write(0x5, 0x34, &mut mock_ram_interface).unwrap();
 
         let expected_output = (0x1234, format!("---- {}\n", test_dir_name));
         let write_arguments_received = mock_ram_interface.write_arguments_received.borrow_mut().pop_front().unwrap();
@@ -854,8 +1031,8 @@ mod tests {
         // assert that the 'success' field has been written to with the 
         // expected number of bytes
         let success = u16::from_be_bytes([
-            file_device.read(0x2),
-            file_device.read(0x3),
+            file_device.read(0x2).unwrap(),
+            file_device.read(0x3).unwrap(),
         ]);
         assert_eq!(success, u16::try_from(len).unwrap());
 
@@ -884,13 +1061,13 @@ mod tests {
 
         // set length of memory area the file stat should be written to
         let len = 99_u16;
-        file_device.write(0xa, len.to_be_bytes()[0], &mut mock_ram_interface);
-        file_device.write(0xb, len.to_be_bytes()[1], &mut mock_ram_interface);
+        file_device.write(0xa, len.to_be_bytes()[0], &mut mock_ram_interface).unwrap();
+        file_device.write(0xb, len.to_be_bytes()[1], &mut mock_ram_interface).unwrap();
 
         // write to the file device, setting the address that the
         // file name should be read from
-        file_device.write(0x8, 0xcc, &mut mock_ram_interface);
-        file_device.write(0x9, 0xcd, &mut mock_ram_interface);
+        file_device.write(0x8, 0xcc, &mut mock_ram_interface).unwrap();
+        file_device.write(0x9, 0xcd, &mut mock_ram_interface).unwrap();
 
         let mut expected_start_address = 0xcccd;
         let read_arguments_expected = non_existent_file_path.bytes()
@@ -907,14 +1084,14 @@ mod tests {
             read_arguments_expected);
 
         // write to the address(stat) port
-        file_device.write(0x4, 0x12, &mut mock_ram_interface);
-        file_device.write(0x5, 0x34, &mut mock_ram_interface);
+        file_device.write(0x4, 0x12, &mut mock_ram_interface).unwrap();
+        file_device.write(0x5, 0x34, &mut mock_ram_interface).unwrap();
 
         // assert that the 'success' field has been written to with 0
         // since the file does not exist
         let success = u16::from_be_bytes([
-            file_device.read(0x2),
-            file_device.read(0x3),
+            file_device.read(0x2).unwrap(),
+            file_device.read(0x3).unwrap(),
         ]);
         assert_eq!(success, 0_u16);
     }
@@ -944,13 +1121,13 @@ mod tests {
         // set length of memory area the file stat should be written to to be just too small
         let len = test_file_name.len() + 5;
         let len = u16::try_from(len).unwrap();
-        file_device.write(0xa, len.to_be_bytes()[0], &mut mock_ram_interface);
-        file_device.write(0xb, len.to_be_bytes()[1], &mut mock_ram_interface);
+        file_device.write(0xa, len.to_be_bytes()[0], &mut mock_ram_interface).unwrap();
+        file_device.write(0xb, len.to_be_bytes()[1], &mut mock_ram_interface).unwrap();
 
         // write to the file device, setting the address that the
         // file name should be read from
-        file_device.write(0x8, 0xcc, &mut mock_ram_interface);
-        file_device.write(0x9, 0xcd, &mut mock_ram_interface);
+        file_device.write(0x8, 0xcc, &mut mock_ram_interface).unwrap();
+        file_device.write(0x9, 0xcd, &mut mock_ram_interface).unwrap();
 
         let mut expected_start_address = 0xcccd;
         let read_arguments_expected = test_file_path.bytes()
@@ -968,12 +1145,12 @@ mod tests {
 
         // write to the address(stat) port, and assert that success is set 
         // to 0 (since there isn't enough space to write the entry)
-        file_device.write(0x4, 0x12, &mut mock_ram_interface);
-        file_device.write(0x5, 0x34, &mut mock_ram_interface);
+        file_device.write(0x4, 0x12, &mut mock_ram_interface).unwrap();
+        file_device.write(0x5, 0x34, &mut mock_ram_interface).unwrap();
 
         let success = u16::from_be_bytes([
-            file_device.read(0x2),
-            file_device.read(0x3),
+            file_device.read(0x2).unwrap(),
+            file_device.read(0x3).unwrap(),
         ]);
         assert_eq!(success, 0_u16);
         fs::remove_file(test_file_path).expect("Failed to clean up test file");
@@ -1003,8 +1180,8 @@ mod tests {
 
         // write to the file device, setting the address that the
         // file name should be read from
-        file_device.write(0x8, 0xcc, &mut mock_ram_interface);
-        file_device.write(0x9, 0xcd, &mut mock_ram_interface);
+        file_device.write(0x8, 0xcc, &mut mock_ram_interface).unwrap();
+        file_device.write(0x9, 0xcd, &mut mock_ram_interface).unwrap();
 
         let mut expected_start_address = 0xcccd;
         let read_arguments_expected = test_file_path.bytes()
@@ -1022,11 +1199,11 @@ mod tests {
 
         // write to the delete port, and assert that success is not set 
         // to 0
-        file_device.write(0x6, 0x01, &mut mock_ram_interface);
+        file_device.write(0x6, 0x01, &mut mock_ram_interface).unwrap();
 
         let success = u16::from_be_bytes([
-            file_device.read(0x2),
-            file_device.read(0x3),
+            file_device.read(0x2).unwrap(),
+            file_device.read(0x3).unwrap(),
         ]);
         assert_ne!(success, 0_u16);
 
@@ -1057,8 +1234,8 @@ mod tests {
 
         // write to the file device, setting the address that the
         // file name should be read from
-        file_device.write(0x8, 0xcc, &mut mock_ram_interface);
-        file_device.write(0x9, 0xcd, &mut mock_ram_interface);
+        file_device.write(0x8, 0xcc, &mut mock_ram_interface).unwrap();
+        file_device.write(0x9, 0xcd, &mut mock_ram_interface).unwrap();
 
         let mut expected_start_address = 0xcccd;
         let read_arguments_expected = test_file_path.bytes()
@@ -1079,8 +1256,8 @@ mod tests {
         // set length of memory area that the file contents should be read from
         let len = test_file_contents.len();
         let len = u16::try_from(len).unwrap();
-        file_device.write(0xa, len.to_be_bytes()[0], &mut mock_ram_interface);
-        file_device.write(0xb, len.to_be_bytes()[1], &mut mock_ram_interface);
+        file_device.write(0xa, len.to_be_bytes()[0], &mut mock_ram_interface).unwrap();
+        file_device.write(0xb, len.to_be_bytes()[1], &mut mock_ram_interface).unwrap();
 
         // prepare the ram interface to provide the contents of the file that is to
         // be written
@@ -1088,8 +1265,8 @@ mod tests {
             Ok(test_file_contents.clone().bytes().collect::<Vec<_>>()));
 
         // write to the addr(write) port
-        file_device.write(0xe, 0x12, &mut mock_ram_interface);
-        file_device.write(0xf, 0x34, &mut mock_ram_interface);
+        file_device.write(0xe, 0x12, &mut mock_ram_interface).unwrap();
+        file_device.write(0xf, 0x34, &mut mock_ram_interface).unwrap();
 
         // assert that the mock ram interface had its read method called with
         // the expected address and number of bytes to read
@@ -1106,8 +1283,8 @@ mod tests {
         // set length of memory area that the file contents should be read from
         let len = test_file_contents.len();
         let len = u16::try_from(len).unwrap();
-        file_device.write(0xa, len.to_be_bytes()[0], &mut mock_ram_interface);
-        file_device.write(0xb, len.to_be_bytes()[1], &mut mock_ram_interface);
+        file_device.write(0xa, len.to_be_bytes()[0], &mut mock_ram_interface).unwrap();
+        file_device.write(0xb, len.to_be_bytes()[1], &mut mock_ram_interface).unwrap();
 
         // prepare the ram interface to provide the contents of the file that is to
         // be written
@@ -1115,8 +1292,8 @@ mod tests {
             Ok(test_file_contents.clone().bytes().collect::<Vec<_>>()));
 
         // write to the addr(write) port
-        file_device.write(0xe, 0x12, &mut mock_ram_interface);
-        file_device.write(0xf, 0x34, &mut mock_ram_interface);
+        file_device.write(0xe, 0x12, &mut mock_ram_interface).unwrap();
+        file_device.write(0xf, 0x34, &mut mock_ram_interface).unwrap();
 
         // assert that the mock ram interface had its read method called with
         // the expected address and number of bytes to read
@@ -1133,8 +1310,8 @@ mod tests {
         // set length of memory area that the file contents should be read from
         let len = appended_contents.len();
         let len = u16::try_from(len).unwrap();
-        file_device.write(0xa, len.to_be_bytes()[0], &mut mock_ram_interface);
-        file_device.write(0xb, len.to_be_bytes()[1], &mut mock_ram_interface);
+        file_device.write(0xa, len.to_be_bytes()[0], &mut mock_ram_interface).unwrap();
+        file_device.write(0xb, len.to_be_bytes()[1], &mut mock_ram_interface).unwrap();
 
         // prepare the ram interface to provide the contents of the file that is to
         // be written
@@ -1142,11 +1319,11 @@ mod tests {
             Ok(appended_contents.clone().bytes().collect::<Vec<_>>()));
 
         // set the 'append' byte
-        file_device.write(0x7, 0x1, &mut mock_ram_interface);
+        file_device.write(0x7, 0x1, &mut mock_ram_interface).unwrap();
 
         // write to the addr(write) port
-        file_device.write(0xe, 0x12, &mut mock_ram_interface);
-        file_device.write(0xf, 0x34, &mut mock_ram_interface);
+        file_device.write(0xe, 0x12, &mut mock_ram_interface).unwrap();
+        file_device.write(0xf, 0x34, &mut mock_ram_interface).unwrap();
 
         // verify that the file actually exists and contains what is expected
         // (the original file contents with the new section appended)
@@ -1,4 +1,5 @@
-use crate::emulators::uxn::device::{Device, MainRamInterface};
+use crate::emulators::uxn::device::{Device, DeviceError, MainRamInterface};
+use crate::emulators::snapshot::{Snapshot, SnapshotWriter, SnapshotReader, SnapshotError};
 
 pub struct MouseDevice {
     vector: [u8; 2],
@@ -7,12 +8,19 @@ pub struct MouseDevice {
     click_state: u8,
 }
 
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum Button {
     Left,
     Middle,
     Right,
 }
 
+impl Default for MouseDevice {
+    fn default() -> Self {
+        return MouseDevice::new();
+    }
+}
+
 impl MouseDevice {
     pub fn new() -> Self {
         MouseDevice {
@@ -59,9 +67,9 @@ impl MouseDevice {
 }
 
 impl Device for MouseDevice {
-    fn write(&mut self, port: u8, val: u8, _main_ram: &mut dyn MainRamInterface) {
+    fn write(&mut self, port: u8, val: u8, _main_ram: &mut dyn MainRamInterface) -> Result<(), DeviceError> {
         if port > 0xf {
-            panic!("attempting to write to port out of range");
+            return Err(DeviceError::PortOutOfRange(port));
         }
 
         match port {
@@ -73,32 +81,53 @@ impl Device for MouseDevice {
             },
             _ => {}
         }
+
+        return Ok(());
     }
 
-    fn read(&mut self, port: u8) -> u8 {
+    fn read(&mut self, port: u8) -> Result<u8, DeviceError> {
         if port > 0xf {
-            panic!("attempting to read from port out of range");
+            return Err(DeviceError::PortOutOfRange(port));
         }
 
         match port {
-            0x0 => return self.vector[0],
-            0x1 => return self.vector[1],
-            0x2 => return self.cursor_pos[0][0],
-            0x3 => return self.cursor_pos[0][1],
-            0x4 => return self.cursor_pos[1][0],
-            0x5 => return self.cursor_pos[1][1],
-            0x6 => return self.click_state,
-            0xa => return self.scroll[0][0],
-            0xb => return self.scroll[0][1],
-            0xc => return self.scroll[1][0],
-            0xd => return self.scroll[1][1],
+            0x0 => return Ok(self.vector[0]),
+            0x1 => return Ok(self.vector[1]),
+            0x2 => return Ok(self.cursor_pos[0][0]),
+            0x3 => return Ok(self.cursor_pos[0][1]),
+            0x4 => return Ok(self.cursor_pos[1][0]),
+            0x5 => return Ok(self.cursor_pos[1][1]),
+            0x6 => return Ok(self.click_state),
+            0xa => return Ok(self.scroll[0][0]),
+            0xb => return Ok(self.scroll[0][1]),
+            0xc => return Ok(self.scroll[1][0]),
+            0xd => return Ok(self.scroll[1][1]),
             _ => {
-                return 0x0;
+                return Ok(0x0);
             },
         }
     }
 }
 
+impl Snapshot for MouseDevice {
+    fn save_state(&self, writer: &mut SnapshotWriter) {
+        writer.put_bytes(&self.vector);
+        for coordinate in self.cursor_pos.iter().chain(self.scroll.iter()) {
+            writer.put_bytes(coordinate);
+        }
+        writer.put_u8(self.click_state);
+    }
+
+    fn load_state(&mut self, reader: &mut SnapshotReader) -> Result<(), SnapshotError> {
+        self.vector = reader.get_array()?;
+        for coordinate in self.cursor_pos.iter_mut().chain(self.scroll.iter_mut()) {
+            *coordinate = reader.get_array()?;
+        }
+        self.click_state = reader.get_u8()?;
+        return Ok(());
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -122,14 +151,14 @@ mod tests {
         let initial_vector = mouse_device.read_vector();
         assert_eq!(initial_vector, 0);
 
-        mouse_device.write(0x0, 0xab, &mut MockMainRamInterface{});
-        mouse_device.write(0x1, 0xcd, &mut MockMainRamInterface{});
+        mouse_device.write(0x0, 0xab, &mut MockMainRamInterface{}).unwrap();
+        mouse_device.write(0x1, 0xcd, &mut MockMainRamInterface{}).unwrap();
 
         let vector = mouse_device.read_vector();
         assert_eq!(vector, 0xabcd);
 
-        assert_eq!(mouse_device.read(0x0), 0xab);
-        assert_eq!(mouse_device.read(0x1), 0xcd);
+        assert_eq!(mouse_device.read(0x0).unwrap(), 0xab);
+        assert_eq!(mouse_device.read(0x1).unwrap(), 0xcd);
     }
 
     #[test]
@@ -138,11 +167,11 @@ mod tests {
 
         mouse_device.notify_cursor_position(&[123, 65535]);
 
-        assert_eq!(mouse_device.read(0x2), 0x00);
-        assert_eq!(mouse_device.read(0x3), 0x7b);
+        assert_eq!(mouse_device.read(0x2).unwrap(), 0x00);
+        assert_eq!(mouse_device.read(0x3).unwrap(), 0x7b);
 
-        assert_eq!(mouse_device.read(0x4), 0xff);
-        assert_eq!(mouse_device.read(0x5), 0xff);
+        assert_eq!(mouse_device.read(0x4).unwrap(), 0xff);
+        assert_eq!(mouse_device.read(0x5).unwrap(), 0xff);
     }
 
     #[test]
@@ -152,10 +181,10 @@ mod tests {
         mouse_device.notify_button_down(Button::Left);
         mouse_device.notify_button_down(Button::Right);
 
-        assert_eq!(mouse_device.read(0x6), 1 | (1<<2));
+        assert_eq!(mouse_device.read(0x6).unwrap(), 1 | (1<<2));
 
         mouse_device.notify_button_up(Button::Right);
-        assert_eq!(mouse_device.read(0x6), 1);
+        assert_eq!(mouse_device.read(0x6).unwrap(), 1);
     }
 
     #[test]
@@ -164,10 +193,10 @@ mod tests {
 
         mouse_device.notify_scroll(&[2, -1]);
 
-        assert_eq!(mouse_device.read(0xa), 0x00);
-        assert_eq!(mouse_device.read(0xb), 0x02);
+        assert_eq!(mouse_device.read(0xa).unwrap(), 0x00);
+        assert_eq!(mouse_device.read(0xb).unwrap(), 0x02);
 
-        assert_eq!(mouse_device.read(0xc), 0xff);
-        assert_eq!(mouse_device.read(0xd), 0xff);
+        assert_eq!(mouse_device.read(0xc).unwrap(), 0xff);
+        assert_eq!(mouse_device.read(0xd).unwrap(), 0xff);
     }
 }
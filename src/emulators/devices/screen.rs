@@ -1,4 +1,5 @@
-use crate::emulators::uxn::device::{Device, MainRamInterface};
+use crate::emulators::uxn::device::{Device, DeviceError, MainRamInterface};
+use crate::emulators::snapshot::{Snapshot, SnapshotWriter, SnapshotReader, SnapshotError};
 use std::collections::HashMap;
 
 pub trait UxnSystemScreenInterface {
@@ -13,6 +14,17 @@ pub enum UxnColorIndex {
     Three,
 }
 
+impl From<UxnColorIndex> for u8 {
+    fn from(value: UxnColorIndex) -> u8 {
+        match value {
+            UxnColorIndex::Zero => 0,
+            UxnColorIndex::One => 1,
+            UxnColorIndex::Two => 2,
+            UxnColorIndex::Three => 3,
+        }
+    }
+}
+
 impl TryFrom<u8> for UxnColorIndex {
     type Error = &'static str;
 
@@ -161,7 +173,7 @@ impl ScreenDevice {
         }
     }
 
-    fn sprites_write(&mut self, val: u8, main_ram: &mut dyn MainRamInterface) {
+    fn sprites_write(&mut self, val: u8, main_ram: &mut dyn MainRamInterface) -> Result<(), DeviceError> {
         let palette_choice = val & 0xf;
         let flip_x = if (val & 0x10) != 0 { true } else { false };
         let flip_y = if (val & 0x20) != 0 { true } else { false };
@@ -193,17 +205,17 @@ impl ScreenDevice {
         for _i in 0..self.sprite_repeat+1 {
             self.sprite_write(sprite_address, two_bpp, layer, target_x, target_y, 
                               &palette, color_0_transparent,
-                              flip_x, flip_y, main_ram);
-            sprite_address += address_inc;
+                              flip_x, flip_y, main_ram)?;
+            sprite_address = sprite_address.wrapping_add(address_inc);
 
             // if x is set to auto-increment at the end of the drawing operation then within
             // the drawing operation increment y (this enables drawing of arbitrary rectangular
             // sprites by setting `sprite_repeat+1` to be equal to the height of the rectangular
             // sprite, and repeating the whole drawing operation the 'width of the sprite' times)
-            target_y += if self.auto_inc_x { 8 } else { 0 };
+            target_y = target_y.wrapping_add(if self.auto_inc_x { 8 } else { 0 });
 
             // similar logic for if y is set to increment at the end of the drawing operation
-            target_x += if self.auto_inc_y { 8 } else { 0 };
+            target_x = target_x.wrapping_add(if self.auto_inc_y { 8 } else { 0 });
         }
 
         // save sprite_address and target_location if auto incremented.
@@ -217,39 +229,41 @@ impl ScreenDevice {
         if self.auto_inc_x {
             let target_x = u16::from_be_bytes(
                 [self.target_location[0][0], self.target_location[0][1]]);
-            [self.target_location[0][0], self.target_location[0][1]] = (target_x + 8).to_be_bytes();
+            [self.target_location[0][0], self.target_location[0][1]] = target_x.wrapping_add(8).to_be_bytes();
         }
         if self.auto_inc_y {
             let target_y = u16::from_be_bytes(
                 [self.target_location[1][0], self.target_location[1][1]]);
-            [self.target_location[1][0], self.target_location[1][1]] = (target_y + 8).to_be_bytes();
+            [self.target_location[1][0], self.target_location[1][1]] = target_y.wrapping_add(8).to_be_bytes();
         }
+
+        return Ok(());
     }
 
     fn sprite_write(&mut self, sprite_address: u16, two_bpp: bool, layer: usize, target_x: u16, target_y: u16,
                     palette: &[UxnColorIndex; 4], color_0_transparent: bool,
                     flip_x: bool, flip_y: bool,
-                    main_ram: &mut dyn MainRamInterface) {
-        let sprite_bytes = main_ram.read(sprite_address, SPRITE_SIZE_1BPP).expect(
-            "could not read sprite bytes from memory");
+                    main_ram: &mut dyn MainRamInterface) -> Result<(), DeviceError> {
+        let sprite_bytes = main_ram.read(sprite_address, SPRITE_SIZE_1BPP)?;
 
         // for two bpp sprites the higher bit comes from the next SPRITE_SIZE_1BPP 
         // bytes of memory. In case of one bpp sprites can therefore just replace
         // this with SPRITE_SIZE_1BPP's worth of zeros
         let higher_sprite_bytes = if two_bpp {
             // read from sprite_address + SPRITE_SIZE_1BPP
-            main_ram.read(sprite_address+SPRITE_SIZE_1BPP, SPRITE_SIZE_1BPP).expect(
-                "could not read upper sprite bytes from memory")
+            let higher_sprite_address = sprite_address.checked_add(SPRITE_SIZE_1BPP)
+                .ok_or(DeviceError::OutOfRangeMemoryAddress)?;
+            main_ram.read(higher_sprite_address, SPRITE_SIZE_1BPP)?
         } else {
             // produce SPRITE_SIZE_1BPP's worth of zeros
             vec![0u8; SPRITE_SIZE_1BPP.into()]
         };
 
-        let mut current_y = if flip_y { target_y + 7 } else { target_y };
+        let mut current_y = if flip_y { target_y.wrapping_add(7) } else { target_y };
         let increment_x = if flip_x { -1 } else { 1 };
         let increment_y = if flip_y { -1 } else { 1 };
         for (bit_row_higher, bit_row) in higher_sprite_bytes.iter().zip(sprite_bytes) {
-            let mut current_x = if flip_x { target_x + 7 } else { target_x };
+            let mut current_x = if flip_x { target_x.wrapping_add(7) } else { target_x };
 
             for bit_index_x in (0..8).rev() {
                 let sprite_pixel_val_higher_bit = (bit_row_higher >> bit_index_x) & 1;
@@ -271,6 +285,8 @@ impl ScreenDevice {
 
             current_y = (i32::try_from(current_y).unwrap() + increment_y) as u16;
         }
+
+        return Ok(());
     }
 
     fn update_system_colors(&mut self) {
@@ -374,10 +390,57 @@ impl ScreenDevice {
     }
 }
 
+impl Snapshot for ScreenDevice {
+    fn save_state(&self, writer: &mut SnapshotWriter) {
+        writer.put_bytes(&self.vector);
+        writer.put_bytes(&self.dim[0]);
+        writer.put_bytes(&self.dim[1]);
+
+        for layer in &self.layers {
+            for pixel in layer.pixels.iter().flatten() {
+                writer.put_u8(u8::from(*pixel));
+            }
+        }
+
+        writer.put_u8(self.auto_byte);
+        writer.put_bytes(&self.target_location[0]);
+        writer.put_bytes(&self.target_location[1]);
+        writer.put_bytes(&self.sprite_address);
+        writer.put_u8(self.last_pixel_value);
+        writer.put_u8(self.last_sprite_value);
+        writer.put_bytes(&self.system_colors_raw);
+    }
+
+    fn load_state(&mut self, reader: &mut SnapshotReader) -> Result<(), SnapshotError> {
+        self.vector = reader.get_array()?;
+        self.dim = [reader.get_array()?, reader.get_array()?];
+
+        // resizing allocates layers with the saved dimensions, which are then filled in
+        self.resize();
+        for layer in self.layers.iter_mut() {
+            for pixel in layer.pixels.iter_mut().flatten() {
+                *pixel = UxnColorIndex::try_from(reader.get_u8()?)
+                    .map_err(|_| SnapshotError::InvalidData("invalid screen pixel"))?;
+            }
+        }
+
+        self.auto_byte = reader.get_u8()?;
+        self.set_auto(self.auto_byte);
+        self.target_location = [reader.get_array()?, reader.get_array()?];
+        self.sprite_address = reader.get_array()?;
+        self.last_pixel_value = reader.get_u8()?;
+        self.last_sprite_value = reader.get_u8()?;
+        self.system_colors_raw = reader.get_array()?;
+        self.update_system_colors();
+
+        return Ok(());
+    }
+}
+
 impl Device for ScreenDevice {
-    fn write(&mut self, port: u8, val: u8, main_ram: &mut dyn MainRamInterface) {
+    fn write(&mut self, port: u8, val: u8, main_ram: &mut dyn MainRamInterface) -> Result<(), DeviceError> {
         if port > 0xf {
-            panic!("attempting to write to port out of range");
+            return Err(DeviceError::PortOutOfRange(port));
         }
 
         match port {
@@ -429,32 +492,38 @@ impl Device for ScreenDevice {
             },
             0xf => {
                 self.last_sprite_value = val;
-                self.sprites_write(val, main_ram);
+                self.sprites_write(val, main_ram)?;
             },
             _ => {}
         }
+
+        return Ok(());
     }
 
-    fn read(&mut self, port: u8) -> u8 {
+    fn read(&mut self, port: u8) -> Result<u8, DeviceError> {
+        if port > 0xf {
+            return Err(DeviceError::PortOutOfRange(port));
+        }
+
         match port {
-            0x0 => return self.vector[0],
-            0x1 => return self.vector[1],
-            0x2 => return self.dim[0][0],
-            0x3 => return self.dim[0][1],
-            0x4 => return self.dim[1][0],
-            0x5 => return self.dim[1][1],
-            0x6 => return self.auto_byte,
-            0x8 => return self.target_location[0][0],
-            0x9 => return self.target_location[0][1],
-            0xa => return self.target_location[1][0],
-            0xb => return self.target_location[1][1],
-            0xc => return self.sprite_address[0],
-            0xd => return self.sprite_address[1],
-            0xe => return self.last_pixel_value,
+            0x0 => return Ok(self.vector[0]),
+            0x1 => return Ok(self.vector[1]),
+            0x2 => return Ok(self.dim[0][0]),
+            0x3 => return Ok(self.dim[0][1]),
+            0x4 => return Ok(self.dim[1][0]),
+            0x5 => return Ok(self.dim[1][1]),
+            0x6 => return Ok(self.auto_byte),
+            0x8 => return Ok(self.target_location[0][0]),
+            0x9 => return Ok(self.target_location[0][1]),
+            0xa => return Ok(self.target_location[1][0]),
+            0xb => return Ok(self.target_location[1][1]),
+            0xc => return Ok(self.sprite_address[0]),
+            0xd => return Ok(self.sprite_address[1]),
+            0xe => return Ok(self.last_pixel_value),
             _ => {},
         }
 
-        return 0;
+        return Ok(0);
     }
 }
 
@@ -518,15 +587,15 @@ mod tests {
 
         // set location to (0x18, 0x2d)
         let target_x = u16::to_be_bytes(0x18);
-        screen.write(0x8, target_x[0], &mut mock_ram_interface);
-        screen.write(0x9, target_x[1], &mut mock_ram_interface);
+        screen.write(0x8, target_x[0], &mut mock_ram_interface).unwrap();
+        screen.write(0x9, target_x[1], &mut mock_ram_interface).unwrap();
         let target_y = u16::to_be_bytes(0x2d);
-        screen.write(0xa, target_y[0], &mut mock_ram_interface);
-        screen.write(0xb, target_y[1], &mut mock_ram_interface);
+        screen.write(0xa, target_y[0], &mut mock_ram_interface).unwrap();
+        screen.write(0xb, target_y[1], &mut mock_ram_interface).unwrap();
 
         // set the background to colour index 2 and paint the pixel
         let color = 0x02; 
-        screen.write(0xe, color, &mut mock_ram_interface);
+        screen.write(0xe, color, &mut mock_ram_interface).unwrap();
 
         let mut expected_pixels = vec![[0x00_u8, 0x44_u8, 0x88_u8]; 0x1f*0x2f];
         expected_pixels[0x1f*0x2d + 0x18] = [0x22, 0x66, 0xaa];
@@ -542,6 +611,48 @@ mod tests {
         screen.draw(&mut draw_fn);
     }
 
+    // draw a pixel, save the state of the screen and restore it into a screen of a different
+    // size. Assert that the restored screen draws the same bitmap, without needing the system
+    // colours to be looked up again
+    #[test]
+    fn test_save_and_load_state() {
+        let mut screen = ScreenDevice::new(&[0x1f, 0x2f]);
+        let mut mock_ram_interface = MockMainRamInterface::new();
+        let mock_system_screen_interface = MockUxnSystemScreenInterface{
+            system_colors_raw: [0x01, 0x23, 0x45, 0x67, 0x89, 0xab]};
+        assert_eq!(screen.get_draw_required(&mock_system_screen_interface), true);
+
+        // set auto x and location to (0x18, 0x2d), then paint the foreground with colour index 1
+        screen.write(0x6, 0x01, &mut mock_ram_interface).unwrap();
+        screen.write(0x9, 0x18, &mut mock_ram_interface).unwrap();
+        screen.write(0xb, 0x2d, &mut mock_ram_interface).unwrap();
+        screen.write(0xe, 0x41, &mut mock_ram_interface).unwrap();
+
+        let mut writer = SnapshotWriter::new();
+        screen.save_state(&mut writer);
+        let state = writer.into_bytes();
+
+        let mut restored = ScreenDevice::new(&[8, 8]);
+        restored.load_state(&mut SnapshotReader::new(&state)).unwrap();
+
+        let mut expected_pixels = vec![[0x00_u8, 0x44_u8, 0x88_u8]; 0x1f*0x2f];
+        expected_pixels[0x1f*0x2d + 0x18] = [0x11, 0x55, 0x99];
+        let expected_pixels = expected_pixels
+            .into_iter().flatten().collect::<Vec<_>>();
+
+        let mut draw_fn = |dim: &[u16; 2], pixels: &[u8]| {
+            assert_eq!(pixels, &expected_pixels);
+            assert_eq!(&[0x1f, 0x2f], dim);
+        };
+
+        assert_eq!(restored.get_draw_required(&mock_system_screen_interface), true);
+        restored.draw(&mut draw_fn);
+
+        // the auto byte and target location should also have been restored
+        assert_eq!(restored.read(0x6).unwrap(), 0x01);
+        assert_eq!(restored.read(0x9).unwrap(), 0x19);
+    }
+
     // drawing a pixel to screen, assert that calling get_draw_required only returns true if
     // something has changed
     #[test]
@@ -552,12 +663,12 @@ mod tests {
             system_colors_raw: [0x01, 0x23, 0x45, 0x67, 0x89, 0xab]};
 
         // set location to (2, 3)
-        screen.write(0x9, 2, &mut mock_ram_interface);
-        screen.write(0xb, 3, &mut mock_ram_interface);
+        screen.write(0x9, 2, &mut mock_ram_interface).unwrap();
+        screen.write(0xb, 3, &mut mock_ram_interface).unwrap();
 
         // set the background to colour index 3 and paint the pixel
         let color = 0x03; 
-        screen.write(0xe, color, &mut mock_ram_interface);
+        screen.write(0xe, color, &mut mock_ram_interface).unwrap();
 
         let mut expected_pixels = vec![[0x00_u8, 0x44_u8, 0x88_u8]; 16*9];
         expected_pixels[16*3 + 2] = [0x33, 0x77, 0xbb];
@@ -576,10 +687,10 @@ mod tests {
         assert_eq!(screen.get_draw_required(&mock_system_screen_interface), false);
 
         // set location to (0, 0) and draw a pixel colour index 1 (on foreground)
-        screen.write(0x9, 0, &mut mock_ram_interface);
-        screen.write(0xb, 0, &mut mock_ram_interface);
+        screen.write(0x9, 0, &mut mock_ram_interface).unwrap();
+        screen.write(0xb, 0, &mut mock_ram_interface).unwrap();
         let color = 0x41; 
-        screen.write(0xe, color, &mut mock_ram_interface);
+        screen.write(0xe, color, &mut mock_ram_interface).unwrap();
 
         // now that something has changed, draw_fn should be called with new bitmap
         let mut expected_pixels = vec![[0x00_u8, 0x44_u8, 0x88_u8]; 16*9];
@@ -604,12 +715,12 @@ mod tests {
             system_colors_raw: [0x01, 0x23, 0x45, 0x67, 0x89, 0xab]};
 
         // set location to (2, 3)
-        screen.write(0x9, 2, &mut mock_ram_interface);
-        screen.write(0xb, 3, &mut mock_ram_interface);
+        screen.write(0x9, 2, &mut mock_ram_interface).unwrap();
+        screen.write(0xb, 3, &mut mock_ram_interface).unwrap();
 
         // set the background to colour index 3 and paint the pixel
         let color = 0x03; 
-        screen.write(0xe, color, &mut mock_ram_interface);
+        screen.write(0xe, color, &mut mock_ram_interface).unwrap();
 
         let mut expected_pixels = vec![[0x00_u8, 0x44_u8, 0x88_u8]; 16*9];
         expected_pixels[16*3 + 2] = [0x33, 0x77, 0xbb];
@@ -653,12 +764,12 @@ mod tests {
             system_colors_raw: [0x01, 0x23, 0x45, 0x67, 0x89, 0xab]};
 
         // set location to (2, 3)
-        screen.write(0x9, 2, &mut mock_ram_interface);
-        screen.write(0xb, 3, &mut mock_ram_interface);
+        screen.write(0x9, 2, &mut mock_ram_interface).unwrap();
+        screen.write(0xb, 3, &mut mock_ram_interface).unwrap();
 
         // set the background to colour index 3 and paint the pixel
         let color = 0x03; 
-        screen.write(0xe, color, &mut mock_ram_interface);
+        screen.write(0xe, color, &mut mock_ram_interface).unwrap();
 
         let mut expected_pixels = vec![[0x00_u8, 0x44_u8, 0x88_u8]; 16*9];
         expected_pixels[16*3 + 2] = [0x33, 0x77, 0xbb];
@@ -675,7 +786,7 @@ mod tests {
 
         // set the foreground to color index 1 and paint the pixel
         let color = 0x41; 
-        screen.write(0xe, color, &mut mock_ram_interface);
+        screen.write(0xe, color, &mut mock_ram_interface).unwrap();
 
         let mut expected_pixels = vec![[0x00_u8, 0x44_u8, 0x88_u8]; 16*9];
         expected_pixels[16*3 + 2] = [0x11, 0x55, 0x99];
@@ -693,7 +804,7 @@ mod tests {
         // set foreground to color index 0 so that background should show
         // through again
         let color = 0x40; 
-        screen.write(0xe, color, &mut mock_ram_interface);
+        screen.write(0xe, color, &mut mock_ram_interface).unwrap();
 
         let mut expected_pixels = vec![[0x00_u8, 0x44_u8, 0x88_u8]; 16*9];
         expected_pixels[16*3 + 2] = [0x33, 0x77, 0xbb];
@@ -717,12 +828,12 @@ mod tests {
             system_colors_raw: [0x01, 0x23, 0x45, 0x67, 0x89, 0xab]};
 
         // set location to (2, 3)
-        screen.write(0x9, 2, &mut mock_ram_interface);
-        screen.write(0xb, 3, &mut mock_ram_interface);
+        screen.write(0x9, 2, &mut mock_ram_interface).unwrap();
+        screen.write(0xb, 3, &mut mock_ram_interface).unwrap();
 
         // set the background to colour index 3 and paint the pixel
         let color = 0x03; 
-        screen.write(0xe, color, &mut mock_ram_interface);
+        screen.write(0xe, color, &mut mock_ram_interface).unwrap();
 
         let mut expected_pixels = vec![[0x00_u8, 0x44_u8, 0x88_u8]; 16*9];
         expected_pixels[16*3 + 2] = [0x33, 0x77, 0xbb];
@@ -740,8 +851,8 @@ mod tests {
         // change the width dimension
         let new_width = 12_u16;
         let new_width_bytes = new_width.to_be_bytes();
-        screen.write(0x2, new_width_bytes[0], &mut mock_ram_interface);
-        screen.write(0x3, new_width_bytes[1], &mut mock_ram_interface);
+        screen.write(0x2, new_width_bytes[0], &mut mock_ram_interface).unwrap();
+        screen.write(0x3, new_width_bytes[1], &mut mock_ram_interface).unwrap();
 
         // screen should now be of new dimension, and blank
         let expected_pixels = vec![[0x00_u8, 0x44_u8, 0x88_u8]; 12*9];
@@ -760,8 +871,8 @@ mod tests {
         // change the height dimension
         let new_height = 4_u16;
         let new_height_bytes = new_height.to_be_bytes();
-        screen.write(0x4, new_height_bytes[0], &mut mock_ram_interface);
-        screen.write(0x5, new_height_bytes[1], &mut mock_ram_interface);
+        screen.write(0x4, new_height_bytes[0], &mut mock_ram_interface).unwrap();
+        screen.write(0x5, new_height_bytes[1], &mut mock_ram_interface).unwrap();
 
         // screen should now be of new dimension, and blank
         let expected_pixels = vec![[0x00_u8, 0x44_u8, 0x88_u8]; 12*4];
@@ -787,19 +898,19 @@ mod tests {
             system_colors_raw: [0x01, 0x23, 0x45, 0x67, 0x89, 0xab]};
 
         // set location to (2, 3)
-        screen.write(0x9, 2, &mut mock_ram_interface);
-        screen.write(0xb, 3, &mut mock_ram_interface);
+        screen.write(0x9, 2, &mut mock_ram_interface).unwrap();
+        screen.write(0xb, 3, &mut mock_ram_interface).unwrap();
 
         // set the auto byte to increment x
-        screen.write(0x6, 0x1, &mut mock_ram_interface);
+        screen.write(0x6, 0x1, &mut mock_ram_interface).unwrap();
 
         // set the background to colour index 3 and paint the pixel
         let color = 0x03; 
-        screen.write(0xe, color, &mut mock_ram_interface);
+        screen.write(0xe, color, &mut mock_ram_interface).unwrap();
 
         // x coordinate should be incremented by 1
-        let new_x = u16::from_be_bytes([screen.read(0x8), screen.read(0x9)]);
-        let new_y = u16::from_be_bytes([screen.read(0xa), screen.read(0xb)]);
+        let new_x = u16::from_be_bytes([screen.read(0x8).unwrap(), screen.read(0x9).unwrap()]);
+        let new_y = u16::from_be_bytes([screen.read(0xa).unwrap(), screen.read(0xb).unwrap()]);
         assert_eq!([new_x, new_y], [3, 3]);
 
         let mut expected_pixels = vec![[0x00_u8, 0x44_u8, 0x88_u8]; 16*9];
@@ -817,7 +928,7 @@ mod tests {
 
         // set the foreground to colour index 1 and paint the pixel
         let color = 0x11; 
-        screen.write(0xe, color, &mut mock_ram_interface);
+        screen.write(0xe, color, &mut mock_ram_interface).unwrap();
 
         // pixel that is painted should have x coordinate incremented by 1
         let mut expected_pixels = vec![[0x00_u8, 0x44_u8, 0x88_u8]; 16*9];
@@ -833,22 +944,22 @@ mod tests {
         screen.draw(&mut draw_fn);
 
         // set the auto byte to increment x and y simulataneously
-        screen.write(0x6, 0x3, &mut mock_ram_interface);
+        screen.write(0x6, 0x3, &mut mock_ram_interface).unwrap();
         // set the foreground to colour index 1 and paint the pixel (location will be 4,3)
         let color = 0x11; 
 
-        let new_x = u16::from_be_bytes([screen.read(0x8), screen.read(0x9)]);
-        let new_y = u16::from_be_bytes([screen.read(0xa), screen.read(0xb)]);
+        let new_x = u16::from_be_bytes([screen.read(0x8).unwrap(), screen.read(0x9).unwrap()]);
+        let new_y = u16::from_be_bytes([screen.read(0xa).unwrap(), screen.read(0xb).unwrap()]);
         assert_eq!([new_x, new_y], [4, 3]);
 
-        screen.write(0xe, color, &mut mock_ram_interface);
+        screen.write(0xe, color, &mut mock_ram_interface).unwrap();
 
         // x and y coordinate should be both incremented by 1
-        let new_x = u16::from_be_bytes([screen.read(0x8), screen.read(0x9)]);
-        let new_y = u16::from_be_bytes([screen.read(0xa), screen.read(0xb)]);
+        let new_x = u16::from_be_bytes([screen.read(0x8).unwrap(), screen.read(0x9).unwrap()]);
+        let new_y = u16::from_be_bytes([screen.read(0xa).unwrap(), screen.read(0xb).unwrap()]);
         assert_eq!([new_x, new_y], [5, 4]);
 
-        screen.write(0xe, color, &mut mock_ram_interface);
+        screen.write(0xe, color, &mut mock_ram_interface).unwrap();
 
         let mut expected_pixels = vec![[0x00_u8, 0x44_u8, 0x88_u8]; 16*9];
         expected_pixels[16*3 + 2] = [0x33, 0x77, 0xbb];
@@ -876,11 +987,11 @@ mod tests {
 
         // set location to (0x01, 0x03)
         let target_x = 0x01u16;
-        screen.write(0x8, target_x.to_be_bytes()[0], &mut mock_ram_interface);
-        screen.write(0x9, target_x.to_be_bytes()[1], &mut mock_ram_interface);
+        screen.write(0x8, target_x.to_be_bytes()[0], &mut mock_ram_interface).unwrap();
+        screen.write(0x9, target_x.to_be_bytes()[1], &mut mock_ram_interface).unwrap();
         let target_y = 0x03u16;
-        screen.write(0xa, target_y.to_be_bytes()[0], &mut mock_ram_interface);
-        screen.write(0xb, target_y.to_be_bytes()[1], &mut mock_ram_interface);
+        screen.write(0xa, target_y.to_be_bytes()[0], &mut mock_ram_interface).unwrap();
+        screen.write(0xb, target_y.to_be_bytes()[1], &mut mock_ram_interface).unwrap();
 
         // fill the mock ram with data for a 1bpp sprite
         mock_ram_interface.read_values_to_return = RefCell::new(
@@ -888,14 +999,14 @@ mod tests {
 
         // set the address for the sprite
         let test_sprite_address = 0xaabbu16;
-        screen.write(0xc, test_sprite_address.to_be_bytes()[0], &mut mock_ram_interface);
-        screen.write(0xd, test_sprite_address.to_be_bytes()[1], &mut mock_ram_interface);
+        screen.write(0xc, test_sprite_address.to_be_bytes()[0], &mut mock_ram_interface).unwrap();
+        screen.write(0xd, test_sprite_address.to_be_bytes()[1], &mut mock_ram_interface).unwrap();
 
         // paint the sprite, using palette of index 6. Bits in the sprite data of value 1 will be
         // colored UxnColorIndex::Two (rgb 0x22,0x66, 0xaa), bits in the sprite data of value 0
         // will be colored UxnColorIndex::One (rbg 0x11, 0x55, 0x99)
         let val = 0x06; 
-        screen.write(0xf, val, &mut mock_ram_interface);
+        screen.write(0xf, val, &mut mock_ram_interface).unwrap();
 
         let mut expected_pixels = vec![[0x00_u8, 0x44_u8, 0x88_u8]; 0x0f*0x0f];
         for row in 0..4 {
@@ -948,11 +1059,11 @@ mod tests {
 
         // set location to (0x01, 0x03)
         let target_x = 0x01u16;
-        screen.write(0x8, target_x.to_be_bytes()[0], &mut mock_ram_interface);
-        screen.write(0x9, target_x.to_be_bytes()[1], &mut mock_ram_interface);
+        screen.write(0x8, target_x.to_be_bytes()[0], &mut mock_ram_interface).unwrap();
+        screen.write(0x9, target_x.to_be_bytes()[1], &mut mock_ram_interface).unwrap();
         let target_y = 0x03u16;
-        screen.write(0xa, target_y.to_be_bytes()[0], &mut mock_ram_interface);
-        screen.write(0xb, target_y.to_be_bytes()[1], &mut mock_ram_interface);
+        screen.write(0xa, target_y.to_be_bytes()[0], &mut mock_ram_interface).unwrap();
+        screen.write(0xb, target_y.to_be_bytes()[1], &mut mock_ram_interface).unwrap();
 
         // fill the mock ram with data for a 1bpp sprite
         mock_ram_interface.read_values_to_return = RefCell::new(
@@ -960,12 +1071,12 @@ mod tests {
 
         // set the address for the sprite
         let test_sprite_address = 0xaabbu16;
-        screen.write(0xc, test_sprite_address.to_be_bytes()[0], &mut mock_ram_interface);
-        screen.write(0xd, test_sprite_address.to_be_bytes()[1], &mut mock_ram_interface);
+        screen.write(0xc, test_sprite_address.to_be_bytes()[0], &mut mock_ram_interface).unwrap();
+        screen.write(0xd, test_sprite_address.to_be_bytes()[1], &mut mock_ram_interface).unwrap();
 
         // paint the sprite, using palette of index 6, with the 'flip x' flag on
         let val = 0x16; 
-        screen.write(0xf, val, &mut mock_ram_interface);
+        screen.write(0xf, val, &mut mock_ram_interface).unwrap();
 
         let mut expected_pixels = vec![[0x00_u8, 0x44_u8, 0x88_u8]; 0x0f*0x0f];
         for row in 0..4 {
@@ -1005,7 +1116,7 @@ mod tests {
 
         // paint the sprite again, using palette of index 6, with the 'flip y' flag on
         let val = 0x26; 
-        screen.write(0xf, val, &mut mock_ram_interface);
+        screen.write(0xf, val, &mut mock_ram_interface).unwrap();
 
         let mut expected_pixels = vec![[0x00_u8, 0x44_u8, 0x88_u8]; 0x0f*0x0f];
         for row in 0..4 {
@@ -1042,11 +1153,11 @@ mod tests {
 
         // set location to (0x01, 0x03)
         let target_x = 0x01u16;
-        screen.write(0x8, target_x.to_be_bytes()[0], &mut mock_ram_interface);
-        screen.write(0x9, target_x.to_be_bytes()[1], &mut mock_ram_interface);
+        screen.write(0x8, target_x.to_be_bytes()[0], &mut mock_ram_interface).unwrap();
+        screen.write(0x9, target_x.to_be_bytes()[1], &mut mock_ram_interface).unwrap();
         let target_y = 0x03u16;
-        screen.write(0xa, target_y.to_be_bytes()[0], &mut mock_ram_interface);
-        screen.write(0xb, target_y.to_be_bytes()[1], &mut mock_ram_interface);
+        screen.write(0xa, target_y.to_be_bytes()[0], &mut mock_ram_interface).unwrap();
+        screen.write(0xb, target_y.to_be_bytes()[1], &mut mock_ram_interface).unwrap();
 
         // fill the mock ram with data for a 1bpp sprite
         mock_ram_interface.read_values_to_return = RefCell::new(
@@ -1054,12 +1165,12 @@ mod tests {
 
         // set the address for the sprite
         let test_sprite_address = 0xaabbu16;
-        screen.write(0xc, test_sprite_address.to_be_bytes()[0], &mut mock_ram_interface);
-        screen.write(0xd, test_sprite_address.to_be_bytes()[1], &mut mock_ram_interface);
+        screen.write(0xc, test_sprite_address.to_be_bytes()[0], &mut mock_ram_interface).unwrap();
+        screen.write(0xd, test_sprite_address.to_be_bytes()[1], &mut mock_ram_interface).unwrap();
 
         // paint the sprite, using palette of index 6 onto the background
         let val = 0x06; 
-        screen.write(0xf, val, &mut mock_ram_interface);
+        screen.write(0xf, val, &mut mock_ram_interface).unwrap();
         let mut draw_fn = |_dim: &[u16; 2], _pixels: &[u8]| {};
         assert_eq!(screen.get_draw_required(&mock_system_screen_interface), true);
         screen.draw(&mut draw_fn);
@@ -1070,7 +1181,7 @@ mod tests {
 
         // paint the sprite, using palette of index 3 onto the foreground
         let val = 0x43; 
-        screen.write(0xf, val, &mut mock_ram_interface);
+        screen.write(0xf, val, &mut mock_ram_interface).unwrap();
 
         // since palette 3 uses the color UxnColorIndex::Zero for pixels bits of value 0,
         // those pixels should let the background sprite (colored UxnColorIndex::Two)
@@ -1105,7 +1216,7 @@ mod tests {
 
         // paint the sprite, using palette of index 5 onto the foreground
         let val = 0x45; 
-        screen.write(0xf, val, &mut mock_ram_interface);
+        screen.write(0xf, val, &mut mock_ram_interface).unwrap();
 
         // in palette 5 a pixel bit of 0 is transparent (i.e. not painted at all),
         // so the background and old foreground should remain
@@ -1149,11 +1260,11 @@ mod tests {
 
         // set location to (0x01, 0x03)
         let target_x = 0x01u16;
-        screen.write(0x8, target_x.to_be_bytes()[0], &mut mock_ram_interface);
-        screen.write(0x9, target_x.to_be_bytes()[1], &mut mock_ram_interface);
+        screen.write(0x8, target_x.to_be_bytes()[0], &mut mock_ram_interface).unwrap();
+        screen.write(0x9, target_x.to_be_bytes()[1], &mut mock_ram_interface).unwrap();
         let target_y = 0x03u16;
-        screen.write(0xa, target_y.to_be_bytes()[0], &mut mock_ram_interface);
-        screen.write(0xb, target_y.to_be_bytes()[1], &mut mock_ram_interface);
+        screen.write(0xa, target_y.to_be_bytes()[0], &mut mock_ram_interface).unwrap();
+        screen.write(0xb, target_y.to_be_bytes()[1], &mut mock_ram_interface).unwrap();
 
         // fill the mock ram with data for a 2bpp sprite
         mock_ram_interface.read_values_to_return = RefCell::new(
@@ -1164,12 +1275,12 @@ mod tests {
 
         // set the address for the sprite
         let test_sprite_address = 0xaabbu16;
-        screen.write(0xc, test_sprite_address.to_be_bytes()[0], &mut mock_ram_interface);
-        screen.write(0xd, test_sprite_address.to_be_bytes()[1], &mut mock_ram_interface);
+        screen.write(0xc, test_sprite_address.to_be_bytes()[0], &mut mock_ram_interface).unwrap();
+        screen.write(0xd, test_sprite_address.to_be_bytes()[1], &mut mock_ram_interface).unwrap();
 
         // paint the sprite, using palette of index a
         let val = 0x8a; 
-        screen.write(0xf, val, &mut mock_ram_interface);
+        screen.write(0xf, val, &mut mock_ram_interface).unwrap();
 
         let mut expected_pixels = vec![[0x00_u8, 0x44_u8, 0x88_u8]; 0x0f*0x0f];
         // first row is all pixel values of 0x1 (UxnColorIndex::Two)
@@ -1231,20 +1342,20 @@ mod tests {
 
         // set location to (0x01, 0x03)
         let target_x = 0x01u16;
-        screen.write(0x8, target_x.to_be_bytes()[0], &mut mock_ram_interface);
-        screen.write(0x9, target_x.to_be_bytes()[1], &mut mock_ram_interface);
+        screen.write(0x8, target_x.to_be_bytes()[0], &mut mock_ram_interface).unwrap();
+        screen.write(0x9, target_x.to_be_bytes()[1], &mut mock_ram_interface).unwrap();
         let target_y = 0x03u16;
-        screen.write(0xa, target_y.to_be_bytes()[0], &mut mock_ram_interface);
-        screen.write(0xb, target_y.to_be_bytes()[1], &mut mock_ram_interface);
+        screen.write(0xa, target_y.to_be_bytes()[0], &mut mock_ram_interface).unwrap();
+        screen.write(0xb, target_y.to_be_bytes()[1], &mut mock_ram_interface).unwrap();
 
         // set the auto byte to repeat a paint operation three times, and to
         // increment x and the sprite address
-        screen.write(0x6, 0x25, &mut mock_ram_interface);
+        screen.write(0x6, 0x25, &mut mock_ram_interface).unwrap();
 
         // set the address for the sprite
         let test_sprite_address = 0xaabbu16;
-        screen.write(0xc, test_sprite_address.to_be_bytes()[0], &mut mock_ram_interface);
-        screen.write(0xd, test_sprite_address.to_be_bytes()[1], &mut mock_ram_interface);
+        screen.write(0xc, test_sprite_address.to_be_bytes()[0], &mut mock_ram_interface).unwrap();
+        screen.write(0xd, test_sprite_address.to_be_bytes()[1], &mut mock_ram_interface).unwrap();
 
         // fill the mock ram with data for three 1bpp sprites
         mock_ram_interface.read_values_to_return = RefCell::new(
@@ -1256,7 +1367,7 @@ mod tests {
 
         // paint the sprite, using palette 6
         let val = 0x06; 
-        screen.write(0xf, val, &mut mock_ram_interface);
+        screen.write(0xf, val, &mut mock_ram_interface).unwrap();
 
         // the way auto-increment works is that the coordinate *that isn't* the one set to 
         // auto-increment increases for each repeat of the length of sprites to be drawn 
@@ -1311,15 +1422,15 @@ mod tests {
 
         // test that the sprite address has been auto incremented by n*8 (where n is number of
         // repeats that were done)
-        let new_address = u16::from_be_bytes([screen.read(0xc), screen.read(0xd)]);
+        let new_address = u16::from_be_bytes([screen.read(0xc).unwrap(), screen.read(0xd).unwrap()]);
         assert_eq!(new_address, test_sprite_address + 3*8);
 
         // test the x location is increased by one sprite's width
-        let new_x = u16::from_be_bytes([screen.read(0x8), screen.read(0x9)]);
+        let new_x = u16::from_be_bytes([screen.read(0x8).unwrap(), screen.read(0x9).unwrap()]);
         assert_eq!(new_x, target_x + 8);
 
         // y location shouldn't be changed
-        let new_y = u16::from_be_bytes([screen.read(0xa), screen.read(0xb)]);
+        let new_y = u16::from_be_bytes([screen.read(0xa).unwrap(), screen.read(0xb).unwrap()]);
         assert_eq!(new_y, target_y);
     }
 
@@ -1333,20 +1444,20 @@ mod tests {
 
         // set location to (0x01, 0x03)
         let target_x = 0x01u16;
-        screen.write(0x8, target_x.to_be_bytes()[0], &mut mock_ram_interface);
-        screen.write(0x9, target_x.to_be_bytes()[1], &mut mock_ram_interface);
+        screen.write(0x8, target_x.to_be_bytes()[0], &mut mock_ram_interface).unwrap();
+        screen.write(0x9, target_x.to_be_bytes()[1], &mut mock_ram_interface).unwrap();
         let target_y = 0x03u16;
-        screen.write(0xa, target_y.to_be_bytes()[0], &mut mock_ram_interface);
-        screen.write(0xb, target_y.to_be_bytes()[1], &mut mock_ram_interface);
+        screen.write(0xa, target_y.to_be_bytes()[0], &mut mock_ram_interface).unwrap();
+        screen.write(0xb, target_y.to_be_bytes()[1], &mut mock_ram_interface).unwrap();
 
         // set the auto byte to repeat a paint operation twice, and to
         // increment y
-        screen.write(0x6, 0x12, &mut mock_ram_interface);
+        screen.write(0x6, 0x12, &mut mock_ram_interface).unwrap();
 
         // set the address for the sprite
         let test_sprite_address = 0xaabbu16;
-        screen.write(0xc, test_sprite_address.to_be_bytes()[0], &mut mock_ram_interface);
-        screen.write(0xd, test_sprite_address.to_be_bytes()[1], &mut mock_ram_interface);
+        screen.write(0xc, test_sprite_address.to_be_bytes()[0], &mut mock_ram_interface).unwrap();
+        screen.write(0xd, test_sprite_address.to_be_bytes()[1], &mut mock_ram_interface).unwrap();
 
         // fill the mock ram with data for the 2bpp sprite (note, the same chunk of 
         // memory will be read for each repeat)
@@ -1361,7 +1472,7 @@ mod tests {
 
         // paint the 2bpp sprite, using palette 6
         let val = 0x86; 
-        screen.write(0xf, val, &mut mock_ram_interface);
+        screen.write(0xf, val, &mut mock_ram_interface).unwrap();
 
         // y is set to increment at the end of the draw operation, meaning that, for the repeats
         // within the draw operation, the x coordinate is increased
@@ -1403,15 +1514,15 @@ mod tests {
         assert_eq!(received_len, expected_sprite_len);
 
         // sprite address should not have been incremented
-        let new_address = u16::from_be_bytes([screen.read(0xc), screen.read(0xd)]);
+        let new_address = u16::from_be_bytes([screen.read(0xc).unwrap(), screen.read(0xd).unwrap()]);
         assert_eq!(new_address, test_sprite_address);
 
         // x location should not have been incremented
-        let new_x = u16::from_be_bytes([screen.read(0x8), screen.read(0x9)]);
+        let new_x = u16::from_be_bytes([screen.read(0x8).unwrap(), screen.read(0x9).unwrap()]);
         assert_eq!(new_x, target_x);
 
         // y location should be incremented by one sprite's width
-        let new_y = u16::from_be_bytes([screen.read(0xa), screen.read(0xb)]);
+        let new_y = u16::from_be_bytes([screen.read(0xa).unwrap(), screen.read(0xb).unwrap()]);
         assert_eq!(new_y, target_y + 8);
     }
 
@@ -1425,11 +1536,11 @@ mod tests {
 
         // set location to (0x0d, 0x0d)
         let target_x = 0x0du16;
-        screen.write(0x8, target_x.to_be_bytes()[0], &mut mock_ram_interface);
-        screen.write(0x9, target_x.to_be_bytes()[1], &mut mock_ram_interface);
+        screen.write(0x8, target_x.to_be_bytes()[0], &mut mock_ram_interface).unwrap();
+        screen.write(0x9, target_x.to_be_bytes()[1], &mut mock_ram_interface).unwrap();
         let target_y = 0x0du16;
-        screen.write(0xa, target_y.to_be_bytes()[0], &mut mock_ram_interface);
-        screen.write(0xb, target_y.to_be_bytes()[1], &mut mock_ram_interface);
+        screen.write(0xa, target_y.to_be_bytes()[0], &mut mock_ram_interface).unwrap();
+        screen.write(0xb, target_y.to_be_bytes()[1], &mut mock_ram_interface).unwrap();
 
         // fill the mock ram with data for a 1bpp sprite
         mock_ram_interface.read_values_to_return = RefCell::new(
@@ -1437,14 +1548,14 @@ mod tests {
 
         // set the address for the sprite
         let test_sprite_address = 0xaabbu16;
-        screen.write(0xc, test_sprite_address.to_be_bytes()[0], &mut mock_ram_interface);
-        screen.write(0xd, test_sprite_address.to_be_bytes()[1], &mut mock_ram_interface);
+        screen.write(0xc, test_sprite_address.to_be_bytes()[0], &mut mock_ram_interface).unwrap();
+        screen.write(0xd, test_sprite_address.to_be_bytes()[1], &mut mock_ram_interface).unwrap();
 
         // paint the sprite, using palette of index 6. Bits in the sprite data of value 1 will be
         // colored UxnColorIndex::Two (rgb 0x22,0x66, 0xaa), bits in the sprite data of value 0
         // will be colored UxnColorIndex::One (rbg 0x11, 0x55, 0x99)
         let val = 0x06; 
-        screen.write(0xf, val, &mut mock_ram_interface);
+        screen.write(0xf, val, &mut mock_ram_interface).unwrap();
 
         // because the full sprite would go off the screen, only part of the 
         // sprite will be drawn
@@ -1466,4 +1577,30 @@ mod tests {
         assert_eq!(screen.get_draw_required(&mock_system_screen_interface), true);
         screen.draw(&mut draw_fn);
     }
+
+    // a sprite that can't be read from memory should give an error rather than a panic
+    #[test]
+    fn test_sprite_draw_out_of_range() {
+        let mut screen = ScreenDevice::new(&[0x0f, 0x0f]);
+        let mut mock_ram_interface = MockMainRamInterface::new();
+
+        mock_ram_interface.read_values_to_return = RefCell::new(
+            VecDeque::from([Err(MainRamInterfaceError::AddressOutOfBounds),]));
+        let test_sprite_address = 0xfffcu16;
+        screen.write(0xc, test_sprite_address.to_be_bytes()[0], &mut mock_ram_interface).unwrap();
+        screen.write(0xd, test_sprite_address.to_be_bytes()[1], &mut mock_ram_interface).unwrap();
+        assert_eq!(screen.write(0xf, 0x06, &mut mock_ram_interface),
+            Err(DeviceError::OutOfRangeMemoryAddress));
+
+        // for a 2bpp sprite, the upper half of the sprite would start beyond the end of memory
+        mock_ram_interface.read_values_to_return = RefCell::new(
+            VecDeque::from([Ok(vec![0xff; 8]),]));
+        let test_sprite_address = 0xfffau16;
+        screen.write(0xc, test_sprite_address.to_be_bytes()[0], &mut mock_ram_interface).unwrap();
+        screen.write(0xd, test_sprite_address.to_be_bytes()[1], &mut mock_ram_interface).unwrap();
+        assert_eq!(screen.write(0xf, 0x86, &mut mock_ram_interface),
+            Err(DeviceError::OutOfRangeMemoryAddress));
+
+        assert_eq!(screen.read(0x10), Err(DeviceError::PortOutOfRange(0x10)));
+    }
 }
@@ -1,4 +1,5 @@
 use std::io::Write;
+use crate::uxninterface::DeviceError;
 
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum UxnSystemColor {
@@ -17,6 +18,18 @@ pub trait UxnSystemInterface {
     fn set_return_stack_index(&mut self, index: u8);
     fn get_return_stack_index(&self) -> u8;
 
+    fn set_system_vector(&mut self, vector: u16);
+    fn get_system_vector(&self) -> u16;
+
+    fn set_expansion_address(&mut self, address: u16);
+    fn get_expansion_address(&self) -> u16;
+
+    // access a byte of one of the banks of memory. Bank 0 is the main ram, the others are
+    // expansion memory. Reads from a bank that doesn't exist give 0, and writes to one are
+    // ignored
+    fn read_from_bank(&self, bank: u16, address: u16) -> u8;
+    fn write_to_bank(&mut self, bank: u16, address: u16, val: u8);
+
     fn set_system_color(&mut self, slot: UxnSystemColor, val: u8);
     fn get_system_color(&self, slot: UxnSystemColor) -> u8;
 
@@ -45,20 +58,35 @@ impl<'a, J, K> System<'a, J, K>
         }
     }
 
-    pub fn write(&mut self, port: u8, val: u8) {
+    pub fn write(&mut self, port: u8, val: u8) -> Result<(), DeviceError> {
         match port {
             0x0..=0x1 => {
-                // not used
+                // set one byte of the system vector, where stack faults are handled
+                let mut vector = self.uxn.get_system_vector().to_be_bytes();
+                vector[usize::from(port)] = val;
+                self.uxn.set_system_vector(u16::from_be_bytes(vector));
             }
-            0x2 => {
+            0x2..=0x3 => {
+                // set one byte of the address of an expansion command, writing the low byte
+                // runs the command
+                let mut address = self.uxn.get_expansion_address().to_be_bytes();
+                address[usize::from(port - 0x2)] = val;
+                let address = u16::from_be_bytes(address);
+                self.uxn.set_expansion_address(address);
+
+                if port == 0x3 {
+                    self.run_expansion_command(address);
+                }
+            },
+            0x4 => {
                 // set working stack index to `val`
                 self.uxn.set_working_stack_index(val);
             },
-            0x3 => {
+            0x5 => {
                 // set return stack index to `val`
                 self.uxn.set_return_stack_index(val);
             },
-            0x4..=0x7 => {
+            0x6..=0x7 => {
                 // not used
             },
             0x8 => {
@@ -88,8 +116,7 @@ impl<'a, J, K> System<'a, J, K>
                 let working_stack_status_string = 
                     working_stack_status_string.join(" ");
 
-                writeln!(self.debug_writer, "<wst> {}", working_stack_status_string)
-                    .expect("could not write debug output");
+                writeln!(self.debug_writer, "<wst> {}", working_stack_status_string)?;
 
                 let return_stack_status_string = 
                     self.uxn.get_return_stack_iter()
@@ -98,8 +125,7 @@ impl<'a, J, K> System<'a, J, K>
                 let return_stack_status_string = 
                     return_stack_status_string.join(" ");
 
-                writeln!(self.debug_writer, "<rst> {}", return_stack_status_string)
-                    .expect("could not write debug output");
+                writeln!(self.debug_writer, "<rst> {}", return_stack_status_string)?;
             },
             0xf => {
                 // terminate application
@@ -108,45 +134,52 @@ impl<'a, J, K> System<'a, J, K>
                 }
             },
             _ => {
-                panic!("attempting to write to port out of range");
+                return Err(DeviceError::PortOutOfRange(port));
             }
         }
+
+        return Ok(());
     }
 
 
-    pub fn read(&mut self, port: u8) -> u8 {
+    pub fn read(&mut self, port: u8) -> Result<u8, DeviceError> {
         match port {
             0x0..=0x1 => {
-                // not used
+                // get one byte of the system vector
+                return Ok(self.uxn.get_system_vector().to_be_bytes()[usize::from(port)]);
             }
-            0x2 => {
+            0x2..=0x3 => {
+                // get one byte of the address of the last expansion command
+                return Ok(self.uxn.get_expansion_address().to_be_bytes()[usize::from(port - 0x2)]);
+            },
+            0x4 => {
                 // get working stack index
-                return self.uxn.get_working_stack_index();
+                return Ok(self.uxn.get_working_stack_index());
             },
-            0x3 => {
+            0x5 => {
                 // get return stack index
-                return self.uxn.get_return_stack_index();
+                return Ok(self.uxn.get_return_stack_index());
             },
-            0x4..=0x7 => {
+            0x6..=0x7 => {
                 // not used
             },
             0x8 => {
-                return self.uxn.get_system_color(UxnSystemColor::Red1);
+                return Ok(self.uxn.get_system_color(UxnSystemColor::Red1));
             },
             0x9 => {
-                return self.uxn.get_system_color(UxnSystemColor::Red2);
+                return Ok(self.uxn.get_system_color(UxnSystemColor::Red2));
             },
             0xa => {
-                return self.uxn.get_system_color(UxnSystemColor::Green1);
+                return Ok(self.uxn.get_system_color(UxnSystemColor::Green1));
             },
             0xb => {
-                return self.uxn.get_system_color(UxnSystemColor::Green2);
+                return Ok(self.uxn.get_system_color(UxnSystemColor::Green2));
             },
             0xc => {
-                return self.uxn.get_system_color(UxnSystemColor::Blue1);
+                return Ok(self.uxn.get_system_color(UxnSystemColor::Blue1));
             },
             0xd => {
-                return self.uxn.get_system_color(UxnSystemColor::Blue2);
+                return Ok(self.uxn.get_system_color(UxnSystemColor::Blue2));
             },
             0xe => {
                 // print debug status (no-op for read)
@@ -155,11 +188,62 @@ impl<'a, J, K> System<'a, J, K>
                 // terminate application
             },
             _ => {
-                panic!("attempting to read from port out of range");
+                return Err(DeviceError::PortOutOfRange(port));
             }
         }
 
-        return 0;
+        return Ok(0);
+    }
+
+    // run the expansion command whose block starts at `address` in the main ram. The first byte
+    // of the block is the operation, which is followed by its (big endian) short arguments:
+    //
+    // 0x00 fill:       length, bank, address, value (a byte)
+    // 0x01 copy left:  length, source bank, source address, destination bank, destination address
+    // 0x02 copy right: as for copy left
+    //
+    // copy left copies from the lowest address up and copy right from the highest address down,
+    // so that overlapping ranges can be moved in either direction. Addresses wrap around within
+    // their bank. Unrecognised operations are ignored
+    fn run_expansion_command(&mut self, address: u16) {
+        let read_short = |uxn: &J, offset: u16| {
+            return u16::from_be_bytes([
+                uxn.read_from_bank(0, address.wrapping_add(offset)),
+                uxn.read_from_bank(0, address.wrapping_add(offset + 1))]);
+        };
+
+        let operation = self.uxn.read_from_bank(0, address);
+        let length = read_short(self.uxn, 1);
+
+        match operation {
+            0x0 => {
+                let bank = read_short(self.uxn, 3);
+                let start = read_short(self.uxn, 5);
+                let val = self.uxn.read_from_bank(0, address.wrapping_add(7));
+
+                for i in 0..length {
+                    self.uxn.write_to_bank(bank, start.wrapping_add(i), val);
+                }
+            },
+            0x1 | 0x2 => {
+                let src_bank = read_short(self.uxn, 3);
+                let src = read_short(self.uxn, 5);
+                let dst_bank = read_short(self.uxn, 7);
+                let dst = read_short(self.uxn, 9);
+
+                let mut copy_byte = |i: u16| {
+                    let val = self.uxn.read_from_bank(src_bank, src.wrapping_add(i));
+                    self.uxn.write_to_bank(dst_bank, dst.wrapping_add(i), val);
+                };
+
+                if operation == 0x1 {
+                    (0..length).for_each(&mut copy_byte);
+                } else {
+                    (0..length).rev().for_each(&mut copy_byte);
+                }
+            },
+            _ => {},
+        }
     }
 }
 
@@ -168,6 +252,7 @@ mod tests {
     use super::*;
     use std::collections::VecDeque;
     use std::cell::RefCell;
+    use std::collections::HashMap;
 
     struct MockUxn {
         set_working_stack_index_arguments_received: RefCell<VecDeque<(u8,)>>,
@@ -180,6 +265,13 @@ mod tests {
         get_return_stack_index_arguments_received: RefCell<VecDeque<()>>,
         get_return_stack_index_values_to_return: RefCell<VecDeque<u8>>,
 
+        set_system_vector_arguments_received: RefCell<VecDeque<(u16,)>>,
+        get_system_vector_values_to_return: RefCell<VecDeque<u16>>,
+
+        expansion_address: u16,
+        banks: HashMap<(u16, u16), u8>,
+        write_to_bank_arguments_received: RefCell<VecDeque<(u16, u16, u8)>>,
+
         set_system_color_arguments_received: RefCell<VecDeque<(UxnSystemColor, u8)>>,
 
         get_system_color_arguments_received: RefCell<VecDeque<(UxnSystemColor,)>>,
@@ -202,6 +294,13 @@ mod tests {
                 get_return_stack_index_arguments_received: RefCell::new(VecDeque::new()),
                 get_return_stack_index_values_to_return: RefCell::new(VecDeque::new()),
 
+                set_system_vector_arguments_received: RefCell::new(VecDeque::new()),
+                get_system_vector_values_to_return: RefCell::new(VecDeque::new()),
+
+                expansion_address: 0,
+                banks: HashMap::new(),
+                write_to_bank_arguments_received: RefCell::new(VecDeque::new()),
+
                 set_system_color_arguments_received: RefCell::new(VecDeque::new()),
 
                 get_system_color_arguments_received: RefCell::new(VecDeque::new()),
@@ -252,6 +351,39 @@ mod tests {
                 .unwrap();
         }
 
+        fn set_system_vector(&mut self, vector: u16) {
+            self.set_system_vector_arguments_received
+                .borrow_mut()
+                .push_back((vector,));
+        }
+
+        fn get_system_vector(&self) -> u16 {
+            return self
+                .get_system_vector_values_to_return
+                .borrow_mut()
+                .pop_front()
+                .unwrap();
+        }
+
+        fn set_expansion_address(&mut self, address: u16) {
+            self.expansion_address = address;
+        }
+
+        fn get_expansion_address(&self) -> u16 {
+            return self.expansion_address;
+        }
+
+        fn read_from_bank(&self, bank: u16, address: u16) -> u8 {
+            return *self.banks.get(&(bank, address)).unwrap_or(&0);
+        }
+
+        fn write_to_bank(&mut self, bank: u16, address: u16, val: u8) {
+            self.write_to_bank_arguments_received
+                .borrow_mut()
+                .push_back((bank, address, val));
+            self.banks.insert((bank, address), val);
+        }
+
         fn set_system_color(&mut self, slot: UxnSystemColor, val: u8) {
             self.set_system_color_arguments_received
                 .borrow_mut()
@@ -297,9 +429,9 @@ mod tests {
             debug_writer: Vec::new(),
         };
 
-        // 0x2 is the port for setting the working stack index,
+        // 0x4 is the port for setting the working stack index,
         // 0x76 is the value to set it to
-        system.write(0x2, 0x76);
+        system.write(0x4, 0x76).unwrap();
 
         assert_eq!(mock_uxn.set_working_stack_index_arguments_received.into_inner(),
           VecDeque::from([(0x76,)]));
@@ -318,8 +450,8 @@ mod tests {
             debug_writer: Vec::new(),
         };
 
-        // 0x2 is the port for getting the working stack index,
-        let res = system.read(0x2);
+        // 0x4 is the port for getting the working stack index,
+        let res = system.read(0x4).unwrap();
 
         assert_eq!(res, 0x76);
         assert_eq!(mock_uxn.get_working_stack_index_arguments_received.into_inner(),
@@ -335,9 +467,9 @@ mod tests {
             debug_writer: Vec::new(),
         };
 
-        // 0x3 is the port for setting the return stack index,
+        // 0x5 is the port for setting the return stack index,
         // 0x76 is the value to set it to
-        system.write(0x3, 0x76);
+        system.write(0x5, 0x76).unwrap();
 
         assert_eq!(mock_uxn.set_return_stack_index_arguments_received.into_inner(),
           VecDeque::from([(0x76,)]));
@@ -356,14 +488,130 @@ mod tests {
             debug_writer: Vec::new(),
         };
 
-        // 0x3 is the port for getting the return stack index,
-        let res = system.read(0x3);
+        // 0x5 is the port for getting the return stack index,
+        let res = system.read(0x5).unwrap();
 
         assert_eq!(res, 0x76);
         assert_eq!(mock_uxn.get_return_stack_index_arguments_received.into_inner(),
           VecDeque::from([()]));
     }
 
+    #[test]
+    fn test_set_get_system_vector() {
+        let mut mock_uxn = MockUxn::new();
+
+        mock_uxn.get_system_vector_values_to_return
+            .borrow_mut()
+            .extend([0x0000, 0x1200, 0x1234]);
+
+        let mut system = System {
+            uxn: &mut mock_uxn,
+            debug_writer: Vec::new(),
+        };
+
+        // 0x0 and 0x1 are the ports for the high and low bytes of the system vector,
+        // each write should only modify its own byte
+        system.write(0x0, 0x12).unwrap();
+        system.write(0x1, 0x34).unwrap();
+        assert_eq!(system.read(0x1).unwrap(), 0x34);
+
+        assert_eq!(mock_uxn.set_system_vector_arguments_received.into_inner(),
+          VecDeque::from([(0x1200,), (0x1234,)]));
+    }
+
+    // load an expansion command block into bank 0 of the mock at `address`
+    fn load_expansion_command(mock_uxn: &mut MockUxn, address: u16, command: &[u8]) {
+        for (i, byte) in command.iter().enumerate() {
+            mock_uxn.banks.insert((0, address + u16::try_from(i).unwrap()), *byte);
+        }
+    }
+
+    #[test]
+    fn test_expansion_fill() {
+        let mut mock_uxn = MockUxn::new();
+
+        // fill 3 bytes of bank 2, from address 0xfffe, with 0xaa
+        load_expansion_command(&mut mock_uxn, 0x1234,
+            &[0x00, 0x00, 0x03, 0x00, 0x02, 0xff, 0xfe, 0xaa]);
+
+        let mut system = System {
+            uxn: &mut mock_uxn,
+            debug_writer: Vec::new(),
+        };
+
+        // 0x2 and 0x3 are the ports for the high and low bytes of the expansion command address,
+        // the command should only be run once the low byte is written
+        system.write(0x2, 0x12).unwrap();
+        assert_eq!(system.uxn.write_to_bank_arguments_received.borrow().len(), 0);
+        system.write(0x3, 0x34).unwrap();
+        assert_eq!(system.read(0x2).unwrap(), 0x12);
+        assert_eq!(system.read(0x3).unwrap(), 0x34);
+
+        // the fill wraps around within the bank
+        assert_eq!(mock_uxn.write_to_bank_arguments_received.into_inner(),
+          VecDeque::from([(0x2, 0xfffe, 0xaa), (0x2, 0xffff, 0xaa), (0x2, 0x0000, 0xaa)]));
+    }
+
+    #[test]
+    fn test_expansion_copy_between_banks() {
+        let mut mock_uxn = MockUxn::new();
+
+        for (i, byte) in [0x11, 0x22, 0x33].into_iter().enumerate() {
+            mock_uxn.banks.insert((0x1, 0x0100 + u16::try_from(i).unwrap()), byte);
+        }
+
+        // copy 3 bytes from bank 1 address 0x0100 to bank 0 address 0x0800
+        load_expansion_command(&mut mock_uxn, 0x0010,
+            &[0x01, 0x00, 0x03, 0x00, 0x01, 0x01, 0x00, 0x00, 0x00, 0x08, 0x00]);
+
+        let mut system = System {
+            uxn: &mut mock_uxn,
+            debug_writer: Vec::new(),
+        };
+
+        system.write(0x2, 0x00).unwrap();
+        system.write(0x3, 0x10).unwrap();
+
+        assert_eq!(mock_uxn.write_to_bank_arguments_received.into_inner(),
+          VecDeque::from([(0x0, 0x0800, 0x11), (0x0, 0x0801, 0x22), (0x0, 0x0802, 0x33)]));
+    }
+
+    #[test]
+    fn test_expansion_copy_left_right() {
+        // moving the bytes [1 2 3 4] in bank 3 along by one, in each direction. For the ranges
+        // to be moved intact, copying towards lower addresses (left) must start from the lowest
+        // address and copying towards higher addresses (right) from the highest
+        let test_cases = [
+            (0x01, 0x0201u16, 0x0200u16, [0x1, 0x2, 0x3, 0x4, 0x4, 0x0]),
+            (0x02, 0x0201, 0x0202, [0x0, 0x1, 0x1, 0x2, 0x3, 0x4]),
+        ];
+
+        for (operation, src, dst, expected) in test_cases {
+            let mut mock_uxn = MockUxn::new();
+
+            for (i, byte) in [0x1, 0x2, 0x3, 0x4].into_iter().enumerate() {
+                mock_uxn.banks.insert((0x3, 0x0201 + u16::try_from(i).unwrap()), byte);
+            }
+
+            let src = src.to_be_bytes();
+            let dst = dst.to_be_bytes();
+            load_expansion_command(&mut mock_uxn, 0x0010,
+                &[operation, 0x00, 0x04, 0x00, 0x03, src[0], src[1], 0x00, 0x03, dst[0], dst[1]]);
+
+            let mut system = System {
+                uxn: &mut mock_uxn,
+                debug_writer: Vec::new(),
+            };
+
+            system.write(0x3, 0x10).unwrap();
+
+            let result = (0x0200..0x0206)
+                .map(|address| mock_uxn.read_from_bank(0x3, address))
+                .collect::<Vec<u8>>();
+            assert_eq!(result, expected);
+        }
+    }
+
     #[test]
     fn test_set_system_color() {
         let mut mock_uxn = MockUxn::new();
@@ -382,7 +630,7 @@ mod tests {
                 uxn: &mut mock_uxn,
                 debug_writer: Vec::new(),
             };
-            system.write(port, expected_val);
+            system.write(port, expected_val).unwrap();
 
             assert_eq!(mock_uxn.set_system_color_arguments_received.borrow_mut()
                        .pop_back().unwrap(),
@@ -413,7 +661,7 @@ mod tests {
                 debug_writer: Vec::new(),
             };
 
-            let res = system.read(port);
+            let res = system.read(port).unwrap();
 
             assert_eq!(res, expected_val);
             assert_eq!(mock_uxn.get_system_color_arguments_received
@@ -441,7 +689,7 @@ mod tests {
 
         // 0xe is the port for printing debug information, it doesn't matter what byte is written
         // there so the value 0x22 is entirely arbritrary
-        system.write(0xe, 0x22);
+        system.write(0xe, 0x22).unwrap();
 
         assert_eq!(&(String::from_utf8(output_received).unwrap()),
             "<wst> 04 05 06\n<rst> 01 02 03\n");
@@ -457,7 +705,7 @@ mod tests {
             debug_writer: &mut output_received,
         };
 
-        system.write(0xf, 0x1);
+        system.write(0xf, 0x1).unwrap();
         assert_eq!(mock_uxn.start_termination_arguments_received.into_inner(), VecDeque::from([()]));
     }
 }
@@ -0,0 +1,528 @@
+use std::collections::BTreeSet;
+use std::io;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+use crate::instruction::InstructionFactory;
+use crate::emulators::uxn::UxnImpl;
+use crate::emulators::uxn::device::DeviceList;
+use crate::emulators::devices::system::UxnSystemInterface;
+use crate::uxninterface::{Uxn, UxnCrash, UxnStatus};
+
+// A stub for debuggers that speak the GDB remote serial protocol. The uxn's registers are given
+// (by the `g` packet, in this order) as the program counter (two bytes, big endian), then the
+// working stack pointer and the return stack pointer (a byte each). Memory addresses 0x0000 to
+// 0xffff are the main ram, and every slot of the working and return stacks follows it
+
+pub const WORKING_STACK_ADDRESS: u32 = 0x10000;
+pub const RETURN_STACK_ADDRESS: u32 = 0x10100;
+const MEMORY_END: u32 = 0x10200;
+
+// the signals given in stop replies
+const SIGINT: u8 = 0x02;
+const SIGTRAP: u8 = 0x05;
+const SIGSEGV: u8 = 0x0b;
+
+// how many instructions are executed between checks for an interrupt from the debugger
+const INTERRUPT_CHECK_INTERVAL: u64 = 0x1000;
+
+// a byte of memory, as the debugger sees it
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum MemoryLocation {
+    Ram(u16),
+    WorkingStack(u8),
+    ReturnStack(u8),
+}
+
+impl MemoryLocation {
+    fn from_address(address: u32) -> Option<Self> {
+        if address < WORKING_STACK_ADDRESS {
+            return Some(MemoryLocation::Ram(address as u16));
+        } else if address < RETURN_STACK_ADDRESS {
+            return Some(MemoryLocation::WorkingStack((address - WORKING_STACK_ADDRESS) as u8));
+        } else if address < MEMORY_END {
+            return Some(MemoryLocation::ReturnStack((address - RETURN_STACK_ADDRESS) as u8));
+        }
+        return None;
+    }
+}
+
+// what the stub needs from the uxn being debugged
+pub trait GdbTarget {
+    // the address of the next instruction to be executed
+    fn program_counter(&self) -> u16;
+    fn working_stack_pointer(&self) -> u8;
+    fn return_stack_pointer(&self) -> u8;
+    fn read_memory(&self, location: MemoryLocation) -> u8;
+    fn write_memory(&mut self, location: MemoryLocation, val: u8);
+}
+
+impl<J> GdbTarget for UxnImpl<J>
+where
+J: InstructionFactory,
+{
+    fn program_counter(&self) -> u16 {
+        return self.get_program_counter().unwrap_or(0);
+    }
+
+    fn working_stack_pointer(&self) -> u8 {
+        return self.get_working_stack_index();
+    }
+
+    fn return_stack_pointer(&self) -> u8 {
+        return self.get_return_stack_index();
+    }
+
+    fn read_memory(&self, location: MemoryLocation) -> u8 {
+        return match location {
+            MemoryLocation::Ram(address) => self.read_from_ram(address),
+            MemoryLocation::WorkingStack(index) => self.read_from_working_stack(index),
+            MemoryLocation::ReturnStack(index) => self.read_from_return_stack(index),
+        };
+    }
+
+    // changes made by the debugger aren't recorded in the history, as they aren't part of
+    // any instruction
+    fn write_memory(&mut self, location: MemoryLocation, val: u8) {
+        self.unrecorded(|uxn| {
+            match location {
+                MemoryLocation::Ram(address) => uxn.write_to_ram(address, val),
+                MemoryLocation::WorkingStack(index) => uxn.write_to_working_stack(index, val),
+                MemoryLocation::ReturnStack(index) => uxn.write_to_return_stack(index, val),
+            }
+        });
+    }
+}
+
+// how the debugger has asked for the program to carry on
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Resume {
+    Continue,
+    Step,
+    Kill,
+    Detach,
+}
+
+fn checksum(data: &[u8]) -> u8 {
+    return data.iter().fold(0u8, |sum, b| { return sum.wrapping_add(*b); });
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    return bytes.iter().map(|b| { return format!("{:02x}", b); }).collect::<String>();
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    return (0..hex.len()).step_by(2)
+        .map(|i| { return u8::from_str_radix(hex.get(i..i+2)?, 16).ok(); })
+        .collect();
+}
+
+// parse the `ADDR,LENGTH` arguments of the memory packets
+fn parse_address_and_length(args: &str) -> Option<(u32, usize)> {
+    let (address, length) = args.split_once(',')?;
+    return Some((u32::from_str_radix(address, 16).ok()?, usize::from_str_radix(length, 16).ok()?));
+}
+
+// parse the `TYPE,ADDR,KIND` arguments of the breakpoint packets, giving the address of a
+// software or hardware breakpoint (both are treated as software breakpoints)
+fn parse_breakpoint(args: &str) -> Option<Option<u16>> {
+    let mut args = args.split(',');
+    let kind = args.next()?;
+    let address = u16::from_str_radix(args.next()?, 16).ok()?;
+    if kind != "0" && kind != "1" {
+        return Some(None);
+    }
+    return Some(Some(address));
+}
+
+pub struct GdbStub {
+    stream: TcpStream,
+    breakpoints: BTreeSet<u16>,
+    // the signal to report before the next instruction, if the program should stop there
+    stop_signal: Option<u8>,
+    // whether the next instruction is the first since the program was resumed, in which case
+    // a breakpoint on it has already been reported
+    just_resumed: bool,
+    // whether the debugger has resumed the program and is waiting to hear that it has stopped
+    running: bool,
+    // set once the debugger has detached or disconnected, after which the program runs freely
+    detached: bool,
+    last_packet: Vec<u8>,
+}
+
+impl GdbStub {
+    // wait for a debugger to connect to `port` on localhost
+    pub fn listen(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        eprintln!("waiting for a debugger to connect on 127.0.0.1:{}", port);
+        let (stream, _) = listener.accept()?;
+        // packets are small and each waits on a reply, so they shouldn't be held back to be
+        // sent together
+        stream.set_nodelay(true)?;
+        return Ok(GdbStub::new(stream));
+    }
+
+    // the program is stopped before its first instruction, until the debugger resumes it
+    pub fn new(stream: TcpStream) -> Self {
+        GdbStub{stream, breakpoints: BTreeSet::new(), stop_signal: Some(SIGTRAP),
+            just_resumed: false, running: false, detached: false, last_packet: Vec::new()}
+    }
+
+    // run `vector` (or, if `vector` is `None`, resume the suspended vector) as
+    // `UxnImpl::run_with_budget` would, but one instruction at a time so that the debugger can
+    // stop the program before any of them
+    pub fn run<J, K>(&mut self, uxn: &mut UxnImpl<J>, vector: Option<u16>, mut devices: K,
+        max_instructions: Option<u64>) -> Result<UxnStatus, UxnCrash>
+    where J: InstructionFactory, K: DeviceList,
+    {
+        match vector {
+            Some(vector) => {
+                // start the vector without executing any of it
+                if uxn.run_with_budget(vector, &mut devices, 0)? == UxnStatus::Halt {
+                    return Ok(UxnStatus::Halt);
+                }
+            },
+            None => {
+                if !uxn.is_suspended() {
+                    return Ok(UxnStatus::Halt);
+                }
+            },
+        }
+
+        let mut instructions = 0u64;
+        loop {
+            if self.detached {
+                let remaining = max_instructions.map_or(u64::MAX, |max| { return max - instructions; });
+                return uxn.resume_with_budget(&mut devices, remaining);
+            }
+            if Some(instructions) == max_instructions {
+                return Ok(UxnStatus::BudgetExhausted);
+            }
+
+            if self.before_instruction(uxn, instructions) == Resume::Kill {
+                return Ok(UxnStatus::Terminate);
+            }
+
+            instructions += 1;
+            match uxn.resume_with_budget(&mut devices, 1) {
+                Ok(UxnStatus::BudgetExhausted) => {},
+                Ok(UxnStatus::Halt) => {
+                    return Ok(UxnStatus::Halt);
+                },
+                Ok(UxnStatus::Terminate) => {
+                    self.finish("W00");
+                    return Ok(UxnStatus::Terminate);
+                },
+                Err(crash) => {
+                    // let the debugger inspect the crashed program before it goes
+                    if !self.detached && self.stop(uxn, SIGSEGV) != Resume::Kill {
+                        self.finish(&format!("X{:02x}", SIGSEGV));
+                    }
+                    self.detached = true;
+                    return Err(crash);
+                },
+            }
+        }
+    }
+
+    // check whether the program should stop before the instruction at the program counter, and
+    // if so hand control to the debugger until it resumes the program
+    fn before_instruction<T: GdbTarget>(&mut self, target: &mut T, instructions: u64) -> Resume {
+        let program_counter = target.program_counter();
+
+        let mut signal = self.stop_signal.take();
+        if signal.is_none() && !self.just_resumed && self.breakpoints.contains(&program_counter) {
+            signal = Some(SIGTRAP);
+        }
+        self.just_resumed = false;
+        if signal.is_none() && instructions.is_multiple_of(INTERRUPT_CHECK_INTERVAL) && self.interrupt_requested() {
+            signal = Some(SIGINT);
+        }
+
+        return match signal {
+            Some(signal) => self.stop(target, signal),
+            None => Resume::Continue,
+        };
+    }
+
+    // report that the program has stopped, and serve the debugger's requests until it resumes
+    // the program. If the connection fails the debugger is treated as having detached
+    fn stop<T: GdbTarget>(&mut self, target: &mut T, signal: u8) -> Resume {
+        let res = if self.running {
+            self.send_packet(&format!("S{:02x}", signal))
+        } else {
+            Ok(())
+        };
+        let res = res.and_then(|_| { return self.serve(target, signal); });
+
+        let resume = match res {
+            Ok(resume) => resume,
+            Err(e) => {
+                eprintln!("debugger disconnected: {}", e);
+                Resume::Detach
+            },
+        };
+
+        match resume {
+            Resume::Step => {
+                self.stop_signal = Some(SIGTRAP);
+            },
+            Resume::Detach => {
+                self.detached = true;
+            },
+            Resume::Continue | Resume::Kill => {},
+        }
+        self.just_resumed = true;
+        self.running = true;
+        return resume;
+    }
+
+    // tell the debugger, if it is waiting, that the program has gone with `reply`
+    fn finish(&mut self, reply: &str) {
+        if !self.detached && self.running {
+            // the program is finishing regardless, so there is nothing to be done about an error
+            let _ = self.send_packet(reply);
+        }
+        self.detached = true;
+    }
+
+    // whether the debugger has sent an interrupt (a lone 0x03 byte) while the program was running
+    fn interrupt_requested(&mut self) -> bool {
+        if self.stream.set_nonblocking(true).is_err() {
+            return false;
+        }
+
+        let mut requested = false;
+        let mut buf = [0u8; 1];
+        loop {
+            match self.stream.read(&mut buf) {
+                Ok(1) if buf[0] == 0x03 => { requested = true; },
+                Ok(1) => {},
+                Ok(_) => {
+                    // the connection has closed
+                    self.detached = true;
+                    break;
+                },
+                Err(_) => break,
+            }
+        }
+
+        if self.stream.set_nonblocking(false).is_err() {
+            self.detached = true;
+        }
+        return requested;
+    }
+
+    fn read_byte(&mut self) -> io::Result<u8> {
+        let mut buf = [0u8; 1];
+        self.stream.read_exact(&mut buf)?;
+        return Ok(buf[0]);
+    }
+
+    // read the next packet from the debugger, acknowledging it
+    fn read_packet(&mut self) -> io::Result<String> {
+        loop {
+            match self.read_byte()? {
+                b'$' => {},
+                b'-' => {
+                    // the last packet sent didn't arrive intact
+                    let last_packet = self.last_packet.clone();
+                    self.stream.write_all(&last_packet)?;
+                    continue;
+                },
+                // acknowledgements, and interrupts received whilst already stopped
+                _ => continue,
+            }
+
+            let mut data = Vec::new();
+            loop {
+                let b = self.read_byte()?;
+                if b == b'#' {
+                    break;
+                }
+                data.push(b);
+            }
+            let received_checksum = [self.read_byte()?, self.read_byte()?];
+
+            let valid = std::str::from_utf8(&received_checksum).ok()
+                .and_then(|received_checksum| { return u8::from_str_radix(received_checksum, 16).ok(); })
+                == Some(checksum(&data));
+            if !valid {
+                self.stream.write_all(b"-")?;
+                continue;
+            }
+
+            self.stream.write_all(b"+")?;
+            return String::from_utf8(data)
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "packet is not valid utf-8"));
+        }
+    }
+
+    fn send_packet(&mut self, data: &str) -> io::Result<()> {
+        self.last_packet = format!("${}#{:02x}", data, checksum(data.as_bytes())).into_bytes();
+        self.stream.write_all(&self.last_packet)?;
+        return self.stream.flush();
+    }
+
+    // serve the debugger's requests, until it asks for the program to be resumed
+    fn serve<T: GdbTarget>(&mut self, target: &mut T, signal: u8) -> io::Result<Resume> {
+        loop {
+            let packet = self.read_packet()?;
+            let (command, args) = packet.split_at(packet.chars().next().map_or(0, |c| { return c.len_utf8(); }));
+
+            let reply = match command {
+                "?" => format!("S{:02x}", signal),
+                "g" => {
+                    let program_counter = target.program_counter().to_be_bytes();
+                    to_hex(&[program_counter[0], program_counter[1],
+                        target.working_stack_pointer(), target.return_stack_pointer()])
+                },
+                "p" => {
+                    match u8::from_str_radix(args, 16) {
+                        Ok(0) => to_hex(&target.program_counter().to_be_bytes()),
+                        Ok(1) => to_hex(&[target.working_stack_pointer()]),
+                        Ok(2) => to_hex(&[target.return_stack_pointer()]),
+                        _ => "E01".to_string(),
+                    }
+                },
+                "m" => {
+                    match parse_address_and_length(args) {
+                        Some((address, length)) => {
+                            let bytes = (address..address.saturating_add(length as u32))
+                                .map_while(MemoryLocation::from_address)
+                                .map(|location| { return target.read_memory(location); })
+                                .collect::<Vec<u8>>();
+                            if bytes.is_empty() && length > 0 { "E01".to_string() } else { to_hex(&bytes) }
+                        },
+                        None => "E01".to_string(),
+                    }
+                },
+                "M" => {
+                    let write = args.split_once(':').and_then(|(range, data)| {
+                        let (address, length) = parse_address_and_length(range)?;
+                        let data = from_hex(data).filter(|data| { return data.len() == length; })?;
+                        let locations = (address..address.saturating_add(length as u32))
+                            .map(MemoryLocation::from_address)
+                            .collect::<Option<Vec<MemoryLocation>>>()?;
+                        return Some((locations, data));
+                    });
+                    match write {
+                        Some((locations, data)) => {
+                            for (location, val) in locations.into_iter().zip(data) {
+                                target.write_memory(location, val);
+                            }
+                            "OK".to_string()
+                        },
+                        None => "E01".to_string(),
+                    }
+                },
+                "Z" | "z" => {
+                    match parse_breakpoint(args) {
+                        Some(Some(address)) => {
+                            if command == "Z" {
+                                self.breakpoints.insert(address);
+                            } else {
+                                self.breakpoints.remove(&address);
+                            }
+                            "OK".to_string()
+                        },
+                        // watchpoints aren't supported
+                        Some(None) => String::new(),
+                        None => "E01".to_string(),
+                    }
+                },
+                "c" => {
+                    return Ok(Resume::Continue);
+                },
+                "s" => {
+                    return Ok(Resume::Step);
+                },
+                "k" => {
+                    return Ok(Resume::Kill);
+                },
+                "D" => {
+                    self.send_packet("OK")?;
+                    return Ok(Resume::Detach);
+                },
+                "H" => "OK".to_string(),
+                "q" if args.starts_with("Supported") => "PacketSize=1000".to_string(),
+                "q" if args == "Attached" => "1".to_string(),
+                // anything else isn't supported, which is indicated with an empty reply
+                _ => String::new(),
+            };
+
+            self.send_packet(&reply)?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ops::OpObjectFactory;
+    use crate::emulators::devices::device_list_impl::{DeviceListImpl, DeviceEntry};
+    use std::collections::HashMap;
+    use std::thread;
+
+    // send each packet in turn, returning the replies
+    fn run_client(port: u16, packets: Vec<&'static str>) -> Vec<String> {
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        stream.set_nodelay(true).unwrap();
+
+        let mut replies = Vec::new();
+        for packet in packets {
+            write!(stream, "${}#{:02x}", packet, checksum(packet.as_bytes())).unwrap();
+
+            let mut reply = Vec::new();
+            let mut buf = [0u8; 1];
+            loop {
+                stream.read_exact(&mut buf).unwrap();
+                if buf[0] == b'$' || !reply.is_empty() {
+                    reply.push(buf[0]);
+                }
+                if reply.len() > 3 && reply[reply.len() - 3] == b'#' {
+                    break;
+                }
+            }
+            stream.write_all(b"+").unwrap();
+
+            let reply = String::from_utf8(reply).unwrap();
+            replies.push(reply[1..reply.len()-3].to_string());
+        }
+        return replies;
+    }
+
+    // a debugger should be able to examine and modify the uxn, set breakpoints, step through
+    // the program and continue it to the end
+    #[test]
+    fn test_session() {
+        // |100 #1234 #01 ADD #01 #0f DEO BRK
+        let rom = vec!(0xa0, 0x12, 0x34, 0x80, 0x01, 0x18, 0x80, 0x01, 0x80, 0x0f, 0x17, 0x00);
+        let mut uxn = UxnImpl::new(rom.into_iter(), OpObjectFactory{}).unwrap();
+
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let client = thread::spawn(move || {
+            return run_client(port, vec!(
+                "qSupported:swbreak+", "?", "g", "m100,3", "Z0,105,1", "c",
+                "g", "m10000,3", "M10002,1:02", "s", "p0", "m10000,2", "m101ff,2", "m10200,1",
+                "vCont?", "z0,105,1", "c"));
+        });
+
+        let stream = listener.accept().unwrap().0;
+        stream.set_nodelay(true).unwrap();
+        let mut gdb = GdbStub::new(stream);
+        let device_list = DeviceListImpl::new(HashMap::from([
+            (0x0, DeviceEntry::SystemPlaceHolder(Vec::new()))]));
+        assert_eq!(gdb.run(&mut uxn, Some(0x100), device_list, None),
+            Ok(UxnStatus::Terminate));
+
+        assert_eq!(client.join().unwrap(), vec!(
+            "PacketSize=1000", "S05", "01000000", "a01234", "OK", "S05",
+            "01050300", "123401", "OK", "S05", "0106", "1236", "00", "E01",
+            "", "OK", "W00"));
+    }
+}
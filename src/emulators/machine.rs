@@ -0,0 +1,259 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::io::Write;
+
+use crate::ops::OpObjectFactory;
+use crate::emulators::uxn;
+use crate::emulators::devices::device_list_impl::{DeviceListImpl, DeviceEntry};
+use crate::emulators::devices::system::UxnSystemInterface;
+use crate::uxninterface::Uxn;
+
+pub use crate::emulators::uxn::device::{Device, DeviceError, MainRamInterface, MainRamInterfaceError};
+pub use crate::emulators::uxn::{StackFaultPolicy, DeviceAccessPolicy, INIT_VECTOR, MAX_ROM_SIZE};
+pub use crate::emulators::observer::UxnObserver;
+pub use crate::uxninterface::{UxnCrash, UxnError, UxnStatus};
+
+// the device slot of the System device, which is always present and can't be replaced
+pub const SYSTEM_DEVICE_SLOT: u8 = 0x0;
+
+#[derive(Debug, PartialEq)]
+pub enum MachineBuildError {
+    RomTooLarge{size: usize, max_size: usize},
+    SlotOutOfRange(u8),
+    SystemSlot,
+}
+
+impl fmt::Display for MachineBuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MachineBuildError::RomTooLarge{size, max_size} => {
+                write!(f, "ROM is too large: {} bytes (the maximum is {} bytes)", size, max_size)
+            },
+            MachineBuildError::SlotOutOfRange(slot) => {
+                write!(f, "device slot 0x{:x} is out of range (slots are 0x0 to 0xf)", slot)
+            },
+            MachineBuildError::SystemSlot => {
+                write!(f, "device slot 0x{:x} is reserved for the System device", SYSTEM_DEVICE_SLOT)
+            },
+        }
+    }
+}
+
+impl Error for MachineBuildError {}
+
+// assembles a `Machine`: a uxn with a rom loaded and a set of devices mounted in its device
+// slots. For example, to run a rom with the console device in slot 0x1:
+//
+//     let mut machine = MachineBuilder::new()
+//         .rom(rom_bytes)
+//         .device(0x1, Box::new(Console::new(io::stdout(), io::stderr())))
+//         .build()?;
+//     machine.run_vector(INIT_VECTOR)?;
+//
+// To keep access to a device once it is mounted, mount it as an `Rc<RefCell<_>>` and keep a
+// clone of the `Rc`
+pub struct MachineBuilder {
+    rom: Vec<u8>,
+    devices: HashMap<u8, Box<dyn Device>>,
+    debug_writer: Box<dyn Write>,
+    stack_fault_policy: StackFaultPolicy,
+    device_access_policy: DeviceAccessPolicy,
+    observer: Option<Box<dyn UxnObserver>>,
+    history_capacity: usize,
+}
+
+impl MachineBuilder {
+    pub fn new() -> Self {
+        MachineBuilder{
+            rom: Vec::new(),
+            devices: HashMap::new(),
+            debug_writer: Box::new(io::stderr()),
+            stack_fault_policy: StackFaultPolicy::Error,
+            device_access_policy: DeviceAccessPolicy::Ignore,
+            observer: None,
+            history_capacity: 0,
+        }
+    }
+
+    // the rom to load at `INIT_VECTOR`. Defaults to an empty rom
+    pub fn rom(mut self, rom: Vec<u8>) -> Self {
+        self.rom = rom;
+        return self;
+    }
+
+    // mount `device` in `slot` (the high nibble of the addresses of its ports), replacing any
+    // device already mounted there
+    pub fn device(mut self, slot: u8, device: Box<dyn Device>) -> Self {
+        self.devices.insert(slot, device);
+        return self;
+    }
+
+    // where the System device writes its debug output. Defaults to stderr
+    pub fn debug_writer(mut self, debug_writer: Box<dyn Write>) -> Self {
+        self.debug_writer = debug_writer;
+        return self;
+    }
+
+    pub fn stack_fault_policy(mut self, stack_fault_policy: StackFaultPolicy) -> Self {
+        self.stack_fault_policy = stack_fault_policy;
+        return self;
+    }
+
+    // what happens when the rom accesses a slot with no device mounted in it. Defaults to
+    // ignoring the access
+    pub fn device_access_policy(mut self, device_access_policy: DeviceAccessPolicy) -> Self {
+        self.device_access_policy = device_access_policy;
+        return self;
+    }
+
+    pub fn observer(mut self, observer: Box<dyn UxnObserver>) -> Self {
+        self.observer = Some(observer);
+        return self;
+    }
+
+    // how many of the most recently executed instructions are recorded, so that they can be
+    // undone with `Machine::step_back`. Defaults to none
+    pub fn history_capacity(mut self, history_capacity: usize) -> Self {
+        self.history_capacity = history_capacity;
+        return self;
+    }
+
+    pub fn build(self) -> Result<Machine, MachineBuildError> {
+        if self.rom.len() > MAX_ROM_SIZE {
+            return Err(MachineBuildError::RomTooLarge{size: self.rom.len(), max_size: MAX_ROM_SIZE});
+        }
+
+        for slot in self.devices.keys() {
+            if *slot > 0xf {
+                return Err(MachineBuildError::SlotOutOfRange(*slot));
+            }
+            if *slot == SYSTEM_DEVICE_SLOT {
+                return Err(MachineBuildError::SystemSlot);
+            }
+        }
+
+        let mut uxn = uxn::UxnImpl::new(self.rom.into_iter(), OpObjectFactory{})
+            .expect("creating uxn failed");
+        uxn.set_stack_fault_policy(self.stack_fault_policy);
+        uxn.set_device_access_policy(self.device_access_policy);
+        if let Some(observer) = self.observer {
+            uxn.set_observer(observer);
+        }
+        uxn.set_history_capacity(self.history_capacity);
+
+        return Ok(Machine{uxn, devices: self.devices, debug_writer: self.debug_writer});
+    }
+}
+
+impl Default for MachineBuilder {
+    fn default() -> Self {
+        return MachineBuilder::new();
+    }
+}
+
+// a uxn and its devices, built by a `MachineBuilder`
+pub struct Machine {
+    uxn: uxn::UxnImpl<OpObjectFactory>,
+    devices: HashMap<u8, Box<dyn Device>>,
+    debug_writer: Box<dyn Write>,
+}
+
+impl Machine {
+    // the device list for a single run of the uxn. This takes the fields it needs rather than
+    // `self`, so that the uxn can be borrowed at the same time
+    fn device_list_of<'a>(devices: &'a mut HashMap<u8, Box<dyn Device>>,
+        debug_writer: &'a mut Box<dyn Write>) -> DeviceListImpl<'a, &'a mut Box<dyn Write>>
+    {
+        let mut device_list: HashMap<u8, DeviceEntry<&mut Box<dyn Write>>> = devices.iter_mut()
+            .map(|(slot, device)| { return (*slot, DeviceEntry::Device(device.as_mut())); })
+            .collect();
+        device_list.insert(SYSTEM_DEVICE_SLOT, DeviceEntry::SystemPlaceHolder(debug_writer));
+        return DeviceListImpl::new(device_list);
+    }
+
+    // run the program from `vector` until it halts or terminates
+    pub fn run_vector(&mut self, vector: u16) -> Result<UxnStatus, UxnCrash> {
+        let device_list = Machine::device_list_of(&mut self.devices, &mut self.debug_writer);
+        return self.uxn.run(vector, device_list);
+    }
+
+    // run the program from `vector` for at most `max_instructions` instructions. If it hasn't
+    // finished by then, `UxnStatus::BudgetExhausted` is returned and it can be continued with
+    // `resume_with_budget`
+    pub fn run_vector_with_budget(&mut self, vector: u16, max_instructions: u64)
+        -> Result<UxnStatus, UxnCrash>
+    {
+        let device_list = Machine::device_list_of(&mut self.devices, &mut self.debug_writer);
+        return self.uxn.run_with_budget(vector, device_list, max_instructions);
+    }
+
+    pub fn resume_with_budget(&mut self, max_instructions: u64) -> Result<UxnStatus, UxnCrash> {
+        let device_list = Machine::device_list_of(&mut self.devices, &mut self.debug_writer);
+        return self.uxn.resume_with_budget(device_list, max_instructions);
+    }
+
+    pub fn is_suspended(&self) -> bool {
+        return self.uxn.is_suspended();
+    }
+
+    // undo the last `n` instructions executed, returning how many could be undone. Only the
+    // uxn is rewound, not its devices. Execution can then carry on with `resume_with_budget`.
+    // Changes made by the host through `write_ram` and the stack functions aren't undone,
+    // though undoing an instruction returns the stack pointers to where they were before it
+    pub fn step_back(&mut self, n: usize) -> usize {
+        return self.uxn.step_back(n);
+    }
+
+    // undo instructions until the program counter is back at the most recent instruction
+    // executed from `address`, returning how many were undone (or `None` if there is no such
+    // instruction in the history)
+    pub fn run_back_to(&mut self, address: u16) -> Option<usize> {
+        return self.uxn.run_back_to(address);
+    }
+
+    // the number of instructions that can currently be undone
+    pub fn history_len(&self) -> usize {
+        return self.uxn.history_len();
+    }
+
+    pub fn read_ram(&self, address: u16) -> u8 {
+        return self.uxn.read_from_ram(address);
+    }
+
+    pub fn write_ram(&mut self, address: u16, val: u8) {
+        self.uxn.unrecorded(|uxn| { uxn.write_to_ram(address, val); });
+    }
+
+    // the contents of the working stack, from the bottom up
+    pub fn working_stack(&self) -> &[u8] {
+        return self.uxn.get_working_stack_iter().as_slice();
+    }
+
+    // the contents of the return stack, from the bottom up
+    pub fn return_stack(&self) -> &[u8] {
+        return self.uxn.get_return_stack_iter().as_slice();
+    }
+
+    pub fn push_to_working_stack(&mut self, byte: u8) -> Result<(), UxnError> {
+        return self.uxn.unrecorded(|uxn| { return uxn.push_to_working_stack(byte); });
+    }
+
+    pub fn pop_from_working_stack(&mut self) -> Result<u8, UxnError> {
+        return self.uxn.pop_from_working_stack();
+    }
+
+    pub fn push_to_return_stack(&mut self, byte: u8) -> Result<(), UxnError> {
+        return self.uxn.unrecorded(|uxn| { return uxn.push_to_return_stack(byte); });
+    }
+
+    pub fn pop_from_return_stack(&mut self) -> Result<u8, UxnError> {
+        return self.uxn.pop_from_return_stack();
+    }
+
+    // the device mounted in `slot`, if there is one
+    pub fn device_mut(&mut self, slot: u8) -> Option<&mut (dyn Device + 'static)> {
+        return self.devices.get_mut(&slot).map(|device| device.as_mut());
+    }
+}
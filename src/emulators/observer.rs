@@ -0,0 +1,148 @@
+use std::io;
+use std::io::Write;
+
+use crate::ops::OpObject;
+use crate::symbols::SymbolTable;
+
+// receives notifications of what a uxn is doing as it runs. Every method does nothing by default,
+// so an observer need only implement those it is interested in
+pub trait UxnObserver {
+    // called before each instruction is executed, with the address it was read from and the
+    // contents of both stacks (from the bottom up)
+    fn before_instruction(&mut self, _address: u16, _op: &OpObject,
+        _working_stack: &[u8], _return_stack: &[u8]) {}
+
+    // called after each instruction that executed without a fault, with the contents of both
+    // stacks afterwards
+    fn after_instruction(&mut self, _address: u16, _op: &OpObject,
+        _working_stack: &[u8], _return_stack: &[u8]) {}
+
+    // called when an instruction reads `val` from the device port at `device_address`
+    fn device_read(&mut self, _device_address: u8, _val: u8) {}
+
+    // called when an instruction writes `val` to the device port at `device_address`
+    fn device_write(&mut self, _device_address: u8, _val: u8) {}
+
+    // called when `val` is written to the main ram at `address`, by an instruction or by a device
+    // (such as the file device reading a file into ram)
+    fn ram_write(&mut self, _address: u16, _val: u8) {}
+}
+
+// an observer that writes a line to `writer` for every instruction executed, giving its address,
+// its mnemonic and the stacks as they were before it ran. If writing fails (for example because
+// the disk is full) a warning is printed and the trace stops, rather than the program
+pub struct TraceWriter<J: Write> {
+    writer: J,
+    // the labels of the program, each of which is written on a line of its own before the
+    // instructions at its address
+    symbols: SymbolTable,
+    // set once writing to `writer` has failed, after which nothing more is written
+    failed: bool,
+}
+
+impl<J: Write> TraceWriter<J> {
+    pub fn new(writer: J) -> Self {
+        TraceWriter{writer, symbols: SymbolTable::new(), failed: false}
+    }
+
+    pub fn with_symbols(writer: J, symbols: SymbolTable) -> Self {
+        TraceWriter{writer, symbols, failed: false}
+    }
+
+    fn write_instruction(&mut self, address: u16, op: &OpObject,
+        working_stack: &[u8], return_stack: &[u8]) -> io::Result<()> {
+        for label in self.symbols.labels_at(address) {
+            writeln!(self.writer, "@{}", label)?;
+        }
+        writeln!(self.writer, "{:04x} {:<7} <wst>{} <rst>{}", address, op.to_string(),
+            format_stack(working_stack), format_stack(return_stack))?;
+        return Ok(());
+    }
+}
+
+fn format_stack(stack: &[u8]) -> String {
+    return stack.iter()
+        .map(|x| { return format!(" {:02x}", x); })
+        .collect::<String>();
+}
+
+impl<J: Write> UxnObserver for TraceWriter<J> {
+    fn before_instruction(&mut self, address: u16, op: &OpObject,
+        working_stack: &[u8], return_stack: &[u8]) {
+        if self.failed {
+            return;
+        }
+
+        if let Err(e) = self.write_instruction(address, op, working_stack, return_stack) {
+            eprintln!("warning: could not write trace output, tracing stopped: {}", e);
+            self.failed = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trace_writer() {
+        let mut output = Vec::new();
+        let mut trace_writer = TraceWriter::new(&mut output);
+
+        trace_writer.before_instruction(0x0100, &OpObject::from_byte(0xa0), &[], &[]);
+        trace_writer.before_instruction(0x0103, &OpObject::from_byte(0x38), &[0x12, 0x34], &[0x01]);
+        trace_writer.after_instruction(0x0103, &OpObject::from_byte(0x38), &[0x12, 0x34], &[0x01]);
+
+        assert_eq!(String::from_utf8(output).unwrap(),
+            "0100 LIT2    <wst> <rst>\n0103 ADD2    <wst> 12 34 <rst> 01\n");
+    }
+
+    // a writer that fails after accepting `capacity` bytes
+    struct FullWriter {
+        written: Vec<u8>,
+        capacity: usize,
+    }
+
+    impl Write for FullWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.written.len() + buf.len() > self.capacity {
+                return Err(io::Error::new(io::ErrorKind::Other, "no space left"));
+            }
+            self.written.extend_from_slice(buf);
+            return Ok(buf.len());
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            return Ok(());
+        }
+    }
+
+    // once writing the trace fails, the trace should stop without taking the program with it
+    #[test]
+    fn test_trace_writer_failure() {
+        let mut trace_writer = TraceWriter::new(FullWriter{written: Vec::new(), capacity: 30});
+
+        trace_writer.before_instruction(0x0100, &OpObject::from_byte(0xa0), &[], &[]);
+        trace_writer.before_instruction(0x0103, &OpObject::from_byte(0x38), &[0x12, 0x34], &[0x01]);
+        assert!(trace_writer.failed);
+
+        let written = trace_writer.writer.written.len();
+        trace_writer.before_instruction(0x0104, &OpObject::from_byte(0x01), &[], &[]);
+        assert_eq!(trace_writer.writer.written.len(), written);
+    }
+
+    #[test]
+    fn test_trace_writer_symbols() {
+        let mut output = Vec::new();
+        let mut symbols = SymbolTable::new();
+        symbols.insert(0x0103, "add".to_string());
+        symbols.insert(0x0103, "add/loop".to_string());
+        let mut trace_writer = TraceWriter::with_symbols(&mut output, symbols);
+
+        trace_writer.before_instruction(0x0100, &OpObject::from_byte(0xa0), &[], &[]);
+        trace_writer.before_instruction(0x0103, &OpObject::from_byte(0x38), &[0x12, 0x34], &[0x01]);
+
+        assert_eq!(String::from_utf8(output).unwrap(),
+            "0100 LIT2    <wst> <rst>\n@add\n@add/loop\n0103 ADD2    <wst> 12 34 <rst> 01\n");
+    }
+}
@@ -0,0 +1,469 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::rc::Rc;
+
+use crate::emulators::devices::{mouse, controller};
+use crate::emulators::snapshot::{SnapshotWriter, SnapshotReader, SnapshotError};
+use crate::emulators::uxn::device::{Device, DeviceError, MainRamInterface};
+
+// the first bytes of every input log
+const MAGIC: [u8; 8] = *b"UXNINLOG";
+
+// the version of the input log format that is written. This should be incremented whenever the
+// way entries are serialized changes
+pub const INPUT_LOG_VERSION: u16 = 1;
+
+// an external stimulus to the machine. Each is passed to the device it is for, and then the
+// device's vector is run to handle it
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum InputEvent {
+    Console(u8),
+    MouseMove{x: u16, y: u16},
+    MouseButtonDown(mouse::Button),
+    MouseButtonUp(mouse::Button),
+    MouseScroll{x: i16, y: i16},
+    ControllerButtonDown(controller::Button),
+    ControllerButtonUp(controller::Button),
+    ControllerKey(u8),
+    // the screen refresh timer fired, running the screen vector (or resuming a vector that ran
+    // out of instructions)
+    ScreenTick,
+}
+
+// an entry in an input log
+#[derive(Debug, PartialEq)]
+pub enum LogEntry {
+    // `event` was given to the machine and handled by the vector at `vector` (0 if the event
+    // didn't trigger a vector)
+    Input{event: InputEvent, vector: u16},
+    // the program read `value` from the port at `device_address`, of a device whose values
+    // come from outside the machine (such as the date time device)
+    DeviceRead{device_address: u8, value: u8},
+}
+
+#[derive(Debug)]
+pub enum InputLogError {
+    Io(String),
+    BadMagic,
+    UnsupportedVersion(u16),
+    Truncated,
+    InvalidData(&'static str),
+    // the machine didn't behave as it did when the log was recorded, for instance because a
+    // different rom is being run
+    Diverged(String),
+    // the log has an event for a device that this emulator doesn't have
+    UnsupportedEvent(InputEvent),
+}
+
+impl fmt::Display for InputLogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InputLogError::Io(msg) => write!(f, "Error accessing input log: {}", msg),
+            InputLogError::BadMagic => write!(f, "Not an input log file"),
+            InputLogError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported input log version: {} (expected {})", version, INPUT_LOG_VERSION)
+            },
+            InputLogError::Truncated => write!(f, "Input log is truncated"),
+            InputLogError::InvalidData(msg) => write!(f, "Invalid input log: {}", msg),
+            InputLogError::Diverged(msg) => write!(f, "Replay diverged from the recorded session: {}", msg),
+            InputLogError::UnsupportedEvent(event) => {
+                write!(f, "Input log has an event that can't be replayed by this emulator: {:?}", event)
+            },
+        }
+    }
+}
+
+impl Error for InputLogError {}
+
+impl From<SnapshotError> for InputLogError {
+    fn from(err: SnapshotError) -> Self {
+        match err {
+            SnapshotError::Io(msg) => return InputLogError::Io(msg),
+            SnapshotError::BadMagic => return InputLogError::BadMagic,
+            SnapshotError::UnsupportedVersion(version) => return InputLogError::UnsupportedVersion(version),
+            SnapshotError::Truncated => return InputLogError::Truncated,
+            SnapshotError::InvalidData(msg) => return InputLogError::InvalidData(msg),
+        }
+    }
+}
+
+// how entries and events are tagged in an input log
+const ENTRY_INPUT: u8 = 0;
+const ENTRY_DEVICE_READ: u8 = 1;
+
+const EVENT_CONSOLE: u8 = 0;
+const EVENT_MOUSE_MOVE: u8 = 1;
+const EVENT_MOUSE_BUTTON_DOWN: u8 = 2;
+const EVENT_MOUSE_BUTTON_UP: u8 = 3;
+const EVENT_MOUSE_SCROLL: u8 = 4;
+const EVENT_CONTROLLER_BUTTON_DOWN: u8 = 5;
+const EVENT_CONTROLLER_BUTTON_UP: u8 = 6;
+const EVENT_CONTROLLER_KEY: u8 = 7;
+const EVENT_SCREEN_TICK: u8 = 8;
+
+const MOUSE_BUTTONS: [mouse::Button; 3] = [
+    mouse::Button::Left, mouse::Button::Middle, mouse::Button::Right];
+
+const CONTROLLER_BUTTONS: [controller::Button; 8] = [
+    controller::Button::A, controller::Button::B, controller::Button::Select, controller::Button::Start,
+    controller::Button::Up, controller::Button::Down, controller::Button::Left, controller::Button::Right];
+
+fn put_button<J: PartialEq>(writer: &mut SnapshotWriter, buttons: &[J], button: &J) {
+    let index = buttons.iter().position(|b| { return b == button; }).unwrap();
+    writer.put_u8(u8::try_from(index).unwrap());
+}
+
+fn get_button<J: Copy>(reader: &mut SnapshotReader, buttons: &[J]) -> Result<J, SnapshotError> {
+    return buttons.get(usize::from(reader.get_u8()?)).copied()
+        .ok_or(SnapshotError::InvalidData("invalid button"));
+}
+
+fn put_entry(writer: &mut SnapshotWriter, entry: &LogEntry) {
+    let (event, vector) = match entry {
+        LogEntry::Input{event, vector} => (event, vector),
+        LogEntry::DeviceRead{device_address, value} => {
+            writer.put_u8(ENTRY_DEVICE_READ);
+            writer.put_u8(*device_address);
+            writer.put_u8(*value);
+            return;
+        },
+    };
+
+    writer.put_u8(ENTRY_INPUT);
+    match event {
+        InputEvent::Console(c) => {
+            writer.put_u8(EVENT_CONSOLE);
+            writer.put_u8(*c);
+        },
+        InputEvent::MouseMove{x, y} => {
+            writer.put_u8(EVENT_MOUSE_MOVE);
+            writer.put_u16(*x);
+            writer.put_u16(*y);
+        },
+        InputEvent::MouseButtonDown(button) => {
+            writer.put_u8(EVENT_MOUSE_BUTTON_DOWN);
+            put_button(writer, &MOUSE_BUTTONS, button);
+        },
+        InputEvent::MouseButtonUp(button) => {
+            writer.put_u8(EVENT_MOUSE_BUTTON_UP);
+            put_button(writer, &MOUSE_BUTTONS, button);
+        },
+        InputEvent::MouseScroll{x, y} => {
+            writer.put_u8(EVENT_MOUSE_SCROLL);
+            writer.put_bytes(&x.to_be_bytes());
+            writer.put_bytes(&y.to_be_bytes());
+        },
+        InputEvent::ControllerButtonDown(button) => {
+            writer.put_u8(EVENT_CONTROLLER_BUTTON_DOWN);
+            put_button(writer, &CONTROLLER_BUTTONS, button);
+        },
+        InputEvent::ControllerButtonUp(button) => {
+            writer.put_u8(EVENT_CONTROLLER_BUTTON_UP);
+            put_button(writer, &CONTROLLER_BUTTONS, button);
+        },
+        InputEvent::ControllerKey(key) => {
+            writer.put_u8(EVENT_CONTROLLER_KEY);
+            writer.put_u8(*key);
+        },
+        InputEvent::ScreenTick => {
+            writer.put_u8(EVENT_SCREEN_TICK);
+        },
+    }
+    writer.put_u16(*vector);
+}
+
+fn get_entry(reader: &mut SnapshotReader) -> Result<LogEntry, SnapshotError> {
+    match reader.get_u8()? {
+        ENTRY_INPUT => {},
+        ENTRY_DEVICE_READ => {
+            let device_address = reader.get_u8()?;
+            let value = reader.get_u8()?;
+            return Ok(LogEntry::DeviceRead{device_address, value});
+        },
+        _ => {
+            return Err(SnapshotError::InvalidData("invalid entry type"));
+        },
+    }
+
+    let event = match reader.get_u8()? {
+        EVENT_CONSOLE => InputEvent::Console(reader.get_u8()?),
+        EVENT_MOUSE_MOVE => InputEvent::MouseMove{x: reader.get_u16()?, y: reader.get_u16()?},
+        EVENT_MOUSE_BUTTON_DOWN => InputEvent::MouseButtonDown(get_button(reader, &MOUSE_BUTTONS)?),
+        EVENT_MOUSE_BUTTON_UP => InputEvent::MouseButtonUp(get_button(reader, &MOUSE_BUTTONS)?),
+        EVENT_MOUSE_SCROLL => InputEvent::MouseScroll{
+            x: i16::from_be_bytes(reader.get_array()?),
+            y: i16::from_be_bytes(reader.get_array()?),
+        },
+        EVENT_CONTROLLER_BUTTON_DOWN => InputEvent::ControllerButtonDown(get_button(reader, &CONTROLLER_BUTTONS)?),
+        EVENT_CONTROLLER_BUTTON_UP => InputEvent::ControllerButtonUp(get_button(reader, &CONTROLLER_BUTTONS)?),
+        EVENT_CONTROLLER_KEY => InputEvent::ControllerKey(reader.get_u8()?),
+        EVENT_SCREEN_TICK => InputEvent::ScreenTick,
+        _ => {
+            return Err(SnapshotError::InvalidData("invalid event type"));
+        },
+    };
+    let vector = reader.get_u16()?;
+
+    return Ok(LogEntry::Input{event, vector});
+}
+
+// writes an input log. Each entry is written as soon as it is recorded, so that the log is
+// complete however the emulator exits
+pub struct InputRecorder<J: Write> {
+    writer: J,
+}
+
+impl InputRecorder<File> {
+    pub fn create(path: &Path) -> Result<Self, InputLogError> {
+        let file = File::create(path)
+            .map_err(|err| InputLogError::Io(format!("{}: {}", path.display(), err)))?;
+        return InputRecorder::new(file);
+    }
+}
+
+impl<J: Write> InputRecorder<J> {
+    pub fn new(mut writer: J) -> Result<Self, InputLogError> {
+        let mut header = SnapshotWriter::new();
+        header.put_bytes(&MAGIC);
+        header.put_u16(INPUT_LOG_VERSION);
+        writer.write_all(&header.into_bytes())
+            .map_err(|err| InputLogError::Io(err.to_string()))?;
+
+        return Ok(InputRecorder{writer});
+    }
+
+    pub fn record(&mut self, entry: &LogEntry) -> Result<(), InputLogError> {
+        let mut writer = SnapshotWriter::new();
+        put_entry(&mut writer, entry);
+        self.writer.write_all(&writer.into_bytes())
+            .map_err(|err| InputLogError::Io(err.to_string()))?;
+        return Ok(());
+    }
+}
+
+// the entries of an input log, consumed in order as the session is replayed
+pub struct InputReplay {
+    entries: VecDeque<LogEntry>,
+}
+
+impl InputReplay {
+    pub fn from_bytes(data: &[u8]) -> Result<Self, InputLogError> {
+        let mut reader = SnapshotReader::new(data);
+
+        if !matches!(reader.get_array::<8>(), Ok(magic) if magic == MAGIC) {
+            return Err(InputLogError::BadMagic);
+        }
+
+        let version = reader.get_u16()?;
+        if version != INPUT_LOG_VERSION {
+            return Err(InputLogError::UnsupportedVersion(version));
+        }
+
+        let mut entries = VecDeque::new();
+        while !reader.is_empty() {
+            entries.push_back(get_entry(&mut reader)?);
+        }
+
+        return Ok(InputReplay{entries});
+    }
+
+    pub fn read_from_file(path: &Path) -> Result<Self, InputLogError> {
+        let data = fs::read(path)
+            .map_err(|err| InputLogError::Io(format!("{}: {}", path.display(), err)))?;
+        return InputReplay::from_bytes(&data);
+    }
+
+    // the next event to give to the machine, along with the vector that handled it when it was
+    // recorded. Gives `None` once the log is finished, and an error if the machine didn't make
+    // a device read that it made when the log was recorded
+    pub fn next_input(&mut self) -> Result<Option<(InputEvent, u16)>, InputLogError> {
+        match self.entries.pop_front() {
+            None => return Ok(None),
+            Some(LogEntry::Input{event, vector}) => return Ok(Some((event, vector))),
+            Some(LogEntry::DeviceRead{device_address, ..}) => {
+                return Err(InputLogError::Diverged(format!(
+                    "expected a read from device port 0x{:02x} that didn't happen", device_address)));
+            },
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        return self.entries.is_empty();
+    }
+
+    // the value read from the port at `device_address` when the log was recorded, if the next
+    // entry of the log is a read from that port
+    pub fn next_device_read(&mut self, device_address: u8) -> Option<u8> {
+        match self.entries.front() {
+            Some(LogEntry::DeviceRead{device_address: recorded_address, value})
+                if *recorded_address == device_address => {
+                let value = *value;
+                self.entries.pop_front();
+                return Some(value);
+            },
+            _ => return None,
+        }
+    }
+}
+
+// check that the machine is about to handle `event` with the same vector that handled it when it
+// was recorded
+pub fn check_vector(event: &InputEvent, recorded_vector: u16, vector: u16) -> Result<(), InputLogError> {
+    if recorded_vector != vector {
+        return Err(InputLogError::Diverged(format!(
+            "{:?} was handled by vector 0x{:04x}, but is now handled by vector 0x{:04x}",
+            event, recorded_vector, vector)));
+    }
+
+    return Ok(());
+}
+
+// a device whose reads are recorded to an input log, for devices whose values come from outside
+// the machine
+pub struct RecordingDevice<J: Device, K: Write> {
+    inner: J,
+    slot: u8,
+    recorder: Rc<RefCell<InputRecorder<K>>>,
+}
+
+impl<J: Device, K: Write> RecordingDevice<J, K> {
+    pub fn new(inner: J, slot: u8, recorder: Rc<RefCell<InputRecorder<K>>>) -> Self {
+        RecordingDevice{inner, slot, recorder}
+    }
+}
+
+impl<J: Device, K: Write> Device for RecordingDevice<J, K> {
+    fn write(&mut self, port: u8, val: u8, main_ram: &mut dyn MainRamInterface) -> Result<(), DeviceError> {
+        return self.inner.write(port, val, main_ram);
+    }
+
+    fn read(&mut self, port: u8) -> Result<u8, DeviceError> {
+        let value = self.inner.read(port)?;
+        let device_address = (self.slot << 4) | port;
+        if let Err(err) = self.recorder.borrow_mut().record(&LogEntry::DeviceRead{device_address, value}) {
+            eprintln!("warning: could not record device read: {}", err);
+        }
+        return Ok(value);
+    }
+}
+
+// a device whose reads are taken from an input log rather than from the device itself, so that
+// they give the same values as they did when the log was recorded. Writes are passed through to
+// the device, as are reads once the whole log has been replayed
+pub struct ReplayingDevice<J: Device> {
+    inner: J,
+    slot: u8,
+    replay: Rc<RefCell<InputReplay>>,
+}
+
+impl<J: Device> ReplayingDevice<J> {
+    pub fn new(inner: J, slot: u8, replay: Rc<RefCell<InputReplay>>) -> Self {
+        ReplayingDevice{inner, slot, replay}
+    }
+}
+
+impl<J: Device> Device for ReplayingDevice<J> {
+    fn write(&mut self, port: u8, val: u8, main_ram: &mut dyn MainRamInterface) -> Result<(), DeviceError> {
+        return self.inner.write(port, val, main_ram);
+    }
+
+    fn read(&mut self, port: u8) -> Result<u8, DeviceError> {
+        let mut replay = self.replay.borrow_mut();
+        if replay.is_finished() {
+            return self.inner.read(port);
+        }
+
+        let device_address = (self.slot << 4) | port;
+        return replay.next_device_read(device_address)
+            .ok_or(DeviceError::ReplayMismatch);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct MockDevice {
+        next_value: u8,
+    }
+
+    impl Device for MockDevice {
+        fn write(&mut self, _port: u8, _val: u8, _main_ram: &mut dyn MainRamInterface) -> Result<(), DeviceError> {
+            return Ok(());
+        }
+
+        fn read(&mut self, _port: u8) -> Result<u8, DeviceError> {
+            self.next_value += 1;
+            return Ok(self.next_value);
+        }
+    }
+
+    #[test]
+    fn test_log_round_trip() {
+        let entries = vec![
+            LogEntry::DeviceRead{device_address: 0xc0, value: 0x07},
+            LogEntry::Input{event: InputEvent::Console(b'a'), vector: 0x0123},
+            LogEntry::Input{event: InputEvent::MouseMove{x: 0x0102, y: 0x0304}, vector: 0x0200},
+            LogEntry::Input{event: InputEvent::MouseButtonDown(mouse::Button::Right), vector: 0x0200},
+            LogEntry::Input{event: InputEvent::MouseButtonUp(mouse::Button::Middle), vector: 0x0200},
+            LogEntry::Input{event: InputEvent::MouseScroll{x: -1, y: 2}, vector: 0x0200},
+            LogEntry::Input{event: InputEvent::ControllerButtonDown(controller::Button::Start), vector: 0x0300},
+            LogEntry::Input{event: InputEvent::ControllerButtonUp(controller::Button::Right), vector: 0x0300},
+            LogEntry::Input{event: InputEvent::ControllerKey(b'z'), vector: 0x0000},
+            LogEntry::Input{event: InputEvent::ScreenTick, vector: 0x0400},
+        ];
+
+        let mut log = Vec::new();
+        let mut recorder = InputRecorder::new(&mut log).unwrap();
+        for entry in entries.iter() {
+            recorder.record(entry).unwrap();
+        }
+        drop(recorder);
+
+        let replay = InputReplay::from_bytes(&log).unwrap();
+        assert_eq!(replay.entries, VecDeque::from(entries));
+
+        assert!(matches!(InputReplay::from_bytes(&log[..log.len()-1]), Err(InputLogError::Truncated)));
+        assert!(matches!(InputReplay::from_bytes(&log[1..]), Err(InputLogError::BadMagic)));
+    }
+
+    // reads from a recording device should be recorded, and reads from a replaying device should
+    // give the recorded values, for as long as they match the recording
+    #[test]
+    fn test_record_and_replay_device() {
+        let mut log = Vec::new();
+        let recorder = Rc::new(RefCell::new(InputRecorder::new(&mut log).unwrap()));
+        let mut device = RecordingDevice::new(MockDevice{next_value: 0}, 0xc, recorder.clone());
+        assert_eq!(device.read(0x2), Ok(1));
+        recorder.borrow_mut().record(&LogEntry::Input{event: InputEvent::Console(b'a'), vector: 0x0123}).unwrap();
+        assert_eq!(device.read(0x3), Ok(2));
+        drop(device);
+        drop(recorder);
+
+        let replay = Rc::new(RefCell::new(InputReplay::from_bytes(&log).unwrap()));
+        let mut device = ReplayingDevice::new(MockDevice{next_value: 0x10}, 0xc, replay.clone());
+
+        // the input is still waiting to be replayed
+        assert_eq!(device.read(0x3), Err(DeviceError::ReplayMismatch));
+        assert_eq!(device.read(0x2), Ok(1));
+        assert_eq!(device.read(0x2), Err(DeviceError::ReplayMismatch));
+
+        let (event, vector) = replay.borrow_mut().next_input().unwrap().unwrap();
+        assert_eq!(event, InputEvent::Console(b'a'));
+        assert!(check_vector(&event, vector, 0x0123).is_ok());
+        assert!(matches!(check_vector(&event, vector, 0x0124), Err(InputLogError::Diverged(_))));
+
+        // the second read was never made
+        assert!(matches!(replay.borrow_mut().next_input(), Err(InputLogError::Diverged(_))));
+        assert!(matches!(replay.borrow_mut().next_input(), Ok(None)));
+
+        // once the log is finished, reads come from the device itself
+        assert_eq!(device.read(0x2), Ok(0x11));
+    }
+}
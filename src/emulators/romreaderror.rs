@@ -12,3 +12,19 @@ impl fmt::Display for RomReadError {
 }
 
 impl Error for RomReadError {}
+
+#[derive(Debug)]
+pub struct RomTooLargeError {
+    pub fname: String,
+    pub size: usize,
+    pub max_size: usize,
+}
+
+impl fmt::Display for RomTooLargeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ROM {} is too large: {} bytes (the maximum is {} bytes)",
+            self.fname, self.size, self.max_size)
+    }
+}
+
+impl Error for RomTooLargeError {}
@@ -0,0 +1,319 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+// the first bytes of every save state file
+const MAGIC: [u8; 8] = *b"UXNSTATE";
+
+// the version of the save state format that is written. This should be incremented whenever the
+// way the machine or any device serializes its state changes
+pub const SNAPSHOT_VERSION: u16 = 4;
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(String),
+    BadMagic,
+    UnsupportedVersion(u16),
+    Truncated,
+    InvalidData(&'static str),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Io(msg) => write!(f, "Error accessing save state: {}", msg),
+            SnapshotError::BadMagic => write!(f, "Not a save state file"),
+            SnapshotError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported save state version: {} (expected {})", version, SNAPSHOT_VERSION)
+            },
+            SnapshotError::Truncated => write!(f, "Save state is truncated"),
+            SnapshotError::InvalidData(msg) => write!(f, "Invalid save state: {}", msg),
+        }
+    }
+}
+
+impl Error for SnapshotError {}
+
+// accumulates the serialized state of a machine component. All multi-byte values are written big
+// endian, matching the uxn itself
+pub struct SnapshotWriter {
+    buf: Vec<u8>,
+}
+
+impl SnapshotWriter {
+    pub fn new() -> Self {
+        SnapshotWriter{buf: Vec::new()}
+    }
+
+    pub fn put_u8(&mut self, val: u8) {
+        self.buf.push(val);
+    }
+
+    pub fn put_bool(&mut self, val: bool) {
+        self.buf.push(u8::from(val));
+    }
+
+    pub fn put_u16(&mut self, val: u16) {
+        self.buf.extend_from_slice(&val.to_be_bytes());
+    }
+
+    pub fn put_u32(&mut self, val: u32) {
+        self.buf.extend_from_slice(&val.to_be_bytes());
+    }
+
+    pub fn put_u64(&mut self, val: u64) {
+        self.buf.extend_from_slice(&val.to_be_bytes());
+    }
+
+    pub fn put_bytes(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    // write a length (as a u32) followed by the bytes themselves
+    pub fn put_sized_bytes(&mut self, bytes: &[u8]) {
+        self.put_u32(u32::try_from(bytes.len()).expect("snapshot field too large"));
+        self.put_bytes(bytes);
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+// reads back values in the order they were written by a `SnapshotWriter`
+pub struct SnapshotReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> SnapshotReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        SnapshotReader{data}
+    }
+
+    pub fn get_bytes(&mut self, len: usize) -> Result<&'a [u8], SnapshotError> {
+        if len > self.data.len() {
+            return Err(SnapshotError::Truncated);
+        }
+
+        let (ret, rest) = self.data.split_at(len);
+        self.data = rest;
+        return Ok(ret);
+    }
+
+    pub fn get_u8(&mut self) -> Result<u8, SnapshotError> {
+        return Ok(self.get_bytes(1)?[0]);
+    }
+
+    pub fn get_bool(&mut self) -> Result<bool, SnapshotError> {
+        match self.get_u8()? {
+            0 => return Ok(false),
+            1 => return Ok(true),
+            _ => return Err(SnapshotError::InvalidData("expected boolean")),
+        }
+    }
+
+    pub fn get_u16(&mut self) -> Result<u16, SnapshotError> {
+        let bytes = self.get_bytes(2)?;
+        return Ok(u16::from_be_bytes([bytes[0], bytes[1]]));
+    }
+
+    pub fn get_u32(&mut self) -> Result<u32, SnapshotError> {
+        let bytes = self.get_bytes(4)?;
+        return Ok(u32::from_be_bytes(bytes.try_into().unwrap()));
+    }
+
+    pub fn get_u64(&mut self) -> Result<u64, SnapshotError> {
+        let bytes = self.get_bytes(8)?;
+        return Ok(u64::from_be_bytes(bytes.try_into().unwrap()));
+    }
+
+    pub fn get_array<const N: usize>(&mut self) -> Result<[u8; N], SnapshotError> {
+        return Ok(self.get_bytes(N)?.try_into().unwrap());
+    }
+
+    pub fn get_sized_bytes(&mut self) -> Result<&'a [u8], SnapshotError> {
+        let len = self.get_u32()?;
+        return self.get_bytes(usize::try_from(len).unwrap());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+}
+
+// implemented by the uxn and by devices so that their complete state can be captured and later
+// restored
+pub trait Snapshot {
+    fn save_state(&self, writer: &mut SnapshotWriter);
+
+    fn load_state(&mut self, reader: &mut SnapshotReader) -> Result<(), SnapshotError>;
+}
+
+// the saved state of a whole machine: the uxn itself plus one section per device, keyed by the
+// device's index in the device list (i.e. the high nibble of its address)
+pub struct SaveState {
+    machine: Vec<u8>,
+    devices: BTreeMap<u8, Vec<u8>>,
+}
+
+impl SaveState {
+    pub fn new() -> Self {
+        SaveState{machine: Vec::new(), devices: BTreeMap::new()}
+    }
+
+    pub fn save_machine(&mut self, machine: &dyn Snapshot) {
+        let mut writer = SnapshotWriter::new();
+        machine.save_state(&mut writer);
+        self.machine = writer.into_bytes();
+    }
+
+    pub fn save_device(&mut self, device_index: u8, device: &dyn Snapshot) {
+        let mut writer = SnapshotWriter::new();
+        device.save_state(&mut writer);
+        self.devices.insert(device_index, writer.into_bytes());
+    }
+
+    pub fn load_machine(&self, machine: &mut dyn Snapshot) -> Result<(), SnapshotError> {
+        return load_section(&self.machine, machine);
+    }
+
+    // restore a device from its section of the save state. A device with no section (for
+    // instance the screen, when loading a state saved by uxncli) is left as it is
+    pub fn load_device(&self, device_index: u8, device: &mut dyn Snapshot) -> Result<(), SnapshotError> {
+        if let Some(section) = self.devices.get(&device_index) {
+            return load_section(section, device);
+        }
+
+        return Ok(());
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = SnapshotWriter::new();
+        writer.put_bytes(&MAGIC);
+        writer.put_u16(SNAPSHOT_VERSION);
+        writer.put_sized_bytes(&self.machine);
+
+        writer.put_u8(u8::try_from(self.devices.len()).unwrap());
+        for (device_index, section) in self.devices.iter() {
+            writer.put_u8(*device_index);
+            writer.put_sized_bytes(section);
+        }
+
+        return writer.into_bytes();
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, SnapshotError> {
+        let mut reader = SnapshotReader::new(data);
+
+        if !matches!(reader.get_array::<8>(), Ok(magic) if magic == MAGIC) {
+            return Err(SnapshotError::BadMagic);
+        }
+
+        let version = reader.get_u16()?;
+        if version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+
+        let machine = reader.get_sized_bytes()?.to_vec();
+
+        let mut devices = BTreeMap::new();
+        for _ in 0..reader.get_u8()? {
+            let device_index = reader.get_u8()?;
+            devices.insert(device_index, reader.get_sized_bytes()?.to_vec());
+        }
+
+        if !reader.is_empty() {
+            return Err(SnapshotError::InvalidData("trailing bytes after last section"));
+        }
+
+        return Ok(SaveState{machine, devices});
+    }
+
+    pub fn write_to_file(&self, path: &Path) -> Result<(), SnapshotError> {
+        return fs::write(path, self.to_bytes())
+            .map_err(|err| SnapshotError::Io(format!("{}: {}", path.display(), err)));
+    }
+
+    pub fn read_from_file(path: &Path) -> Result<Self, SnapshotError> {
+        let data = fs::read(path)
+            .map_err(|err| SnapshotError::Io(format!("{}: {}", path.display(), err)))?;
+        return SaveState::from_bytes(&data);
+    }
+}
+
+fn load_section(section: &[u8], target: &mut dyn Snapshot) -> Result<(), SnapshotError> {
+    let mut reader = SnapshotReader::new(section);
+    target.load_state(&mut reader)?;
+
+    if !reader.is_empty() {
+        return Err(SnapshotError::InvalidData("section is longer than expected"));
+    }
+
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct MockComponent {
+        a: u8,
+        b: u16,
+        c: Vec<u8>,
+    }
+
+    impl Snapshot for MockComponent {
+        fn save_state(&self, writer: &mut SnapshotWriter) {
+            writer.put_u8(self.a);
+            writer.put_u16(self.b);
+            writer.put_sized_bytes(&self.c);
+        }
+
+        fn load_state(&mut self, reader: &mut SnapshotReader) -> Result<(), SnapshotError> {
+            self.a = reader.get_u8()?;
+            self.b = reader.get_u16()?;
+            self.c = reader.get_sized_bytes()?.to_vec();
+            return Ok(());
+        }
+    }
+
+    #[test]
+    fn test_save_state_round_trip() {
+        let machine = MockComponent{a: 0x12, b: 0x3456, c: vec![1, 2, 3]};
+        let device = MockComponent{a: 0xaa, b: 0xbbcc, c: vec![]};
+
+        let mut save_state = SaveState::new();
+        save_state.save_machine(&machine);
+        save_state.save_device(0x1, &device);
+
+        let save_state = SaveState::from_bytes(&save_state.to_bytes()).unwrap();
+
+        let mut machine = MockComponent{a: 0, b: 0, c: vec![]};
+        let mut device = MockComponent{a: 0, b: 0, c: vec![9]};
+        let mut missing_device = MockComponent{a: 0x77, b: 0, c: vec![]};
+        save_state.load_machine(&mut machine).unwrap();
+        save_state.load_device(0x1, &mut device).unwrap();
+        save_state.load_device(0x2, &mut missing_device).unwrap();
+
+        assert_eq!((machine.a, machine.b, machine.c), (0x12, 0x3456, vec![1, 2, 3]));
+        assert_eq!((device.a, device.b, device.c), (0xaa, 0xbbcc, vec![]));
+        assert_eq!(missing_device.a, 0x77);
+    }
+
+    #[test]
+    fn test_save_state_bad_header() {
+        let mut bytes = SaveState::new().to_bytes();
+
+        bytes[9] += 1;
+        assert!(matches!(SaveState::from_bytes(&bytes),
+            Err(SnapshotError::UnsupportedVersion(v)) if v == SNAPSHOT_VERSION + 1));
+
+        bytes[0] = b'X';
+        assert!(matches!(SaveState::from_bytes(&bytes), Err(SnapshotError::BadMagic)));
+
+        let bytes = SaveState::new().to_bytes();
+        assert!(matches!(SaveState::from_bytes(&bytes[..bytes.len()-1]), Err(SnapshotError::Truncated)));
+    }
+}
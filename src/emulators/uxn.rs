@@ -1,15 +1,31 @@
-use crate::instruction::InstructionFactory;
+use crate::instruction::{Instruction, InstructionFactory};
 
 pub const INIT_VECTOR: u16 = 0x100;
 
+// the number of banks of memory that can be accessed through the System device's expansion
+// port, including the main ram as bank 0
+pub const BANK_COUNT: u16 = 0x10;
+const BANK_SIZE: usize = 0x10000;
+
+// the largest rom that can be loaded. Roms are loaded at `INIT_VECTOR`, and anything that doesn't
+// fit in the main ram continues into the expansion banks
+pub const MAX_ROM_SIZE: usize = BANK_SIZE * BANK_COUNT as usize - INIT_VECTOR as usize;
+
 pub mod device; 
+mod stack;
+mod history;
+pub use stack::StackFaultPolicy;
+use stack::{Stack, STACK_SIZE};
+use history::{History, HistoryEntry, Change, Registers};
+pub use device::DeviceAccessPolicy;
 use device::{DeviceList, DeviceWriteReturnCode, DeviceReadReturnCode, MainRamInterface, MainRamInterfaceError};
 use crate::emulators::devices;
 use crate::emulators::devices::system::{UxnSystemInterface, UxnSystemColor};
 use crate::emulators::devices::screen::UxnSystemScreenInterface;
-use crate::uxninterface::{Uxn, UxnError, UxnStatus, UxnWithDevices};
-
-const STACK_MAX_LEN : usize = 0xff;
+use crate::emulators::snapshot::{Snapshot, SnapshotWriter, SnapshotReader, SnapshotError};
+use crate::emulators::observer::UxnObserver;
+use crate::ops::OpObject;
+use crate::uxninterface::{Uxn, UxnCrash, UxnError, UxnStatus, UxnWithDevices};
 
 struct UxnWithDevicesImpl<'a, J, K>
     where J: Uxn + UxnSystemInterface,
@@ -17,6 +33,7 @@ struct UxnWithDevicesImpl<'a, J, K>
 {
     uxn: &'a mut J,
     device_list: K,
+    observer: Option<&'a mut dyn UxnObserver>,
 }
 
 impl <'a, J, K> Uxn for UxnWithDevicesImpl<'a, J, K>
@@ -32,6 +49,9 @@ impl <'a, J, K> Uxn for UxnWithDevicesImpl<'a, J, K>
     }
 
     fn write_to_ram(&mut self, addr: u16, val: u8) {
+        if let Some(observer) = self.observer.as_mut() {
+            observer.ram_write(addr, val);
+        }
         return self.uxn.write_to_ram(addr, val);
     }
 
@@ -60,29 +80,144 @@ impl <'a, J, K> Uxn for UxnWithDevicesImpl<'a, J, K>
     }
 }
 
+// a uxn as it is lent to its devices, reporting the writes they make to its main ram (by reading
+// a file into it, or with the System device's expansion commands) to the observer
+struct ObservedUxn<'a, 'b, J> {
+    uxn: &'a mut J,
+    observer: &'a mut Option<&'b mut dyn UxnObserver>,
+}
+
+impl<'a, 'b, J: MainRamInterface> MainRamInterface for ObservedUxn<'a, 'b, J> {
+    fn read(&self, address: u16, num_bytes: u16) -> Result<Vec<u8>, MainRamInterfaceError> {
+        return self.uxn.read(address, num_bytes);
+    }
+
+    fn write(&mut self, address: u16, bytes: &[u8]) -> Result<usize, MainRamInterfaceError> {
+        let written = self.uxn.write(address, bytes)?;
+        if let Some(observer) = self.observer.as_mut() {
+            for (offset, val) in bytes.iter().enumerate() {
+                observer.ram_write(address.wrapping_add(offset as u16), *val);
+            }
+        }
+        return Ok(written);
+    }
+}
+
+impl<'a, 'b, J: UxnSystemInterface> UxnSystemInterface for ObservedUxn<'a, 'b, J> {
+    fn set_working_stack_index(&mut self, index: u8) {
+        self.uxn.set_working_stack_index(index);
+    }
+
+    fn get_working_stack_index(&self) -> u8 {
+        self.uxn.get_working_stack_index()
+    }
+
+    fn set_return_stack_index(&mut self, index: u8) {
+        self.uxn.set_return_stack_index(index);
+    }
+
+    fn get_return_stack_index(&self) -> u8 {
+        self.uxn.get_return_stack_index()
+    }
+
+    fn set_system_vector(&mut self, vector: u16) {
+        self.uxn.set_system_vector(vector);
+    }
+
+    fn get_system_vector(&self) -> u16 {
+        self.uxn.get_system_vector()
+    }
+
+    fn set_expansion_address(&mut self, address: u16) {
+        self.uxn.set_expansion_address(address);
+    }
+
+    fn get_expansion_address(&self) -> u16 {
+        self.uxn.get_expansion_address()
+    }
+
+    fn read_from_bank(&self, bank: u16, address: u16) -> u8 {
+        self.uxn.read_from_bank(bank, address)
+    }
+
+    fn write_to_bank(&mut self, bank: u16, address: u16, val: u8) {
+        // bank 0 is the main ram
+        if let (0, Some(observer)) = (bank, self.observer.as_mut()) {
+            observer.ram_write(address, val);
+        }
+        self.uxn.write_to_bank(bank, address, val);
+    }
+
+    fn set_system_color(&mut self, slot: UxnSystemColor, val: u8) {
+        self.uxn.set_system_color(slot, val);
+    }
+
+    fn get_system_color(&self, slot: UxnSystemColor) -> u8 {
+        self.uxn.get_system_color(slot)
+    }
+
+    fn start_termination(&mut self) {
+        self.uxn.start_termination();
+    }
+
+    fn get_working_stack_iter(&self) -> std::slice::Iter<u8> {
+        self.uxn.get_working_stack_iter()
+    }
+
+    fn get_return_stack_iter(&self) -> std::slice::Iter<u8> {
+        self.uxn.get_return_stack_iter()
+    }
+}
+
+// deals with a program accessing a device slot that has no device in it
+trait UnmappedDeviceHandler {
+    fn access_unmapped_device(&mut self, device_address: u8) -> Result<(), UxnError>;
+}
+
 impl <'a, J, K> UxnWithDevices for UxnWithDevicesImpl<'a, J, K>
-    where J: Uxn + UxnSystemInterface + MainRamInterface,
+    where J: Uxn + UxnSystemInterface + MainRamInterface + UnmappedDeviceHandler,
           K: DeviceList,
 {
     fn read_from_device(&mut self, device_address: u8) -> Result<u8, UxnError> {
-        match self.device_list.read_from_device(device_address) {
-            DeviceReadReturnCode::Success(res) => return res,
+        let res = match self.device_list.read_from_device(device_address) {
+            DeviceReadReturnCode::Success(Err(UxnError::UnrecognisedDevice)) => {
+                self.uxn.access_unmapped_device(device_address).map(|_| 0)
+            },
+            DeviceReadReturnCode::Success(res) => res,
             DeviceReadReturnCode::ReadFromSystemDevice(port) => {
                 let mut temp_writer = Vec::new();
                 let mut system = devices::system::System::new(self.uxn, &mut temp_writer);
-                return Ok(system.read(port));
+                system.read(port).map_err(UxnError::from)
             },
+        };
+
+        if let (Some(observer), Ok(val)) = (self.observer.as_mut(), &res) {
+            observer.device_read(device_address, *val);
         }
+        return res;
     }
 
-    fn write_to_device(&mut self, device_address: u8, val: u8) {
-        match self.device_list.write_to_device(device_address, val, self.uxn) {
+    fn write_to_device(&mut self, device_address: u8, val: u8) -> Result<(), UxnError> {
+        if let Some(observer) = self.observer.as_mut() {
+            observer.device_write(device_address, val);
+        }
+
+        let mut uxn = ObservedUxn{uxn: &mut *self.uxn, observer: &mut self.observer};
+        match self.device_list.write_to_device(device_address, val, &mut uxn) {
             DeviceWriteReturnCode::Success => {},
             DeviceWriteReturnCode::WriteToSystemDevice(port, debug_printer) => {
-                let mut system = devices::system::System::new(self.uxn, debug_printer);
-                system.write(port, val);
+                let mut system = devices::system::System::new(&mut uxn, debug_printer);
+                system.write(port, val)?;
+            },
+            DeviceWriteReturnCode::UnrecognisedDevice => {
+                return self.uxn.access_unmapped_device(device_address);
+            },
+            DeviceWriteReturnCode::Failure(err) => {
+                return Err(UxnError::DeviceError(err));
             },
         }
+
+        return Ok(());
     }
 }
 
@@ -90,12 +225,24 @@ pub struct UxnImpl<J>
    where J: InstructionFactory, 
 {
     ram: Vec<u8>,
+    // expansion memory, banks 1 and upwards. Banks are only allocated once they are written to
+    expansion_banks: Vec<Vec<u8>>,
+    expansion_address: u16,
     program_counter: Result<u16, ()>,
-    working_stack: Vec<u8>,
-    return_stack: Vec<u8>,
+    working_stack: Stack,
+    return_stack: Stack,
+    stack_fault_policy: StackFaultPolicy,
+    device_access_policy: DeviceAccessPolicy,
+    // a bit for each device slot, set once a warning about the slot being unmapped has been given
+    unmapped_device_warnings: u16,
     instruction_factory: J,
+    system_vector: u16,
     system_colors: [u8;6],
     should_terminate: bool,
+    suspended: bool,
+    observer: Option<Box<dyn UxnObserver>>,
+    // a record of the most recently executed instructions, so that they can be undone
+    history: Option<History>,
 }
 
 impl<J> MainRamInterface for UxnImpl<J>
@@ -123,6 +270,12 @@ J: InstructionFactory,
             return Err(MainRamInterfaceError::AddressOutOfBounds);
         };
 
+        if let Some(history) = self.history.as_mut() {
+            for (address, old_val) in (address..).zip(target.iter()) {
+                history.record(Change::Ram{address, old_val: *old_val});
+            }
+        }
+
         target.clone_from_slice(bytes);
         return Ok(bytes.len());
     }
@@ -153,6 +306,7 @@ J: InstructionFactory,
     }
 
     fn write_to_ram(&mut self, addr: u16, val: u8) {
+        self.record_change(Change::Ram{address: addr, old_val: self.ram[usize::from(addr)]});
         self.ram[usize::from(addr)] = val;
     }
 
@@ -168,37 +322,23 @@ J: InstructionFactory,
     }
 
     fn push_to_return_stack(&mut self, byte: u8) -> Result<(), UxnError> {
-        if self.return_stack.len() == STACK_MAX_LEN {
-            return Err(UxnError::StackOverflow);
-        }
-
-        self.return_stack.push(byte);
-        Ok(())
+        let index = self.return_stack.get_pointer();
+        self.record_change(Change::ReturnStack{index, old_val: self.return_stack.data()[usize::from(index)]});
+        return self.return_stack.push(byte, self.stack_fault_policy);
     }
 
     fn push_to_working_stack(&mut self, byte: u8) -> Result<(), UxnError> {
-        if self.working_stack.len() == STACK_MAX_LEN {
-            return Err(UxnError::StackOverflow);
-        }
-
-        self.working_stack.push(byte);
-        Ok(())
+        let index = self.working_stack.get_pointer();
+        self.record_change(Change::WorkingStack{index, old_val: self.working_stack.data()[usize::from(index)]});
+        return self.working_stack.push(byte, self.stack_fault_policy);
     }
 
     fn pop_from_working_stack(&mut self) -> Result<u8, UxnError> {
-        if let Some(v) = self.working_stack.pop() {
-            return Ok(v);
-        }
-
-        Err(UxnError::StackUnderflow)
+        return self.working_stack.pop(self.stack_fault_policy);
     }
 
     fn pop_from_return_stack(&mut self) -> Result<u8, UxnError> {
-        if let Some(v) = self.return_stack.pop() {
-            return Ok(v);
-        }
-
-        Err(UxnError::StackUnderflow)
+        return self.return_stack.pop(self.stack_fault_policy);
     }
 }
 
@@ -206,27 +346,158 @@ impl<J> UxnImpl<J>
 where
 J: InstructionFactory,
 {
-    pub fn new<I>(rom: I, instruction_factory: J) -> Result<Self, UxnError>
+    // create a uxn with `rom` loaded into its memory. Any of the rom past `MAX_ROM_SIZE` bytes is
+    // ignored, so callers should check the size of the rom first
+    pub fn new<I>(mut rom: I, instruction_factory: J) -> Result<Self, UxnError>
     where
         I: Iterator<Item = u8>,
         J: InstructionFactory,
     {
-        let mut ram = vec![0x0; 0x10000];
+        let mut ram = vec![0x0; BANK_SIZE];
 
         let init_vector: usize = INIT_VECTOR.into();
-        for (ram_loc, val) in (&mut ram[init_vector..]).iter_mut().zip(rom).take(0x10000 - init_vector) {
+        for (ram_loc, val) in (&mut ram[init_vector..]).iter_mut().zip(&mut rom) {
             *ram_loc = val;
         }
 
+        // the rest of the rom is loaded into the expansion banks, in order
+        let rest_of_rom = rom.take(MAX_ROM_SIZE - (BANK_SIZE - init_vector)).collect::<Vec<u8>>();
+        let expansion_banks = rest_of_rom.chunks(BANK_SIZE).map(|chunk| {
+            let mut expansion_bank = chunk.to_vec();
+            expansion_bank.resize(BANK_SIZE, 0x0);
+            return expansion_bank;
+        }).collect();
+
         let system_colors = [0x0, 0x0, 0x0, 0x0, 0x0, 0x0];
 
         let should_terminate = false;
 
-        return Ok(UxnImpl{ram, program_counter:Ok(0), working_stack: Vec::new(),
-        return_stack: Vec::new(), instruction_factory, system_colors, should_terminate});
+        return Ok(UxnImpl{ram, expansion_banks, expansion_address: 0, program_counter:Ok(0), working_stack: Stack::new(),
+        return_stack: Stack::new(), stack_fault_policy: StackFaultPolicy::Error,
+        device_access_policy: DeviceAccessPolicy::Ignore, unmapped_device_warnings: 0,
+        instruction_factory, system_vector: 0, system_colors, should_terminate,
+        suspended: false, observer: None, history: None});
+    }
+
+    // choose what happens when the program accesses a device slot with no device in it (by
+    // default the access is ignored)
+    pub fn set_device_access_policy(&mut self, policy: DeviceAccessPolicy) {
+        self.device_access_policy = policy;
+    }
+
+    // register an observer, to be told about each instruction the uxn executes and the device
+    // accesses and ram writes it makes
+    pub fn set_observer(&mut self, observer: Box<dyn UxnObserver>) {
+        self.observer = Some(observer);
+    }
+
+    // choose what happens when a stack overflows or underflows (by default the operation
+    // fails with an error)
+    pub fn set_stack_fault_policy(&mut self, policy: StackFaultPolicy) {
+        self.stack_fault_policy = policy;
+    }
+
+    // keep a record of the last `capacity` instructions executed, so that they can be undone
+    // with `step_back` and `run_back_to`. A capacity of zero (the default) keeps no record.
+    // Any instructions already recorded are forgotten
+    pub fn set_history_capacity(&mut self, capacity: usize) {
+        self.history = if capacity > 0 { Some(History::new(capacity)) } else { None };
+    }
+
+    // make changes to the uxn from outside of any instruction, as the host embedding it does.
+    // These changes aren't recorded in the history, so undoing instructions leaves them in place
+    pub fn unrecorded<T>(&mut self, change: impl FnOnce(&mut Self) -> T) -> T {
+        let history = self.history.take();
+        let ret = change(self);
+        self.history = history;
+        return ret;
+    }
+
+    // the number of instructions that can currently be undone
+    pub fn history_len(&self) -> usize {
+        return self.history.as_ref().map_or(0, |history| { return history.len(); });
+    }
+
+    // undo the last `n` instructions executed (or as many as are recorded, if that is fewer),
+    // returning how many were undone. The ram, stacks and registers of the uxn are restored
+    // exactly, but the state of its devices is not. Having been stepped back, the uxn is left
+    // part way through a vector, so it can carry on forwards with `resume_with_budget`
+    pub fn step_back(&mut self, n: usize) -> usize {
+        let mut steps = 0;
+        while steps < n {
+            let entry = match self.history.as_mut().and_then(|history| { return history.pop(); }) {
+                Some(entry) => entry,
+                None => break,
+            };
+            self.undo(entry);
+            steps += 1;
+        }
+
+        if steps > 0 {
+            self.suspended = true;
+        }
+        return steps;
+    }
+
+    // undo instructions until the program counter is at `address`, as it was before the most
+    // recent instruction executed from there, returning how many instructions were undone. If
+    // no instruction at `address` is recorded nothing is undone and `None` is returned
+    pub fn run_back_to(&mut self, address: u16) -> Option<usize> {
+        let steps = self.history.as_ref()?.steps_back_to(address)?;
+        return Some(self.step_back(steps));
+    }
+
+    fn registers(&self) -> Registers {
+        return Registers{
+            program_counter: self.program_counter,
+            working_stack_depth: self.working_stack.get_depth(),
+            return_stack_depth: self.return_stack.get_depth(),
+            system_vector: self.system_vector,
+            expansion_address: self.expansion_address,
+            system_colors: self.system_colors,
+            should_terminate: self.should_terminate,
+        };
+    }
+
+    fn record_change(&mut self, change: Change) {
+        if let Some(history) = self.history.as_mut() {
+            history.record(change);
+        }
+    }
+
+    fn undo(&mut self, entry: HistoryEntry) {
+        for change in entry.changes.iter().rev() {
+            match *change {
+                Change::Ram{address, old_val} => {
+                    self.ram[usize::from(address)] = old_val;
+                },
+                Change::Bank{bank, address, old_val} => {
+                    self.expansion_banks[usize::from(bank - 1)][usize::from(address)] = old_val;
+                },
+                Change::BankCount(count) => {
+                    self.expansion_banks.truncate(count);
+                },
+                Change::WorkingStack{index, old_val} => {
+                    self.working_stack.set_slot(index, old_val);
+                },
+                Change::ReturnStack{index, old_val} => {
+                    self.return_stack.set_slot(index, old_val);
+                },
+            }
+        }
+
+        let registers = entry.registers;
+        self.program_counter = registers.program_counter;
+        self.working_stack.set_depth(registers.working_stack_depth);
+        self.return_stack.set_depth(registers.return_stack_depth);
+        self.system_vector = registers.system_vector;
+        self.expansion_address = registers.expansion_address;
+        self.system_colors = registers.system_colors;
+        self.should_terminate = registers.should_terminate;
     }
 
-    pub fn run<K: DeviceList>(&mut self, vector: u16, devices: K) -> Result<UxnStatus, UxnError>
+    // execute the program starting at `vector` until it halts or terminates
+    pub fn run<K: DeviceList>(&mut self, vector: u16, devices: K) -> Result<UxnStatus, UxnCrash>
     {
         // vector of zero means that nothing should be done (it is what devices have as their
         // vectors when they are unset)
@@ -235,34 +506,225 @@ J: InstructionFactory,
         }
 
         self.set_program_counter(vector);
+        return self.execute(devices, None);
+    }
+
+    // execute the program starting at `vector`, but stop after at most `max_instructions`
+    // instructions. If the budget runs out before the program halts `UxnStatus::BudgetExhausted`
+    // is returned and the program counter and stacks are left untouched, so that execution can
+    // be picked up again with `resume_with_budget`
+    pub fn run_with_budget<K: DeviceList>(&mut self, vector: u16, devices: K, max_instructions: u64)
+        -> Result<UxnStatus, UxnCrash>
+    {
+        if vector == 0 {
+            return Ok(UxnStatus::Halt);
+        }
+
+        self.set_program_counter(vector);
+        return self.execute(devices, Some(max_instructions));
+    }
+
+    // continue executing a vector that previously returned `UxnStatus::BudgetExhausted`, for at
+    // most another `max_instructions` instructions. If there is no such vector this is a no-op
+    pub fn resume_with_budget<K: DeviceList>(&mut self, devices: K, max_instructions: u64)
+        -> Result<UxnStatus, UxnCrash>
+    {
+        if !self.suspended {
+            return Ok(UxnStatus::Halt);
+        }
+
+        return self.execute(devices, Some(max_instructions));
+    }
+
+    // whether a vector ran out of instruction budget part way through, and is waiting to be
+    // resumed
+    pub fn is_suspended(&self) -> bool {
+        return self.suspended;
+    }
+
+    // the byte in slot `index` of the working stack, whether or not it is below the stack pointer
+    pub fn read_from_working_stack(&self, index: u8) -> u8 {
+        return self.working_stack.data()[usize::from(index)];
+    }
 
+    // set the byte in slot `index` of the working stack, leaving the stack pointer where it is
+    pub fn write_to_working_stack(&mut self, index: u8, val: u8) {
+        self.record_change(Change::WorkingStack{index, old_val: self.read_from_working_stack(index)});
+        self.working_stack.set_slot(index, val);
+    }
+
+    // the byte in slot `index` of the return stack, whether or not it is below the stack pointer
+    pub fn read_from_return_stack(&self, index: u8) -> u8 {
+        return self.return_stack.data()[usize::from(index)];
+    }
+
+    // set the byte in slot `index` of the return stack, leaving the stack pointer where it is
+    pub fn write_to_return_stack(&mut self, index: u8, val: u8) {
+        self.record_change(Change::ReturnStack{index, old_val: self.read_from_return_stack(index)});
+        self.return_stack.set_slot(index, val);
+    }
+
+    fn execute<K: DeviceList>(&mut self, devices: K, max_instructions: Option<u64>)
+        -> Result<UxnStatus, UxnCrash>
+    {
+        self.suspended = false;
+
+        // the observer is moved out of the uxn whilst it executes, so that it can be lent out
+        // alongside it
+        let mut observer = self.observer.take();
+        let res = self.execute_observed(devices, max_instructions,
+            observer.as_deref_mut().map(|observer| -> &mut dyn UxnObserver { observer }));
+        self.observer = observer;
+        return res;
+    }
+
+    fn execute_observed<'a, K: DeviceList>(&'a mut self, devices: K, max_instructions: Option<u64>,
+        observer: Option<&'a mut dyn UxnObserver>) -> Result<UxnStatus, UxnCrash>
+    {
         let mut uxn_with_devices = UxnWithDevicesImpl {
             uxn: self,
             device_list: devices,
+            observer,
         };
 
+        let mut instructions_executed = 0u64;
+        let mut handling_fault = false;
+
         loop {
+            if let Some(max_instructions) = max_instructions {
+                if instructions_executed == max_instructions {
+                    uxn_with_devices.uxn.suspended = true;
+                    return Ok(UxnStatus::BudgetExhausted);
+                }
+                instructions_executed += 1;
+            }
+
+            let instr_addr = uxn_with_devices.get_program_counter();
             let instr = uxn_with_devices.read_next_byte_from_ram();
             if instr == Err(UxnError::OutOfRangeMemoryAddress) {
                 return Ok(UxnStatus::Halt);
             }
             let instr = instr.unwrap();
+            let instr_addr = instr_addr.unwrap();
 
             if instr == 0x0 {
                 return Ok(UxnStatus::Halt);
             }
 
-            // get the operation that the instruction represents
+            // get the operation that the instruction represents (this is a table lookup for
+            // OpObjectFactory, so no allocation happens per instruction)
             let op = uxn_with_devices.uxn.instruction_factory.from_byte(instr);
 
+            // the registers are recorded as they were before the instruction was fetched, so
+            // that undoing it leaves the program counter pointing at it
+            if uxn_with_devices.uxn.history.is_some() {
+                let registers = Registers{program_counter: Ok(instr_addr), ..uxn_with_devices.uxn.registers()};
+                if let Some(history) = uxn_with_devices.uxn.history.as_mut() {
+                    history.begin_instruction(registers);
+                }
+            }
+
+            if let Some(observer) = uxn_with_devices.observer.as_mut() {
+                let uxn = &uxn_with_devices.uxn;
+                observer.before_instruction(instr_addr, &OpObject::from_byte(instr),
+                    uxn.working_stack.contents(), uxn.return_stack.contents());
+            }
+
             // call its handler
-            op.execute(&mut uxn_with_devices)?;
+            if let Err(err) = op.execute(&mut uxn_with_devices) {
+                let uxn = &mut uxn_with_devices.uxn;
+
+                // a fault raised by the fault handler itself isn't passed back to it, as that
+                // could loop forever
+                if handling_fault || !uxn.dispatch_fault(&err, instr_addr, instr) {
+                    return Err(UxnCrash{
+                        error: err,
+                        address: instr_addr,
+                        instruction: instr,
+                        working_stack: uxn.working_stack.contents().to_vec(),
+                        return_stack: uxn.return_stack.contents().to_vec(),
+                    });
+                }
+                handling_fault = true;
+            } else if let Some(observer) = uxn_with_devices.observer.as_mut() {
+                let uxn = &uxn_with_devices.uxn;
+                observer.after_instruction(instr_addr, &OpObject::from_byte(instr),
+                    uxn.working_stack.contents(), uxn.return_stack.contents());
+            }
 
             if uxn_with_devices.uxn.should_terminate {
                 return Ok(UxnStatus::Terminate);
             }
         }
     }
+
+    // if a handler is installed at the system vector, set up the working stack with the details
+    // of a fault and jump to the handler. Following the reference Varvara machine, the working
+    // stack is left holding the address of the faulting instruction, the instruction itself and
+    // an error code. Stack faults are only passed to the handler under the `Handler` stack
+    // fault policy. Returns whether the fault was passed to a handler
+    fn dispatch_fault(&mut self, err: &UxnError, instr_addr: u16, instr: u8) -> bool {
+        if self.system_vector == 0 {
+            return false;
+        }
+
+        let stack_fault = matches!(err, UxnError::StackUnderflow | UxnError::StackOverflow);
+        if stack_fault && self.stack_fault_policy != StackFaultPolicy::Handler {
+            return false;
+        }
+
+        let error_code = match err {
+            UxnError::StackUnderflow => 0x1,
+            UxnError::StackOverflow => 0x2,
+            UxnError::DivisionByZero => 0x3,
+            UxnError::OutOfRangeMemoryAddress => 0x4,
+            UxnError::UnrecognisedDevice => 0x5,
+            UxnError::DeviceError(_) => 0x6,
+        };
+
+        let instr_addr = instr_addr.to_be_bytes();
+        self.working_stack.set_pointer(0);
+        for index in 0..4 {
+            self.record_change(Change::WorkingStack{index, old_val: self.working_stack.data()[usize::from(index)]});
+        }
+        for byte in [instr_addr[0], instr_addr[1], instr, error_code] {
+            self.working_stack.push(byte, StackFaultPolicy::Wrap).unwrap();
+        }
+
+        self.program_counter = Ok(self.system_vector);
+        return true;
+    }
+}
+
+impl<J> UnmappedDeviceHandler for UxnImpl<J>
+where
+J: InstructionFactory,
+{
+    fn access_unmapped_device(&mut self, device_address: u8) -> Result<(), UxnError> {
+        match self.device_access_policy {
+            DeviceAccessPolicy::Ignore => {},
+            DeviceAccessPolicy::Warn => {
+                let slot = device_address >> 4;
+                if self.unmapped_device_warnings & (1 << slot) == 0 {
+                    self.unmapped_device_warnings |= 1 << slot;
+
+                    // DEI and DEO are single byte instructions, so the instruction making the
+                    // access is the one before the program counter
+                    let instr_addr = match self.program_counter {
+                        Ok(program_counter) => program_counter.wrapping_sub(1),
+                        Err(()) => u16::MAX,
+                    };
+                    eprintln!("warning: access to unmapped device slot 0x{:x} (port 0x{:02x}) at 0x{:04x}",
+                        slot, device_address, instr_addr);
+                }
+            },
+            DeviceAccessPolicy::Fault => {
+                return Err(UxnError::UnrecognisedDevice);
+            },
+        }
+
+        return Ok(());
+    }
 }
 
 fn system_color_to_index(system_color: UxnSystemColor) -> usize {
@@ -281,19 +743,66 @@ where
 J: InstructionFactory,
 {
     fn set_working_stack_index(&mut self, index: u8) {
-        self.working_stack.resize(index.into(), 0);
+        self.working_stack.set_pointer(index);
     }
 
     fn get_working_stack_index(&self) -> u8 {
-        u8::try_from(self.working_stack.len()).unwrap()
+        self.working_stack.get_pointer()
     }
 
     fn set_return_stack_index(&mut self, index: u8) {
-        self.return_stack.resize(index.into(), 0);
+        self.return_stack.set_pointer(index);
     }
 
     fn get_return_stack_index(&self) -> u8 {
-        u8::try_from(self.return_stack.len()).unwrap()
+        self.return_stack.get_pointer()
+    }
+
+    fn set_system_vector(&mut self, vector: u16) {
+        self.system_vector = vector;
+    }
+
+    fn get_system_vector(&self) -> u16 {
+        self.system_vector
+    }
+
+    fn set_expansion_address(&mut self, address: u16) {
+        self.expansion_address = address;
+    }
+
+    fn get_expansion_address(&self) -> u16 {
+        self.expansion_address
+    }
+
+    fn read_from_bank(&self, bank: u16, address: u16) -> u8 {
+        if bank == 0 {
+            return self.ram[usize::from(address)];
+        }
+
+        return match self.expansion_banks.get(usize::from(bank - 1)) {
+            Some(expansion_bank) => expansion_bank[usize::from(address)],
+            None => 0,
+        };
+    }
+
+    fn write_to_bank(&mut self, bank: u16, address: u16, val: u8) {
+        if bank == 0 {
+            self.write_to_ram(address, val);
+            return;
+        }
+
+        if bank >= BANK_COUNT {
+            return;
+        }
+
+        let bank_index = usize::from(bank - 1);
+        if bank_index >= self.expansion_banks.len() {
+            self.record_change(Change::BankCount(self.expansion_banks.len()));
+            self.expansion_banks.resize(bank_index + 1, vec![0x0; BANK_SIZE]);
+        }
+        self.record_change(Change::Bank{bank, address,
+            old_val: self.expansion_banks[bank_index][usize::from(address)]});
+        self.expansion_banks[bank_index][usize::from(address)] = val;
     }
 
     fn set_system_color(&mut self, slot: UxnSystemColor, val: u8) {
@@ -309,11 +818,11 @@ J: InstructionFactory,
     }
 
     fn get_working_stack_iter(&self) -> std::slice::Iter<u8> {
-        self.working_stack.iter()
+        self.working_stack.contents().iter()
     }
 
     fn get_return_stack_iter(&self) -> std::slice::Iter<u8> {
-        self.return_stack.iter()
+        self.return_stack.contents().iter()
     }
 }
 
@@ -331,12 +840,101 @@ J: InstructionFactory,
     }
 }
 
+impl<J> Snapshot for UxnImpl<J>
+where
+J: InstructionFactory,
+{
+    fn save_state(&self, writer: &mut SnapshotWriter) {
+        writer.put_bytes(&self.ram);
+
+        writer.put_u8(u8::try_from(self.expansion_banks.len()).unwrap());
+        for expansion_bank in self.expansion_banks.iter() {
+            writer.put_bytes(expansion_bank);
+        }
+        writer.put_u16(self.expansion_address);
+
+        match self.program_counter {
+            Ok(program_counter) => {
+                writer.put_bool(true);
+                writer.put_u16(program_counter);
+            },
+            Err(()) => {
+                writer.put_bool(false);
+                writer.put_u16(0);
+            },
+        }
+
+        // the whole of each stack is saved, as slots above the pointer can be exposed again by
+        // moving the pointer
+        writer.put_u16(self.working_stack.get_depth());
+        writer.put_bytes(self.working_stack.data());
+        writer.put_u16(self.return_stack.get_depth());
+        writer.put_bytes(self.return_stack.data());
+
+        writer.put_u16(self.system_vector);
+        writer.put_bytes(&self.system_colors);
+        writer.put_bool(self.should_terminate);
+        writer.put_bool(self.suspended);
+    }
+
+    fn load_state(&mut self, reader: &mut SnapshotReader) -> Result<(), SnapshotError> {
+        let ram = reader.get_bytes(self.ram.len())?;
+
+        let expansion_bank_count = reader.get_u8()?;
+        if u16::from(expansion_bank_count) >= BANK_COUNT {
+            return Err(SnapshotError::InvalidData("too many expansion banks"));
+        }
+        let mut expansion_banks = Vec::new();
+        for _ in 0..expansion_bank_count {
+            expansion_banks.push(reader.get_bytes(BANK_SIZE)?.to_vec());
+        }
+        let expansion_address = reader.get_u16()?;
+
+        let program_counter_valid = reader.get_bool()?;
+        let program_counter = reader.get_u16()?;
+        let program_counter = if program_counter_valid { Ok(program_counter) } else { Err(()) };
+
+        let working_stack_depth = reader.get_u16()?;
+        let working_stack = reader.get_array::<STACK_SIZE>()?;
+        let return_stack_depth = reader.get_u16()?;
+        let return_stack = reader.get_array::<STACK_SIZE>()?;
+        if usize::from(working_stack_depth) > STACK_SIZE || usize::from(return_stack_depth) > STACK_SIZE {
+            return Err(SnapshotError::InvalidData("stack deeper than the stack size"));
+        }
+
+        let system_vector = reader.get_u16()?;
+        let system_colors = reader.get_array::<6>()?;
+        let should_terminate = reader.get_bool()?;
+        let suspended = reader.get_bool()?;
+
+        // only modify the uxn once the whole state has been read successfully
+        self.ram.copy_from_slice(ram);
+        self.expansion_banks = expansion_banks;
+        self.expansion_address = expansion_address;
+        self.program_counter = program_counter;
+        self.working_stack.set_data(working_stack);
+        self.working_stack.set_depth(working_stack_depth);
+        self.return_stack.set_data(return_stack);
+        self.return_stack.set_depth(return_stack_depth);
+        self.system_vector = system_vector;
+        self.system_colors = system_colors;
+        self.should_terminate = should_terminate;
+        self.suspended = suspended;
+
+        // the recorded instructions led up to the state that has just been replaced
+        if let Some(history) = self.history.as_mut() {
+            history.clear();
+        }
+
+        return Ok(());
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::rc::Rc;
     use std::cell::RefCell;
-    use crate::instruction::Instruction;
 
     struct MockInstruction {
         byte: u8,
@@ -346,7 +944,7 @@ mod tests {
     impl Instruction for MockInstruction {
         fn execute(&self, uxn: &mut dyn UxnWithDevices) -> Result<(), UxnError> {
             if self.is_terminate_instruction {
-                uxn.write_to_device(0x99, 0x99);
+                uxn.write_to_device(0x99, 0x99)?;
             }
 
 
@@ -367,13 +965,15 @@ mod tests {
         }
     }
     impl InstructionFactory for MockInstructionFactory {
-        fn from_byte(&self, byte: u8) -> Box<dyn Instruction> {
+        type Instr = MockInstruction;
+
+        fn from_byte(&self, byte: u8) -> MockInstruction {
             let is_terminate_instruction = if byte == self.terminate_instruction {
                 true
             } else {
                 false
             };
-            return Box::new(MockInstruction{byte: byte, ret_vec: Rc::clone(&self.ret_vec), is_terminate_instruction});
+            return MockInstruction{byte: byte, ret_vec: Rc::clone(&self.ret_vec), is_terminate_instruction};
         }
     }
 
@@ -429,7 +1029,7 @@ mod tests {
     #[test]
     fn test_run_ram_full() -> Result<(), UxnError> {
         // note that this rom is larger than the portion of ram it is copied to,
-        // it will just be truncated
+        // the rest of it will be in the first expansion bank
         let rom : Vec<u8> = vec!(0xaa; 0x10000);
 
         let mut uxn = UxnImpl::new(
@@ -464,6 +1064,61 @@ mod tests {
         Ok(())
     }
 
+    // test running a vector with an instruction budget that is smaller than
+    // the number of instructions in it, verify that execution stops once the
+    // budget is used up and can then be resumed from where it stopped
+    #[test]
+    fn test_run_with_budget_resume() -> Result<(), UxnError> {
+        let rom : Vec<u8> = vec!(0xaa, 0xbb, 0xcc, 0xdd);
+
+        let mut uxn = UxnImpl::new(
+            rom.into_iter(),
+            MockInstructionFactory::new(0xff))?;
+
+        uxn.push_to_working_stack(0x12)?;
+
+        let res = uxn.run_with_budget(0x100, MockDeviceList::new(), 2)?;
+
+        assert_eq!(UxnStatus::BudgetExhausted, res);
+        assert_eq!(vec!(0xaa, 0xbb), *uxn.instruction_factory.ret_vec.borrow());
+        assert_eq!(uxn.get_program_counter(), Ok(0x102));
+        assert_eq!(uxn.working_stack.contents(), &[0x12]);
+        assert_eq!(uxn.is_suspended(), true);
+
+        let res = uxn.resume_with_budget(MockDeviceList::new(), 10)?;
+
+        assert_eq!(UxnStatus::Halt, res);
+        assert_eq!(vec!(0xaa, 0xbb, 0xcc, 0xdd), *uxn.instruction_factory.ret_vec.borrow());
+        assert_eq!(uxn.is_suspended(), false);
+
+        // nothing is suspended any more, so resuming should not execute anything
+        let res = uxn.resume_with_budget(MockDeviceList::new(), 10)?;
+
+        assert_eq!(UxnStatus::Halt, res);
+        assert_eq!(vec!(0xaa, 0xbb, 0xcc, 0xdd), *uxn.instruction_factory.ret_vec.borrow());
+
+        Ok(())
+    }
+
+    // test running a vector with an instruction budget large enough to complete
+    // it, verify that it behaves the same as `run`
+    #[test]
+    fn test_run_with_budget_sufficient() -> Result<(), UxnError> {
+        let rom : Vec<u8> = vec!(0xaa, 0xbb, 0xff, 0xdd);
+
+        let mut uxn = UxnImpl::new(
+            rom.into_iter(),
+            MockInstructionFactory::new(0xff))?;
+
+        let res = uxn.run_with_budget(0x100, MockDeviceList::new(), 3)?;
+
+        assert_eq!(UxnStatus::Terminate, res);
+        assert_eq!(vec!(0xaa, 0xbb, 0xff), *uxn.instruction_factory.ret_vec.borrow());
+        assert_eq!(uxn.is_suspended(), false);
+
+        Ok(())
+    }
+
     #[test]
     fn test_read_write_normal_device() {
         struct MockUxn {}
@@ -521,6 +1176,30 @@ mod tests {
                 panic!("should not be called");
             }
 
+            fn set_system_vector(&mut self, _vector: u16) {
+                panic!("should not be called");
+            }
+
+            fn get_system_vector(&self) -> u16 {
+                panic!("should not be called");
+            }
+
+            fn set_expansion_address(&mut self, _address: u16) {
+                panic!("should not be called");
+            }
+
+            fn get_expansion_address(&self) -> u16 {
+                panic!("should not be called");
+            }
+
+            fn read_from_bank(&self, _bank: u16, _address: u16) -> u8 {
+                panic!("should not be called");
+            }
+
+            fn write_to_bank(&mut self, _bank: u16, _address: u16, _val: u8) {
+                panic!("should not be called");
+            }
+
             fn set_system_color(&mut self, _slot: UxnSystemColor, _val: u8) {
                 panic!("should not be called");
             }
@@ -550,6 +1229,11 @@ mod tests {
                 panic!("should not be called");
             }
         }
+        impl UnmappedDeviceHandler for MockUxn {
+            fn access_unmapped_device(&mut self, _device_address: u8) -> Result<(), UxnError> {
+                panic!("should not be called");
+            }
+        }
 
         struct MockDeviceList {}
 
@@ -569,7 +1253,7 @@ mod tests {
             }
         }
 
-        let mut uxn_with_devices = UxnWithDevicesImpl{uxn: &mut MockUxn{}, device_list: MockDeviceList{}};
+        let mut uxn_with_devices = UxnWithDevicesImpl{uxn: &mut MockUxn{}, device_list: MockDeviceList{}, observer: None};
 
         // test read_from_device, MockDeviceList::read_from_device should be passed the
         // correct arguments
@@ -579,7 +1263,7 @@ mod tests {
 
         // test write_to_device, MockDeviceList::write_to_device should be passed the
         // correct arguments
-        uxn_with_devices.write_to_device(0x35, 0x22);
+        assert_eq!(uxn_with_devices.write_to_device(0x35, 0x22), Ok(()));
     }
 
     #[test]
@@ -644,6 +1328,30 @@ mod tests {
                 panic!("should not be called");
             }
 
+            fn set_system_vector(&mut self, _vector: u16) {
+                panic!("should not be called");
+            }
+
+            fn get_system_vector(&self) -> u16 {
+                panic!("should not be called");
+            }
+
+            fn set_expansion_address(&mut self, _address: u16) {
+                panic!("should not be called");
+            }
+
+            fn get_expansion_address(&self) -> u16 {
+                panic!("should not be called");
+            }
+
+            fn read_from_bank(&self, _bank: u16, _address: u16) -> u8 {
+                panic!("should not be called");
+            }
+
+            fn write_to_bank(&mut self, _bank: u16, _address: u16, _val: u8) {
+                panic!("should not be called");
+            }
+
             fn set_system_color(&mut self, _slot: UxnSystemColor, _val: u8) {
                 panic!("should not be called");
             }
@@ -673,6 +1381,11 @@ mod tests {
                 panic!("should not be called");
             }
         }
+        impl UnmappedDeviceHandler for MockUxn {
+            fn access_unmapped_device(&mut self, _device_address: u8) -> Result<(), UxnError> {
+                panic!("should not be called");
+            }
+        }
 
         struct MockDeviceList {
             debug_printer: Vec<u8>,
@@ -703,27 +1416,26 @@ mod tests {
 
             fn read_from_device(&mut self, device_address: u8) -> DeviceReadReturnCode {
                 assert_eq!(device_address, 0x42);
-                return DeviceReadReturnCode::ReadFromSystemDevice(0x2);
+                return DeviceReadReturnCode::ReadFromSystemDevice(0x4);
             }
         }
 
         let mut uxn_with_devices = UxnWithDevicesImpl{
             uxn: &mut MockUxn{set_working_stack_index_called: false, mock_working_stack: vec![1,2,3], mock_return_stack: vec![4,5,6]},
-            device_list: MockDeviceList::new()};
+            device_list: MockDeviceList::new(), observer: None};
 
         // test write_to_device, MockDeviceList::write_to_device should be passed the correct
         // arguments and since it returns WriteToSystemDevice and the device address ends in the
-        // nibble 0x2 then the System device should result in
+        // nibble 0x4 then the System device should result in
         // UxnSystemInterface::set_working_stack_index being called with the value 0x96
-        uxn_with_devices.device_list.expected_device_address = 0x32;
+        uxn_with_devices.device_list.expected_device_address = 0x34;
         uxn_with_devices.device_list.expected_val = 0x96;
-        uxn_with_devices.write_to_device(0x32, 0x96);
+        assert_eq!(uxn_with_devices.write_to_device(0x34, 0x96), Ok(()));
         assert_eq!(uxn_with_devices.uxn.set_working_stack_index_called, true);
 
 
         // test read_from_device, MockDeviceList::read_from_device should be passed the correct
-        // arguments and since it returns ReadFromSystemDevice and the device address ends in the
-        // nibble 0x2 then the System device should result in
+        // arguments and since it returns ReadFromSystemDevice with the port 0x4 then the System device should result in
         // UxnSystemInterface::get_working_stack_index being called with the value 0x91
         let ret = uxn_with_devices.read_from_device(0x42);
         assert_eq!(ret, Ok(0x91));
@@ -734,7 +1446,7 @@ mod tests {
         // used for the debug string printing
         uxn_with_devices.device_list.expected_device_address = 0x3e;
         uxn_with_devices.device_list.expected_val = 0x11;
-        uxn_with_devices.write_to_device(0x3e, 0x11);
+        assert_eq!(uxn_with_devices.write_to_device(0x3e, 0x11), Ok(()));
         assert_eq!(uxn_with_devices.device_list.debug_printer,
                    "<wst> 01 02 03\n<rst> 04 05 06\n".as_bytes());
     }
@@ -752,11 +1464,16 @@ mod tests {
 
         uxn.set_working_stack_index(6);
 
-        assert_eq!(uxn.working_stack, vec!(0x2, 0x3, 0x0, 0x0, 0x0, 0x0));
+        assert_eq!(uxn.working_stack.contents(), &[0x2, 0x3, 0x0, 0x0, 0x0, 0x0]);
 
         uxn.set_working_stack_index(1);
 
-        assert_eq!(uxn.working_stack, vec!(0x2,));
+        assert_eq!(uxn.working_stack.contents(), &[0x2,]);
+
+        // moving the index back up exposes the values that were previously on the stack
+        uxn.set_working_stack_index(2);
+
+        assert_eq!(uxn.working_stack.contents(), &[0x2, 0x3,]);
 
         uxn.push_to_return_stack(0x4)?;
         uxn.push_to_return_stack(0x6)?;
@@ -766,11 +1483,15 @@ mod tests {
 
         uxn.set_return_stack_index(5);
 
-        assert_eq!(uxn.return_stack, vec!(0x4, 0x6, 0x3, 0x0, 0x0,));
+        assert_eq!(uxn.return_stack.contents(), &[0x4, 0x6, 0x3, 0x0, 0x0,]);
 
         uxn.set_return_stack_index(1);
 
-        assert_eq!(uxn.return_stack, vec!(0x4,));
+        assert_eq!(uxn.return_stack.contents(), &[0x4,]);
+
+        uxn.set_return_stack_index(3);
+
+        assert_eq!(uxn.return_stack.contents(), &[0x4, 0x6, 0x3,]);
 
         Ok(())
     }
@@ -781,7 +1502,7 @@ mod tests {
             vec!().into_iter(),
             MockInstructionFactory::new(0xff))?;
 
-        for _ in 0..STACK_MAX_LEN {
+        for _ in 0..STACK_SIZE {
             uxn.push_to_working_stack(0x1)?;
             uxn.push_to_return_stack(0x1)?;
         }
@@ -852,4 +1573,529 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_save_and_load_state() -> Result<(), UxnError> {
+        let mut uxn = UxnImpl::new(
+            vec!(0xaa, 0xbb).into_iter(),
+            MockInstructionFactory::new(0xff))?;
+
+        uxn.push_to_working_stack(0x2)?;
+        uxn.push_to_return_stack(0x5)?;
+        uxn.push_to_return_stack(0x6)?;
+        uxn.write_to_ram(0x10, 0x77);
+        uxn.set_program_counter(0x1234);
+        uxn.set_system_color(UxnSystemColor::Green2, 0x9a);
+        uxn.write_to_bank(0x2, 0xabcd, 0x42);
+
+        let mut writer = SnapshotWriter::new();
+        uxn.save_state(&mut writer);
+        let state = writer.into_bytes();
+
+        let mut restored = UxnImpl::new(
+            vec!().into_iter(),
+            MockInstructionFactory::new(0xff))?;
+        restored.load_state(&mut SnapshotReader::new(&state)).unwrap();
+
+        assert_eq!(restored.ram, uxn.ram);
+        assert_eq!(restored.get_program_counter(), Ok(0x1234));
+        assert_eq!(restored.working_stack.contents(), &[0x2]);
+        assert_eq!(restored.return_stack.contents(), &[0x5, 0x6]);
+        assert_eq!(restored.get_system_color(UxnSystemColor::Green2), 0x9a);
+        assert_eq!(restored.read_from_bank(0x2, 0xabcd), 0x42);
+
+        // a truncated state should be rejected, leaving the uxn untouched
+        restored.push_to_working_stack(0x9)?;
+        assert!(restored.load_state(&mut SnapshotReader::new(&state[..state.len()-1])).is_err());
+        assert_eq!(restored.working_stack.contents(), &[0x2, 0x9]);
+
+        Ok(())
+    }
+
+    // accesses to a device slot with no device in it should be handled according to the device
+    // access policy, in the same way for reads and writes
+    #[test]
+    fn test_device_access_policy() -> Result<(), UxnError> {
+        use crate::ops::OpObjectFactory;
+        use crate::emulators::devices::device_list_impl::{DeviceListImpl, DeviceEntry};
+        use std::collections::HashMap;
+
+        let device_list = || {
+            return DeviceListImpl::new(HashMap::from([
+                (0x0, DeviceEntry::SystemPlaceHolder(Vec::new()))]));
+        };
+
+        // |100 #12 #a0 DEO #b1 DEI BRK
+        let rom = vec!(0x80, 0x12, 0x80, 0xa0, 0x17, 0x80, 0xb1, 0x16, 0x00);
+
+        for policy in [DeviceAccessPolicy::Ignore, DeviceAccessPolicy::Warn] {
+            let mut uxn = UxnImpl::new(rom.clone().into_iter(), OpObjectFactory{})?;
+            uxn.set_device_access_policy(policy);
+            assert_eq!(uxn.run(0x100, device_list()), Ok(UxnStatus::Halt));
+            assert_eq!(uxn.working_stack.contents(), &[0x00]);
+
+            let expected_warnings = if policy == DeviceAccessPolicy::Warn { 0x0c00 } else { 0x0 };
+            assert_eq!(uxn.unmapped_device_warnings, expected_warnings);
+        }
+
+        let mut uxn = UxnImpl::new(rom.clone().into_iter(), OpObjectFactory{})?;
+        uxn.set_device_access_policy(DeviceAccessPolicy::Fault);
+        assert_eq!(uxn.run(0x100, device_list()).map_err(|crash| (crash.error, crash.address)),
+            Err((UxnError::UnrecognisedDevice, 0x104)));
+
+        // with the DEO skipped, the DEI should fault too
+        assert_eq!(uxn.run(0x105, device_list()).map_err(|crash| (crash.error, crash.address)),
+            Err((UxnError::UnrecognisedDevice, 0x107)));
+
+        Ok(())
+    }
+
+    // the immediate jumps should jump relative to the end of their operand, with JSI returning
+    // to there and JCI only jumping if its condition isn't zero
+    #[test]
+    fn test_immediate_jumps() -> Result<(), UxnError> {
+        use crate::ops::OpObjectFactory;
+        use crate::emulators::devices::device_list_impl::{DeviceListImpl, DeviceEntry};
+        use std::collections::HashMap;
+
+        // |100 JSI @sub ( 0103 ) #01 JCI @skip ( 0108 ) #ff @skip ( 010a ) BRK
+        // @sub ( 010b ) #56 #00 JCI @never ( 0112 ) JMI @end ( 0115 ) #ff @end ( 0117 ) JMP2r
+        let rom = vec!(0x60, 0x00, 0x08, 0x80, 0x01, 0x20, 0x00, 0x02, 0x80, 0xff, 0x00,
+            0x80, 0x56, 0x80, 0x00, 0x20, 0x00, 0xff, 0x40, 0x00, 0x02, 0x80, 0xff, 0x6c);
+
+        let mut uxn = UxnImpl::new(rom.into_iter(), OpObjectFactory{})?;
+        assert_eq!(uxn.run(0x100, DeviceListImpl::new(HashMap::from([
+            (0x0, DeviceEntry::SystemPlaceHolder(Vec::new()))]))), Ok(UxnStatus::Halt));
+        assert_eq!(uxn.working_stack.contents(), &[0x56]);
+        assert!(uxn.return_stack.contents().is_empty());
+
+        Ok(())
+    }
+
+    // an error raised by a device should fault the instruction that accessed the device
+    #[test]
+    fn test_device_error() -> Result<(), UxnError> {
+        use crate::ops::OpObjectFactory;
+        use crate::emulators::devices::device_list_impl::{DeviceListImpl, DeviceEntry};
+        use crate::emulators::devices::file::FileDevice;
+        use crate::uxninterface::DeviceError;
+        use std::collections::HashMap;
+
+        // write a file name that isn't valid utf8 to the zero page and give it to the file
+        // device:
+        // |100 #ff #00 STZ #0000 #a8 DEO2 BRK
+        let rom = vec!(0x80, 0xff, 0x80, 0x00, 0x11, 0xa0, 0x00, 0x00, 0x80, 0xa8, 0x37, 0x00);
+
+        let mut file_device = FileDevice::new();
+        let device_list = DeviceListImpl::new(HashMap::from([
+            (0x0, DeviceEntry::SystemPlaceHolder(Vec::new())),
+            (0xa, DeviceEntry::Device(&mut file_device))]));
+
+        let mut uxn = UxnImpl::new(rom.into_iter(), OpObjectFactory{})?;
+        assert_eq!(uxn.run(0x100, device_list).map_err(|crash| (crash.error, crash.address)),
+            Err((UxnError::DeviceError(DeviceError::InvalidFileName), 0x10a)));
+
+        Ok(())
+    }
+
+    // an observer should be told about each instruction, with the stacks before and after it
+    // runs, and about the device accesses and ram writes made by the instructions
+    #[test]
+    fn test_observer() -> Result<(), UxnError> {
+        use crate::ops::OpObjectFactory;
+        use crate::emulators::devices::device_list_impl::{DeviceListImpl, DeviceEntry};
+        use std::collections::HashMap;
+
+        struct MockObserver {
+            events: Rc<RefCell<Vec<String>>>,
+        }
+
+        impl UxnObserver for MockObserver {
+            fn before_instruction(&mut self, address: u16, op: &OpObject,
+                working_stack: &[u8], return_stack: &[u8]) {
+                self.events.borrow_mut().push(
+                    format!("before {:04x} {} {:02x?} {:02x?}", address, op, working_stack, return_stack));
+            }
+
+            fn after_instruction(&mut self, address: u16, op: &OpObject,
+                working_stack: &[u8], return_stack: &[u8]) {
+                self.events.borrow_mut().push(
+                    format!("after {:04x} {} {:02x?} {:02x?}", address, op, working_stack, return_stack));
+            }
+
+            fn device_read(&mut self, device_address: u8, val: u8) {
+                self.events.borrow_mut().push(format!("read {:02x} {:02x}", device_address, val));
+            }
+
+            fn device_write(&mut self, device_address: u8, val: u8) {
+                self.events.borrow_mut().push(format!("write {:02x} {:02x}", device_address, val));
+            }
+
+            fn ram_write(&mut self, address: u16, val: u8) {
+                self.events.borrow_mut().push(format!("ram {:04x} {:02x}", address, val));
+            }
+        }
+
+        // |100 #12 #08 DEO #08 DEI #ab #0200 STA BRK
+        let rom = vec!(0x80, 0x12, 0x80, 0x08, 0x17, 0x80, 0x08, 0x16,
+            0x80, 0xab, 0xa0, 0x02, 0x00, 0x15, 0x00);
+        let mut uxn = UxnImpl::new(rom.into_iter(), OpObjectFactory{})?;
+
+        let events = Rc::new(RefCell::new(Vec::new()));
+        uxn.set_observer(Box::new(MockObserver{events: Rc::clone(&events)}));
+
+        let device_list = DeviceListImpl::new(HashMap::from([
+            (0x0, DeviceEntry::SystemPlaceHolder(Vec::new()))]));
+        assert_eq!(uxn.run(0x100, device_list), Ok(UxnStatus::Halt));
+
+        assert_eq!(*events.borrow(), vec!(
+            "before 0100 LIT [] []",
+            "after 0100 LIT [12] []",
+            "before 0102 LIT [12] []",
+            "after 0102 LIT [12, 08] []",
+            "before 0104 DEO [12, 08] []",
+            "write 08 12",
+            "after 0104 DEO [] []",
+            "before 0105 LIT [] []",
+            "after 0105 LIT [08] []",
+            "before 0107 DEI [08] []",
+            "read 08 12",
+            "after 0107 DEI [12] []",
+            "before 0108 LIT [12] []",
+            "after 0108 LIT [12, ab] []",
+            "before 010a LIT2 [12, ab] []",
+            "after 010a LIT2 [12, ab, 02, 00] []",
+            "before 010d STA [12, ab, 02, 00] []",
+            "ram 0200 ab",
+            "after 010d STA [12] []",
+        ));
+
+        // writes to ram made by devices are reported too
+        struct RamWritingDevice {}
+
+        impl device::Device for RamWritingDevice {
+            fn write(&mut self, _port: u8, val: u8, main_ram: &mut dyn MainRamInterface) -> Result<(), device::DeviceError> {
+                main_ram.write(0x300, &[val, val])?;
+                return Ok(());
+            }
+
+            fn read(&mut self, _port: u8) -> Result<u8, device::DeviceError> {
+                return Ok(0);
+            }
+        }
+
+        // |100 #55 #a0 DEO ;fill #02 DEO2 BRK
+        // |120 @fill 00 0002 0000 0310 cc
+        let mut rom = vec!(0x80, 0x55, 0x80, 0xa0, 0x17, 0xa0, 0x01, 0x20, 0x80, 0x02, 0x37, 0x00);
+        rom.resize(0x20, 0x0);
+        rom.extend([0x00, 0x00, 0x02, 0x00, 0x00, 0x03, 0x10, 0xcc]);
+        let mut uxn = UxnImpl::new(rom.into_iter(), OpObjectFactory{})?;
+
+        let events = Rc::new(RefCell::new(Vec::new()));
+        uxn.set_observer(Box::new(MockObserver{events: Rc::clone(&events)}));
+
+        let mut device = RamWritingDevice{};
+        let device_list = DeviceListImpl::new(HashMap::from([
+            (0x0, DeviceEntry::SystemPlaceHolder(Vec::new())),
+            (0xa, DeviceEntry::Device(&mut device))]));
+        assert_eq!(uxn.run(0x100, device_list), Ok(UxnStatus::Halt));
+
+        let events: Vec<String> = events.borrow().iter()
+            .filter(|event| { return event.starts_with("ram") || event.starts_with("write"); })
+            .cloned()
+            .collect();
+        assert_eq!(events, vec!(
+            "write a0 55",
+            "ram 0300 55",
+            "ram 0301 55",
+            "write 02 01",
+            "write 03 20",
+            "ram 0310 cc",
+            "ram 0311 cc",
+        ));
+
+        Ok(())
+    }
+
+    // a rom too large for the main ram should continue into the expansion banks, and anything
+    // past the last bank should be dropped
+    #[test]
+    fn test_load_large_rom() -> Result<(), UxnError> {
+        let mut rom = vec!(0x11; BANK_SIZE - usize::from(INIT_VECTOR));
+        rom.extend(vec!(0x22; BANK_SIZE));
+        rom.extend([0x33, 0x44]);
+
+        let uxn = UxnImpl::new(rom.into_iter(), MockInstructionFactory::new(0xff))?;
+        assert_eq!(uxn.read_from_bank(0x0, 0xffff), 0x11);
+        assert_eq!(uxn.read_from_bank(0x1, 0x0000), 0x22);
+        assert_eq!(uxn.read_from_bank(0x1, 0xffff), 0x22);
+        assert_eq!(uxn.read_from_bank(0x2, 0x0000), 0x33);
+        assert_eq!(uxn.read_from_bank(0x2, 0x0001), 0x44);
+        assert_eq!(uxn.read_from_bank(0x2, 0x0002), 0x00);
+        assert_eq!(uxn.expansion_banks.len(), 2);
+
+        let rom = vec!(0x55; MAX_ROM_SIZE + 1);
+        let uxn = UxnImpl::new(rom.into_iter(), MockInstructionFactory::new(0xff))?;
+        assert_eq!(uxn.read_from_bank(BANK_COUNT - 1, 0xffff), 0x55);
+        assert_eq!(uxn.expansion_banks.len(), usize::from(BANK_COUNT - 1));
+
+        Ok(())
+    }
+
+    // bank 0 should be the main ram, expansion banks should be zeroed until written to, and banks
+    // past the last one shouldn't exist
+    #[test]
+    fn test_read_write_bank() -> Result<(), UxnError> {
+        let mut uxn = UxnImpl::new(
+            vec!(0xaa, 0xbb).into_iter(),
+            MockInstructionFactory::new(0xff))?;
+
+        assert_eq!(uxn.read_from_bank(0x0, 0x101), 0xbb);
+        uxn.write_to_bank(0x0, 0x102, 0xcc);
+        assert_eq!(uxn.read_from_ram(0x102), 0xcc);
+
+        assert_eq!(uxn.read_from_bank(0x3, 0x102), 0x0);
+        uxn.write_to_bank(0x3, 0x102, 0xdd);
+        assert_eq!(uxn.read_from_bank(0x3, 0x102), 0xdd);
+        assert_eq!(uxn.read_from_bank(0x2, 0x102), 0x0);
+        assert_eq!(uxn.read_from_ram(0x102), 0xcc);
+
+        uxn.write_to_bank(BANK_COUNT, 0x102, 0xee);
+        assert_eq!(uxn.read_from_bank(BANK_COUNT, 0x102), 0x0);
+        assert_eq!(uxn.expansion_banks.len(), 3);
+
+        Ok(())
+    }
+
+    // writing the stack pointers through the System device: lowering a pointer and pushing
+    // overwrites what was on the stack, raising it exposes what was there before, and what
+    // happens when a push or pop takes the pointer past either end depends on the policy
+    #[test]
+    fn test_system_stack_pointer_writes() -> Result<(), UxnError> {
+        let mut uxn = UxnImpl::new(
+            vec!().into_iter(),
+            MockInstructionFactory::new(0xff))?;
+
+        for val in [0x1, 0x2, 0x3] {
+            uxn.push_to_working_stack(val)?;
+            uxn.push_to_return_stack(val)?;
+        }
+
+        let mut system = devices::system::System::new(&mut uxn, Vec::new());
+        system.write(0x4, 0x1).unwrap();
+        system.write(0x5, 0x3).unwrap();
+        assert_eq!(system.read(0x4).unwrap(), 0x1);
+
+        uxn.push_to_working_stack(0x7)?;
+        assert_eq!(uxn.working_stack.contents(), &[0x1, 0x7]);
+
+        let mut system = devices::system::System::new(&mut uxn, Vec::new());
+        system.write(0x4, 0x3).unwrap();
+        assert_eq!(uxn.working_stack.contents(), &[0x1, 0x7, 0x3]);
+        assert_eq!(uxn.return_stack.contents(), &[0x1, 0x2, 0x3]);
+
+        // with the pointer at the top slot a push fills the stack, and the next push overflows,
+        // unless wrapping
+        let mut system = devices::system::System::new(&mut uxn, Vec::new());
+        system.write(0x4, 0xff).unwrap();
+        system.write(0x5, 0x0).unwrap();
+        uxn.push_to_working_stack(0x8)?;
+        assert_eq!(uxn.working_stack.contents().len(), STACK_SIZE);
+        assert_eq!(uxn.get_working_stack_index(), 0x0);
+        assert_eq!(uxn.push_to_working_stack(0x9), Err(UxnError::StackOverflow));
+        assert_eq!(uxn.pop_from_return_stack(), Err(UxnError::StackUnderflow));
+
+        uxn.set_stack_fault_policy(StackFaultPolicy::Wrap);
+        uxn.set_working_stack_index(0xff);
+        uxn.push_to_working_stack(0x8)?;
+        assert_eq!(uxn.get_working_stack_index(), 0x0);
+        assert_eq!(uxn.working_stack.data()[0xff], 0x8);
+
+        // popping from the empty return stack wraps round to its top slot
+        assert_eq!(uxn.pop_from_return_stack(), Ok(0x0));
+        assert_eq!(uxn.get_return_stack_index(), 0xff);
+
+        Ok(())
+    }
+
+    // a fault should jump to the handler at the system vector, with the details of the fault on
+    // the working stack. Without a handler, or for a stack fault when the stack fault policy is
+    // `Error`, the uxn should stop and report the crash
+    #[test]
+    fn test_fault_handler() -> Result<(), UxnError> {
+        use crate::ops::OpObjectFactory;
+        use crate::emulators::devices::device_list_impl::{DeviceListImpl, DeviceEntry};
+        use std::collections::HashMap;
+
+        let device_list = || {
+            return DeviceListImpl::new(HashMap::from([
+                (0x0, DeviceEntry::SystemPlaceHolder(Vec::new()))]));
+        };
+
+        // |100 #12 POP POP BRK
+        let rom = vec!(0x80, 0x12, 0x02, 0x02, 0x00);
+        let mut uxn = UxnImpl::new(rom.into_iter(), OpObjectFactory{})?;
+        assert_eq!(uxn.run(0x100, device_list()), Err(UxnCrash{
+            error: UxnError::StackUnderflow,
+            address: 0x103,
+            instruction: 0x02,
+            working_stack: vec![],
+            return_stack: vec![],
+        }));
+
+        // |100 #0200 #00 DEO2 POP BRK
+        // |200 #ff BRK
+        let mut rom = vec!(0xa0, 0x02, 0x00, 0x80, 0x00, 0x37, 0x02, 0x00);
+        rom.resize(0x100, 0x0);
+        rom.extend([0x80, 0xff, 0x00]);
+        let mut uxn = UxnImpl::new(rom.clone().into_iter(), OpObjectFactory{})?;
+        assert_eq!(uxn.run(0x100, device_list()), Err(UxnCrash{
+            error: UxnError::StackUnderflow,
+            address: 0x106,
+            instruction: 0x02,
+            working_stack: vec![],
+            return_stack: vec![],
+        }));
+
+        let mut uxn = UxnImpl::new(rom.into_iter(), OpObjectFactory{})?;
+        uxn.set_stack_fault_policy(StackFaultPolicy::Handler);
+        assert_eq!(uxn.run(0x100, device_list()), Ok(UxnStatus::Halt));
+
+        // the address of the POP, the POP instruction, the underflow error code, then the
+        // value pushed by the handler
+        assert_eq!(uxn.working_stack.contents(), &[0x01, 0x06, 0x02, 0x01, 0xff]);
+
+        // faults other than those of the stacks are handled too
+        // |100 #0200 #00 DEO2 #01 #00 DIV BRK
+        // |200 #ff BRK
+        let mut rom = vec!(0xa0, 0x02, 0x00, 0x80, 0x00, 0x37, 0x80, 0x01, 0x80, 0x00, 0x1b, 0x00);
+        rom.resize(0x100, 0x0);
+        rom.extend([0x80, 0xff, 0x00]);
+        let mut uxn = UxnImpl::new(rom.into_iter(), OpObjectFactory{})?;
+        assert_eq!(uxn.run(0x100, device_list()), Ok(UxnStatus::Halt));
+        assert_eq!(uxn.working_stack.contents(), &[0x01, 0x0a, 0x1b, 0x03, 0xff]);
+
+        // a fault within the handler stops the uxn
+        // |100 #0200 #00 DEO2 POP BRK
+        // |200 POP POP POP POP POP BRK
+        let mut rom = vec!(0xa0, 0x02, 0x00, 0x80, 0x00, 0x37, 0x02, 0x00);
+        rom.resize(0x100, 0x0);
+        rom.extend([0x02, 0x02, 0x02, 0x02, 0x02, 0x00]);
+        let mut uxn = UxnImpl::new(rom.into_iter(), OpObjectFactory{})?;
+        uxn.set_stack_fault_policy(StackFaultPolicy::Handler);
+        assert_eq!(uxn.run(0x100, device_list()).map_err(|crash| crash.address), Err(0x204));
+
+        Ok(())
+    }
+
+    // everything about a uxn that stepping back should restore
+    fn saved_state<J: InstructionFactory>(uxn: &UxnImpl<J>) -> Vec<u8> {
+        let mut writer = SnapshotWriter::new();
+        uxn.save_state(&mut writer);
+        return writer.into_bytes();
+    }
+
+    // executing forwards and then stepping back should restore the ram, stacks and registers
+    // exactly as they were before each instruction, and executing forwards again should give
+    // the same result as the first time
+    #[test]
+    fn test_step_back() -> Result<(), UxnError> {
+        use crate::ops::OpObjectFactory;
+        use crate::emulators::devices::device_list_impl::{DeviceListImpl, DeviceEntry};
+        use std::collections::HashMap;
+
+        let device_list = || {
+            return DeviceListImpl::new(HashMap::from([
+                (0x0, DeviceEntry::SystemPlaceHolder(Vec::new()))]));
+        };
+
+        // |100 #1234 #0200 STA2 #56 STH #78 #0201 STA BRK
+        let rom = vec!(0xa0, 0x12, 0x34, 0xa0, 0x02, 0x00, 0x35, 0x80, 0x56, 0x0f,
+            0x80, 0x78, 0xa0, 0x02, 0x01, 0x15, 0x00);
+        let mut uxn = UxnImpl::new(rom.into_iter(), OpObjectFactory{})?;
+        uxn.set_history_capacity(100);
+
+        // the state before each instruction
+        let mut states = Vec::new();
+        let mut res = uxn.run_with_budget(0x100, device_list(), 0);
+        while res == Ok(UxnStatus::BudgetExhausted) {
+            states.push(saved_state(&uxn));
+            res = uxn.resume_with_budget(device_list(), 1);
+        }
+        assert_eq!(res, Ok(UxnStatus::Halt));
+        let halted_state = saved_state(&uxn);
+
+        assert_eq!(states.len(), 9);
+        assert_eq!(uxn.history_len(), 8);
+        assert_eq!(uxn.read_from_ram(0x200), 0x12);
+        assert_eq!(uxn.read_from_ram(0x201), 0x78);
+        assert_eq!(uxn.return_stack.contents(), &[0x56]);
+
+        for state in states[..8].iter().rev() {
+            assert_eq!(uxn.step_back(1), 1);
+            assert_eq!(saved_state(&uxn), *state);
+        }
+        assert_eq!(uxn.step_back(1), 0);
+        assert_eq!(uxn.read_from_ram(0x200), 0x00);
+        assert_eq!(uxn.read_from_ram(0x201), 0x00);
+
+        assert_eq!(uxn.resume_with_budget(device_list(), 100), Ok(UxnStatus::Halt));
+        assert_eq!(saved_state(&uxn), halted_state);
+
+        // only as many instructions as the history holds can be undone
+        uxn.set_history_capacity(3);
+        assert_eq!(uxn.run(0x100, device_list()), Ok(UxnStatus::Halt));
+        assert_eq!(uxn.history_len(), 3);
+        assert_eq!(uxn.step_back(5), 3);
+        assert_eq!(uxn.get_program_counter(), Ok(0x10a));
+
+        Ok(())
+    }
+
+    // running back to an address should undo everything since the most recent instruction
+    // executed from there, including the jump to a fault handler and the System device writes
+    #[test]
+    fn test_run_back_to() -> Result<(), UxnError> {
+        use crate::ops::OpObjectFactory;
+        use crate::emulators::devices::device_list_impl::{DeviceListImpl, DeviceEntry};
+        use std::collections::HashMap;
+
+        let device_list = || {
+            return DeviceListImpl::new(HashMap::from([
+                (0x0, DeviceEntry::SystemPlaceHolder(Vec::new()))]));
+        };
+
+        // |100 #0200 #00 DEO2 POP BRK
+        // |200 #ff BRK
+        let mut rom = vec!(0xa0, 0x02, 0x00, 0x80, 0x00, 0x37, 0x02, 0x00);
+        rom.resize(0x100, 0x0);
+        rom.extend([0x80, 0xff, 0x00]);
+
+        let mut expected_uxn = UxnImpl::new(rom.clone().into_iter(), OpObjectFactory{})?;
+        expected_uxn.set_stack_fault_policy(StackFaultPolicy::Handler);
+        let mut uxn = UxnImpl::new(rom.into_iter(), OpObjectFactory{})?;
+        uxn.set_stack_fault_policy(StackFaultPolicy::Handler);
+        uxn.set_history_capacity(100);
+        assert_eq!(uxn.run(0x100, device_list()), Ok(UxnStatus::Halt));
+        assert_eq!(uxn.working_stack.contents(), &[0x01, 0x06, 0x02, 0x01, 0xff]);
+
+        assert_eq!(uxn.run_back_to(0x150), None);
+        assert_eq!(uxn.history_len(), 5);
+
+        // back to the POP, before it faulted
+        assert_eq!(uxn.run_back_to(0x106), Some(2));
+        expected_uxn.run_with_budget(0x100, device_list(), 3).unwrap();
+        assert_eq!(saved_state(&uxn), saved_state(&expected_uxn));
+        assert_eq!(uxn.get_system_vector(), 0x200);
+
+        // back to the start, before the handler was installed
+        assert_eq!(uxn.run_back_to(0x100), Some(3));
+        let mut expected_uxn = UxnImpl::new(vec!().into_iter(), OpObjectFactory{})?;
+        expected_uxn.ram.copy_from_slice(&uxn.ram);
+        expected_uxn.run_with_budget(0x100, device_list(), 0).unwrap();
+        assert_eq!(saved_state(&uxn), saved_state(&expected_uxn));
+        assert_eq!(uxn.get_system_vector(), 0x0);
+
+        Ok(())
+    }
 }
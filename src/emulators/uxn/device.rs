@@ -1,7 +1,10 @@
 use crate::uxninterface::UxnError;
+pub use crate::uxninterface::DeviceError;
 use std::io;
 use std::fmt;
 use std::error::Error;
+use std::rc::Rc;
+use std::cell::RefCell;
 
 #[derive(PartialEq, Debug)]
 pub enum DeviceWriteReturnCode<'a, K>
@@ -9,6 +12,8 @@ pub enum DeviceWriteReturnCode<'a, K>
 {
     Success,
     WriteToSystemDevice(u8, &'a mut K),
+    UnrecognisedDevice,
+    Failure(DeviceError),
 }
 
 #[derive(PartialEq, Debug)]
@@ -17,6 +22,17 @@ pub enum DeviceReadReturnCode {
     ReadFromSystemDevice(u8),
 }
 
+// what happens when a program reads from or writes to a device slot that has no device in it
+#[derive(Debug, PartialEq, Clone, Copy, clap::ArgEnum)]
+pub enum DeviceAccessPolicy {
+    // reads give 0 and writes are discarded
+    Ignore,
+    // as for `Ignore`, but a warning is printed the first time each slot is accessed
+    Warn,
+    // the access fails with `UxnError::UnrecognisedDevice`
+    Fault,
+}
+
 pub trait DeviceList 
 {
     type DebugWriter: io::Write;
@@ -25,9 +41,34 @@ pub trait DeviceList
     fn read_from_device(&mut self, device_address: u8) -> DeviceReadReturnCode;
 }
 
+// lets a single device list be used for several runs of the uxn
+impl<J: DeviceList> DeviceList for &mut J {
+    type DebugWriter = J::DebugWriter;
+
+    fn write_to_device(&mut self, device_address: u8, val: u8, main_ram: &mut dyn MainRamInterface) -> DeviceWriteReturnCode<'_, Self::DebugWriter> {
+        return (**self).write_to_device(device_address, val, main_ram);
+    }
+
+    fn read_from_device(&mut self, device_address: u8) -> DeviceReadReturnCode {
+        return (**self).read_from_device(device_address);
+    }
+}
+
 pub trait Device {
-    fn write(&mut self, port: u8, val: u8, main_ram: &mut dyn MainRamInterface);
-    fn read(&mut self, port: u8) -> u8;
+    fn write(&mut self, port: u8, val: u8, main_ram: &mut dyn MainRamInterface) -> Result<(), DeviceError>;
+    fn read(&mut self, port: u8) -> Result<u8, DeviceError>;
+}
+
+// a device shared with the code that created it, so that the device can still be accessed once
+// it has been handed over to a device list
+impl<J: Device> Device for Rc<RefCell<J>> {
+    fn write(&mut self, port: u8, val: u8, main_ram: &mut dyn MainRamInterface) -> Result<(), DeviceError> {
+        return self.borrow_mut().write(port, val, main_ram);
+    }
+
+    fn read(&mut self, port: u8) -> Result<u8, DeviceError> {
+        return self.borrow_mut().read(port);
+    }
 }
 
 #[derive(Debug, PartialEq)]
@@ -47,6 +88,16 @@ impl fmt::Display for MainRamInterfaceError {
 
 impl Error for MainRamInterfaceError {}
 
+impl From<MainRamInterfaceError> for DeviceError {
+    fn from(err: MainRamInterfaceError) -> Self {
+        match err {
+            MainRamInterfaceError::AddressOutOfBounds => {
+                return DeviceError::OutOfRangeMemoryAddress;
+            },
+        }
+    }
+}
+
 pub trait MainRamInterface {
     fn read(&self, address: u16, num_bytes: u16) -> Result<Vec<u8>, MainRamInterfaceError>;
     fn write(&mut self, address: u16, bytes: &[u8]) -> Result<usize, MainRamInterfaceError>;
@@ -0,0 +1,115 @@
+use std::collections::VecDeque;
+
+// a change made to the memory of the uxn, holding the value that was there before so that the
+// change can be undone
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Change {
+    Ram{address: u16, old_val: u8},
+    Bank{bank: u16, address: u16, old_val: u8},
+    // expansion banks are allocated when they are first written to, so this holds how many
+    // there were before
+    BankCount(usize),
+    WorkingStack{index: u8, old_val: u8},
+    ReturnStack{index: u8, old_val: u8},
+}
+
+// the registers of the uxn, as they were before an instruction executed
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Registers {
+    pub program_counter: Result<u16, ()>,
+    pub working_stack_depth: u16,
+    pub return_stack_depth: u16,
+    pub system_vector: u16,
+    pub expansion_address: u16,
+    pub system_colors: [u8; 6],
+    pub should_terminate: bool,
+}
+
+// everything needed to undo a single instruction
+#[derive(Debug, PartialEq)]
+pub struct HistoryEntry {
+    pub registers: Registers,
+    // in the order they were made, so they must be undone in reverse
+    pub changes: Vec<Change>,
+}
+
+// a record of the most recently executed instructions. Once it holds `capacity` instructions,
+// the oldest is dropped for each new one
+pub struct History {
+    entries: VecDeque<HistoryEntry>,
+    capacity: usize,
+}
+
+impl History {
+    pub fn new(capacity: usize) -> Self {
+        History{entries: VecDeque::new(), capacity}
+    }
+
+    // start recording a new instruction, which executes from the state in `registers`
+    pub fn begin_instruction(&mut self, registers: Registers) {
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(HistoryEntry{registers, changes: Vec::new()});
+    }
+
+    // record a change made by the current instruction
+    pub fn record(&mut self, change: Change) {
+        if let Some(entry) = self.entries.back_mut() {
+            entry.changes.push(change);
+        }
+    }
+
+    // remove the most recent instruction, so that it can be undone
+    pub fn pop(&mut self) -> Option<HistoryEntry> {
+        return self.entries.pop_back();
+    }
+
+    pub fn len(&self) -> usize {
+        return self.entries.len();
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    // how many instructions must be undone to get back to before the most recent instruction
+    // executed from `address`, if it is still recorded
+    pub fn steps_back_to(&self, address: u16) -> Option<usize> {
+        return self.entries.iter().rev()
+            .position(|entry| { return entry.registers.program_counter == Ok(address); })
+            .map(|position| { return position + 1; });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registers_at(program_counter: u16) -> Registers {
+        return Registers{program_counter: Ok(program_counter), working_stack_depth: 0,
+            return_stack_depth: 0, system_vector: 0, expansion_address: 0,
+            system_colors: [0; 6], should_terminate: false};
+    }
+
+    // once full, the oldest instructions should be dropped to make room for new ones
+    #[test]
+    fn test_bounded() {
+        let mut history = History::new(2);
+
+        for program_counter in [0x100, 0x101, 0x102] {
+            history.begin_instruction(registers_at(program_counter));
+            history.record(Change::Ram{address: program_counter, old_val: 0x12});
+        }
+
+        assert_eq!(history.len(), 2);
+        assert_eq!(history.steps_back_to(0x102), Some(1));
+        assert_eq!(history.steps_back_to(0x101), Some(2));
+        assert_eq!(history.steps_back_to(0x100), None);
+
+        assert_eq!(history.pop(), Some(HistoryEntry{registers: registers_at(0x102),
+            changes: vec!(Change::Ram{address: 0x102, old_val: 0x12})}));
+        assert_eq!(history.pop().map(|entry| { return entry.registers; }), Some(registers_at(0x101)));
+        assert_eq!(history.pop(), None);
+    }
+}
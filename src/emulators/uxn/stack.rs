@@ -0,0 +1,159 @@
+use crate::uxninterface::UxnError;
+
+pub const STACK_SIZE: usize = 0x100;
+
+// what happens when a value is pushed to a full stack, or popped from an empty one
+#[derive(Debug, PartialEq, Clone, Copy, clap::ArgEnum)]
+pub enum StackFaultPolicy {
+    // the push or pop fails with a `UxnError`
+    Error,
+    // the stack pointer silently wraps around, as it does on the reference Varvara machine
+    Wrap,
+    // the push or pop fails and the fault is passed to the handler installed at the system
+    // vector. If no handler is installed this behaves like `Error`
+    Handler,
+}
+
+// a 256 byte circular stack, addressed by an 8 bit pointer that indexes the next free slot
+pub struct Stack {
+    data: [u8; STACK_SIZE],
+    // the number of bytes on the stack. This is the pointer, except that a full stack (which
+    // only a stack that doesn't wrap can be) has a depth of 256 rather than a pointer of 0
+    depth: u16,
+}
+
+impl Stack {
+    pub fn new() -> Self {
+        Stack{data: [0; STACK_SIZE], depth: 0}
+    }
+
+    pub fn push(&mut self, byte: u8, policy: StackFaultPolicy) -> Result<(), UxnError> {
+        if policy == StackFaultPolicy::Wrap {
+            let pointer = self.get_pointer();
+            self.data[usize::from(pointer)] = byte;
+            self.depth = u16::from(pointer.wrapping_add(1));
+            return Ok(());
+        }
+
+        if usize::from(self.depth) == STACK_SIZE {
+            return Err(UxnError::StackOverflow);
+        }
+
+        self.data[usize::from(self.depth)] = byte;
+        self.depth += 1;
+        return Ok(());
+    }
+
+    pub fn pop(&mut self, policy: StackFaultPolicy) -> Result<u8, UxnError> {
+        if policy == StackFaultPolicy::Wrap {
+            self.depth = u16::from(self.get_pointer().wrapping_sub(1));
+            return Ok(self.data[usize::from(self.depth)]);
+        }
+
+        if self.depth == 0 {
+            return Err(UxnError::StackUnderflow);
+        }
+
+        self.depth -= 1;
+        return Ok(self.data[usize::from(self.depth)]);
+    }
+
+    // the pointer wraps round to 0 when the stack is full
+    pub fn get_pointer(&self) -> u8 {
+        self.depth as u8
+    }
+
+    // moving the pointer leaves the contents of the stack untouched, so moving it up exposes
+    // whatever was previously stored in those slots
+    pub fn set_pointer(&mut self, pointer: u8) {
+        self.depth = u16::from(pointer);
+    }
+
+    pub fn get_depth(&self) -> u16 {
+        self.depth
+    }
+
+    // returns false, leaving the stack untouched, if `depth` is more than the stack can hold
+    pub fn set_depth(&mut self, depth: u16) -> bool {
+        if usize::from(depth) > STACK_SIZE {
+            return false;
+        }
+        self.depth = depth;
+        return true;
+    }
+
+    // the bytes currently on the stack, from the bottom up
+    pub fn contents(&self) -> &[u8] {
+        &self.data[..usize::from(self.depth)]
+    }
+
+    // every slot of the stack, including those above the pointer
+    pub fn data(&self) -> &[u8; STACK_SIZE] {
+        &self.data
+    }
+
+    pub fn set_data(&mut self, data: [u8; STACK_SIZE]) {
+        self.data = data;
+    }
+
+    pub fn set_slot(&mut self, index: u8, byte: u8) {
+        self.data[usize::from(index)] = byte;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_pop() {
+        let mut stack = Stack::new();
+
+        stack.push(0x12, StackFaultPolicy::Error).unwrap();
+        stack.push(0x34, StackFaultPolicy::Error).unwrap();
+        assert_eq!(stack.contents(), &[0x12, 0x34]);
+
+        assert_eq!(stack.pop(StackFaultPolicy::Error), Ok(0x34));
+        assert_eq!(stack.pop(StackFaultPolicy::Error), Ok(0x12));
+        assert_eq!(stack.pop(StackFaultPolicy::Error), Err(UxnError::StackUnderflow));
+        assert_eq!(stack.pop(StackFaultPolicy::Handler), Err(UxnError::StackUnderflow));
+        assert_eq!(stack.get_pointer(), 0);
+    }
+
+    #[test]
+    fn test_overflow() {
+        let mut stack = Stack::new();
+
+        // every one of the 256 slots can be used
+        for i in 0..=0xff {
+            stack.push(i, StackFaultPolicy::Error).unwrap();
+        }
+        assert_eq!(stack.contents().len(), STACK_SIZE);
+        assert_eq!(stack.get_pointer(), 0);
+
+        assert_eq!(stack.push(0x12, StackFaultPolicy::Error), Err(UxnError::StackOverflow));
+        assert_eq!(stack.push(0x12, StackFaultPolicy::Handler), Err(UxnError::StackOverflow));
+        assert_eq!(stack.get_depth(), 0x100);
+
+        assert_eq!(stack.pop(StackFaultPolicy::Error), Ok(0xff));
+        assert_eq!(stack.get_pointer(), 0xff);
+    }
+
+    #[test]
+    fn test_wrap() {
+        let mut stack = Stack::new();
+
+        // popping from an empty stack wraps the pointer round to the top slot
+        stack.set_data([0xaa; STACK_SIZE]);
+        assert_eq!(stack.pop(StackFaultPolicy::Wrap), Ok(0xaa));
+        assert_eq!(stack.get_pointer(), 0xff);
+
+        // pushing to the top slot wraps the pointer round to the bottom
+        stack.push(0x12, StackFaultPolicy::Wrap).unwrap();
+        assert_eq!(stack.get_pointer(), 0);
+        assert_eq!(stack.data()[0xff], 0x12);
+
+        stack.push(0x34, StackFaultPolicy::Wrap).unwrap();
+        assert_eq!(stack.contents(), &[0x34]);
+    }
+}
@@ -2,20 +2,34 @@ use clap::Parser;
 use std::error::Error;
 use std::fs::File;
 use std::io::BufReader;
+use std::io::BufWriter;
 use std::io::Read;
 use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::path::Path;
 
-use crate::uxninterface::UxnStatus;
+use crate::uxninterface::{Uxn, UxnStatus};
 use crate::ops::OpObjectFactory;
+use crate::instruction::InstructionFactory;
 use crate::emulators::devices::console::Console;
 use crate::emulators::devices::file::FileDevice;
 use crate::emulators::devices::datetime::DateTimeDevice;
-use crate::emulators::RomReadError;
+use crate::emulators::{RomReadError, RomTooLargeError};
+use crate::emulators::snapshot::{SaveState, SnapshotError};
+use crate::emulators::observer::TraceWriter;
+use crate::symbols::SymbolTable;
+use crate::emulators::gdbstub::GdbStub;
+use crate::emulators::replay::{InputRecorder, InputReplay, InputEvent, InputLogError, LogEntry,
+    RecordingDevice, ReplayingDevice, check_vector};
+use crate::emulators::uxn::device::Device;
 
 use crate::emulators::devices::device_list_impl::{DeviceListImpl, DeviceEntry};
 use std::io::Write;
 
 use crate::emulators::uxn;
+pub use crate::emulators::uxn::{StackFaultPolicy, DeviceAccessPolicy};
 
 /// A rust implementation of the uxn virtual machine (without graphical display)
 #[derive(Parser)]
@@ -26,8 +40,75 @@ pub struct Cli {
 
     /// Initial console input for uxn virtual machine
     pub input: Vec<String>,
+
+    /// What happens when a stack overflows or underflows: stop with an error,
+    /// wrap the stack pointer around, or jump to the handler at the system
+    /// vector (stopping with an error if the rom hasn't installed one)
+    #[clap(long, arg_enum, default_value = "error")]
+    pub stack_faults: StackFaultPolicy,
+
+    /// What happens when the rom reads from or writes to a device slot with
+    /// no device in it: ignore it, print a warning (once for each slot), or
+    /// fault
+    #[clap(long, arg_enum, default_value = "ignore")]
+    pub strict_devices: DeviceAccessPolicy,
+
+    /// Maximum number of instructions a single vector may execute before
+    /// the program is treated as stuck and stopped
+    #[clap(long)]
+    pub max_steps: Option<u64>,
+
+    /// Save state to restore the machine from before running, instead of
+    /// starting the rom from the beginning
+    #[clap(long, parse(from_os_str))]
+    pub load_state: Option<std::path::PathBuf>,
+
+    /// File to save the state of the machine to when the program exits
+    #[clap(long, parse(from_os_str))]
+    pub save_state_on_exit: Option<std::path::PathBuf>,
+
+    /// File to write a trace of the program to, with a line for each
+    /// instruction executed giving its address, its mnemonic and the
+    /// stacks before it ran
+    #[clap(long, parse(from_os_str))]
+    pub trace: Option<std::path::PathBuf>,
+
+    /// Symbol file giving the labels of the rom, which are shown in the
+    /// trace [default: <ROM>.sym, if it exists]
+    #[clap(long, parse(from_os_str))]
+    pub symbols: Option<std::path::PathBuf>,
+
+    /// File to record the console input and the date time device reads of
+    /// the session to, so that it can be replayed
+    #[clap(long, parse(from_os_str))]
+    pub record: Option<std::path::PathBuf>,
+
+    /// Input log (made with --record) to replay, in place of the console
+    /// input given on the command line and from stdin
+    #[clap(long, parse(from_os_str), conflicts_with = "record")]
+    pub replay: Option<std::path::PathBuf>,
+
+    /// Port to wait on (on localhost) for a debugger to connect to, using
+    /// the GDB remote serial protocol, before the rom starts
+    #[clap(long)]
+    pub gdb: Option<u16>,
+}
+
+#[derive(Debug)]
+pub struct MaxStepsExceededError {
+    pub vector: u16,
+    pub max_steps: u64,
 }
 
+impl fmt::Display for MaxStepsExceededError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "vector 0x{:04x} did not complete within {} instructions",
+            self.vector, self.max_steps)
+    }
+}
+
+impl Error for MaxStepsExceededError {}
+
 pub struct Config<J: Write, K: Read, L: Write, M: Write> {
     pub stdout_writer: J, // used by console device for stdout
     pub stdin_reader: K,  // used for reading console input and passing on to console device
@@ -35,85 +116,286 @@ pub struct Config<J: Write, K: Read, L: Write, M: Write> {
     pub debug_writer: M,  // used by system device for debug output
 }
 
-struct CliDevices<J: Write, K: Write, M: Write> {
-    console_device: Console<J, K>,
-    file_device: FileDevice,
-    datetime_device: DateTimeDevice,
-    debug_writer: M,
+// the devices of the command line machine, shared with the debugger so that programs behave the
+// same under it
+pub(crate) struct CliDevices<J: Write, K: Write, M: Write> {
+    pub(crate) console_device: Console<J, K>,
+    pub(crate) file_device: FileDevice,
+    pub(crate) datetime_device: Box<dyn Device>,
+    pub(crate) debug_writer: M,
+}
+
+impl<J: Write, K: Write, M: Write> CliDevices<J, K, M> {
+    pub(crate) fn new(console_device: Console<J, K>, datetime_device: Box<dyn Device>, debug_writer: M) -> Self {
+        CliDevices{console_device, file_device: FileDevice::new(), datetime_device, debug_writer}
+    }
 }
 
-fn construct_device_list<J: Write, K: Write, M: Write>(devices: &mut CliDevices<J, K, M>) -> DeviceListImpl<'_, &mut M> {
+pub(crate) fn construct_device_list<J: Write, K: Write, M: Write>(devices: &mut CliDevices<J, K, M>) -> DeviceListImpl<'_, &mut M> {
     let mut device_list: HashMap::<u8, DeviceEntry<&mut M>> = HashMap::new();
     device_list.insert(0x0, DeviceEntry::SystemPlaceHolder(&mut devices.debug_writer));
     device_list.insert(0x1, DeviceEntry::Device(&mut devices.console_device));
     device_list.insert(0xa, DeviceEntry::Device(&mut devices.file_device));
-    device_list.insert(0xc, DeviceEntry::Device(&mut devices.datetime_device));
+    device_list.insert(0xc, DeviceEntry::Device(devices.datetime_device.as_mut()));
     let device_list = DeviceListImpl::new(device_list);
     return device_list;
 }
 
-pub fn run<J: Write, K: Read, L: Write, M: Write>(cli_config: Cli, other_config: Config<J, K, L, M>) -> Result<(), Box<dyn Error>> {
-    let rom = match File::open(cli_config.rom.as_path()) {
+// runs the vectors of the program, limiting each to `max_steps` instructions if a limit has been
+// given, under the control of a debugger if one is attached
+struct VectorRunner {
+    max_steps: Option<u64>,
+    gdb: Option<GdbStub>,
+}
+
+impl VectorRunner {
+    // run `vector`, or if `vector` is `None` finish whatever vector was part way through
+    // executing (if any)
+    fn run_vector<J, K, M, N>(&mut self, uxn: &mut uxn::UxnImpl<N>, vector: Option<u16>,
+        devices: &mut CliDevices<J, K, M>) -> Result<UxnStatus, Box<dyn Error>>
+    where J: Write, K: Write, M: Write, N: InstructionFactory,
+    {
+        let reported_vector = vector.unwrap_or_else(|| uxn.get_program_counter().unwrap_or(0));
+
+        let devices = construct_device_list(devices);
+        let res = match (self.gdb.as_mut(), vector, self.max_steps) {
+            (Some(gdb), vector, max_steps) => gdb.run(uxn, vector, devices, max_steps)?,
+            (None, Some(vector), Some(max_steps)) => uxn.run_with_budget(vector, devices, max_steps)?,
+            (None, Some(vector), None) => uxn.run(vector, devices)?,
+            (None, None, max_steps) => uxn.resume_with_budget(devices, max_steps.unwrap_or(u64::MAX))?,
+        };
+        match (res, self.max_steps) {
+            (UxnStatus::BudgetExhausted, Some(max_steps)) => {
+                return Err(Box::new(MaxStepsExceededError{vector: reported_vector, max_steps}));
+            },
+            (res, _) => {
+                return Ok(res);
+            },
+        }
+    }
+}
+
+// create a uxn with the rom at `rom_path` loaded into it
+pub(crate) fn load_uxn(rom_path: &Path) -> Result<uxn::UxnImpl<OpObjectFactory>, Box<dyn Error>> {
+    let rom = match File::open(rom_path) {
         Ok(fp) => fp,
         Err(_err) => {
             return Err(Box::new(RomReadError {
-                fname: cli_config.rom.as_path().display().to_string().clone(),
+                fname: rom_path.display().to_string(),
             }));
         }
     };
+    // the rom is checked up front, so that a rom too large for the uxn isn't silently
+    // truncated
+    let rom_size = usize::try_from(rom.metadata()?.len()).unwrap_or(usize::MAX);
+    if rom_size > uxn::MAX_ROM_SIZE {
+        return Err(Box::new(RomTooLargeError {
+            fname: rom_path.display().to_string(),
+            size: rom_size,
+            max_size: uxn::MAX_ROM_SIZE,
+        }));
+    }
+
     let rom = BufReader::new(rom).bytes();
     let rom = rom.map(|b| b.unwrap());
     let instruction_factory_impl = OpObjectFactory{};
 
-    let mut uxn = uxn::UxnImpl::new(rom, instruction_factory_impl)?;
+    return Ok(uxn::UxnImpl::new(rom, instruction_factory_impl)?);
+}
+
+pub fn run<J: Write, K: Read, L: Write, M: Write>(cli_config: Cli, other_config: Config<J, K, L, M>) -> Result<(), Box<dyn Error>> {
+    let mut uxn = load_uxn(&cli_config.rom)?;
+    uxn.set_stack_fault_policy(cli_config.stack_faults);
+    uxn.set_device_access_policy(cli_config.strict_devices);
+
+    if let Some(path) = &cli_config.trace {
+        let trace_file = File::create(path)?;
+        let symbols = match &cli_config.symbols {
+            Some(path) => SymbolTable::read_from_file(path)?,
+            None => SymbolTable::read_for_rom(&cli_config.rom)?,
+        };
+        uxn.set_observer(Box::new(TraceWriter::with_symbols(BufWriter::new(trace_file), symbols)));
+    }
 
     let console_device = Console::new(
         other_config.stdout_writer,
         other_config.stderr_writer);
 
-    let file_device = FileDevice::new();
+    // the date time device gives values from outside the machine, so when recording its reads
+    // are recorded and when replaying they are taken from the recording
+    let recorder = match &cli_config.record {
+        Some(path) => Some(Rc::new(RefCell::new(InputRecorder::create(path)?))),
+        None => None,
+    };
+    let replay = match &cli_config.replay {
+        Some(path) => Some(Rc::new(RefCell::new(InputReplay::read_from_file(path)?))),
+        None => None,
+    };
+    let datetime_device: Box<dyn Device> = match (&recorder, &replay) {
+        (Some(recorder), _) => {
+            Box::new(RecordingDevice::new(DateTimeDevice::new(), 0xc, recorder.clone()))
+        },
+        (_, Some(replay)) => {
+            Box::new(ReplayingDevice::new(DateTimeDevice::new(), 0xc, replay.clone()))
+        },
+        (None, None) => Box::new(DateTimeDevice::new()),
+    };
+
+    let mut cli_devices = CliDevices::new(console_device, datetime_device, other_config.debug_writer);
+
+    if let Some(path) = &cli_config.load_state {
+        let save_state = SaveState::read_from_file(path)?;
+        load_machine_state(&save_state, &mut uxn, &mut cli_devices)?;
+    }
+
+    let input_source = match replay.as_deref() {
+        Some(replay) => InputSource::Replay(replay),
+        None => InputSource::Live(recorder.as_deref()),
+    };
+    let gdb = match cli_config.gdb {
+        Some(port) => Some(GdbStub::listen(port)?),
+        None => None,
+    };
+    let mut runner = VectorRunner{max_steps: cli_config.max_steps, gdb};
+    let res = run_program(&mut uxn, &mut cli_devices, cli_config.input, other_config.stdin_reader,
+        &mut runner, cli_config.load_state.is_some(), input_source);
 
-    let datetime_device = DateTimeDevice::new();
+    // the state is saved however the program finished, so that a state leading up to an error
+    // can be inspected
+    if let Some(path) = &cli_config.save_state_on_exit {
+        let mut save_state = SaveState::new();
+        save_machine_state(&mut save_state, &uxn, &cli_devices);
+        save_state.write_to_file(path)?;
+    }
 
-    let mut cli_devices = CliDevices{
-        console_device, file_device, datetime_device, debug_writer: other_config.debug_writer};
+    return res;
+}
 
-    // initial run of program
-    let res = uxn.run(uxn::INIT_VECTOR, construct_device_list(&mut cli_devices))?;
+fn save_machine_state<J, K, M, N>(save_state: &mut SaveState, uxn: &uxn::UxnImpl<N>,
+    devices: &CliDevices<J, K, M>)
+where J: Write, K: Write, M: Write, N: InstructionFactory,
+{
+    save_state.save_machine(uxn);
+    save_state.save_device(0x1, &devices.console_device);
+    save_state.save_device(0xa, &devices.file_device);
+}
+
+fn load_machine_state<J, K, M, N>(save_state: &SaveState, uxn: &mut uxn::UxnImpl<N>,
+    devices: &mut CliDevices<J, K, M>) -> Result<(), SnapshotError>
+where J: Write, K: Write, M: Write, N: InstructionFactory,
+{
+    save_state.load_machine(uxn)?;
+    save_state.load_device(0x1, &mut devices.console_device)?;
+    save_state.load_device(0xa, &mut devices.file_device)?;
+    return Ok(());
+}
+
+// give `c` to the console device and run the console vector to handle it, recording the input
+// if the session is being recorded
+fn console_input<J, K, M, N>(uxn: &mut uxn::UxnImpl<N>, cli_devices: &mut CliDevices<J, K, M>,
+    c: u8, runner: &mut VectorRunner, recorder: Option<&RefCell<InputRecorder<File>>>)
+    -> Result<UxnStatus, Box<dyn Error>>
+where J: Write, K: Write, M: Write, N: InstructionFactory,
+{
+    cli_devices.console_device.provide_input(c);
+    let console_vector = cli_devices.console_device.read_vector();
+
+    if let Some(recorder) = recorder {
+        recorder.borrow_mut().record(
+            &LogEntry::Input{event: InputEvent::Console(c), vector: console_vector})?;
+    }
+
+    return runner.run_vector(uxn, Some(console_vector), cli_devices);
+}
+
+// feed the console input of a recorded session to the program, checking that each input is
+// handled by the same vector that handled it when it was recorded
+fn replay_program<J, K, M, N>(uxn: &mut uxn::UxnImpl<N>, cli_devices: &mut CliDevices<J, K, M>,
+    runner: &mut VectorRunner, replay: &RefCell<InputReplay>) -> Result<(), Box<dyn Error>>
+where J: Write, K: Write, M: Write, N: InstructionFactory,
+{
+    // the replay is only borrowed between vectors, as the date time device reads from it
+    // while they run
+    while let Some((event, recorded_vector)) = replay.borrow_mut().next_input()? {
+        let c = if let InputEvent::Console(c) = event {
+            c
+        } else {
+            return Err(Box::new(InputLogError::UnsupportedEvent(event)));
+        };
+
+        cli_devices.console_device.provide_input(c);
+        let console_vector = cli_devices.console_device.read_vector();
+        check_vector(&event, recorded_vector, console_vector)?;
+
+        match runner.run_vector(uxn, Some(console_vector), cli_devices)? {
+            UxnStatus::Terminate => { return Ok(()); },
+            UxnStatus::Halt | UxnStatus::BudgetExhausted => {},
+        }
+    }
+
+    return Ok(());
+}
+
+// where the console input of a session comes from
+enum InputSource<'a> {
+    // the command line and stdin, recording the input if a recorder is given
+    Live(Option<&'a RefCell<InputRecorder<File>>>),
+    // a recorded session
+    Replay(&'a RefCell<InputReplay>),
+}
+
+// run the program loaded into `uxn`, feeding it the console input from `input_source`. For live
+// input this is `input` and then `stdin_reader`. If `resuming` is set the machine has been
+// restored from a save state and so the initial vector isn't run
+fn run_program<J, K, M, N, R>(uxn: &mut uxn::UxnImpl<N>, cli_devices: &mut CliDevices<J, K, M>,
+    input: Vec<String>, stdin_reader: R, runner: &mut VectorRunner, resuming: bool,
+    input_source: InputSource) -> Result<(), Box<dyn Error>>
+where J: Write, K: Write, M: Write, N: InstructionFactory, R: Read,
+{
+    let res = if resuming {
+        // the machine was initialised before its state was saved, so rather than running the
+        // initial vector finish whatever vector was part way through executing (if any)
+        runner.run_vector(uxn, None, cli_devices)?
+    } else {
+        // initial run of program
+        runner.run_vector(uxn, Some(uxn::INIT_VECTOR), cli_devices)?
+    };
 
     match res {
         UxnStatus::Terminate => { return Ok(()); },
-        UxnStatus::Halt => {},
+        UxnStatus::Halt | UxnStatus::BudgetExhausted => {},
     }
 
+    let recorder = match input_source {
+        InputSource::Live(recorder) => recorder,
+        InputSource::Replay(replay) => {
+            return replay_program(uxn, cli_devices, runner, replay);
+        },
+    };
+
     // for the input given on the command line, make each byte of it, in turn, available through
     // the console device and trigger the console input vector
-    for input in cli_config.input {
+    for input in input {
         for c in input.bytes().chain("\n".bytes()) { 
-            cli_devices.console_device.provide_input(c);
-
-            let console_vector = cli_devices.console_device.read_vector();
-            let res = uxn.run(console_vector, construct_device_list(&mut cli_devices))?;
+            let res = console_input(uxn, cli_devices, c, runner, recorder)?;
 
             match res {
                 UxnStatus::Terminate => { return Ok(()); },
-                UxnStatus::Halt => {},
+                UxnStatus::Halt | UxnStatus::BudgetExhausted => {},
             }
         }
     }
 
     // for input provided via stdin, make each byte available through the console device and
     // trigger the console input vector
-    for c in other_config.stdin_reader.bytes() {
+    for c in stdin_reader.bytes() {
         match c {
             Ok(c) => {
-                cli_devices.console_device.provide_input(c);
-                let console_vector = cli_devices.console_device.read_vector();
-                let res = uxn.run(console_vector, construct_device_list(&mut cli_devices))?;
+                let res = console_input(uxn, cli_devices, c, runner, recorder)?;
 
                 match res {
                     UxnStatus::Terminate => { return Ok(()); },
-                    UxnStatus::Halt => {},
+                    UxnStatus::Halt | UxnStatus::BudgetExhausted => {},
                 }
             },
             Err(e) => {
@@ -0,0 +1,579 @@
+use clap::Parser;
+use std::collections::{BTreeSet, VecDeque};
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+use crate::uxninterface::{Uxn, UxnCrash, UxnError, UxnStatus};
+use crate::ops::{OpObject, OpObjectFactory};
+use crate::symbols::SymbolTable;
+use crate::emulators::uxn;
+use crate::emulators::devices::console::Console;
+use crate::emulators::devices::datetime::DateTimeDevice;
+use crate::emulators::devices::system::UxnSystemInterface;
+use crate::emulators::uxnclilib::{CliDevices, construct_device_list, load_uxn};
+pub use crate::emulators::uxn::{StackFaultPolicy, DeviceAccessPolicy};
+
+/// An interactive debugger for uxn roms, running them on the same machine as uxncli
+#[derive(Parser)]
+pub struct Cli {
+    /// Rom to debug
+    #[clap(parse(from_os_str))]
+    pub rom: std::path::PathBuf,
+
+    /// Initial console input for uxn virtual machine
+    pub input: Vec<String>,
+
+    /// Symbol file giving the labels of the rom, which can then be used in
+    /// place of addresses [default: <ROM>.sym, if it exists]
+    #[clap(long, parse(from_os_str))]
+    pub symbols: Option<std::path::PathBuf>,
+
+    /// What happens when a stack overflows or underflows: stop with an error,
+    /// wrap the stack pointer around, or jump to the handler at the system
+    /// vector (stopping with an error if the rom hasn't installed one)
+    #[clap(long, arg_enum, default_value = "error")]
+    pub stack_faults: StackFaultPolicy,
+
+    /// What happens when the rom reads from or writes to a device slot with
+    /// no device in it: ignore it, print a warning (once for each slot), or
+    /// fault
+    #[clap(long, arg_enum, default_value = "ignore")]
+    pub strict_devices: DeviceAccessPolicy,
+}
+
+pub struct Config<J: Write, K: BufRead, L: Write, M: Write, N: Write> {
+    pub stdout_writer: J,   // used by console device for stdout
+    pub command_reader: K,  // where the debugger's commands are read from
+    pub stderr_writer: L,   // used by console device for stderr
+    pub debug_writer: M,    // used by system device for debug output
+    pub output_writer: N,   // where the debugger writes its own output
+}
+
+const HELP: &str = "\
+Addresses can be given in hexadecimal or as a label from the symbol file. Bytes
+are given in hexadecimal and counts in decimal.
+
+  step, s [COUNT]            execute COUNT instructions (default 1)
+  next, n                    execute an instruction, running any subroutine it calls
+  continue, c                run until a breakpoint, or the program needs input
+  break, b [ADDRESS]         set a breakpoint at ADDRESS, or list the breakpoints
+  delete, d ADDRESS          remove the breakpoint at ADDRESS
+  stack, st                  show the working and return stacks
+  mem, x ADDRESS [COUNT]     show COUNT bytes of memory from ADDRESS (default 16)
+  list, l [ADDRESS] [COUNT]  disassemble COUNT instructions from ADDRESS
+                             (default the program counter, and 10)
+  poke ADDRESS BYTE...       write bytes to memory, starting at ADDRESS
+  push wst|rst BYTE...       push bytes to a stack
+  pop wst|rst                pop a byte from a stack
+  input, i TEXT              give TEXT and a newline to the program as console input
+  help, h                    show this help
+  quit, q                    leave the debugger";
+
+#[derive(Debug)]
+enum CommandError {
+    UnknownCommand(String),
+    MissingArgument(&'static str),
+    InvalidAddress(String),
+    InvalidByte(String),
+    InvalidCount(String),
+    InvalidStack(String),
+    NoBreakpoint(u16),
+    Uxn(UxnError),
+    // the debugger's own output couldn't be written, which ends the session
+    Output(io::Error),
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandError::UnknownCommand(command) => {
+                write!(f, "unknown command `{}` (try `help`)", command)
+            },
+            CommandError::MissingArgument(argument) => write!(f, "missing {}", argument),
+            CommandError::InvalidAddress(address) => {
+                write!(f, "`{}` is neither a label nor a hexadecimal address", address)
+            },
+            CommandError::InvalidByte(byte) => write!(f, "`{}` is not a hexadecimal byte", byte),
+            CommandError::InvalidCount(count) => write!(f, "`{}` is not a count", count),
+            CommandError::InvalidStack(stack) => {
+                write!(f, "`{}` is not a stack (use `wst` or `rst`)", stack)
+            },
+            CommandError::NoBreakpoint(address) => write!(f, "no breakpoint at {:04x}", address),
+            CommandError::Uxn(err) => write!(f, "{}", err),
+            CommandError::Output(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<io::Error> for CommandError {
+    fn from(err: io::Error) -> Self {
+        return CommandError::Output(err);
+    }
+}
+
+// where the program being debugged has got to
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum ProgramState {
+    // part way through a vector, about to execute the instruction at the program counter
+    Paused,
+    // between vectors, with no console input left to give it
+    WaitingForInput,
+    // terminated or crashed, so it can't run any further
+    Finished,
+}
+
+// the outcome of trying to execute a single instruction
+enum StepResult {
+    Stepped,
+    WaitingForInput,
+    Terminated,
+    Crashed(UxnCrash),
+    Finished,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum StackId {
+    Working,
+    Return,
+}
+
+// the byte of the JSR instruction, without any mode flags
+const JSR_BYTE: u8 = 0x0e;
+
+// the byte of the JSI instruction, which has no modes
+const JSI_BYTE: u8 = 0x60;
+
+// the number of bytes following an instruction that are its operand
+fn operand_len(instr: u8) -> u16 {
+    match instr {
+        0x80 | 0xc0 => 1,
+        0xa0 | 0xe0 => 2,
+        // the immediate jumps JCI, JMI and JSI
+        0x20 | 0x40 | 0x60 => 2,
+        _ => 0,
+    }
+}
+
+struct Debugger<J: Write, K: Write, M: Write, N: Write> {
+    uxn: uxn::UxnImpl<OpObjectFactory>,
+    devices: CliDevices<J, K, M>,
+    symbols: SymbolTable,
+    breakpoints: BTreeSet<u16>,
+    // console input waiting to be given to the program, a byte each time a vector completes
+    pending_input: VecDeque<u8>,
+    state: ProgramState,
+    output: N,
+}
+
+impl<J: Write, K: Write, M: Write, N: Write> Debugger<J, K, M, N> {
+    fn new(mut uxn: uxn::UxnImpl<OpObjectFactory>, mut devices: CliDevices<J, K, M>,
+        symbols: SymbolTable, input: Vec<String>, output: N) -> Result<Self, UxnCrash>
+    {
+        // start the initial vector without executing any of it, so that the program is paused
+        // at its first instruction
+        uxn.run_with_budget(uxn::INIT_VECTOR, construct_device_list(&mut devices), 0)?;
+
+        let mut pending_input = VecDeque::new();
+        for input in input {
+            pending_input.extend(input.bytes().chain("\n".bytes()));
+        }
+
+        return Ok(Debugger{uxn, devices, symbols, breakpoints: BTreeSet::new(), pending_input,
+            state: ProgramState::Paused, output});
+    }
+
+    fn print(&mut self, text: &str) -> io::Result<()> {
+        return writeln!(self.output, "{}", text);
+    }
+
+    fn program_counter(&self) -> u16 {
+        return self.uxn.get_program_counter().unwrap_or(0);
+    }
+
+    // give the program its next byte of console input, and start the console vector to handle
+    // it. Returns false if there is no input left for the program to handle
+    fn start_next_input(&mut self) -> bool {
+        while let Some(c) = self.pending_input.pop_front() {
+            self.devices.console_device.provide_input(c);
+            let console_vector = self.devices.console_device.read_vector();
+
+            // a vector of zero isn't run, so the input is dropped just as uxncli would drop it
+            let res = self.uxn.run_with_budget(console_vector, construct_device_list(&mut self.devices), 0);
+            if res == Ok(UxnStatus::BudgetExhausted) {
+                self.state = ProgramState::Paused;
+                return true;
+            }
+        }
+
+        self.state = ProgramState::WaitingForInput;
+        return false;
+    }
+
+    // execute the instruction at the program counter. When a vector completes, the program is
+    // moved on to the vector handling the next console input, if there is any
+    fn step_instruction(&mut self) -> StepResult {
+        match self.state {
+            ProgramState::Paused => {},
+            ProgramState::WaitingForInput => {
+                if !self.start_next_input() {
+                    return StepResult::WaitingForInput;
+                }
+            },
+            ProgramState::Finished => {
+                return StepResult::Finished;
+            },
+        }
+
+        match self.uxn.resume_with_budget(construct_device_list(&mut self.devices), 1) {
+            Ok(UxnStatus::BudgetExhausted) => {
+                return StepResult::Stepped;
+            },
+            Ok(UxnStatus::Halt) => {
+                if !self.start_next_input() {
+                    return StepResult::WaitingForInput;
+                }
+                return StepResult::Stepped;
+            },
+            Ok(UxnStatus::Terminate) => {
+                self.state = ProgramState::Finished;
+                return StepResult::Terminated;
+            },
+            Err(crash) => {
+                self.state = ProgramState::Finished;
+                return StepResult::Crashed(crash);
+            },
+        }
+    }
+
+    // execute instructions until `done` says to stop, a breakpoint is reached, or the program
+    // can't run any further
+    fn run_until<F: FnMut(&Self) -> bool>(&mut self, mut done: F) -> io::Result<()> {
+        loop {
+            match self.step_instruction() {
+                StepResult::Stepped => {},
+                StepResult::WaitingForInput => {
+                    return self.print("the program is waiting for console input (give it some with `input`)");
+                },
+                StepResult::Terminated => {
+                    return self.print("the program has terminated");
+                },
+                StepResult::Crashed(crash) => {
+                    return self.print(&crash.to_string());
+                },
+                StepResult::Finished => {
+                    return self.print("the program has finished, so can't run any further");
+                },
+            }
+
+            if done(self) {
+                break;
+            }
+
+            let program_counter = self.program_counter();
+            if self.breakpoints.contains(&program_counter) {
+                self.print(&format!("breakpoint at {:04x}", program_counter))?;
+                break;
+            }
+        }
+
+        return self.print_location();
+    }
+
+    fn step(&mut self, count: usize) -> io::Result<()> {
+        let mut remaining = count;
+        return self.run_until(|_| {
+            remaining -= 1;
+            return remaining == 0;
+        });
+    }
+
+    // step over the instruction at the program counter. If it calls a subroutine, run until the
+    // subroutine has returned
+    fn next(&mut self) -> io::Result<()> {
+        let program_counter = self.program_counter();
+        let instr = self.uxn.read_from_ram(program_counter);
+        let is_call = instr & 0x1f == JSR_BYTE || instr == JSI_BYTE;
+        if self.state != ProgramState::Paused || !is_call {
+            return self.step(1);
+        }
+
+        // JSR pushes the return address to the return stack, unless it is in return mode in
+        // which case it goes on the working stack, while JSI always pushes it to the return
+        // stack. The call has returned once the program counter is back at the return address
+        // (after any operand) with that stack no deeper than it started
+        let return_address = program_counter.wrapping_add(1 + operand_len(instr));
+        let return_stack = if instr != JSI_BYTE && instr & 0x40 > 0 {
+            StackId::Working
+        } else {
+            StackId::Return
+        };
+        let depth = self.stack_depth(return_stack);
+        return self.run_until(|debugger| {
+            return debugger.state == ProgramState::Paused
+                && debugger.program_counter() == return_address
+                && debugger.stack_depth(return_stack) <= depth;
+        });
+    }
+
+    fn stack_depth(&self, stack: StackId) -> u8 {
+        match stack {
+            StackId::Working => self.uxn.get_working_stack_index(),
+            StackId::Return => self.uxn.get_return_stack_index(),
+        }
+    }
+
+    // where the program has got to
+    fn print_location(&mut self) -> io::Result<()> {
+        match self.state {
+            ProgramState::Paused => {
+                return self.list(self.program_counter(), 1);
+            },
+            ProgramState::WaitingForInput => {
+                return self.print("the program is waiting for console input (give it some with `input`)");
+            },
+            ProgramState::Finished => {
+                return self.print("the program has finished");
+            },
+        }
+    }
+
+    // disassemble `count` instructions from `address`. The instruction at the program counter is
+    // marked with `=>` and those with breakpoints with `*`
+    fn list(&mut self, address: u16, count: usize) -> io::Result<()> {
+        let mut address = address;
+        for _ in 0..count {
+            let labels = self.symbols.labels_at(address)
+                .map(|label| { return format!("@{}\n", label); })
+                .collect::<String>();
+
+            let instr = self.uxn.read_from_ram(address);
+            let bytes = (0..=operand_len(instr))
+                .map(|offset| { return self.uxn.read_from_ram(address.wrapping_add(offset)); })
+                .collect::<Vec<u8>>();
+            let hex_bytes = bytes.iter()
+                .map(|b| { return format!("{:02x}", b); })
+                .collect::<Vec<String>>();
+            let operand = hex_bytes[1..].concat();
+
+            let at_program_counter = self.state == ProgramState::Paused
+                && address == self.program_counter();
+            let line = format!("{}{}{}{:04x}  {:<9} {} {}",
+                labels,
+                if at_program_counter { "=>" } else { "  " },
+                if self.breakpoints.contains(&address) { "*" } else { " " },
+                address, hex_bytes.join(" "), OpObject::from_byte(instr), operand);
+            self.print(line.trim_end())?;
+
+            address = address.wrapping_add(u16::try_from(bytes.len()).unwrap());
+        }
+
+        return Ok(());
+    }
+
+    fn print_stacks(&mut self) -> io::Result<()> {
+        let working_stack = self.uxn.get_working_stack_iter()
+            .map(|b| { return format!(" {:02x}", b); })
+            .collect::<String>();
+        let return_stack = self.uxn.get_return_stack_iter()
+            .map(|b| { return format!(" {:02x}", b); })
+            .collect::<String>();
+        return self.print(&format!("<wst>{}\n<rst>{}", working_stack, return_stack));
+    }
+
+    fn print_memory(&mut self, address: u16, count: usize) -> io::Result<()> {
+        let addresses = (0..count).map(|offset| { return address.wrapping_add(offset as u16); })
+            .collect::<Vec<u16>>();
+        for row in addresses.chunks(0x10) {
+            let line = row.iter()
+                .map(|address| { return format!(" {:02x}", self.uxn.read_from_ram(*address)); })
+                .collect::<String>();
+            self.print(&format!("{:04x} {}", row[0], line))?;
+        }
+
+        return Ok(());
+    }
+
+    fn parse_address(&self, arg: Option<&&str>) -> Result<u16, CommandError> {
+        let arg = arg.ok_or(CommandError::MissingArgument("address"))?;
+        if let Some(address) = self.symbols.address_of(arg) {
+            return Ok(address);
+        }
+        return u16::from_str_radix(arg.trim_start_matches("0x"), 16)
+            .map_err(|_| CommandError::InvalidAddress(arg.to_string()));
+    }
+
+    fn parse_bytes(args: &[&str]) -> Result<Vec<u8>, CommandError> {
+        if args.is_empty() {
+            return Err(CommandError::MissingArgument("bytes"));
+        }
+        return args.iter()
+            .map(|arg| {
+                return u8::from_str_radix(arg.trim_start_matches("0x"), 16)
+                    .map_err(|_| CommandError::InvalidByte(arg.to_string()));
+            })
+            .collect();
+    }
+
+    fn parse_count(arg: Option<&&str>, default: usize) -> Result<usize, CommandError> {
+        return match arg {
+            Some(arg) => match arg.parse::<usize>() {
+                Ok(count) if count > 0 => Ok(count),
+                _ => Err(CommandError::InvalidCount(arg.to_string())),
+            },
+            None => Ok(default),
+        };
+    }
+
+    fn parse_stack(arg: Option<&&str>) -> Result<StackId, CommandError> {
+        return match arg {
+            Some(&"wst") => Ok(StackId::Working),
+            Some(&"rst") => Ok(StackId::Return),
+            Some(arg) => Err(CommandError::InvalidStack(arg.to_string())),
+            None => Err(CommandError::MissingArgument("stack")),
+        };
+    }
+
+    // carry out a single command, returning whether the debugger should carry on
+    fn execute_command(&mut self, line: &str) -> Result<bool, CommandError> {
+        let line = line.trim();
+        let (command, rest) = match line.split_once(' ') {
+            Some((command, rest)) => (command, rest.trim_start()),
+            None => (line, ""),
+        };
+        let args = rest.split_whitespace().collect::<Vec<&str>>();
+
+        match command {
+            "" => {},
+            "step" | "s" => {
+                let count = Self::parse_count(args.first(), 1)?;
+                self.step(count)?;
+            },
+            "next" | "n" => {
+                self.next()?;
+            },
+            "continue" | "c" => {
+                self.run_until(|_| { return false; })?;
+            },
+            "break" | "b" => {
+                if args.is_empty() {
+                    let breakpoints = self.breakpoints.iter()
+                        .map(|address| { return format!("{:04x}", address); })
+                        .collect::<Vec<String>>();
+                    if breakpoints.is_empty() {
+                        self.print("no breakpoints")?;
+                    } else {
+                        self.print(&breakpoints.join("\n"))?;
+                    }
+                } else {
+                    let address = self.parse_address(args.first())?;
+                    self.breakpoints.insert(address);
+                    self.print(&format!("breakpoint set at {:04x}", address))?;
+                }
+            },
+            "delete" | "d" => {
+                let address = self.parse_address(args.first())?;
+                if !self.breakpoints.remove(&address) {
+                    return Err(CommandError::NoBreakpoint(address));
+                }
+            },
+            "stack" | "st" => {
+                self.print_stacks()?;
+            },
+            "mem" | "x" => {
+                let address = self.parse_address(args.first())?;
+                let count = Self::parse_count(args.get(1), 0x10)?;
+                self.print_memory(address, count)?;
+            },
+            "list" | "l" => {
+                let address = match args.first() {
+                    Some(_) => self.parse_address(args.first())?,
+                    None => self.program_counter(),
+                };
+                let count = Self::parse_count(args.get(1), 10)?;
+                self.list(address, count)?;
+            },
+            "poke" => {
+                let address = self.parse_address(args.first())?;
+                let bytes = Self::parse_bytes(args.get(1..).unwrap_or(&[]))?;
+                for (offset, byte) in bytes.into_iter().enumerate() {
+                    self.uxn.write_to_ram(address.wrapping_add(offset as u16), byte);
+                }
+            },
+            "push" => {
+                let stack = Self::parse_stack(args.first())?;
+                let bytes = Self::parse_bytes(args.get(1..).unwrap_or(&[]))?;
+                for byte in bytes {
+                    match stack {
+                        StackId::Working => self.uxn.push_to_working_stack(byte),
+                        StackId::Return => self.uxn.push_to_return_stack(byte),
+                    }.map_err(CommandError::Uxn)?;
+                }
+                self.print_stacks()?;
+            },
+            "pop" => {
+                let byte = match Self::parse_stack(args.first())? {
+                    StackId::Working => self.uxn.pop_from_working_stack(),
+                    StackId::Return => self.uxn.pop_from_return_stack(),
+                }.map_err(CommandError::Uxn)?;
+                self.print(&format!("{:02x}", byte))?;
+            },
+            "input" | "i" => {
+                self.pending_input.extend(rest.bytes().chain("\n".bytes()));
+                if self.state == ProgramState::WaitingForInput && self.start_next_input() {
+                    self.print_location()?;
+                }
+            },
+            "help" | "h" => {
+                self.print(HELP)?;
+            },
+            "quit" | "q" => {
+                return Ok(false);
+            },
+            _ => {
+                return Err(CommandError::UnknownCommand(command.to_string()));
+            },
+        }
+
+        return Ok(true);
+    }
+}
+
+pub fn run<J, K, L, M, N>(cli_config: Cli, other_config: Config<J, K, L, M, N>) -> Result<(), Box<dyn Error>>
+where J: Write, K: BufRead, L: Write, M: Write, N: Write,
+{
+    let mut uxn = load_uxn(&cli_config.rom)?;
+    uxn.set_stack_fault_policy(cli_config.stack_faults);
+    uxn.set_device_access_policy(cli_config.strict_devices);
+
+    let symbols = match &cli_config.symbols {
+        Some(path) => SymbolTable::read_from_file(path)?,
+        None => SymbolTable::read_for_rom(&cli_config.rom)?,
+    };
+
+    let console_device = Console::new(other_config.stdout_writer, other_config.stderr_writer);
+    let devices = CliDevices::new(console_device, Box::new(DateTimeDevice::new()),
+        other_config.debug_writer);
+
+    let mut debugger = Debugger::new(uxn, devices, symbols, cli_config.input,
+        other_config.output_writer)?;
+    debugger.print_location()?;
+
+    let mut command_reader = other_config.command_reader;
+    loop {
+        write!(debugger.output, "(uxndbg) ")?;
+        debugger.output.flush()?;
+
+        let mut line = String::new();
+        if command_reader.read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+
+        match debugger.execute_command(&line) {
+            Ok(true) => {},
+            Ok(false) => { return Ok(()); },
+            Err(CommandError::Output(e)) => { return Err(Box::new(e)); },
+            Err(e) => debugger.print(&e.to_string())?,
+        }
+    }
+}
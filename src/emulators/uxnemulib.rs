@@ -5,9 +5,12 @@ use std::io;
 use std::io::BufReader;
 use std::io::Read;
 use std::collections::HashMap;
+use std::collections::VecDeque;
 use std::thread;
 use std::time::Duration;
-use crate::uxninterface::UxnStatus;
+use std::rc::Rc;
+use std::cell::RefCell;
+use crate::uxninterface::{UxnStatus, UxnCrash};
 
 use speedy2d::Window;
 use speedy2d::window::{WindowHandler, WindowHelper, WindowStartupInfo, WindowCreationOptions, WindowSize,
@@ -27,8 +30,15 @@ use std::io::Write;
 
 use crate::instruction;
 use crate::emulators::uxn;
+pub use crate::emulators::uxn::{StackFaultPolicy, DeviceAccessPolicy};
 
-use crate::emulators::RomReadError;
+use crate::emulators::{RomReadError, RomTooLargeError};
+use crate::emulators::snapshot::{SaveState, SnapshotError};
+use crate::emulators::replay::{InputRecorder, InputReplay, InputEvent, LogEntry, RecordingDevice,
+    ReplayingDevice, check_vector};
+use crate::emulators::uxn::device::Device;
+use crate::emulators::gdbstub::GdbStub;
+use std::path::PathBuf;
 
 #[cfg(debug_assertions)]
 use std::time::Instant;
@@ -46,6 +56,43 @@ pub struct Cli {
 
     /// Initial console input for uxn virtual machine
     pub input: Vec<String>,
+
+    /// What happens when a stack overflows or underflows: stop with an error,
+    /// wrap the stack pointer around, or jump to the handler at the system
+    /// vector (stopping with an error if the rom hasn't installed one)
+    #[clap(long, arg_enum, default_value = "error")]
+    pub stack_faults: StackFaultPolicy,
+
+    /// What happens when the rom reads from or writes to a device slot with
+    /// no device in it: ignore it, print a warning (once for each slot), or
+    /// fault
+    #[clap(long, arg_enum, default_value = "ignore")]
+    pub strict_devices: DeviceAccessPolicy,
+
+    /// File the machine state is saved to (F5) and loaded from (F9) [default: <ROM>.state]
+    #[clap(long, parse(from_os_str))]
+    pub state_file: Option<std::path::PathBuf>,
+
+    /// Save state to restore the machine from before running, instead of
+    /// starting the rom from the beginning
+    #[clap(long, parse(from_os_str))]
+    pub load_state: Option<std::path::PathBuf>,
+
+    /// File to record the input of the session to (console, mouse and
+    /// controller input, screen refreshes and date time device reads), so
+    /// that it can be replayed
+    #[clap(long, parse(from_os_str))]
+    pub record: Option<std::path::PathBuf>,
+
+    /// Input log (made with --record) to replay, in place of the user's
+    /// input. Once the log is finished the user's input is used again
+    #[clap(long, parse(from_os_str), conflicts_with = "record")]
+    pub replay: Option<std::path::PathBuf>,
+
+    /// Port to wait on (on localhost) for a debugger to connect to, using
+    /// the GDB remote serial protocol, before the rom starts
+    #[clap(long)]
+    pub gdb: Option<u16>,
 }
 
 pub struct Config<J: Write> {
@@ -55,7 +102,7 @@ pub struct Config<J: Write> {
 struct EmuDevices<J: Write, K: Write, M: Write> {
     console_device: Console<J, K>,
     file_device: FileDevice,
-    datetime_device: DateTimeDevice,
+    datetime_device: Box<dyn Device>,
     debug_writer: M,
     screen_device: ScreenDevice,
     mouse_device: MouseDevice,
@@ -70,21 +117,99 @@ fn construct_device_list<J: Write, K: Write, M: Write>(devices: &mut EmuDevices<
     device_list.insert(0x8, DeviceEntry::Device(&mut devices.controller_device));
     device_list.insert(0x9, DeviceEntry::Device(&mut devices.mouse_device));
     device_list.insert(0xa, DeviceEntry::Device(&mut devices.file_device));
-    device_list.insert(0xc, DeviceEntry::Device(&mut devices.datetime_device));
+    device_list.insert(0xc, DeviceEntry::Device(devices.datetime_device.as_mut()));
     let device_list = DeviceListImpl::new(device_list);
     return device_list;
 }
 
+fn save_machine_state<J, K, M, N>(save_state: &mut SaveState, uxn: &uxn::UxnImpl<N>,
+    devices: &EmuDevices<J, K, M>)
+where J: Write, K: Write, M: Write, N: instruction::InstructionFactory,
+{
+    save_state.save_machine(uxn);
+    save_state.save_device(0x1, &devices.console_device);
+    save_state.save_device(0x2, &devices.screen_device);
+    save_state.save_device(0x8, &devices.controller_device);
+    save_state.save_device(0x9, &devices.mouse_device);
+    save_state.save_device(0xa, &devices.file_device);
+}
+
+fn load_machine_state<J, K, M, N>(save_state: &SaveState, uxn: &mut uxn::UxnImpl<N>,
+    devices: &mut EmuDevices<J, K, M>) -> Result<(), SnapshotError>
+where J: Write, K: Write, M: Write, N: instruction::InstructionFactory,
+{
+    save_state.load_machine(uxn)?;
+    save_state.load_device(0x1, &mut devices.console_device)?;
+    save_state.load_device(0x2, &mut devices.screen_device)?;
+    save_state.load_device(0x8, &mut devices.controller_device)?;
+    save_state.load_device(0x9, &mut devices.mouse_device)?;
+    save_state.load_device(0xa, &mut devices.file_device)?;
+    return Ok(());
+}
+
+// run `vector` (or, if `vector` is `None`, resume the suspended vector) for at most `budget`
+// instructions, under the control of `gdb` if a debugger is attached
+fn run_vector<K, N>(uxn: &mut uxn::UxnImpl<N>, gdb: Option<&mut GdbStub>, vector: Option<u16>,
+    devices: K, budget: u64) -> Result<UxnStatus, UxnCrash>
+where K: uxn::device::DeviceList, N: instruction::InstructionFactory,
+{
+    if let Some(gdb) = gdb {
+        return gdb.run(uxn, vector, devices, Some(budget));
+    }
+
+    return match vector {
+        Some(vector) => uxn.run_with_budget(vector, devices, budget),
+        None => uxn.resume_with_budget(devices, budget),
+    };
+}
+
 enum UxnEvent {
     ScreenRefresh,
     ConsoleInputEvent(u8),
 }
 
+// the number of instructions a vector may execute before control is handed back to the
+// window event loop. A vector that exceeds this is resumed on later screen refreshes
+const VECTOR_INSTRUCTION_BUDGET: u64 = 1_000_000;
+
+// the part of the window's event loop that running the machine needs, so that the machine
+// can be run without a window
+trait EventLoop {
+    fn terminate_loop(&mut self);
+}
+
+impl EventLoop for WindowHelper<UxnEvent> {
+    fn terminate_loop(&mut self) {
+        WindowHelper::terminate_loop(self);
+    }
+}
+
 struct MyWindowHandler<J: instruction::InstructionFactory, K: Write, L: Write, M: Write> {
     uxn: uxn::UxnImpl<J>,
     devices: EmuDevices<K, L, M>,
     pending_draw: bool,
 
+    // the number of instructions a vector may execute before it is suspended
+    vector_budget: u64,
+
+    // the vector that most recently exhausted its instruction budget, and whether
+    // a warning has been printed about it
+    runaway_vector: Option<u16>,
+    runaway_vector_warned: bool,
+
+    // input that arrived while a vector was suspended, to be handled (in the order it
+    // arrived) once the vector has completed
+    pending_input: VecDeque<InputEvent>,
+
+    // where the save and load state hotkeys write to and read from
+    state_file: PathBuf,
+
+    // where the input of the session is recorded to, or replayed from
+    recorder: Option<Rc<RefCell<InputRecorder<File>>>>,
+    replay: Option<Rc<RefCell<InputReplay>>>,
+
+    // the debugger controlling the uxn, if one is attached
+    gdb: Option<GdbStub>,
 
     #[cfg(debug_assertions)]
     draw_calls: u64,
@@ -104,9 +229,50 @@ struct MyWindowHandler<J: instruction::InstructionFactory, K: Write, L: Write, M
 
 
 impl<J: instruction::InstructionFactory, K: Write, L: Write, M: Write> MyWindowHandler<J, K, L, M> {
-    fn execute_vector(&mut self, vector: u16, helper: &mut WindowHelper<UxnEvent>) {
-        let res = self.uxn.run(vector, construct_device_list(&mut self.devices));
+    fn new(uxn: uxn::UxnImpl<J>, devices: EmuDevices<K, L, M>, state_file: PathBuf,
+        recorder: Option<Rc<RefCell<InputRecorder<File>>>>, replay: Option<Rc<RefCell<InputReplay>>>,
+        gdb: Option<GdbStub>) -> Self {
+        MyWindowHandler{
+            uxn, devices, pending_draw: false,
+            vector_budget: VECTOR_INSTRUCTION_BUDGET,
+            runaway_vector: None, runaway_vector_warned: false, pending_input: VecDeque::new(),
+            state_file, recorder, replay, gdb,
+
+            #[cfg(debug_assertions)]
+            draw_calls: 0,
+
+            #[cfg(debug_assertions)]
+            draw_time: Default::default(),
+
+            #[cfg(debug_assertions)]
+            construct_device_list_calls: 0,
+
+            #[cfg(debug_assertions)]
+            construct_device_list_time: Default::default(),
+
+            #[cfg(debug_assertions)]
+            execute_vector_calls:0,
 
+            #[cfg(debug_assertions)]
+            execute_vector_time: Default::default(),
+        }
+    }
+
+    fn execute_vector(&mut self, vector: u16, helper: &mut dyn EventLoop) {
+        let res = run_vector(&mut self.uxn, self.gdb.as_mut(), Some(vector),
+            construct_device_list(&mut self.devices), self.vector_budget);
+        self.handle_vector_result(vector, res, helper);
+    }
+
+    fn resume_vector(&mut self, helper: &mut dyn EventLoop) {
+        let vector = self.runaway_vector.unwrap_or_default();
+        let res = run_vector(&mut self.uxn, self.gdb.as_mut(), None,
+            construct_device_list(&mut self.devices), self.vector_budget);
+        self.handle_vector_result(vector, res, helper);
+    }
+
+    fn handle_vector_result(&mut self, vector: u16, res: Result<UxnStatus, UxnCrash>,
+        helper: &mut dyn EventLoop) {
         match res {
             Ok(UxnStatus::Terminate) => {
                 // gracefully close
@@ -114,17 +280,54 @@ impl<J: instruction::InstructionFactory, K: Write, L: Write, M: Write> MyWindowH
             },
             Ok(UxnStatus::Halt) => {
                 // continue rendering the screen
+                self.runaway_vector = None;
+                self.runaway_vector_warned = false;
             },
-            Err(e) => {
-                println!("{}", e);
+            Ok(UxnStatus::BudgetExhausted) => {
+                // hand control back to the event loop, the vector will be resumed
+                // on the next screen refresh
+                self.runaway_vector = Some(vector);
+                if !self.runaway_vector_warned {
+                    println!("warning: vector 0x{:04x} has executed more than {} instructions without completing",
+                        vector, self.vector_budget);
+                    self.runaway_vector_warned = true;
+                }
+            },
+            Err(crash) => {
+                // the rom didn't install a fault handler, so report the crash and stop
+                println!("{}", crash);
                 helper.terminate_loop();
             },
         }
     }
 
+    fn save_state(&self) {
+        let mut save_state = SaveState::new();
+        save_machine_state(&mut save_state, &self.uxn, &self.devices);
+
+        match save_state.write_to_file(&self.state_file) {
+            Ok(()) => println!("saved state to {}", self.state_file.display()),
+            Err(e) => println!("{}", e),
+        }
+    }
+
+    fn load_state(&mut self) {
+        let res = SaveState::read_from_file(&self.state_file)
+            .and_then(|save_state| load_machine_state(&save_state, &mut self.uxn, &mut self.devices));
+
+        match res {
+            Ok(()) => {
+                println!("loaded state from {}", self.state_file.display());
+                self.runaway_vector = None;
+                self.runaway_vector_warned = false;
+            },
+            Err(e) => println!("{}", e),
+        }
+    }
+
     fn on_key_press_change(
         &mut self, 
-        helper: &mut WindowHelper<UxnEvent>,
+        helper: &mut dyn EventLoop,
         virtual_key_code: Option<VirtualKeyCode>,
         down: bool
     ) {
@@ -142,18 +345,218 @@ impl<J: instruction::InstructionFactory, K: Write, L: Write, M: Write> MyWindowH
             return;
         };
 
-        let mut trigger_vector = true;
+        let event = if down {
+            InputEvent::ControllerButtonDown(button)
+        } else {
+            InputEvent::ControllerButtonUp(button)
+        };
+        self.live_input(event, helper);
+    }
+
+    // give `event` to the device it is for, returning the vector that should be run to handle
+    // it (if any)
+    fn apply_input(&mut self, event: InputEvent) -> Option<u16> {
+        match event {
+            InputEvent::Console(c) => {
+                self.devices.console_device.provide_input(c);
+                return Some(self.devices.console_device.read_vector());
+            },
+            InputEvent::MouseMove{x, y} => {
+                self.devices.mouse_device.notify_cursor_position(&[x, y]);
+                return Some(self.devices.mouse_device.read_vector());
+            },
+            InputEvent::MouseButtonDown(button) => {
+                self.devices.mouse_device.notify_button_down(button);
+                return Some(self.devices.mouse_device.read_vector());
+            },
+            InputEvent::MouseButtonUp(button) => {
+                self.devices.mouse_device.notify_button_up(button);
+                return Some(self.devices.mouse_device.read_vector());
+            },
+            InputEvent::MouseScroll{x, y} => {
+                self.devices.mouse_device.notify_scroll(&[x, y]);
+                return Some(self.devices.mouse_device.read_vector());
+            },
+            InputEvent::ControllerButtonDown(button) => {
+                if !self.devices.controller_device.notify_button_down(button) {
+                    return None;
+                }
+                return Some(self.devices.controller_device.read_vector());
+            },
+            InputEvent::ControllerButtonUp(button) => {
+                self.devices.controller_device.notify_button_up(button);
+                return Some(self.devices.controller_device.read_vector());
+            },
+            InputEvent::ControllerKey(key) => {
+                self.devices.controller_device.notify_key_press(key);
+                return Some(self.devices.controller_device.read_vector());
+            },
+            InputEvent::ScreenTick => {
+                // a suspended vector is resumed in place of the screen vector
+                if self.uxn.is_suspended() {
+                    return Some(self.runaway_vector.unwrap_or_default());
+                }
+                return Some(self.devices.screen_device.read_vector());
+            },
+        }
+    }
+
+    // run the vector handling `event`, which has already been given to its device
+    fn run_input_vector(&mut self, event: InputEvent, vector: u16, helper: &mut dyn EventLoop) {
+        match event {
+            InputEvent::ScreenTick => {
+                self.screen_tick(helper);
+                self.handle_pending_input(helper);
+            },
+            _ => self.execute_vector(vector, helper),
+        }
+    }
+
+    // handle input from the user (or a screen refresh). Input other than a screen refresh that
+    // arrives while a vector is suspended is held until the vector has completed, as its vector
+    // can't be run before then and giving it to its device would overwrite the device's state
+    // for any input still waiting
+    fn handle_input(&mut self, event: InputEvent, helper: &mut dyn EventLoop) {
+        if event != InputEvent::ScreenTick && (self.uxn.is_suspended() || !self.pending_input.is_empty()) {
+            self.pending_input.push_back(event);
+            return;
+        }
+        self.handle_input_now(event, helper);
+    }
+
+    // handle the input held while a vector was suspended, until there is none left or a vector
+    // is suspended again
+    fn handle_pending_input(&mut self, helper: &mut dyn EventLoop) {
+        while !self.uxn.is_suspended() {
+            let event = if let Some(event) = self.pending_input.pop_front() {
+                event
+            } else {
+                return;
+            };
+            self.handle_input_now(event, helper);
+        }
+    }
+
+    // give `event` to its device and run its vector, recording it if the session is being
+    // recorded
+    fn handle_input_now(&mut self, event: InputEvent, helper: &mut dyn EventLoop) {
+        let vector = self.apply_input(event);
+
+        if let Some(recorder) = &self.recorder {
+            let res = recorder.borrow_mut().record(
+                &LogEntry::Input{event, vector: vector.unwrap_or(0)});
+            if let Err(e) = res {
+                println!("{}", e);
+            }
+        }
+
+        if let Some(vector) = vector {
+            self.run_input_vector(event, vector, helper);
+        }
+    }
+
+    // input from the user is ignored while a recorded session is being replayed
+    fn live_input(&mut self, event: InputEvent, helper: &mut dyn EventLoop) {
+        if self.replay.is_some() {
+            return;
+        }
+        self.handle_input(event, helper);
+    }
 
-        if down {
-            trigger_vector = self.devices.controller_device.notify_button_down(button);
+    // feed the recorded session to the program up to and including its next screen refresh,
+    // checking that each input is handled by the same vector that handled it when it was
+    // recorded
+    fn replay_until_tick(&mut self, helper: &mut dyn EventLoop) {
+        let replay = if let Some(replay) = self.replay.clone() {
+            replay
         } else {
-            self.devices.controller_device.notify_button_up(button);
+            return;
+        };
+
+        loop {
+            // the replay is only borrowed between vectors, as the date time device reads
+            // from it while they run
+            let next_input = replay.borrow_mut().next_input();
+            let (event, recorded_vector) = match next_input {
+                Ok(Some(input)) => input,
+                Ok(None) => {
+                    println!("finished replaying recorded session");
+                    self.replay = None;
+                    return;
+                },
+                Err(e) => {
+                    println!("{}", e);
+                    helper.terminate_loop();
+                    return;
+                },
+            };
+
+            // as when it was recorded, input is held while a vector is suspended
+            if event != InputEvent::ScreenTick && self.uxn.is_suspended() {
+                self.pending_input.push_back(event);
+                continue;
+            }
+
+            let vector = self.apply_input(event);
+            if let Err(e) = check_vector(&event, recorded_vector, vector.unwrap_or(0)) {
+                println!("{}", e);
+                helper.terminate_loop();
+                return;
+            }
+
+            if let Some(vector) = vector {
+                self.run_input_vector(event, vector, helper);
+            }
+
+            if event == InputEvent::ScreenTick {
+                return;
+            }
+        }
+    }
+
+    // resume the suspended vector if there is one, otherwise run the screen vector
+    fn screen_tick(&mut self, helper: &mut dyn EventLoop) {
+        if self.uxn.is_suspended() {
+            self.resume_vector(helper);
+            return;
+        }
+
+        let screen_vector = self.devices.screen_device.read_vector();
+
+        #[cfg(debug_assertions)]
+        let now = Instant::now();
+
+        let device_list = construct_device_list(&mut self.devices);
+
+        #[cfg(debug_assertions)]
+        {
+            self.construct_device_list_time += now.elapsed();
+            self.construct_device_list_calls += 1;
+            if self.construct_device_list_calls == 100 {
+                println!("construct device list time: {:?}", self.construct_device_list_time);
+                self.construct_device_list_time = Default::default();
+                self.construct_device_list_calls = 0;
+            }
         }
 
-        if trigger_vector {
-            let controller_vector = self.devices.controller_device.read_vector();
-            self.execute_vector(controller_vector, helper);
+        #[cfg(debug_assertions)]
+        let now = Instant::now();
+
+        let res = run_vector(&mut self.uxn, self.gdb.as_mut(), Some(screen_vector),
+            device_list, self.vector_budget);
+
+        #[cfg(debug_assertions)]
+        {
+            self.execute_vector_time += now.elapsed();
+            self.execute_vector_calls += 1;
+            if self.execute_vector_calls == 100 {
+                println!("execute vector time: {:?}", self.execute_vector_time);
+                self.execute_vector_time = Default::default();
+                self.execute_vector_calls = 0;
+            }
         }
+
+        self.handle_vector_result(screen_vector, res, helper);
     }
 }
 
@@ -198,6 +601,9 @@ impl<J: instruction::InstructionFactory, K: Write, L: Write, M: Write>  WindowHa
 
     fn on_start(&mut self, helper: &mut WindowHelper<UxnEvent>, _info: WindowStartupInfo) {
         helper.set_cursor_visible(false);
+
+        // the input given on the command line, if the initial vector has completed
+        self.handle_pending_input(helper);
     }
 
     fn on_user_event(
@@ -208,55 +614,10 @@ impl<J: instruction::InstructionFactory, K: Write, L: Write, M: Write>  WindowHa
         match user_event {
             UxnEvent::ScreenRefresh => {
 
-                if !self.pending_draw {
-                    let screen_vector = self.devices.screen_device.read_vector();
-                    
-                    #[cfg(debug_assertions)]
-                    let now = Instant::now();
-
-                    let device_list = construct_device_list(&mut self.devices);
-
-                    #[cfg(debug_assertions)]
-                    {
-                        self.construct_device_list_time += now.elapsed();
-                        self.construct_device_list_calls += 1;
-                        if self.construct_device_list_calls == 100 {
-                            println!("construct device list time: {:?}", self.construct_device_list_time);
-                            self.construct_device_list_time = Default::default();
-                            self.construct_device_list_calls = 0;
-                        }
-                    }
-
-                    #[cfg(debug_assertions)]
-                    let now = Instant::now();
-
-                    let res = self.uxn.run(screen_vector, device_list);
-
-                    #[cfg(debug_assertions)]
-                    {
-                        self.execute_vector_time += now.elapsed();
-                        self.execute_vector_calls += 1;
-                        if self.execute_vector_calls == 100 {
-                            println!("execute vector time: {:?}", self.execute_vector_time);
-                            self.execute_vector_time = Default::default();
-                            self.execute_vector_calls = 0;
-                        }
-                    }
-
-                    match res {
-                        Ok(UxnStatus::Terminate) => {
-                            // gracefully close
-                            helper.terminate_loop();
-                        },
-                        Ok(UxnStatus::Halt) => {
-                            // continue rendering the screen
-                        },
-                        Err(e) => {
-                            println!("{}", e);
-                            helper.terminate_loop();
-                        },
-                    }
-
+                if !self.pending_draw && self.replay.is_some() {
+                    self.replay_until_tick(helper);
+                } else if !self.pending_draw {
+                    self.handle_input(InputEvent::ScreenTick, helper);
                 }
 
                 if self.devices.screen_device.get_draw_required(&self.uxn) {
@@ -265,9 +626,7 @@ impl<J: instruction::InstructionFactory, K: Write, L: Write, M: Write>  WindowHa
                 }
             },
             UxnEvent::ConsoleInputEvent(c) => {
-                self.devices.console_device.provide_input(c);
-                let console_vector = self.devices.console_device.read_vector();
-                self.execute_vector(console_vector, helper);
+                self.live_input(InputEvent::Console(c), helper);
             },
         }
     }
@@ -279,10 +638,7 @@ impl<J: instruction::InstructionFactory, K: Write, L: Write, M: Write>  WindowHa
     ) {
         let x = position.x as u16;
         let y = position.y as u16;
-        self.devices.mouse_device.notify_cursor_position(&[x, y]);
-
-        let mouse_vector = self.devices.mouse_device.read_vector();
-        self.execute_vector(mouse_vector, helper);
+        self.live_input(InputEvent::MouseMove{x, y}, helper);
     }
 
     fn on_mouse_button_down(
@@ -296,10 +652,7 @@ impl<J: instruction::InstructionFactory, K: Write, L: Write, M: Write>  WindowHa
             return;
         };
 
-        self.devices.mouse_device.notify_button_down(button);
-
-        let mouse_vector = self.devices.mouse_device.read_vector();
-        self.execute_vector(mouse_vector, helper);
+        self.live_input(InputEvent::MouseButtonDown(button), helper);
     }
 
     fn on_mouse_button_up(
@@ -313,10 +666,7 @@ impl<J: instruction::InstructionFactory, K: Write, L: Write, M: Write>  WindowHa
             return;
         };
 
-        self.devices.mouse_device.notify_button_up(button);
-
-        let mouse_vector = self.devices.mouse_device.read_vector();
-        self.execute_vector(mouse_vector, helper);
+        self.live_input(InputEvent::MouseButtonUp(button), helper);
     }
 
     fn on_mouse_wheel_scroll(
@@ -331,10 +681,7 @@ impl<J: instruction::InstructionFactory, K: Write, L: Write, M: Write>  WindowHa
 
         // casting down from f64 to i16 could lead to overflow, but in practise
         // the numbers for mouse scroll distance are small
-        self.devices.mouse_device.notify_scroll(&[x as i16, y as i16]);
-
-        let mouse_vector = self.devices.mouse_device.read_vector();
-        self.execute_vector(mouse_vector, helper);
+        self.live_input(InputEvent::MouseScroll{x: x as i16, y: y as i16}, helper);
     }
 
     fn on_keyboard_char(
@@ -342,12 +689,10 @@ impl<J: instruction::InstructionFactory, K: Write, L: Write, M: Write>  WindowHa
         helper: &mut WindowHelper<UxnEvent>,
         unicode_codepoint: char
     ) {
+        // the controller device only takes ascii key presses
         if unicode_codepoint.is_ascii() {
-            self.devices.controller_device.notify_key_press(unicode_codepoint as u8);
+            self.live_input(InputEvent::ControllerKey(unicode_codepoint as u8), helper);
         }
-
-        let controller_vector = self.devices.controller_device.read_vector();
-        self.execute_vector(controller_vector, helper);
     }
 
     fn on_key_down(
@@ -356,6 +701,24 @@ impl<J: instruction::InstructionFactory, K: Write, L: Write, M: Write>  WindowHa
         virtual_key_code: Option<VirtualKeyCode>,
         _scancode: KeyScancode
     ) {
+        match virtual_key_code {
+            Some(VirtualKeyCode::F5) => {
+                self.save_state();
+                return;
+            },
+            Some(VirtualKeyCode::F9) => {
+                // loading a state part way through a recording or a replay would make the
+                // rest of it meaningless
+                if self.recorder.is_some() || self.replay.is_some() {
+                    println!("can't load state while recording or replaying input");
+                } else {
+                    self.load_state();
+                }
+                return;
+            },
+            _ => {},
+        }
+
         self.on_key_press_change(helper, virtual_key_code, true);
     }
 
@@ -404,16 +767,49 @@ pub fn run<J: Write + 'static>(cli_config: Cli, other_config: Config<J>) -> Resu
             }));
         }
     };
+    // the rom is checked up front, so that a rom too large for the uxn isn't silently
+    // truncated
+    let rom_size = usize::try_from(rom.metadata()?.len()).unwrap_or(usize::MAX);
+    if rom_size > uxn::MAX_ROM_SIZE {
+        return Err(Box::new(RomTooLargeError {
+            fname: cli_config.rom.as_path().display().to_string(),
+            size: rom_size,
+            max_size: uxn::MAX_ROM_SIZE,
+        }));
+    }
+
     let rom = BufReader::new(rom).bytes();
     let rom = rom.map(|b| b.unwrap());
     let instruction_factory_impl = OpObjectFactory{};
 
     let mut uxn = uxn::UxnImpl::new(rom, instruction_factory_impl)?;
+    uxn.set_stack_fault_policy(cli_config.stack_faults);
+    uxn.set_device_access_policy(cli_config.strict_devices);
 
     let console_device = Console::new(io::stdout(), io::stderr());
 
     let file_device = FileDevice::new();
-    let datetime_device = DateTimeDevice::new();
+
+    // the date time device gives values from outside the machine, so when recording its reads
+    // are recorded and when replaying they are taken from the recording
+    let recorder = match &cli_config.record {
+        Some(path) => Some(Rc::new(RefCell::new(InputRecorder::create(path)?))),
+        None => None,
+    };
+    let replay = match &cli_config.replay {
+        Some(path) => Some(Rc::new(RefCell::new(InputReplay::read_from_file(path)?))),
+        None => None,
+    };
+    let datetime_device: Box<dyn Device> = match (&recorder, &replay) {
+        (Some(recorder), _) => {
+            Box::new(RecordingDevice::new(DateTimeDevice::new(), 0xc, recorder.clone()))
+        },
+        (_, Some(replay)) => {
+            Box::new(ReplayingDevice::new(DateTimeDevice::new(), 0xc, replay.clone()))
+        },
+        (None, None) => Box::new(DateTimeDevice::new()),
+    };
+
     let screen_device = ScreenDevice::new(&INITIAL_DIMENSIONS);
     let mouse_device = MouseDevice::new();
     let controller_device = ControllerDevice::new();
@@ -428,26 +824,39 @@ pub fn run<J: Write + 'static>(cli_config: Cli, other_config: Config<J>) -> Resu
         "Title",
         window_creation_options).unwrap();
 
-    let res = uxn.run(uxn::INIT_VECTOR, construct_device_list(&mut emu_devices))?;
+    let state_file = cli_config.state_file.clone().unwrap_or_else(|| {
+        let mut state_file = cli_config.rom.clone().into_os_string();
+        state_file.push(".state");
+        PathBuf::from(state_file)
+    });
+
+    let mut gdb = match cli_config.gdb {
+        Some(port) => Some(GdbStub::listen(port)?),
+        None => None,
+    };
+
+    let mut runaway_vector = None;
+    let res = if let Some(path) = &cli_config.load_state {
+        // the machine was initialised before its state was saved, so the initial vector isn't
+        // run. If a vector was part way through executing the window's event loop will finish it
+        let save_state = SaveState::read_from_file(path)?;
+        load_machine_state(&save_state, &mut uxn, &mut emu_devices)?;
+        UxnStatus::Halt
+    } else {
+        run_vector(&mut uxn, gdb.as_mut(), Some(uxn::INIT_VECTOR),
+            construct_device_list(&mut emu_devices), VECTOR_INSTRUCTION_BUDGET)?
+    };
     match res {
         UxnStatus::Terminate => {
             return Ok(());
         },
         UxnStatus::Halt => {},
-    }
-
-    // for input given on command line
-    for input in cli_config.input {
-        for c in input.bytes().chain("\n".bytes()) {
-            emu_devices.console_device.provide_input(c);
-            let console_vector = emu_devices.console_device.read_vector();
-            let res = uxn.run(console_vector, construct_device_list(&mut emu_devices))?;
-
-            match res {
-                UxnStatus::Terminate => { return Ok(()); },
-                UxnStatus::Halt => {},
-            }
-        }
+        UxnStatus::BudgetExhausted => {
+            // let the window's event loop finish running the initial vector
+            println!("warning: vector 0x{:04x} has executed more than {} instructions without completing",
+                uxn::INIT_VECTOR, VECTOR_INSTRUCTION_BUDGET);
+            runaway_vector = Some(uxn::INIT_VECTOR);
+        },
     }
 
     let window_refresh_event_sender = window.create_user_event_sender();
@@ -470,25 +879,109 @@ pub fn run<J: Write + 'static>(cli_config: Cli, other_config: Config<J>) -> Resu
         }
     });
 
-    window.run_loop(MyWindowHandler{
-        uxn, devices: emu_devices, pending_draw: false,
- 
-        #[cfg(debug_assertions)]
-        draw_calls: 0,
+    let mut handler = MyWindowHandler::new(uxn, emu_devices, state_file, recorder, replay, gdb);
+    handler.runaway_vector = runaway_vector;
+    handler.runaway_vector_warned = runaway_vector.is_some();
+
+    // input given on the command line is handled once the initial vector has completed (when
+    // the window's event loop starts, or once the event loop has finished running the initial
+    // vector). When replaying, this input is part of the recording
+    if handler.replay.is_none() {
+        for input in cli_config.input {
+            for c in input.bytes().chain("\n".bytes()) {
+                handler.pending_input.push_back(InputEvent::Console(c));
+            }
+        }
+    }
 
-        #[cfg(debug_assertions)]
-        draw_time: Default::default(),
+    window.run_loop(handler);
+}
 
-        #[cfg(debug_assertions)]
-        construct_device_list_calls: 0,
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::uxninterface::Uxn;
 
-        #[cfg(debug_assertions)]
-        construct_device_list_time: Default::default(),
+    struct MockEventLoop {
+        terminated: bool,
+    }
 
-        #[cfg(debug_assertions)]
-        execute_vector_calls:0,
+    impl EventLoop for MockEventLoop {
+        fn terminate_loop(&mut self) {
+            self.terminated = true;
+        }
+    }
 
-        #[cfg(debug_assertions)]
-        execute_vector_time: Default::default(),
-    });
+    fn test_handler(rom: Vec<u8>) -> MyWindowHandler<OpObjectFactory, Vec<u8>, Vec<u8>, Vec<u8>> {
+        let uxn = uxn::UxnImpl::new(rom.into_iter(), OpObjectFactory{}).unwrap();
+        let devices = EmuDevices{
+            console_device: Console::new(Vec::new(), Vec::new()),
+            file_device: FileDevice::new(),
+            datetime_device: Box::new(DateTimeDevice::new()),
+            debug_writer: Vec::new(),
+            screen_device: ScreenDevice::new(&INITIAL_DIMENSIONS),
+            mouse_device: MouseDevice::new(),
+            controller_device: ControllerDevice::new(),
+        };
+        return MyWindowHandler::new(uxn, devices, PathBuf::new(), None, None, None);
+    }
+
+    #[test]
+    fn test_input_while_vector_suspended() {
+        // |0100
+        // ;on-console .Console/vector DEO2
+        // #10 &loop #01 SUB DUP ,&loop JCN POP
+        // BRK
+        //
+        // ( append each byte of console input to the buffer at 0001, with the number of
+        //   bytes in it at 0000 )
+        // @on-console
+        // .Console/read DEI .len LDZ INC STZ
+        // .len LDZ INC .len STZ
+        // BRK
+        let rom = vec![
+            0xa0, 0x01, 0x11, 0x80, 0x10, 0x37,
+            0x80, 0x10, 0x80, 0x01, 0x19, 0x06, 0x80, 0xf9, 0x0d, 0x02,
+            0x00,
+            0x80, 0x12, 0x16, 0x80, 0x00, 0x10, 0x01, 0x11,
+            0x80, 0x00, 0x10, 0x01, 0x80, 0x00, 0x11,
+            0x00,
+        ];
+        let mut handler = test_handler(rom);
+        handler.vector_budget = 20;
+        let mut event_loop = MockEventLoop{terminated: false};
+
+        // the initial vector's loop takes more than the budget, so it is suspended
+        handler.execute_vector(uxn::INIT_VECTOR, &mut event_loop);
+        assert!(handler.uxn.is_suspended());
+        assert_eq!(handler.runaway_vector, Some(uxn::INIT_VECTOR));
+
+        // input arriving whilst it is suspended is held rather than lost
+        handler.handle_input(InputEvent::Console(b'a'), &mut event_loop);
+        handler.handle_input(InputEvent::Console(b'b'), &mut event_loop);
+        assert_eq!(handler.pending_input.len(), 2);
+        assert_eq!(handler.uxn.read_from_ram(0x0), 0);
+
+        // screen refreshes resume the initial vector, and once it completes the held input
+        // is handled in the order it arrived
+        let mut ticks = 0;
+        while handler.uxn.is_suspended() {
+            handler.handle_input(InputEvent::ScreenTick, &mut event_loop);
+            ticks += 1;
+            assert!(ticks < 10);
+        }
+
+        assert!(handler.pending_input.is_empty());
+        assert_eq!(handler.runaway_vector, None);
+        assert_eq!(handler.uxn.read_from_ram(0x0), 2);
+        assert_eq!(handler.uxn.read_from_ram(0x1), b'a');
+        assert_eq!(handler.uxn.read_from_ram(0x2), b'b');
+        assert!(!event_loop.terminated);
+
+        // with no vector suspended, input is handled straight away
+        handler.handle_input(InputEvent::Console(b'c'), &mut event_loop);
+        assert!(handler.pending_input.is_empty());
+        assert_eq!(handler.uxn.read_from_ram(0x0), 3);
+        assert_eq!(handler.uxn.read_from_ram(0x3), b'c');
+    }
 }
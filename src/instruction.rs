@@ -1,8 +1,12 @@
 use crate::uxninterface::UxnWithDevices;
 use crate::uxninterface::UxnError;
 
+// produces the instruction corresponding to a byte of program memory. The instruction is returned
+// by value so that decoding doesn't require any heap allocation
 pub trait InstructionFactory {
-    fn from_byte(&self, byte: u8) -> Box<dyn Instruction>;
+    type Instr: Instruction;
+
+    fn from_byte(&self, byte: u8) -> Self::Instr;
 }
 
 pub trait Instruction {
@@ -3,6 +3,8 @@
 pub mod uxninterface;
 pub mod instruction;
 pub mod ops;
+pub mod symbols;
+pub mod sourcemap;
 
 #[cfg(feature = "asm")]
 pub mod uxnasmlib;
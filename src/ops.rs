@@ -1,4 +1,5 @@
 use std::str::FromStr;
+use std::fmt;
 
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum OpCode {
@@ -34,6 +35,9 @@ pub enum OpCode {
     Ora,
     Eor,
     Sft,
+    Jci,
+    Jmi,
+    Jsi,
 }
 
 use crate::uxninterface::UxnWithDevices;
@@ -81,20 +85,30 @@ const OP_LIST: &'static [OpDescription] = &[
     OpDescription{op_code: OpCode::Ora, byte: 0x1d, token: "ORA", handler: op_handlers::ora_handler},
     OpDescription{op_code: OpCode::Eor, byte: 0x1e, token: "EOR", handler: op_handlers::eor_handler},
     OpDescription{op_code: OpCode::Sft, byte: 0x1f, token: "SFT", handler: op_handlers::sft_handler},
+    // the immediate jumps, which take the place of BRK with the return and/or short mode flags
+    // set, and so have no modes of their own
+    OpDescription{op_code: OpCode::Jci, byte: 0x20, token: "JCI", handler: op_handlers::jci_handler},
+    OpDescription{op_code: OpCode::Jmi, byte: 0x40, token: "JMI", handler: op_handlers::jmi_handler},
+    OpDescription{op_code: OpCode::Jsi, byte: 0x60, token: "JSI", handler: op_handlers::jsi_handler},
 ];
 
+// the index in OP_LIST of the first of the immediate jumps
+const IMMEDIATE_JUMPS_INDEX: usize = 0x20;
+
 use crate::instruction::Instruction;
 use crate::instruction::InstructionFactory;
 
 pub struct OpObjectFactory {}
 
 impl InstructionFactory for OpObjectFactory {
-    fn from_byte(&self, byte: u8) -> Box<dyn Instruction> {
-        return Box::new(OpObject::from_byte(byte));
+    type Instr = OpObject;
+
+    fn from_byte(&self, byte: u8) -> OpObject {
+        return OpObject::from_byte(byte);
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub struct OpObject {
     keep: bool,
     ret: bool,
@@ -126,26 +140,71 @@ impl OpObject {
     }
 
     pub fn from_byte(byte: u8) -> Self {
-        let keep: bool = if byte & 0b10000000 > 0 { true } else { false };
-        let ret: bool = if byte & 0b01000000 > 0 { true } else { false };
-        let short: bool = if byte & 0b00100000 > 0 { true } else { false };
+        return OP_TABLE[usize::from(byte)];
+    }
+}
+
+// the mnemonic for the instruction, as it would be written in uxntal (e.g. `ADD2k`)
+impl fmt::Display for OpObject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // a BRK with the keep flag set is a literal, for which the keep flag is implied
+        if self.op_code == OpCode::Brk && self.keep {
+            write!(f, "LIT")?;
+        } else {
+            write!(f, "{}", OP_LIST[self.handler_index].token)?;
+        }
+
+        if self.short {
+            write!(f, "2")?;
+        }
+        if self.keep && self.op_code != OpCode::Brk {
+            write!(f, "k")?;
+        }
+        if self.ret {
+            write!(f, "r")?;
+        }
 
-        let byte = byte & 0x1f;
+        return Ok(());
+    }
+}
 
-        let index = OP_LIST.iter().position(
-            |e| e.byte == byte)
-            .expect("No matching OP_LIST entry for byte");
+// every possible instruction byte, decoded ahead of time into its operation and mode flags, so
+// that decoding an instruction whilst running a program is a single table lookup
+static OP_TABLE: [OpObject; 256] = build_op_table();
+
+const fn build_op_table() -> [OpObject; 256] {
+    let mut table = [plain_op_object(OpCode::Brk, 0); 256];
+
+    let mut byte = 0;
+    while byte < table.len() {
+        // OP_LIST is ordered by byte, so the lower five bits of the instruction
+        // byte are the index of its entry
+        let handler_index = byte & 0x1f;
+
+        table[byte] = OpObject {
+            keep: byte & 0b10000000 > 0,
+            ret: byte & 0b01000000 > 0,
+            short: byte & 0b00100000 > 0,
+            op_code: OP_LIST[handler_index].op_code,
+            handler_index,
+        };
 
-        let op_code = OP_LIST[index].op_code;
+        byte += 1;
+    }
 
-        return OpObject {keep, ret, short, op_code, handler_index: index}
+    let mut index = IMMEDIATE_JUMPS_INDEX;
+    while index < OP_LIST.len() {
+        table[OP_LIST[index].byte as usize] = plain_op_object(OP_LIST[index].op_code, index);
+        index += 1;
     }
+
+    return table;
 }
 
 #[derive(Debug, PartialEq)]
 pub struct ParseOpObjectError {}
 
-fn plain_op_object(op_code: OpCode, handler_index: usize) -> OpObject {
+const fn plain_op_object(op_code: OpCode, handler_index: usize) -> OpObject {
     OpObject{
         keep: false,
         ret: false,
@@ -207,7 +266,13 @@ impl FromStr for OpObject {
             },
         };
 
+        let is_immediate_jump = matches!(ret.op_code, OpCode::Jci | OpCode::Jmi | OpCode::Jsi);
+
         for mode_flag in s.chars().skip(3) {
+            if is_immediate_jump {
+                return Err(ParseOpObjectError {});
+            }
+
             match mode_flag {
                 '2' => {
                     ret.short = true;
@@ -270,7 +335,10 @@ mod tests {
             (OpCode::And, vec![0x1c]),
             (OpCode::Ora, vec![0x1d]),
             (OpCode::Eor, vec![0x1e]),
-            (OpCode::Sft, vec![0x1f]),];
+            (OpCode::Sft, vec![0x1f]),
+            (OpCode::Jci, vec![0x20]),
+            (OpCode::Jmi, vec![0x40]),
+            (OpCode::Jsi, vec![0x60]),];
 
         for (input, expected_output) in inputs.into_iter() {
             let input = OpObject {
@@ -352,6 +420,9 @@ mod tests {
             ("ORA", (OpCode::Ora, 29)),
             ("EOR", (OpCode::Eor, 30)),
             ("SFT", (OpCode::Sft, 31)),
+            ("JCI", (OpCode::Jci, 32)),
+            ("JMI", (OpCode::Jmi, 33)),
+            ("JSI", (OpCode::Jsi, 34)),
         ];
 
         for (input, expected_output) in inputs {
@@ -425,7 +496,7 @@ mod tests {
 
     #[test]
     fn test_from_str_forbidden_mode_flags() {
-        let inputs = ["BRKr", "BRKk", "BRK2", "LITk"];
+        let inputs = ["BRKr", "BRKk", "BRK2", "LITk", "JCI2", "JMIk", "JSIr"];
 
         for input in inputs {
             let output = input.parse::<OpObject>();
@@ -433,6 +504,33 @@ mod tests {
         }
     }
 
+    // test `from_byte` function; verify that every entry of the precomputed
+    // table agrees with OP_LIST and with the mode flags encoded in the byte
+    #[test]
+    fn test_from_byte_table() {
+        for byte in 0..=0xffu8 {
+            let op = OpObject::from_byte(byte);
+            let description = &OP_LIST[op.handler_index];
+
+            if let 0x20 | 0x40 | 0x60 = byte {
+                assert_eq!(description.byte, byte);
+                assert_eq!(description.op_code, op.op_code);
+                assert!(!op.keep && !op.ret && !op.short);
+                assert_eq!(op.get_bytes(), vec![byte]);
+                continue;
+            }
+
+            assert_eq!(description.byte, byte & 0x1f);
+            assert_eq!(description.op_code, op.op_code);
+            assert_eq!(op.keep, byte & 0x80 > 0);
+            assert_eq!(op.ret, byte & 0x40 > 0);
+            assert_eq!(op.short, byte & 0x20 > 0);
+
+            // the byte should survive a round trip through `get_bytes`
+            assert_eq!(op.get_bytes(), vec![byte]);
+        }
+    }
+
     #[test]
     fn test_from_str_unrecognised_op_string() {
         let inputs = ["BRKK", "BOK", "BK"];
@@ -442,5 +540,22 @@ mod tests {
             assert_eq!(output, Err(ParseOpObjectError {}));
         }
     }
-}
 
+    // test that the mnemonic of every instruction can be parsed back into the same instruction
+    #[test]
+    fn test_display() {
+        assert_eq!(OpObject::from_byte(0x00).to_string(), "BRK");
+        assert_eq!(OpObject::from_byte(0x80).to_string(), "LIT");
+        assert_eq!(OpObject::from_byte(0xe0).to_string(), "LIT2r");
+        assert_eq!(OpObject::from_byte(0x22).to_string(), "POP2");
+        assert_eq!(OpObject::from_byte(0xf8).to_string(), "ADD2kr");
+        assert_eq!(OpObject::from_byte(0x20).to_string(), "JCI");
+        assert_eq!(OpObject::from_byte(0x40).to_string(), "JMI");
+        assert_eq!(OpObject::from_byte(0x60).to_string(), "JSI");
+
+        for byte in 0..=0xff {
+            let op_object = OpObject::from_byte(byte);
+            assert_eq!(op_object.to_string().parse::<OpObject>(), Ok(op_object));
+        }
+    }
+}
@@ -1,13 +1,17 @@
 use crate::uxninterface::UxnWithDevices;
 use crate::uxninterface::UxnError;
 
+// the most bytes any operation pops from a stack (ROT2 pops three shorts)
+const MAX_POPPED_VALUES: usize = 6;
+
 struct UxnWrapper<'a> {
     uxn: &'a mut dyn UxnWithDevices,
     push_fn: fn(&mut (dyn UxnWithDevices + 'a), u8) -> Result<(), UxnError>,
     push_ret_fn: fn(&mut (dyn UxnWithDevices + 'a), u8) -> Result<(), UxnError>,
     pop_fn: fn(&mut (dyn UxnWithDevices + 'a)) -> Result<u8, UxnError>,
     keep: bool,
-    popped_values: Vec<u8>,
+    popped_values: [u8; MAX_POPPED_VALUES],
+    num_popped_values: usize,
 }
 
 impl<'a> UxnWrapper<'a> {
@@ -36,8 +40,18 @@ impl<'a> UxnWrapper<'a> {
             push_ret_fn,
             pop_fn,
             keep,
-            popped_values: Vec::new(),
+            popped_values: [0; MAX_POPPED_VALUES],
+            num_popped_values: 0,
+        }
+    }
+
+    fn pop_popped_value(&mut self) -> Option<u8> {
+        if self.num_popped_values == 0 {
+            return None;
         }
+
+        self.num_popped_values -= 1;
+        return Some(self.popped_values[self.num_popped_values]);
     }
 
     fn read_next_byte_from_ram(&mut self) -> Result<u8, UxnError> {
@@ -54,7 +68,7 @@ impl<'a> UxnWrapper<'a> {
         // Push these back on the stack to restore it to its
         // state prior to any pops before pushing the desired
         // value
-        while let Some(val) = self.popped_values.pop() {
+        while let Some(val) = self.pop_popped_value() {
             (self.push_fn)(self.uxn, val).expect("Couldn't push");
         }
 
@@ -69,7 +83,8 @@ impl<'a> UxnWrapper<'a> {
         let popped = (self.pop_fn)(self.uxn)?;
 
         if self.keep {
-            self.popped_values.push(popped);
+            self.popped_values[self.num_popped_values] = popped;
+            self.num_popped_values += 1;
         }
 
         return Ok(popped);
@@ -91,7 +106,7 @@ impl<'a> UxnWrapper<'a> {
         Ok(())
     }
 
-    fn write_to_device(&mut self, device_address: u8, val: u8) {
+    fn write_to_device(&mut self, device_address: u8, val: u8) -> Result<(), UxnError> {
         self.uxn.write_to_device(device_address, val)
     }
 }
@@ -101,7 +116,7 @@ impl<'a> Drop for UxnWrapper<'a> {
         // if in keep mode, popped values will be populated with
         // what has been popped in the course of this operation.
         // Push these back onto the stack to ensure they are kept
-        while let Some(val) = self.popped_values.pop() {
+        while let Some(val) = self.pop_popped_value() {
             (self.push_fn)(self.uxn, val).expect("Couldn't push");
         }
     }
@@ -128,6 +143,9 @@ pub use control_flow::jmp_handler;
 pub use control_flow::jcn_handler;
 pub use control_flow::jsr_handler;
 pub use control_flow::sth_handler;
+pub use control_flow::jci_handler;
+pub use control_flow::jmi_handler;
+pub use control_flow::jsi_handler;
 
 mod memory;
 pub use memory::ldz_handler;
@@ -335,10 +353,12 @@ mod tests {
                 .unwrap();
         }
 
-        fn write_to_device(&mut self, device_address: u8, val: u8) {
+        fn write_to_device(&mut self, device_address: u8, val: u8) -> Result<(), UxnError> {
             self.write_to_device_arguments_received
                 .borrow_mut()
                 .push_back((device_address, val));
+
+            return Ok(());
         }
     }
 
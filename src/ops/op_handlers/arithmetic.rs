@@ -88,11 +88,19 @@ pub fn div_handler(
         let a = wrapper.pop_short()?;
         let b = wrapper.pop_short()?;
 
+        if a == 0 {
+            return Err(UxnError::DivisionByZero);
+        }
+
         wrapper.push_short(b/a)?;
     } else {
         let a = wrapper.pop()?;
         let b = wrapper.pop()?;
 
+        if a == 0 {
+            return Err(UxnError::DivisionByZero);
+        }
+
         wrapper.push(b/a)?;
     }
 
@@ -332,4 +340,23 @@ mod tests {
             ])
         );
     }
+
+    #[test]
+    fn test_div_handler_division_by_zero() {
+        let mut mock_uxn = MockUxn::new();
+
+        mock_uxn.pop_from_working_stack_values_to_return = RefCell::new(VecDeque::from([
+            Ok(0x00),
+            Ok(0x08),
+        ]));
+
+        assert_eq!(div_handler(&mut mock_uxn, false, false, false),
+            Err(UxnError::DivisionByZero));
+        assert_eq!(
+            mock_uxn
+                .push_to_working_stack_arguments_received
+                .into_inner(),
+            VecDeque::from([])
+        );
+    }
 }
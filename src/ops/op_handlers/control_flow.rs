@@ -119,6 +119,81 @@ pub fn sth_handler(
     return Ok(());
 }
 
+// reads the short following an immediate jump instruction, which is the distance to jump from
+// the address after it, leaving the program counter at that address
+fn read_immediate_offset(wrapper: &mut UxnWrapper) -> Result<u16, UxnError> {
+    let b2 = wrapper.read_next_byte_from_ram()?;
+    let b1 = wrapper.read_next_byte_from_ram()?;
+
+    return Ok(u16::from_be_bytes([b2, b1]));
+}
+
+// moves the program counter by `offset`, wrapping around the end of memory, returning the value
+// the program counter had before
+fn do_immediate_jump(wrapper: &mut UxnWrapper, offset: u16) -> Result<u16, UxnError> {
+    let current_pc = wrapper.uxn.get_program_counter()?;
+
+    wrapper.uxn.set_program_counter(current_pc.wrapping_add(offset));
+
+    return Ok(current_pc);
+}
+
+// the immediate jumps have no modes, the bits that would hold their mode flags being part of
+// their opcodes, so the flags they are called with are ignored
+
+// jump conditional immediate handler: if the byte on the top of the working stack is not 00, moves the program counter by the short following the instruction
+pub fn jci_handler(
+    u: &mut dyn UxnWithDevices,
+    _keep: bool,
+    _short: bool,
+    _ret: bool,
+) -> Result<(), UxnError> {
+    let mut wrapper = UxnWrapper::new(u, false, false);
+
+    let b = wrapper.pop()?;
+    let offset = read_immediate_offset(&mut wrapper)?;
+
+    if b != 0 {
+        do_immediate_jump(&mut wrapper, offset)?;
+    }
+
+    return Ok(());
+}
+
+// jump immediate handler: moves the program counter by the short following the instruction
+pub fn jmi_handler(
+    u: &mut dyn UxnWithDevices,
+    _keep: bool,
+    _short: bool,
+    _ret: bool,
+) -> Result<(), UxnError> {
+    let mut wrapper = UxnWrapper::new(u, false, false);
+
+    let offset = read_immediate_offset(&mut wrapper)?;
+    do_immediate_jump(&mut wrapper, offset)?;
+
+    return Ok(());
+}
+
+// jump stash return immediate handler: pushes the address following the instruction and its operand to the return-stack and moves the program counter by the short following the instruction
+pub fn jsi_handler(
+    u: &mut dyn UxnWithDevices,
+    _keep: bool,
+    _short: bool,
+    _ret: bool,
+) -> Result<(), UxnError> {
+    let mut wrapper = UxnWrapper::new(u, false, false);
+
+    let offset = read_immediate_offset(&mut wrapper)?;
+    let pc = do_immediate_jump(&mut wrapper, offset)?;
+    let [b2, b1] = pc.to_be_bytes();
+
+    wrapper.push_to_return_stack(b2)?;
+    wrapper.push_to_return_stack(b1)?;
+
+    return Ok(());
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -378,4 +453,95 @@ mod tests {
              (0xab,),(0xcd,),])
         );
     }
+
+    #[test]
+    fn test_jci_handler() {
+        let mut mock_uxn = MockUxn::new();
+
+        let jmp_val = -0x1234i16;
+        let [jmp_b2, jmp_b1] = jmp_val.to_be_bytes();
+
+        mock_uxn.pop_from_working_stack_values_to_return = RefCell::new(VecDeque::from([Ok(0x01)]));
+        mock_uxn.read_next_byte_from_ram_values_to_return = RefCell::new(VecDeque::from([Ok(jmp_b2), Ok(jmp_b1)]));
+        mock_uxn.get_program_counter_values_to_return = RefCell::new(VecDeque::from([Ok(0x5678)]));
+
+        jci_handler(&mut mock_uxn, false, false, false).unwrap();
+
+        assert_eq!(
+            mock_uxn
+                .set_program_counter_arguments_received
+                .into_inner(),
+            VecDeque::from([(0x4444,),]) // program counter is mocked to be 0x5678 once the offset
+                                         // has been read, offset is -0x1234, so program counter
+                                         // should be set to 0x5678-0x1234=0x4444
+        );
+    }
+
+    #[test]
+    fn test_jci_handler_condition_false() {
+        let mut mock_uxn = MockUxn::new();
+
+        mock_uxn.pop_from_working_stack_values_to_return = RefCell::new(VecDeque::from([Ok(0x00)]));
+        mock_uxn.read_next_byte_from_ram_values_to_return = RefCell::new(VecDeque::from([Ok(0x12), Ok(0x34)]));
+
+        jci_handler(&mut mock_uxn, false, false, false).unwrap();
+
+        // the offset should still have been read, so that the program counter is left after it
+        assert_eq!(
+            mock_uxn
+                .read_next_byte_from_ram_arguments_received
+                .into_inner(),
+            VecDeque::from([(), ()])
+        );
+        assert_eq!(
+            mock_uxn
+                .set_program_counter_arguments_received
+                .into_inner(),
+            VecDeque::from([]) // since condition was 0x00 no program counter should be set
+        );
+    }
+
+    #[test]
+    fn test_jmi_handler() {
+        let mut mock_uxn = MockUxn::new();
+
+        mock_uxn.read_next_byte_from_ram_values_to_return = RefCell::new(VecDeque::from([Ok(0x01), Ok(0x02)]));
+        mock_uxn.get_program_counter_values_to_return = RefCell::new(VecDeque::from([Ok(0xff00)]));
+
+        jmi_handler(&mut mock_uxn, false, false, false).unwrap();
+
+        assert_eq!(
+            mock_uxn
+                .set_program_counter_arguments_received
+                .into_inner(),
+            VecDeque::from([(0x0002,),]) // the jump wraps around the end of memory
+        );
+    }
+
+    #[test]
+    fn test_jsi_handler() {
+        let mut mock_uxn = MockUxn::new();
+
+        mock_uxn.read_next_byte_from_ram_values_to_return = RefCell::new(VecDeque::from([Ok(0x00), Ok(0x20)]));
+        mock_uxn.get_program_counter_values_to_return = RefCell::new(VecDeque::from([Ok(0x0103)]));
+        mock_uxn.push_to_return_stack_values_to_return = RefCell::new(VecDeque::from([Ok(()), Ok(()),]));
+
+        jsi_handler(&mut mock_uxn, false, false, false).unwrap();
+
+        assert_eq!(
+            mock_uxn
+                .set_program_counter_arguments_received
+                .into_inner(),
+            VecDeque::from([(0x0123,),])
+        );
+
+        // the address after the operand should have been pushed to the return stack, to be
+        // returned to
+        assert_eq!(
+            mock_uxn
+                .push_to_return_stack_arguments_received
+                .into_inner(),
+            VecDeque::from([(0x01,), (0x03,)])
+        );
+    }
 }
@@ -241,7 +241,7 @@ pub fn deo_handler(
         let value_lo = wrapper.pop()?;
         let value_hi = wrapper.pop()?;
 
-        wrapper.write_to_device(device_address, value_hi);
+        wrapper.write_to_device(device_address, value_hi)?;
 
         let device_address = if let Some(device_address) = device_address.checked_add(1) {
             device_address
@@ -249,13 +249,13 @@ pub fn deo_handler(
             return Err(UxnError::UnrecognisedDevice);
         };
 
-        wrapper.write_to_device(device_address, value_lo);
+        wrapper.write_to_device(device_address, value_lo)?;
     } else {
         // pop byte from working/return stack
         let value = wrapper.pop()?;
 
         // write byte to device responsible for device address
-        wrapper.write_to_device(device_address, value);
+        wrapper.write_to_device(device_address, value)?;
     }
 
     return Ok(());
@@ -0,0 +1,248 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+#[derive(Debug, PartialEq)]
+pub enum SourceMapError {
+    Io(String),
+    Malformed{line: usize},
+}
+
+impl fmt::Display for SourceMapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SourceMapError::Io(msg) => write!(f, "Error accessing source map: {}", msg),
+            SourceMapError::Malformed{line} => write!(f, "Source map is malformed at line {}", line),
+        }
+    }
+}
+
+impl Error for SourceMapError {}
+
+// a place in a source file, with lines and columns counted from 1
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SourceLocation {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+impl FromStr for SourceLocation {
+    type Err = ();
+
+    // the file name comes first and may itself contain colons, so the line and column are
+    // taken from the end
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.rsplitn(3, ':');
+        let column = parts.next().ok_or(())?.parse::<usize>().map_err(|_| ())?;
+        let line = parts.next().ok_or(())?.parse::<usize>().map_err(|_| ())?;
+        let file = parts.next().ok_or(())?.to_owned();
+        return Ok(SourceLocation{file, line, column});
+    }
+}
+
+// an invocation of a macro that a token was produced by
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct MacroExpansion {
+    pub macro_name: String,
+    pub invoked_at: SourceLocation,
+}
+
+// where a token came from. A token produced by expanding a macro is located in the macro's
+// definition, and `expansions` gives the invocations it came through, innermost first
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SourcePos {
+    pub location: SourceLocation,
+    pub expansions: Vec<MacroExpansion>,
+}
+
+impl SourcePos {
+    pub fn new(location: SourceLocation) -> Self {
+        SourcePos{location, expansions: Vec::new()}
+    }
+
+    // the position of a token from the body of `macro_name`, when the macro is invoked at
+    // `invoked_at`
+    pub fn expanded(&self, macro_name: &str, invoked_at: &SourcePos) -> Self {
+        let mut expansions = vec!(MacroExpansion{macro_name: macro_name.to_owned(),
+            invoked_at: invoked_at.location.clone()});
+        expansions.extend(invoked_at.expansions.iter().cloned());
+        return SourcePos{location: self.location.clone(), expansions};
+    }
+}
+
+impl fmt::Display for SourcePos {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.location)?;
+        for expansion in self.expansions.iter() {
+            write!(f, " (in macro '{}' invoked at {})", expansion.macro_name, expansion.invoked_at)?;
+        }
+        return Ok(());
+    }
+}
+
+// the bytes written to the rom for a single token
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SourceMapEntry {
+    pub address: u16,
+    pub length: u16,
+    pub pos: SourcePos,
+}
+
+// where in the source each byte of a rom came from. A source map file has a line for each token
+// that wrote bytes, made up of the address of the first byte (in hexadecimal), the number of
+// bytes, and the location of the token, followed for a token produced by a macro by ` < `, the
+// macro's name and the location it was invoked at, for each invocation (innermost first). For
+// example:
+//
+// 0100 3 main.tal:4:1
+// 0103 1 main.tal:1:14 < EMIT main.tal:5:3
+pub struct SourceMap {
+    // in the order the bytes were written
+    entries: Vec<SourceMapEntry>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        SourceMap{entries: Vec::new()}
+    }
+
+    pub fn push(&mut self, address: u16, length: u16, pos: SourcePos) {
+        self.entries.push(SourceMapEntry{address, length, pos});
+    }
+
+    pub fn entries(&self) -> &[SourceMapEntry] {
+        return &self.entries;
+    }
+
+    // where the byte at `address` came from. If it was written more than once, this is the
+    // token that wrote it last
+    pub fn position_of(&self, address: u16) -> Option<&SourcePos> {
+        return self.entries.iter().rev()
+            .find(|entry| {
+                return address >= entry.address
+                    && u32::from(address) < u32::from(entry.address) + u32::from(entry.length);
+            })
+            .map(|entry| { return &entry.pos; });
+    }
+
+    pub fn read_from_file(path: &Path) -> Result<Self, SourceMapError> {
+        let data = fs::read_to_string(path)
+            .map_err(|err| SourceMapError::Io(format!("{}: {}", path.display(), err)))?;
+        return data.parse::<SourceMap>();
+    }
+
+    pub fn write_to_file(&self, path: &Path) -> Result<(), SourceMapError> {
+        return fs::write(path, self.to_string())
+            .map_err(|err| SourceMapError::Io(format!("{}: {}", path.display(), err)));
+    }
+}
+
+impl Default for SourceMap {
+    fn default() -> Self {
+        return SourceMap::new();
+    }
+}
+
+impl fmt::Display for SourceMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in self.entries.iter() {
+            write!(f, "{:04x} {} {}", entry.address, entry.length, entry.pos.location)?;
+            for expansion in entry.pos.expansions.iter() {
+                write!(f, " < {} {}", expansion.macro_name, expansion.invoked_at)?;
+            }
+            writeln!(f)?;
+        }
+        return Ok(());
+    }
+}
+
+fn parse_entry(line: &str) -> Option<SourceMapEntry> {
+    let mut fields = line.splitn(3, ' ');
+    let address = u16::from_str_radix(fields.next()?, 16).ok()?;
+    let length = fields.next()?.parse::<u16>().ok()?;
+
+    let mut positions = fields.next()?.split(" < ");
+    let location = positions.next()?.parse::<SourceLocation>().ok()?;
+    let mut expansions = Vec::new();
+    for expansion in positions {
+        let (macro_name, invoked_at) = expansion.split_once(' ')?;
+        expansions.push(MacroExpansion{macro_name: macro_name.to_owned(),
+            invoked_at: invoked_at.parse::<SourceLocation>().ok()?});
+    }
+
+    return Some(SourceMapEntry{address, length, pos: SourcePos{location, expansions}});
+}
+
+impl FromStr for SourceMap {
+    type Err = SourceMapError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let entries = s.lines().enumerate()
+            .map(|(i, line)| { return parse_entry(line).ok_or(SourceMapError::Malformed{line: i + 1}); })
+            .collect::<Result<Vec<_>, _>>()?;
+        return Ok(SourceMap{entries});
+    }
+}
+
+// the source map for the rom at `rom_path`, which is named after the rom with `.map` appended
+pub fn source_map_path_for_rom(rom_path: &Path) -> PathBuf {
+    let mut path = rom_path.to_path_buf().into_os_string();
+    path.push(".map");
+    return PathBuf::from(path);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn location(file: &str, line: usize, column: usize) -> SourceLocation {
+        return SourceLocation{file: file.to_owned(), line, column};
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let invocation = SourcePos::new(location("main.tal", 5, 3));
+        let nested_invocation = SourcePos::new(location("main.tal", 2, 7)).expanded("OUTER", &invocation);
+
+        let mut source_map = SourceMap::new();
+        source_map.push(0x100, 3, SourcePos::new(location("main.tal", 4, 1)));
+        source_map.push(0x103, 1, SourcePos::new(location("lib:v2.tal", 1, 14)).expanded("EMIT", &nested_invocation));
+
+        let text = source_map.to_string();
+        assert_eq!(text, "0100 3 main.tal:4:1\n0103 1 lib:v2.tal:1:14 < EMIT main.tal:2:7 < OUTER main.tal:5:3\n");
+
+        let read_back = text.parse::<SourceMap>().unwrap();
+        assert_eq!(read_back.entries(), source_map.entries());
+        assert_eq!(read_back.entries()[1].pos.to_string(),
+            "lib:v2.tal:1:14 (in macro 'EMIT' invoked at main.tal:2:7) (in macro 'OUTER' invoked at main.tal:5:3)");
+    }
+
+    #[test]
+    fn test_position_of() {
+        let mut source_map = SourceMap::new();
+        source_map.push(0x100, 3, SourcePos::new(location("main.tal", 1, 1)));
+        source_map.push(0x101, 1, SourcePos::new(location("main.tal", 2, 1)));
+
+        assert_eq!(source_map.position_of(0x100).map(|pos| { return pos.location.line; }), Some(1));
+        assert_eq!(source_map.position_of(0x101).map(|pos| { return pos.location.line; }), Some(2));
+        assert_eq!(source_map.position_of(0x102).map(|pos| { return pos.location.line; }), Some(1));
+        assert_eq!(source_map.position_of(0x103), None);
+    }
+
+    #[test]
+    fn test_malformed() {
+        assert_eq!("0100 3 main.tal:1:1\n0103 x main.tal:1:1\n".parse::<SourceMap>().err(),
+            Some(SourceMapError::Malformed{line: 2}));
+        assert_eq!("0100 3 main.tal\n".parse::<SourceMap>().err(),
+            Some(SourceMapError::Malformed{line: 1}));
+    }
+}
@@ -0,0 +1,184 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, PartialEq)]
+pub enum SymbolsError {
+    Io(String),
+    Truncated,
+    InvalidName,
+}
+
+impl fmt::Display for SymbolsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SymbolsError::Io(msg) => write!(f, "Error accessing symbol file: {}", msg),
+            SymbolsError::Truncated => write!(f, "Symbol file is truncated"),
+            SymbolsError::InvalidName => write!(f, "Symbol file contains a label that isn't valid utf-8"),
+        }
+    }
+}
+
+impl Error for SymbolsError {}
+
+// the labels of a rom and their addresses, as kept in a symbol file. A symbol file has an entry
+// for each label, made up of the label's address (as a big endian short) followed by its name
+// and a terminating zero byte
+pub struct SymbolTable {
+    // ordered by address
+    symbols: Vec<(u16, String)>,
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        SymbolTable{symbols: Vec::new()}
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, SymbolsError> {
+        let mut symbols = Vec::new();
+
+        let mut remaining = data;
+        while !remaining.is_empty() {
+            if remaining.len() < 2 {
+                return Err(SymbolsError::Truncated);
+            }
+            let address = u16::from_be_bytes([remaining[0], remaining[1]]);
+            remaining = &remaining[2..];
+
+            let name_len = remaining.iter().position(|b| { return *b == 0; })
+                .ok_or(SymbolsError::Truncated)?;
+            let name = String::from_utf8(remaining[..name_len].to_vec())
+                .map_err(|_| SymbolsError::InvalidName)?;
+            remaining = &remaining[name_len+1..];
+
+            symbols.push((address, name));
+        }
+
+        // the sort is stable, so labels at the same address keep the order they were written in
+        symbols.sort_by_key(|(address, _)| { return *address; });
+        return Ok(SymbolTable{symbols});
+    }
+
+    pub fn read_from_file(path: &Path) -> Result<Self, SymbolsError> {
+        let data = fs::read(path)
+            .map_err(|err| SymbolsError::Io(format!("{}: {}", path.display(), err)))?;
+        return SymbolTable::from_bytes(&data);
+    }
+
+    // read the symbol file that accompanies the rom at `rom_path`, if there is one
+    pub fn read_for_rom(rom_path: &Path) -> Result<Self, SymbolsError> {
+        let path = symbols_path_for_rom(rom_path);
+        if !path.exists() {
+            return Ok(SymbolTable::new());
+        }
+        return SymbolTable::read_from_file(&path);
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        for (address, name) in self.symbols.iter() {
+            data.extend_from_slice(&address.to_be_bytes());
+            data.extend_from_slice(name.as_bytes());
+            data.push(0);
+        }
+        return data;
+    }
+
+    pub fn write_to_file(&self, path: &Path) -> Result<(), SymbolsError> {
+        return fs::write(path, self.to_bytes())
+            .map_err(|err| SymbolsError::Io(format!("{}: {}", path.display(), err)));
+    }
+
+    // add a label, after any labels already at the same address
+    pub fn insert(&mut self, address: u16, name: String) {
+        let index = self.symbols.partition_point(|(symbol_address, _)| { return *symbol_address <= address; });
+        self.symbols.insert(index, (address, name));
+    }
+
+    pub fn address_of(&self, name: &str) -> Option<u16> {
+        return self.symbols.iter()
+            .find(|(_, symbol_name)| { return symbol_name == name; })
+            .map(|(address, _)| { return *address; });
+    }
+
+    // the labels at exactly `address`
+    pub fn labels_at(&self, address: u16) -> impl Iterator<Item = &str> {
+        return self.symbols.iter()
+            .filter(move |(symbol_address, _)| { return *symbol_address == address; })
+            .map(|(_, name)| { return name.as_str(); });
+    }
+
+    // the last label at or before `address`, and how far past it `address` is
+    pub fn nearest_label(&self, address: u16) -> Option<(&str, u16)> {
+        return self.symbols.iter().rev()
+            .find(|(symbol_address, _)| { return *symbol_address <= address; })
+            .map(|(symbol_address, name)| { return (name.as_str(), address - symbol_address); });
+    }
+}
+
+// the symbol file for the rom at `rom_path`, which is named after the rom with `.sym` appended
+pub fn symbols_path_for_rom(rom_path: &Path) -> PathBuf {
+    let mut path = rom_path.to_path_buf().into_os_string();
+    path.push(".sym");
+    return PathBuf::from(path);
+}
+
+impl Default for SymbolTable {
+    fn default() -> Self {
+        return SymbolTable::new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_bytes() {
+        let data = [
+            0x01, 0x10, b'l', b'o', b'o', b'p', 0x00,
+            0x01, 0x00, b'o', b'n', b'-', b'r', b'e', b's', b'e', b't', 0x00,
+            0x01, 0x10, b'o', b'n', b'-', b'r', b'e', b's', b'e', b't', b'/', b'x', 0x00,
+        ];
+        let symbols = SymbolTable::from_bytes(&data).unwrap();
+
+        assert_eq!(symbols.address_of("on-reset"), Some(0x100));
+        assert_eq!(symbols.address_of("on-reset/x"), Some(0x110));
+        assert_eq!(symbols.address_of("missing"), None);
+
+        assert_eq!(symbols.labels_at(0x110).collect::<Vec<_>>(), vec!("loop", "on-reset/x"));
+        assert_eq!(symbols.labels_at(0x111).count(), 0);
+
+        assert_eq!(symbols.nearest_label(0x105), Some(("on-reset", 0x5)));
+        assert_eq!(symbols.nearest_label(0x120), Some(("on-reset/x", 0x10)));
+        assert_eq!(symbols.nearest_label(0xff), None);
+    }
+
+    // a table written out should read back the same, with labels at the same address kept in
+    // the order they were inserted
+    #[test]
+    fn test_to_bytes() {
+        let mut symbols = SymbolTable::new();
+        symbols.insert(0x110, "on-reset/loop".to_string());
+        symbols.insert(0x100, "on-reset".to_string());
+        symbols.insert(0x110, "on-reset/x".to_string());
+
+        let data = symbols.to_bytes();
+        assert_eq!(data, vec!(
+            0x01, 0x00, b'o', b'n', b'-', b'r', b'e', b's', b'e', b't', 0x00,
+            0x01, 0x10, b'o', b'n', b'-', b'r', b'e', b's', b'e', b't', b'/', b'l', b'o', b'o', b'p', 0x00,
+            0x01, 0x10, b'o', b'n', b'-', b'r', b'e', b's', b'e', b't', b'/', b'x', 0x00));
+
+        let read_back = SymbolTable::from_bytes(&data).unwrap();
+        assert_eq!(read_back.symbols, symbols.symbols);
+    }
+
+    #[test]
+    fn test_from_bytes_invalid() {
+        assert_eq!(SymbolTable::from_bytes(&[0x01]).err(), Some(SymbolsError::Truncated));
+        assert_eq!(SymbolTable::from_bytes(&[0x01, 0x00, b'a']).err(), Some(SymbolsError::Truncated));
+        assert_eq!(SymbolTable::from_bytes(&[0x01, 0x00, 0xff, 0x00]).err(), Some(SymbolsError::InvalidName));
+        assert_eq!(SymbolTable::from_bytes(&[]).map(|symbols| { return symbols.symbols.len(); }), Ok(0));
+    }
+}
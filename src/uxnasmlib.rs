@@ -1,9 +1,14 @@
 use clap::Parser;
 use std::error::Error;
 use std::fmt;
+use std::fs;
 use std::fs::File;
 use std::io::BufRead;
 use std::io::BufReader;
+use std::io::Cursor;
+
+use crate::symbols::symbols_path_for_rom;
+use crate::sourcemap::source_map_path_for_rom;
 
 /// A rust implementation of assembler for uxn cpu
 #[derive(Parser)]
@@ -12,9 +17,28 @@ pub struct Cli {
     #[clap(parse(from_os_str))]
     pub src_path: std::path::PathBuf,
 
-    /// The path to the output rom
+    /// The path to the output rom. A symbol file giving the address of each
+    /// label is written alongside it, named after the rom with `.sym` appended
     #[clap(parse(from_os_str))]
     pub dst_path: std::path::PathBuf,
+
+    /// Also write a source map, giving the file, line and column (and any
+    /// macro invocations) of the token that produced each byte of the rom,
+    /// named after the rom with `.map` appended
+    #[clap(long)]
+    pub map: bool,
+
+    /// Also write a listing to this file, giving the address and bytes of
+    /// each token of the program alongside the token, with the tokens
+    /// produced by each macro shown under its invocation
+    #[clap(long, value_name = "FILE", parse(from_os_str))]
+    pub listing: Option<std::path::PathBuf>,
+
+    /// A directory to look in for included files that aren't found relative to
+    /// the file including them. May be given more than once, with the
+    /// directories searched in the order given
+    #[clap(short = 'I', long = "include-dir", value_name = "DIR", parse(from_os_str), number_of_values = 1)]
+    pub include_dirs: Vec<std::path::PathBuf>,
 }
 
 #[derive(Debug)]
@@ -46,19 +70,57 @@ pub fn run(config: Cli) -> Result<(), Box<dyn Error>> {
 
     let input = BufReader::new(fp).lines().map(|l| l.unwrap());
 
-    let mut program = asm::Asm::assemble(input)?;
+    let mut program = asm::Asm::assemble(
+        input,
+        &config.src_path.as_path().display().to_string(),
+        &config.include_dirs,
+    );
 
-    let fp = match File::create(config.dst_path.as_path()) {
-        Ok(fp) => fp,
-        Err(_err) => {
+    // the rom is only written once it is known there are no errors
+    let mut rom = Cursor::new(Vec::new());
+    let listing = program.output(&mut rom);
+
+    let diagnostics = program.take_diagnostics();
+    if diagnostics.error_count() > 0 {
+        return Err(Box::new(diagnostics));
+    }
+    if !diagnostics.is_empty() {
+        println!("{}", diagnostics);
+    }
+
+    if fs::write(config.dst_path.as_path(), rom.into_inner()).is_err() {
+        return Err(Box::new(FileOpenError {
+            fname: config.dst_path.as_path().display().to_string().clone(),
+            fdesc: "output".to_string(),
+        }));
+    }
+
+    let symbols_path = symbols_path_for_rom(config.dst_path.as_path());
+    if program.symbols().write_to_file(&symbols_path).is_err() {
+        return Err(Box::new(FileOpenError {
+            fname: symbols_path.display().to_string(),
+            fdesc: "symbol".to_string(),
+        }));
+    }
+
+    if config.map {
+        let source_map_path = source_map_path_for_rom(config.dst_path.as_path());
+        if program.source_map().write_to_file(&source_map_path).is_err() {
             return Err(Box::new(FileOpenError {
-                fname: config.dst_path.as_path().display().to_string().clone(),
-                fdesc: "output".to_string(),
+                fname: source_map_path.display().to_string(),
+                fdesc: "source map".to_string(),
             }));
         }
-    };
+    }
 
-    program.output(fp)?;
+    if let Some(listing_path) = config.listing {
+        if fs::write(&listing_path, listing.to_string()).is_err() {
+            return Err(Box::new(FileOpenError {
+                fname: listing_path.display().to_string(),
+                fdesc: "listing".to_string(),
+            }));
+        }
+    }
 
     return Ok(());
 }
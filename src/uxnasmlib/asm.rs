@@ -1,11 +1,15 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::error;
 use std::fmt;
 use std::io;
 use std::io::Seek;
 use std::io::SeekFrom;
 use std::io::Write;
-use std::str::FromStr;
+use std::mem;
+use std::path::PathBuf;
+
+use crate::sourcemap::{SourceLocation, SourceMap, SourcePos};
+use crate::symbols::SymbolTable;
 
 mod prog_state {
     use std::collections::HashMap;
@@ -36,15 +40,30 @@ use prog_state::Label;
 use prog_state::ProgState;
 
 mod tokens;
-use tokens::UxnToken;
+use tokens::{LabelRef, UxnToken};
 
 mod macros;
 
+mod includes;
+
+mod diagnostics;
+pub use diagnostics::Diagnostics;
+use diagnostics::{DiagnosticKind, SourceFiles};
+
+mod listing;
+pub use listing::Listing;
+use listing::ListingEntry;
+
 pub struct Asm {
-    program: Vec<UxnToken>,
+    program: Vec<(UxnToken, SourcePos)>,
     labels: HashMap<String, Label>,
+    sources: SourceFiles,
+    diagnostics: Diagnostics,
 }
 
+// a token and where it came from, or an error and the position of the token it was found at
+type PositionedToken = Result<(UxnToken, SourcePos), (AsmError, SourcePos)>;
+
 #[derive(Debug, PartialEq)]
 pub enum AsmError {
     ZeroPageWrite,
@@ -89,14 +108,27 @@ pub enum AsmError {
     MalformedMacroDefine {
         macro_name: String,
     },
-    MacroStartDelimiterMisplaced,
     MacroEndDelimiterMisplaced,
-    UnrecognisedMacro {
-        macro_name: String,
+    UnclosedLambda,
+    LambdaLabelName {
+        label_name: String,
+    },
+    UndefinedMacroOrLabel {
+        name: String,
     },
     MaxMacroCallDepthExceeded {
         macro_name: String,
     },
+    IncludeNotFound {
+        file_name: String,
+    },
+    IncludeCycle {
+        file_name: String,
+    },
+    IncludeRead {
+        file_name: String,
+        msg: String,
+    },
 }
 
 impl fmt::Display for AsmError {
@@ -165,14 +197,21 @@ impl fmt::Display for AsmError {
             AsmError::MalformedMacroDefine { macro_name } => {
                 write!(f, "macro '{}' incorrectly defined", macro_name)
             }
-            AsmError::MacroStartDelimiterMisplaced => {
-                write!(f, "misplaced '{{'")
-            }
             AsmError::MacroEndDelimiterMisplaced => {
                 write!(f, "misplaced '}}'")
             }
-            AsmError::UnrecognisedMacro { macro_name } => {
-                write!(f, "undefined macro '{}'", macro_name)
+            AsmError::UnclosedLambda => {
+                write!(f, "lambda opened here is never closed with '}}'")
+            }
+            AsmError::LambdaLabelName { label_name } => {
+                write!(
+                    f,
+                    "label name '{}' is reserved: names starting with 'λ' are given to lambdas",
+                    label_name
+                )
+            }
+            AsmError::UndefinedMacroOrLabel { name } => {
+                write!(f, "undefined macro or label: {}", name)
             }
             AsmError::MaxMacroCallDepthExceeded { macro_name } => {
                 write!(
@@ -181,40 +220,143 @@ impl fmt::Display for AsmError {
                     macro_name
                 )
             }
+            AsmError::IncludeNotFound { file_name } => {
+                write!(f, "included file '{}' not found", file_name)
+            }
+            AsmError::IncludeCycle { file_name } => {
+                write!(f, "'{}' is included from within itself", file_name)
+            }
+            AsmError::IncludeRead { file_name, msg } => {
+                write!(f, "failed to read included file '{}': {}", file_name, msg)
+            }
         }
     }
 }
 
 impl error::Error for AsmError {}
 
+#[derive(Debug, PartialEq)]
+pub enum AsmWarning {
+    UnusedLabel { label_name: String },
+}
+
+impl fmt::Display for AsmWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AsmWarning::UnusedLabel { label_name } => {
+                write!(f, "unused label: {}", label_name)
+            }
+        }
+    }
+}
+
 impl Asm {
-    pub fn assemble<I>(input: I) -> Result<Self, AsmError>
+    // assemble the lines of `input`, which are from the file named `file_name`. Assembly carries
+    // on past errors so that they can all be reported at once, leaving them in the diagnostics
+    pub fn assemble<I>(input: I, file_name: &str, include_dirs: &[PathBuf]) -> Self
     where
         I: Iterator<Item = String>,
     {
-        let token_strings = split_to_token_strings(input);
-
-        let token_strings = strip_comments(token_strings);
-
-        let tokens = token_strings.map(|t| t.parse::<UxnToken>());
+        // the lines of each file read are kept so that errors can quote them
+        let mut sources = SourceFiles::new();
 
-        // convert token stream error tokens::ParseError type into AsmError
-        let tokens = validate_tokens(tokens);
+        // split into tokens, reading in the included files
+        let tokens = includes::read_tokens(input.collect(), file_name, include_dirs, &mut sources);
 
         // strip macro definitions, expand macro invocations
-        let tokens = macros::process_macros(tokens);
+        let tokens = macros::process_macros(tokens.into_iter());
 
-        // populate labels map
+        // populate labels map, giving each lambda a label
         let mut labels = HashMap::new();
-        let tokens = get_labels(tokens, &mut labels);
+        let mut open_lambdas = Vec::new();
+        let tokens = get_labels(tokens, &mut labels, &mut open_lambdas);
+
+        // collect, leaving out the tokens that have errors
+        let mut program = Vec::new();
+        let mut diagnostics = Diagnostics::new();
+        for t in tokens {
+            match t {
+                Ok(t) => program.push(t),
+                Err((error, pos)) => {
+                    diagnostics.push(sources.diagnostic(DiagnosticKind::Error(error), Some(pos)));
+                }
+            }
+        }
+        // a lambda left unclosed runs to the end of the program, so that the reference opening
+        // it doesn't also fail as one to an undefined label
+        let end = program
+            .iter()
+            .fold(0, |prog_loc, (t, _)| t.update_prog_counter(prog_loc));
+        for (lambda_name, pos) in open_lambdas {
+            labels.insert(lambda_name, Label::new(end));
+            let error = AsmError::UnclosedLambda;
+            diagnostics.push(sources.diagnostic(DiagnosticKind::Error(error), Some(pos)));
+        }
 
-        // collect, returning first error encountered
-        let program = tokens.collect::<Result<Vec<_>, AsmError>>()?;
+        let mut asm = Asm {
+            labels,
+            program,
+            sources,
+            diagnostics,
+        };
+        asm.warn_unused_labels(file_name);
+        return asm;
+    }
+
+    // warn of each label in the file `file_name` that is never referred to. Labels starting with
+    // a capital letter are left out, as (following the reference assembler) they are taken to be
+    // devices and the like, which are referred to by their sub-labels only. So are labels from
+    // included files, which are usually libraries that needn't be used in full
+    fn warn_unused_labels(&mut self, file_name: &str) {
+        let mut used = HashSet::new();
+        for (token, _) in &self.program {
+            let label_ref = match token {
+                UxnToken::LitAddressZeroPage(label_ref)
+                | UxnToken::LitAddressRel(label_ref)
+                | UxnToken::LitAddressAbs(label_ref)
+                | UxnToken::RawAbsAddr(label_ref)
+                | UxnToken::RawZeroPageAddr(label_ref)
+                | UxnToken::RawRelAddr(label_ref)
+                | UxnToken::JmiAddress(label_ref)
+                | UxnToken::JciAddress(label_ref)
+                | UxnToken::JsiAddress(label_ref) => label_ref,
+                _ => continue,
+            };
+            match label_ref {
+                LabelRef::Label { label_name } | LabelRef::FullSubLabel { label_name, .. } => {
+                    used.insert(label_name.clone());
+                }
+                LabelRef::SubLabel { .. } | LabelRef::Lambda => {}
+            }
+        }
+
+        for (token, pos) in &self.program {
+            if let UxnToken::LabelDefine(label_name) = token {
+                if used.contains(label_name)
+                    || label_name.starts_with(char::is_uppercase)
+                    || pos.location.file != file_name
+                {
+                    continue;
+                }
+                self.diagnostics.push(self.sources.diagnostic(
+                    DiagnosticKind::Warning(AsmWarning::UnusedLabel {
+                        label_name: label_name.clone(),
+                    }),
+                    Some(pos.clone()),
+                ));
+            }
+        }
+    }
 
-        return Ok(Asm { labels, program });
+    // the errors and warnings found so far, which are left empty
+    pub fn take_diagnostics(&mut self) -> Diagnostics {
+        return mem::take(&mut self.diagnostics);
     }
 
-    pub fn output<W>(&mut self, mut target: W) -> Result<(), AsmError>
+    // write the program to `target`, giving the listing of what was written. A token that can't
+    // be written is reported in the diagnostics and skipped (and left out of the listing), but
+    // failing to write to `target` stops the output
+    pub fn output<W>(&mut self, mut target: W) -> Listing
     where
         W: Write + Seek,
     {
@@ -224,106 +366,90 @@ impl Asm {
             current_label: "".to_owned(),
         };
         let mut high_water_mark = 0u16;
+        let mut listing = Listing::new();
 
-        for token in &self.program {
+        for (token, pos) in &self.program {
             if let UxnToken::LabelDefine(label_name) = token {
                 prog_state.current_label = label_name.clone();
+                listing.push(self.listing_entry(token, pos, prog_state.counter, Vec::new()));
                 continue;
             }
 
-            match token.get_bytes(&prog_state) {
+            let mut written_bytes = Vec::new();
+            let error = match token.get_bytes(&prog_state) {
+                // check for zero page write
+                Ok(_) if prog_state.counter < 0x100 => Some(AsmError::ZeroPageWrite),
                 Ok(bytes) => {
-                    // check for zero page write
-                    if prog_state.counter < 0x100 {
-                        return Err(AsmError::ZeroPageWrite);
-                    }
-
-                    if prog_state.counter > high_water_mark {
-                        // we are writing to a location in the program beyond
-                        // which we have previously written, fill the bytes that
-                        // have not been written yet with zeros
-
-                        if let Err(err) = target.seek(SeekFrom::End(0)) {
-                            return Err(AsmError::Output {
-                                error: err.kind(),
-                                msg: err.to_string(),
-                            });
-                        }
-
-                        if let Err(err) =
-                            target.write(&vec![0x00; (prog_state.counter - high_water_mark).into()])
-                        {
-                            return Err(AsmError::Output {
-                                error: err.kind(),
-                                msg: err.to_string(),
-                            });
-                        }
-                    } else {
-                        // the program counter is pointing to a part of the
-                        // 'target' that has already been written to, seek to
-                        // that location
-                        if let Err(err) =
-                            target.seek(SeekFrom::Start((prog_state.counter - 0x100).into()))
-                        {
-                            return Err(AsmError::Output {
-                                error: err.kind(),
-                                msg: err.to_string(),
-                            });
-                        }
-                    }
-
-                    // at this point the 'target' file/buffer is guaranteed
-                    // to be at the location we want to write the bytes corresponding
-                    // to the current token, so write
-                    if let Err(err) = target.write(&bytes) {
-                        return Err(AsmError::Output {
+                    let written =
+                        write_bytes(&mut target, prog_state.counter, high_water_mark, &bytes);
+                    if let Err(err) = written {
+                        let error = AsmError::Output {
                             error: err.kind(),
                             msg: err.to_string(),
-                        });
+                        };
+                        self.diagnostics
+                            .push(self.sources.diagnostic(DiagnosticKind::Error(error), None));
+                        return listing;
                     }
+                    written_bytes = bytes;
+                    None
                 }
                 Err(tokens::GetBytesError::NotWritableToken) => {
                     // not really an error, just a token (such as PadRel/PadAbs)
                     // that isn't designed to write any bytes, but may
                     // change the program counter
+                    None
                 }
                 // the following are all real errors
+                Err(tokens::GetBytesError::UndefinedLabel { label_name })
+                    if matches!(token, UxnToken::JsiAddress(_)) =>
+                {
+                    // the call was written as a bare word, which could have been meant as a
+                    // macro just as well as a label
+                    Some(AsmError::UndefinedMacroOrLabel { name: label_name })
+                }
                 Err(tokens::GetBytesError::UndefinedLabel { label_name }) => {
-                    return Err(AsmError::UndefinedLabel { label_name });
+                    Some(AsmError::UndefinedLabel { label_name })
                 }
                 Err(tokens::GetBytesError::UndefinedSubLabel {
                     label_name,
                     sub_label_name,
-                }) => {
-                    return Err(AsmError::UndefinedSubLabel {
-                        label_name,
-                        sub_label_name,
-                    });
-                }
+                }) => Some(AsmError::UndefinedSubLabel {
+                    label_name,
+                    sub_label_name,
+                }),
                 Err(tokens::GetBytesError::LabelNotInZeroPage { label_name }) => {
-                    return Err(AsmError::LabelNotInZeroPage { label_name });
+                    Some(AsmError::LabelNotInZeroPage { label_name })
                 }
                 Err(tokens::GetBytesError::SubLabelNotInZeroPage {
                     label_name,
                     sub_label_name,
-                }) => {
-                    return Err(AsmError::SubLabelNotInZeroPage {
-                        label_name,
-                        sub_label_name,
-                    });
-                }
+                }) => Some(AsmError::SubLabelNotInZeroPage {
+                    label_name,
+                    sub_label_name,
+                }),
                 Err(tokens::GetBytesError::RelLabelNotInRange { label_name }) => {
-                    return Err(AsmError::RelLabelNotInRange { label_name });
+                    Some(AsmError::RelLabelNotInRange { label_name })
                 }
                 Err(tokens::GetBytesError::RelSubLabelNotInRange {
                     label_name,
                     sub_label_name,
-                }) => {
-                    return Err(AsmError::RelSubLabelNotInRange {
-                        label_name,
-                        sub_label_name,
-                    });
-                }
+                }) => Some(AsmError::RelSubLabelNotInRange {
+                    label_name,
+                    sub_label_name,
+                }),
+            };
+
+            // the bytes of a token with an error are left unwritten, but the program counter
+            // still moves past them so that the rest of the program can be checked
+            if let Some(error) = error {
+                self.diagnostics.push(
+                    self.sources
+                        .diagnostic(DiagnosticKind::Error(error), Some(pos.clone())),
+                );
+            } else {
+                let entry = self.listing_entry(token, pos, prog_state.counter, written_bytes);
+                listing.push(entry);
             }
 
             prog_state.counter = token.update_prog_counter(prog_state.counter);
@@ -331,80 +457,216 @@ impl Asm {
                 high_water_mark = prog_state.counter;
             }
         }
-        return Ok(());
+
+        return listing;
+    }
+
+    // the entry in the listing for `token`, positioned at `pos`, which wrote `bytes` at
+    // `address`
+    fn listing_entry(
+        &self,
+        token: &UxnToken,
+        pos: &SourcePos,
+        address: u16,
+        bytes: Vec<u8>,
+    ) -> ListingEntry {
+        return ListingEntry {
+            address,
+            bytes,
+            pos: pos.clone(),
+            text: self.sources.token_text(&pos.location).unwrap_or_default(),
+            is_label: matches!(
+                token,
+                UxnToken::LabelDefine(_) | UxnToken::SubLabelDefine(_)
+            ),
+        };
+    }
+
+    // the labels of the assembled program, with sub-labels named `label/sub-label`
+    pub fn symbols(&self) -> SymbolTable {
+        let mut symbols = Vec::new();
+        for (label_name, label) in &self.labels {
+            symbols.push((label.address, label_name.clone()));
+            for (sub_label_name, address) in &label.sub_labels {
+                symbols.push((*address, format!("{}/{}", label_name, sub_label_name)));
+            }
+        }
+
+        // the labels are held in hash maps, so they are sorted to make the symbol table the
+        // same from one assembly to the next
+        symbols.sort();
+
+        let mut symbol_table = SymbolTable::new();
+        for (address, name) in symbols {
+            symbol_table.insert(address, name);
+        }
+        return symbol_table;
     }
-}
 
-struct StringIter<I>
-where
-    I: Iterator<Item = String>,
-{
-    inner_iter: I,
+    // where in the source each byte written by `output` comes from. Tokens that can't be
+    // written (because, for example, they refer to an undefined label) are left out
+    pub fn source_map(&self) -> SourceMap {
+        let mut prog_state = ProgState {
+            counter: 0,
+            labels: &self.labels,
+            current_label: "".to_owned(),
+        };
+        let mut source_map = SourceMap::new();
+
+        for (token, pos) in &self.program {
+            if let UxnToken::LabelDefine(label_name) = token {
+                prog_state.current_label = label_name.clone();
+                continue;
+            }
+
+            if let Ok(bytes) = token.get_bytes(&prog_state) {
+                if !bytes.is_empty() {
+                    source_map.push(prog_state.counter, bytes.len() as u16, pos.clone());
+                }
+            }
+
+            prog_state.counter = token.update_prog_counter(prog_state.counter);
+        }
+
+        return source_map;
+    }
 }
 
-impl<I> Iterator for StringIter<I>
+// write `bytes` to `target` at the program counter `counter`, where `high_water_mark` is the
+// furthest point in the program written to so far
+fn write_bytes<W>(
+    target: &mut W,
+    counter: u16,
+    high_water_mark: u16,
+    bytes: &[u8],
+) -> io::Result<()>
 where
-    I: Iterator<Item = String>,
+    W: Write + Seek,
 {
-    type Item = String;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        self.inner_iter.next()
+    if counter > high_water_mark {
+        // we are writing to a location in the program beyond
+        // which we have previously written, fill the bytes that
+        // have not been written yet with zeros
+        target.seek(SeekFrom::End(0))?;
+        target.write_all(&vec![0x00; (counter - high_water_mark).into()])?;
+    } else {
+        // the program counter is pointing to a part of the
+        // 'target' that has already been written to, seek to
+        // that location
+        target.seek(SeekFrom::Start((counter - 0x100).into()))?;
     }
+
+    // at this point the 'target' file/buffer is guaranteed
+    // to be at the location we want to write the bytes corresponding
+    // to the current token, so write
+    target.write_all(bytes)?;
+    return Ok(());
 }
 
-fn split_to_token_strings<I>(input: I) -> impl Iterator<Item = String>
+// split the lines of `input` into token strings, giving each the location it was found at. The
+// brackets of macros, comments and ignored sections are tokens of their own, even when they
+// aren't separated by whitespace from what surrounds them
+fn split_to_token_strings<'a, I>(
+    input: I,
+    file_name: &'a str,
+) -> impl Iterator<Item = (String, SourceLocation)> + 'a
 where
-    I: Iterator<Item = String>,
+    I: Iterator<Item = String> + 'a,
 {
-    let x = input.flat_map(|l| {
-        let l = l.replace("{", " { ");
-        let l = l.replace("}", " } ");
+    input.enumerate().flat_map(move |(line_index, l)| {
+        let mut token_strings = Vec::new();
+        let mut current: Option<(String, usize)> = None;
+
+        for (column_index, c) in l.chars().enumerate() {
+            // a rune referring to a lambda, such as `?{`, is kept as one token
+            if let Some((ref mut token_string, _)) = current {
+                if c == '{'
+                    && ["?", "!", ";", ",", ".", ":", "-", "_"].contains(&token_string.as_str())
+                {
+                    token_string.push(c);
+                    token_strings.extend(current.take());
+                    continue;
+                }
+            }
 
-        let l = l.replace("(", " ( ");
-        let l = l.replace(")", " ) ");
+            let is_bracket = "{}()[]".contains(c);
+            if c.is_whitespace() || is_bracket {
+                if let Some((token_string, column)) = current.take() {
+                    token_strings.push((token_string, column));
+                }
+                if is_bracket {
+                    token_strings.push((c.to_string(), column_index + 1));
+                }
+                continue;
+            }
 
-        let l = l.replace("[", " [ ");
-        let l = l.replace("]", " ] ");
+            match current {
+                Some((ref mut token_string, _)) => token_string.push(c),
+                None => current = Some((c.to_string(), column_index + 1)),
+            }
+        }
+        if let Some((token_string, column)) = current {
+            token_strings.push((token_string, column));
+        }
 
-        l.split_whitespace()
-            .map(|w| String::from_str(w).unwrap())
+        token_strings
+            .into_iter()
+            .map(|(token_string, column)| {
+                let location = SourceLocation {
+                    file: file_name.to_owned(),
+                    line: line_index + 1,
+                    column,
+                };
+                (token_string, location)
+            })
             .collect::<Vec<_>>()
-    });
-
-    StringIter { inner_iter: x }
+    })
 }
 
-fn validate_tokens<I>(input: I) -> impl Iterator<Item = Result<UxnToken, AsmError>>
+fn validate_tokens<I>(input: I) -> impl Iterator<Item = PositionedToken>
 where
-    I: Iterator<Item = Result<UxnToken, tokens::ParseError>>,
+    I: Iterator<Item = (Result<UxnToken, tokens::ParseError>, SourcePos)>,
 {
-    input.map(|t| match t {
-        Ok(t) => Ok(t),
+    input.map(|(t, pos)| match t {
+        Ok(t) => Ok((t, pos)),
         Err(e) => {
-            return Err(AsmError::TokenParseError { parse_error: e });
+            return Err((AsmError::TokenParseError { parse_error: e }, pos));
         }
     })
 }
 
+// find the address of each label in `input`, putting it in `labels`. Each lambda is given a
+// label too, named after the order it was opened in, with the lambdas left open at the end of
+// the input (with the position of their opening) in `open_lambdas`
 fn get_labels<'a, I: 'a>(
     input: I,
     labels: &'a mut HashMap<String, Label>,
-) -> impl Iterator<Item = Result<UxnToken, AsmError>> + 'a
+    open_lambdas: &'a mut Vec<(String, SourcePos)>,
+) -> impl Iterator<Item = PositionedToken> + 'a
 where
-    I: Iterator<Item = Result<UxnToken, AsmError>>,
+    I: Iterator<Item = PositionedToken>,
 {
     let mut current_label = None;
     let mut prog_loc = 0u16;
+    let mut lambda_count = 0;
 
     input.map(move |t| match t {
-        Ok(UxnToken::LabelDefine(ref label_name)) => {
+        Ok((UxnToken::LabelDefine(ref label_name), ref pos)) => {
+            // a label named like a lambda's would take the place of the lambda's
+            if label_name.starts_with('λ') {
+                return Err((
+                    AsmError::LambdaLabelName {
+                        label_name: label_name.clone(),
+                    },
+                    pos.clone(),
+                ));
+            }
             current_label = Some(label_name.clone());
             let label = Label::new(prog_loc);
             labels.insert(label_name.clone(), label);
             t
         }
-        Ok(UxnToken::SubLabelDefine(ref sub_label_name)) => {
+        Ok((UxnToken::SubLabelDefine(ref sub_label_name), ref pos)) => {
             if let Some(current_label) = &current_label {
                 labels
                     .get_mut(current_label)
@@ -412,26 +674,49 @@ where
                     .sub_labels
                     .insert(sub_label_name.clone(), prog_loc);
             } else {
-                return Err(AsmError::SubLabelWithNoLabel {
-                    sub_label_name: sub_label_name.clone(),
-                });
+                return Err((
+                    AsmError::SubLabelWithNoLabel {
+                        sub_label_name: sub_label_name.clone(),
+                    },
+                    pos.clone(),
+                ));
             }
             t
         }
-        Ok(t) => {
+        Ok((UxnToken::MacroEndDelimiter, pos)) => {
+            // the end of a lambda, which is where its label is. Unlike other labels this doesn't
+            // start a new scope for sub-labels
+            if let Some((lambda_name, _)) = open_lambdas.pop() {
+                labels.insert(lambda_name, Label::new(prog_loc));
+                return Ok((UxnToken::MacroEndDelimiter, pos));
+            }
+            return Err((AsmError::MacroEndDelimiterMisplaced, pos));
+        }
+        Ok((mut t, pos)) => {
+            // a reference to a lambda opens it, and is to the label at its end
+            if t.opens_lambda() {
+                if let Some(label_ref) = t.label_ref_mut() {
+                    let lambda_name = format!("λ{:02x}", lambda_count);
+                    lambda_count += 1;
+                    *label_ref = LabelRef::Label {
+                        label_name: lambda_name.clone(),
+                    };
+                    open_lambdas.push((lambda_name, pos.clone()));
+                }
+            }
             prog_loc = t.update_prog_counter(prog_loc);
-            Ok(t)
+            Ok((t, pos))
         }
         Err(e) => Err(e),
     })
 }
 
-fn strip_comments<I>(input: I) -> impl Iterator<Item = String>
+fn strip_comments<I>(input: I) -> impl Iterator<Item = (String, SourceLocation)>
 where
-    I: Iterator<Item = String>,
+    I: Iterator<Item = (String, SourceLocation)>,
 {
     let mut brack_stack = vec!{};
-    let x = input.filter_map(move |s| {
+    input.filter_map(move |(s, location)| {
         if s == "(" {
             brack_stack.push(true);
             return None;
@@ -449,10 +734,8 @@ where
         if s == "]" {
             return None;
         }
-        return Some(s);
-    });
-
-    StringIter { inner_iter: x }
+        return Some((s, location));
+    })
 }
 
 #[cfg(test)]
@@ -461,6 +744,55 @@ mod tests {
     use std::io::Cursor;
     use tokens::LabelRef;
 
+    // the position given to tokens in tests that aren't concerned with positions
+    pub(super) fn test_pos() -> SourcePos {
+        return SourcePos::new(SourceLocation {
+            file: "test.tal".to_owned(),
+            line: 1,
+            column: 1,
+        });
+    }
+
+    pub(super) fn positioned(
+        tokens: Vec<Result<UxnToken, AsmError>>,
+    ) -> impl Iterator<Item = PositionedToken> {
+        return tokens
+            .into_iter()
+            .map(|t| t.map(|t| (t, test_pos())).map_err(|e| (e, test_pos())));
+    }
+
+    pub(super) fn unpositioned<I>(tokens: I) -> Vec<Result<UxnToken, AsmError>>
+    where
+        I: Iterator<Item = PositionedToken>,
+    {
+        return tokens
+            .map(|t| t.map(|(t, _)| t).map_err(|(e, _)| e))
+            .collect();
+    }
+
+    fn at_test_pos(tokens: Vec<UxnToken>) -> Vec<(UxnToken, SourcePos)> {
+        return tokens.into_iter().map(|t| (t, test_pos())).collect();
+    }
+
+    // output `asm` to `target`, giving the first error found
+    fn first_output_error<W>(asm: &mut Asm, target: W) -> Result<(), AsmError>
+    where
+        W: Write + Seek,
+    {
+        asm.output(target);
+        let first_error = asm
+            .take_diagnostics()
+            .into_iter()
+            .find_map(|d| match d.kind {
+                DiagnosticKind::Error(error) => Some(error),
+                DiagnosticKind::Warning(_) => None,
+            });
+        return match first_error {
+            Some(error) => Err(error),
+            None => Ok(()),
+        };
+    }
+
     // test `split_to_token_strings` function; create input with
     // bracket separators and assert that it is split as expected
     // into token strings
@@ -470,13 +802,17 @@ mod tests {
             "tokenA tokenB tokenC{tokenD".to_owned(),
             "}tokenE               (tokenF            tokenG".to_owned(),
             "tokenH[tokenI]tokenJ [tokenK".to_owned(),
+            "?{tokenL} !{ ;{}".to_owned(),
         ];
 
         assert_eq!(
-            split_to_token_strings(input.into_iter()).collect::<Vec<_>>(),
+            split_to_token_strings(input.into_iter(), "test.tal")
+                .map(|(t, _)| t)
+                .collect::<Vec<_>>(),
             vec!(
                 "tokenA", "tokenB", "tokenC", "{", "tokenD", "}", "tokenE", "(", "tokenF",
-                "tokenG", "tokenH", "[", "tokenI", "]", "tokenJ", "[", "tokenK",
+                "tokenG", "tokenH", "[", "tokenI", "]", "tokenJ", "[", "tokenK", "?{", "tokenL",
+                "}", "!{", ";{", "}",
             )
             .into_iter()
             .map(|t| t.to_owned())
@@ -484,6 +820,30 @@ mod tests {
         );
     }
 
+    // test `split_to_token_strings` function gives the line and
+    // column each token string starts at
+    #[test]
+    fn test_split_to_token_strings_locations() {
+        let input = vec![
+            "  #01 DEO{BRK".to_owned(),
+            "".to_owned(),
+            "\t@label".to_owned(),
+        ];
+
+        assert_eq!(
+            split_to_token_strings(input.into_iter(), "test.tal")
+                .map(|(t, location)| (t, location.line, location.column))
+                .collect::<Vec<_>>(),
+            vec!(
+                ("#01".to_owned(), 1, 3),
+                ("DEO".to_owned(), 1, 7),
+                ("{".to_owned(), 1, 10),
+                ("BRK".to_owned(), 1, 11),
+                ("@label".to_owned(), 3, 2),
+            )
+        );
+    }
+
     // test `strip_comments` function; create token string input
     // and assert that token strings laying between '(' ')' tokens
     // are removed from the input
@@ -496,7 +856,9 @@ mod tests {
         .map(|t| t.to_owned());
 
         assert_eq!(
-            strip_comments(input.into_iter()).collect::<Vec<_>>(),
+            strip_comments(input.map(|t| (t, test_pos().location)))
+                .map(|(t, _)| t)
+                .collect::<Vec<_>>(),
             vec!("tokenA", "tokenB", "tokenC", "tokenG")
                 .into_iter()
                 .map(|t| t.to_owned())
@@ -516,7 +878,7 @@ mod tests {
             Ok(UxnToken::RawShort(0xbbcc)),
         ];
 
-        let output = validate_tokens(input.into_iter()).collect::<Vec<_>>();
+        let output = unpositioned(validate_tokens(input.into_iter().map(|t| (t, test_pos()))));
 
         let expected_output: Vec<Result<UxnToken, AsmError>>;
         expected_output = vec![
@@ -541,7 +903,9 @@ mod tests {
             }),
         ];
 
-        let output = validate_tokens(input.into_iter()).collect::<Result<Vec<_>, AsmError>>();
+        let output = validate_tokens(input.into_iter().map(|t| (t, test_pos())))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|(e, _)| e);
 
         assert_eq!(
             output,
@@ -574,7 +938,7 @@ mod tests {
             )),
         ];
 
-        let output = get_labels(input.into_iter(), &mut labels).collect::<Vec<_>>();
+        let output = unpositioned(get_labels(positioned(input), &mut labels, &mut Vec::new()));
 
         let expected_output: Vec<Result<UxnToken, AsmError>>;
         expected_output = vec![
@@ -625,7 +989,7 @@ mod tests {
             Ok(UxnToken::SubLabelDefine("test_sub_label".to_owned())),
         ];
 
-        let output = get_labels(input.into_iter(), &mut labels).collect::<Vec<_>>();
+        let output = unpositioned(get_labels(positioned(input), &mut labels, &mut Vec::new()));
 
         let expected_output: Vec<Result<UxnToken, AsmError>>;
         expected_output = vec![
@@ -684,8 +1048,9 @@ mod tests {
             Ok(UxnToken::SubLabelDefine("test_sub_label".to_owned())),
         ];
 
-        let output =
-            get_labels(input.into_iter(), &mut labels).collect::<Result<Vec<_>, AsmError>>();
+        let output = get_labels(positioned(input), &mut labels, &mut Vec::new())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|(e, _)| e);
 
         assert_eq!(
             output,
@@ -695,10 +1060,396 @@ mod tests {
         );
     }
 
+    // test `get_labels` function with lambdas; that each is given a label at its end, with nested
+    // lambdas closed innermost first, and that the lambdas left open are given back
+    #[test]
+    fn test_get_labels_lambdas() {
+        let mut labels = HashMap::new();
+        let mut open_lambdas = Vec::new();
+        let input = vec![
+            Ok(UxnToken::PadAbs(0x100)),
+            Ok(UxnToken::JciAddress(LabelRef::Lambda)),
+            Ok(UxnToken::JsiAddress(LabelRef::Lambda)),
+            Ok(UxnToken::RawByte(0xaa)),
+            Ok(UxnToken::MacroEndDelimiter),
+            Ok(UxnToken::MacroEndDelimiter),
+            Ok(UxnToken::JmiAddress(LabelRef::Lambda)),
+        ];
+
+        let output = unpositioned(get_labels(
+            positioned(input),
+            &mut labels,
+            &mut open_lambdas,
+        ));
+
+        let lambda_ref = |label_name: &str| LabelRef::Label {
+            label_name: label_name.to_owned(),
+        };
+        let expected_output: Vec<Result<UxnToken, AsmError>>;
+        expected_output = vec![
+            Ok(UxnToken::PadAbs(0x100)),
+            Ok(UxnToken::JciAddress(lambda_ref("λ00"))),
+            Ok(UxnToken::JsiAddress(lambda_ref("λ01"))),
+            Ok(UxnToken::RawByte(0xaa)),
+            Ok(UxnToken::MacroEndDelimiter),
+            Ok(UxnToken::MacroEndDelimiter),
+            Ok(UxnToken::JmiAddress(lambda_ref("λ02"))),
+        ];
+
+        assert_eq!(output, expected_output);
+
+        let mut expected_labels = HashMap::new();
+        expected_labels.insert("λ00".to_owned(), Label::new(0x107));
+        expected_labels.insert("λ01".to_owned(), Label::new(0x107));
+        assert_eq!(labels, expected_labels);
+
+        assert_eq!(open_lambdas, vec![("λ02".to_owned(), test_pos())]);
+    }
+
+    #[test]
+    fn test_symbols() {
+        let input = vec!["|100 @main #01 &loop INC ,&loop JMP @data &a $1 &b"];
+        let input = input.into_iter().map(|l| l.to_owned());
+        let asm = Asm::assemble(input, "test.tal", &[]);
+
+        let symbols = asm.symbols();
+        assert_eq!(
+            symbols.to_bytes(),
+            [
+                &[0x01, 0x00][..],
+                b"main\0",
+                &[0x01, 0x02][..],
+                b"main/loop\0",
+                &[0x01, 0x06][..],
+                b"data\0",
+                &[0x01, 0x06][..],
+                b"data/a\0",
+                &[0x01, 0x07][..],
+                b"data/b\0",
+            ]
+            .concat()
+        );
+    }
+
+    // every byte written should be mapped back to the token that
+    // produced it, including those produced by macros
+    #[test]
+    fn test_source_map() {
+        let input = vec!["%EMIT { #18 DEO }", "|100 #41 EMIT", "BRK"];
+        let input = input.into_iter().map(|l| l.to_owned());
+        let asm = Asm::assemble(input, "test.tal", &[]);
+
+        let source_map = asm.source_map();
+        assert_eq!(
+            source_map.to_string(),
+            "0100 2 test.tal:2:6\n\
+            0102 2 test.tal:1:9 < EMIT test.tal:2:10\n\
+            0104 1 test.tal:1:13 < EMIT test.tal:2:10\n\
+            0105 1 test.tal:3:1\n"
+        );
+        assert_eq!(
+            source_map.position_of(0x103).map(|pos| pos.to_string()),
+            Some("test.tal:1:9 (in macro 'EMIT' invoked at test.tal:2:10)".to_owned())
+        );
+    }
+
+    // test the listing given by `output`; that each token is given with its address and the
+    // bytes it wrote as it was written in the source, with the tokens of a macro under its
+    // invocation
+    #[test]
+    fn test_listing() {
+        let input = vec![
+            "%EMIT { #18 DEO }",
+            "|100 @Main #41 EMIT ?{ BRK }",
+            "&str \"hi",
+        ];
+        let input = input.into_iter().map(|l| l.to_owned());
+        let mut asm = Asm::assemble(input, "test.tal", &[]);
+
+        let listing = asm.output(&mut Cursor::new(Vec::new()));
+        assert!(asm.take_diagnostics().is_empty());
+        assert_eq!(
+            listing.to_string(),
+            "\
+            0000                 |100\n\
+            0100               @Main\n\
+            0100  80 41          #41\n\
+            0102                 EMIT\n\
+            0102  80 18            #18\n\
+            0104  17               DEO\n\
+            0105  20 00 01       ?{\n\
+            0108  00             BRK\n\
+            0109                 }\n\
+            0109               &str\n\
+            0109  68 69          \"hi\n"
+        );
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let input = vec!["%CALL { ;fo JSR2 }", "|100 CALL", "@foo BRK"];
+        let input = input.into_iter().map(|l| l.to_owned());
+        let mut asm = Asm::assemble(input, "test.tal", &[]);
+        asm.output(&mut Cursor::new(Vec::new()));
+
+        let diagnostics = asm.take_diagnostics();
+        assert_eq!(
+            diagnostics.to_string(),
+            "test.tal:3:1: warning: unused label: foo\n    @foo BRK\n    ^\n\
+            test.tal:1:9: error: undefined label: fo\n    \
+            %CALL { ;fo JSR2 }\n            ^\n\
+            test.tal:2:6: note: in expansion of macro 'CALL'\n\
+            1 error and 1 warning"
+        );
+    }
+
+    // test that assembly carries on past errors, so that every error is found at once
+    #[test]
+    fn test_all_errors_reported() {
+        let input = vec![
+            "%M1 %M2 { #02 }",
+            "|100 #0g ;missing JSR2",
+            "&orphan M2 M3",
+            "@main ,&loop JMP",
+            "@Device BRK",
+        ];
+        let input = input.into_iter().map(|l| l.to_owned());
+        let mut asm = Asm::assemble(input, "test.tal", &[]);
+        let mut output = Cursor::new(Vec::new());
+        asm.output(&mut output);
+
+        let diagnostics = asm
+            .take_diagnostics()
+            .into_iter()
+            .map(|d| (d.kind, d.pos.unwrap().location.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            diagnostics,
+            vec![
+                (
+                    DiagnosticKind::Error(AsmError::MalformedMacroDefine {
+                        macro_name: "M2".to_owned()
+                    }),
+                    "test.tal:1:5".to_owned()
+                ),
+                (
+                    DiagnosticKind::Error(AsmError::TokenParseError {
+                        parse_error: "#0g".parse::<UxnToken>().unwrap_err()
+                    }),
+                    "test.tal:2:6".to_owned()
+                ),
+                (
+                    DiagnosticKind::Error(AsmError::SubLabelWithNoLabel {
+                        sub_label_name: "orphan".to_owned()
+                    }),
+                    "test.tal:3:1".to_owned()
+                ),
+                (
+                    DiagnosticKind::Warning(AsmWarning::UnusedLabel {
+                        label_name: "main".to_owned()
+                    }),
+                    "test.tal:4:1".to_owned()
+                ),
+                (
+                    DiagnosticKind::Error(AsmError::UndefinedLabel {
+                        label_name: "missing".to_owned()
+                    }),
+                    "test.tal:2:10".to_owned()
+                ),
+                (
+                    DiagnosticKind::Error(AsmError::UndefinedMacroOrLabel {
+                        name: "M3".to_owned()
+                    }),
+                    "test.tal:3:12".to_owned()
+                ),
+                (
+                    DiagnosticKind::Error(AsmError::UndefinedSubLabel {
+                        label_name: "main".to_owned(),
+                        sub_label_name: "loop".to_owned()
+                    }),
+                    "test.tal:4:7".to_owned()
+                ),
+            ]
+        );
+
+        // the bytes of labels that couldn't be resolved are left as zero, while the token that
+        // couldn't be parsed (and so has no known size) is left out altogether
+        assert_eq!(
+            output.into_inner(),
+            vec![0x00, 0x00, 0x00, 0x2e, 0x80, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x00]
+        );
+    }
+
+    // test that `!`, `?` and bare words assemble to immediate jumps to their labels, and that a
+    // bare word that is neither a macro nor a label is reported as such
+    #[test]
+    fn test_immediate_jumps() {
+        let input = vec!["|100 @main #01 ?&skip !main &skip sub BRK", "@sub JMP2r"];
+        let input = input.into_iter().map(|l| l.to_owned());
+        let mut asm = Asm::assemble(input, "test.tal", &[]);
+        let mut output = Cursor::new(Vec::new());
+        asm.output(&mut output);
+
+        assert!(asm.take_diagnostics().is_empty());
+        assert_eq!(
+            output.into_inner(),
+            vec![0x80, 0x01, 0x20, 0x00, 0x03, 0x40, 0xff, 0xf8, 0x60, 0x00, 0x01, 0x00, 0x6c]
+        );
+
+        let input = vec!["|100 !nowhere nowhere"];
+        let input = input.into_iter().map(|l| l.to_owned());
+        let mut asm = Asm::assemble(input, "test.tal", &[]);
+        asm.output(&mut Cursor::new(Vec::new()));
+
+        let errors = asm
+            .take_diagnostics()
+            .into_iter()
+            .map(|d| d.kind.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errors,
+            vec![
+                "error: undefined label: nowhere",
+                "error: undefined macro or label: nowhere",
+            ]
+        );
+    }
+
+    // test that the raw zero page and relative address runes assemble to the bytes of their
+    // labels' addresses, with the same range checks as the literal addresses
+    #[test]
+    fn test_raw_zero_page_rel_addresses() {
+        let input = vec![
+            "|00 @ptr $1 @ptr2 $1",
+            "|100 @Table -ptr -ptr2 _&a _&b &a #01 &b #02 BRK",
+        ];
+        let input = input.into_iter().map(|l| l.to_owned());
+        let mut asm = Asm::assemble(input, "test.tal", &[]);
+        let mut output = Cursor::new(Vec::new());
+        asm.output(&mut output);
+
+        assert!(asm.take_diagnostics().is_empty());
+        assert_eq!(
+            output.into_inner(),
+            vec![0x00, 0x01, 0x00, 0x01, 0x80, 0x01, 0x80, 0x02, 0x00]
+        );
+
+        let input = vec!["|100 @far -far $100 _far"];
+        let input = input.into_iter().map(|l| l.to_owned());
+        let mut asm = Asm::assemble(input, "test.tal", &[]);
+        asm.output(&mut Cursor::new(Vec::new()));
+
+        let errors = asm
+            .take_diagnostics()
+            .into_iter()
+            .map(|d| (d.kind, d.pos.unwrap().location.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errors,
+            vec![
+                (
+                    DiagnosticKind::Error(AsmError::LabelNotInZeroPage {
+                        label_name: "far".to_owned()
+                    }),
+                    "test.tal:1:11".to_owned()
+                ),
+                (
+                    DiagnosticKind::Error(AsmError::RelLabelNotInRange {
+                        label_name: "far".to_owned()
+                    }),
+                    "test.tal:1:21".to_owned()
+                ),
+            ]
+        );
+    }
+
+    // test that lambdas, nested or not, assemble to jumps past their bodies, and that a lambda
+    // left unclosed and a '}' with no lambda to close are reported (the unclosed lambda only as
+    // unclosed, not as a reference to an undefined label too)
+    #[test]
+    fn test_lambdas() {
+        let input = vec!["|100 #01 ?{ #02 } !{ #03 } { #04 } BRK"];
+        let input = input.into_iter().map(|l| l.to_owned());
+        let mut asm = Asm::assemble(input, "test.tal", &[]);
+        let mut output = Cursor::new(Vec::new());
+        asm.output(&mut output);
+
+        assert!(asm.take_diagnostics().is_empty());
+        assert_eq!(
+            output.into_inner(),
+            vec![
+                0x80, 0x01, 0x20, 0x00, 0x02, 0x80, 0x02, 0x40, 0x00, 0x02, 0x80, 0x03, 0x60, 0x00,
+                0x02, 0x80, 0x04, 0x00
+            ]
+        );
+
+        let input = vec!["|100 ?{ #01 ?{ #02 } #03 } BRK"];
+        let input = input.into_iter().map(|l| l.to_owned());
+        let mut asm = Asm::assemble(input, "test.tal", &[]);
+        let mut output = Cursor::new(Vec::new());
+        asm.output(&mut output);
+
+        assert!(asm.take_diagnostics().is_empty());
+        assert_eq!(
+            output.into_inner(),
+            vec![0x20, 0x00, 0x09, 0x80, 0x01, 0x20, 0x00, 0x02, 0x80, 0x02, 0x80, 0x03, 0x00]
+        );
+
+        let input = vec!["|100 } { BRK"];
+        let input = input.into_iter().map(|l| l.to_owned());
+        let mut asm = Asm::assemble(input, "test.tal", &[]);
+        asm.output(&mut Cursor::new(Vec::new()));
+
+        let errors = asm
+            .take_diagnostics()
+            .into_iter()
+            .map(|d| (d.kind.to_string(), d.pos.unwrap().location.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errors,
+            vec![
+                ("error: misplaced '}'".to_owned(), "test.tal:1:6".to_owned()),
+                (
+                    "error: lambda opened here is never closed with '}'".to_owned(),
+                    "test.tal:1:8".to_owned()
+                ),
+            ]
+        );
+    }
+
+    // test that a label can't be given a name starting with 'λ', as the lambdas' labels are, so
+    // that it isn't mixed up with a lambda
+    #[test]
+    fn test_lambda_label_name() {
+        let input = vec!["|100 ?{ #01 } BRK @λ00 #02"];
+        let input = input.into_iter().map(|l| l.to_owned());
+        let mut asm = Asm::assemble(input, "test.tal", &[]);
+        let mut output = Cursor::new(Vec::new());
+        asm.output(&mut output);
+
+        let errors = asm
+            .take_diagnostics()
+            .into_iter()
+            .map(|d| (d.kind.to_string(), d.pos.unwrap().location.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errors,
+            vec![(
+                "error: label name 'λ00' is reserved: names starting with 'λ' are given to lambdas"
+                    .to_owned(),
+                "test.tal:1:19".to_owned()
+            )]
+        );
+        assert_eq!(
+            output.into_inner(),
+            vec![0x20, 0x00, 0x02, 0x80, 0x01, 0x00, 0x80, 0x02]
+        );
+    }
+
     #[test]
     fn test_output_happy() {
         let mut input = Asm {
-            program: vec![
+            program: at_test_pos(vec![
                 UxnToken::PadAbs(0x102),
                 UxnToken::RawByte(0x1),
                 UxnToken::LitShort(0xaabb),
@@ -706,8 +1457,10 @@ mod tests {
                 UxnToken::LitByte(0x22),
                 UxnToken::PadRel(0x5),
                 UxnToken::LitByte(0x33),
-            ],
+            ]),
             labels: HashMap::new(),
+            sources: SourceFiles::new(),
+            diagnostics: Diagnostics::new(),
         };
 
         let expected_output = vec![
@@ -716,7 +1469,7 @@ mod tests {
         ];
 
         let mut output = Cursor::new(Vec::new());
-        let res = input.output(&mut output);
+        let res = first_output_error(&mut input, &mut output);
         let output = output.into_inner();
 
         assert_eq!(res, Ok(()));
@@ -727,16 +1480,18 @@ mod tests {
     #[test]
     fn test_output_zero_page_write() {
         let mut input = Asm {
-            program: vec![
+            program: at_test_pos(vec![
                 UxnToken::PadAbs(0xfe),
                 UxnToken::PadRel(0x01),
                 UxnToken::RawByte(0xaa),
-            ],
+            ]),
             labels: HashMap::new(),
+            sources: SourceFiles::new(),
+            diagnostics: Diagnostics::new(),
         };
 
         let mut output = Cursor::new(Vec::new());
-        let res = input.output(&mut output);
+        let res = first_output_error(&mut input, &mut output);
 
         let expected_output = Err(AsmError::ZeroPageWrite);
         assert_eq!(res, expected_output);
@@ -747,18 +1502,20 @@ mod tests {
     #[test]
     fn test_output_zero_page_write_jump_back() {
         let mut input = Asm {
-            program: vec![
+            program: at_test_pos(vec![
                 UxnToken::PadAbs(0xff),
                 UxnToken::PadRel(0x01),
                 UxnToken::RawByte(0xaa),
                 UxnToken::PadAbs(0xff),
                 UxnToken::RawByte(0xbb),
-            ],
+            ]),
             labels: HashMap::new(),
+            sources: SourceFiles::new(),
+            diagnostics: Diagnostics::new(),
         };
 
         let mut output = Cursor::new(Vec::new());
-        let res = input.output(&mut output);
+        let res = first_output_error(&mut input, &mut output);
 
         let expected_output = Err(AsmError::ZeroPageWrite);
         assert_eq!(res, expected_output);
@@ -767,7 +1524,7 @@ mod tests {
     #[test]
     fn test_output_jump_back() {
         let mut input = Asm {
-            program: vec![
+            program: at_test_pos(vec![
                 UxnToken::PadAbs(0x100),
                 UxnToken::RawByte(0xff),
                 UxnToken::RawByte(0xff),
@@ -775,14 +1532,16 @@ mod tests {
                 UxnToken::RawByte(0xff),
                 UxnToken::PadAbs(0x101),
                 UxnToken::RawByte(0xbb),
-            ],
+            ]),
             labels: HashMap::new(),
+            sources: SourceFiles::new(),
+            diagnostics: Diagnostics::new(),
         };
 
         let expected_output = vec![0xff, 0xbb, 0xff, 0xff];
 
         let mut output = Cursor::new(Vec::new());
-        let res = input.output(&mut output);
+        let res = first_output_error(&mut input, &mut output);
         let output = output.into_inner();
 
         assert_eq!(res, Ok(()));
@@ -792,14 +1551,16 @@ mod tests {
     #[test]
     fn test_output_unrecognised_label() {
         let mut input = Asm {
-            program: vec![UxnToken::RawAbsAddr(
+            program: at_test_pos(vec![UxnToken::RawAbsAddr(
                 "unrecognised".parse::<LabelRef>().unwrap(),
-            )],
+            )]),
             labels: HashMap::new(),
+            sources: SourceFiles::new(),
+            diagnostics: Diagnostics::new(),
         };
 
         let mut writer = Cursor::new(Vec::new());
-        let output = input.output(&mut writer);
+        let output = first_output_error(&mut input, &mut writer);
 
         assert_eq!(
             output,
@@ -812,14 +1573,16 @@ mod tests {
     #[test]
     fn test_output_unrecognised_sub_label() {
         let mut input = Asm {
-            program: vec![UxnToken::RawAbsAddr(
+            program: at_test_pos(vec![UxnToken::RawAbsAddr(
                 "label/unrecognised".parse::<LabelRef>().unwrap(),
-            )],
+            )]),
             labels: HashMap::new(),
+            sources: SourceFiles::new(),
+            diagnostics: Diagnostics::new(),
         };
 
         let mut writer = Cursor::new(Vec::new());
-        let output = input.output(&mut writer);
+        let output = first_output_error(&mut input, &mut writer);
 
         assert_eq!(
             output,
@@ -835,14 +1598,16 @@ mod tests {
         let mut labels = HashMap::new();
         labels.insert("label".to_owned(), Label::new(0x100));
         let mut input = Asm {
-            program: vec![UxnToken::LitAddressZeroPage(
+            program: at_test_pos(vec![UxnToken::LitAddressZeroPage(
                 "label".parse::<LabelRef>().unwrap(),
-            )],
+            )]),
             labels,
+            sources: SourceFiles::new(),
+            diagnostics: Diagnostics::new(),
         };
 
         let mut writer = Cursor::new(Vec::new());
-        let output = input.output(&mut writer);
+        let output = first_output_error(&mut input, &mut writer);
 
         assert_eq!(
             output,
@@ -862,14 +1627,16 @@ mod tests {
             .sub_labels
             .insert("sub_label".to_owned(), 0x101);
         let mut input = Asm {
-            program: vec![UxnToken::LitAddressZeroPage(
+            program: at_test_pos(vec![UxnToken::LitAddressZeroPage(
                 "label/sub_label".parse::<LabelRef>().unwrap(),
-            )],
+            )]),
             labels,
+            sources: SourceFiles::new(),
+            diagnostics: Diagnostics::new(),
         };
 
         let mut writer = Cursor::new(Vec::new());
-        let output = input.output(&mut writer);
+        let output = first_output_error(&mut input, &mut writer);
 
         assert_eq!(
             output,
@@ -886,14 +1653,16 @@ mod tests {
         labels.insert("label".to_owned(), Label::new(0xffff));
 
         let mut input = Asm {
-            program: vec![UxnToken::LitAddressRel(
+            program: at_test_pos(vec![UxnToken::LitAddressRel(
                 "label".parse::<LabelRef>().unwrap(),
-            )],
+            )]),
             labels,
+            sources: SourceFiles::new(),
+            diagnostics: Diagnostics::new(),
         };
 
         let mut writer = Cursor::new(Vec::new());
-        let output = input.output(&mut writer);
+        let output = first_output_error(&mut input, &mut writer);
 
         assert_eq!(
             output,
@@ -914,14 +1683,16 @@ mod tests {
             .insert("sub_label".to_owned(), 0xfffd);
 
         let mut input = Asm {
-            program: vec![UxnToken::LitAddressRel(
+            program: at_test_pos(vec![UxnToken::LitAddressRel(
                 "label/sub_label".parse::<LabelRef>().unwrap(),
-            )],
+            )]),
             labels,
+            sources: SourceFiles::new(),
+            diagnostics: Diagnostics::new(),
         };
 
         let mut writer = Cursor::new(Vec::new());
-        let output = input.output(&mut writer);
+        let output = first_output_error(&mut input, &mut writer);
 
         assert_eq!(
             output,
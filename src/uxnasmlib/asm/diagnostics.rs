@@ -0,0 +1,260 @@
+use super::{split_to_token_strings, AsmError, AsmWarning};
+use crate::sourcemap::{SourceLocation, SourcePos};
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+use std::iter;
+
+// the lines of each source file, so that diagnostics can quote them
+pub struct SourceFiles {
+    files: HashMap<String, Vec<String>>,
+}
+
+impl SourceFiles {
+    pub fn new() -> Self {
+        SourceFiles {
+            files: HashMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, file_name: &str, lines: Vec<String>) {
+        self.files.insert(file_name.to_owned(), lines);
+    }
+
+    fn line(&self, location: &SourceLocation) -> Option<&str> {
+        return self
+            .files
+            .get(&location.file)?
+            .get(location.line.checked_sub(1)?)
+            .map(|line| line.as_str());
+    }
+
+    // the token at `location`, as it was written
+    pub fn token_text(&self, location: &SourceLocation) -> Option<String> {
+        let line = self.line(location)?;
+        return split_to_token_strings(iter::once(line.to_owned()), &location.file)
+            .find(|(_, token_location)| token_location.column == location.column)
+            .map(|(text, _)| text);
+    }
+
+    // report `kind`, found at `pos` if it concerns a particular token
+    pub fn diagnostic(&self, kind: DiagnosticKind, pos: Option<SourcePos>) -> Diagnostic {
+        let snippet = pos
+            .as_ref()
+            .and_then(|pos| self.line(&pos.location))
+            .map(|line| line.to_owned());
+        return Diagnostic { kind, pos, snippet };
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DiagnosticKind {
+    // the program can't be assembled
+    Error(AsmError),
+    // the program can be assembled, but probably not into what was intended
+    Warning(AsmWarning),
+}
+
+impl fmt::Display for DiagnosticKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiagnosticKind::Error(error) => write!(f, "error: {}", error),
+            DiagnosticKind::Warning(warning) => write!(f, "warning: {}", warning),
+        }
+    }
+}
+
+// a problem found by the assembler, along with where it was found and the line of source there
+#[derive(Debug, PartialEq)]
+pub struct Diagnostic {
+    pub kind: DiagnosticKind,
+    pub pos: Option<SourcePos>,
+    pub snippet: Option<String>,
+}
+
+impl fmt::Display for Diagnostic {
+    // for example:
+    //
+    // calc.tal:12:9: error: undefined label: fo
+    //     #01 ;fo JSR2
+    //         ^
+    // calc.tal:30:3: note: in expansion of macro 'CALL'
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pos = match &self.pos {
+            Some(pos) => pos,
+            None => {
+                return write!(f, "{}", self.kind);
+            }
+        };
+
+        write!(f, "{}: {}", pos.location, self.kind)?;
+
+        if let Some(snippet) = &self.snippet {
+            // tabs are kept so that the caret lines up however wide they are shown
+            let padding = snippet
+                .chars()
+                .take(pos.location.column.saturating_sub(1))
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect::<String>();
+            write!(f, "\n    {}\n    {}^", snippet, padding)?;
+        }
+
+        for expansion in &pos.expansions {
+            write!(
+                f,
+                "\n{}: note: in expansion of macro '{}'",
+                expansion.invoked_at, expansion.macro_name
+            )?;
+        }
+
+        return Ok(());
+    }
+}
+
+impl error::Error for Diagnostic {}
+
+// every problem found in assembling a program, in the order they were found
+#[derive(Debug, PartialEq, Default)]
+pub struct Diagnostics {
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    pub fn new() -> Self {
+        Diagnostics {
+            diagnostics: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Diagnostic> {
+        return self.diagnostics.iter();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.diagnostics.is_empty();
+    }
+
+    pub fn error_count(&self) -> usize {
+        return self
+            .iter()
+            .filter(|d| matches!(d.kind, DiagnosticKind::Error(_)))
+            .count();
+    }
+
+    pub fn warning_count(&self) -> usize {
+        return self
+            .iter()
+            .filter(|d| matches!(d.kind, DiagnosticKind::Warning(_)))
+            .count();
+    }
+}
+
+impl IntoIterator for Diagnostics {
+    type Item = Diagnostic;
+    type IntoIter = std::vec::IntoIter<Diagnostic>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        return self.diagnostics.into_iter();
+    }
+}
+
+fn plural(count: usize, noun: &str) -> String {
+    if count == 1 {
+        return format!("{} {}", count, noun);
+    }
+    return format!("{} {}s", count, noun);
+}
+
+impl fmt::Display for Diagnostics {
+    // each diagnostic, followed by the number of errors and warnings
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for diagnostic in self.iter() {
+            writeln!(f, "{}", diagnostic)?;
+        }
+        write!(
+            f,
+            "{} and {}",
+            plural(self.error_count(), "error"),
+            plural(self.warning_count(), "warning")
+        )
+    }
+}
+
+impl error::Error for Diagnostics {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(line: usize, column: usize) -> SourcePos {
+        return SourcePos::new(SourceLocation {
+            file: "test.tal".to_owned(),
+            line,
+            column,
+        });
+    }
+
+    #[test]
+    fn test_display() {
+        let mut sources = SourceFiles::new();
+        sources.add(
+            "test.tal",
+            vec!["%CALL { ;fo JSR2 }".to_owned(), "\t#01 CALL".to_owned()],
+        );
+
+        let diagnostic = sources.diagnostic(
+            DiagnosticKind::Error(AsmError::UndefinedLabel {
+                label_name: "fo".to_owned(),
+            }),
+            Some(at(1, 9).expanded("CALL", &at(2, 6))),
+        );
+        assert_eq!(
+            diagnostic.to_string(),
+            "test.tal:1:9: error: undefined label: fo\n    \
+            %CALL { ;fo JSR2 }\n            ^\n\
+            test.tal:2:6: note: in expansion of macro 'CALL'"
+        );
+
+        let diagnostic = sources.diagnostic(
+            DiagnosticKind::Error(AsmError::MacroEndDelimiterMisplaced),
+            Some(at(2, 2)),
+        );
+        assert_eq!(
+            diagnostic.to_string(),
+            "test.tal:2:2: error: misplaced '}'\n    \t#01 CALL\n    \t^"
+        );
+
+        let diagnostic = sources.diagnostic(DiagnosticKind::Error(AsmError::ZeroPageWrite), None);
+        assert_eq!(diagnostic.to_string(), "error: zero page write");
+    }
+
+    #[test]
+    fn test_diagnostics_display() {
+        let mut sources = SourceFiles::new();
+        sources.add("test.tal", vec!["@main BRK".to_owned()]);
+
+        let mut diagnostics = Diagnostics::new();
+        diagnostics.push(sources.diagnostic(
+            DiagnosticKind::Warning(AsmWarning::UnusedLabel {
+                label_name: "main".to_owned(),
+            }),
+            Some(at(1, 1)),
+        ));
+        diagnostics.push(sources.diagnostic(DiagnosticKind::Error(AsmError::ZeroPageWrite), None));
+        diagnostics.push(sources.diagnostic(DiagnosticKind::Error(AsmError::ZeroPageWrite), None));
+
+        assert_eq!(diagnostics.error_count(), 2);
+        assert_eq!(diagnostics.warning_count(), 1);
+        assert_eq!(
+            diagnostics.to_string(),
+            "test.tal:1:1: warning: unused label: main\n    @main BRK\n    ^\n\
+            error: zero page write\n\
+            error: zero page write\n\
+            2 errors and 1 warning"
+        );
+    }
+}
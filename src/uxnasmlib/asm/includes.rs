@@ -0,0 +1,298 @@
+use super::diagnostics::SourceFiles;
+use super::tokens::UxnToken;
+use super::{split_to_token_strings, strip_comments, validate_tokens, AsmError, PositionedToken};
+use crate::sourcemap::SourcePos;
+use std::fs;
+use std::iter;
+use std::path::{Path, PathBuf};
+
+// where the files named by include runes are looked for, and the files being read
+struct Includes<'a> {
+    search_dirs: &'a [PathBuf],
+    // the files currently being read, outermost first, each identified by its canonical path so
+    // that a cycle is caught however the files name each other
+    stack: Vec<PathBuf>,
+}
+
+// the tokens of `lines`, which are from the file named `file_name`, with the tokens of each file
+// it includes in place of the include rune naming it. An included file is looked for relative to
+// the file including it, and then in each of `search_dirs` in turn. The lines of every file read
+// are added to `sources`
+pub fn read_tokens(
+    lines: Vec<String>,
+    file_name: &str,
+    search_dirs: &[PathBuf],
+    sources: &mut SourceFiles,
+) -> Vec<PositionedToken> {
+    let mut includes = Includes {
+        search_dirs,
+        stack: fs::canonicalize(file_name).into_iter().collect(),
+    };
+    return includes.tokens_of_file(lines, file_name, sources);
+}
+
+impl Includes<'_> {
+    fn tokens_of_file(
+        &mut self,
+        lines: Vec<String>,
+        file_name: &str,
+        sources: &mut SourceFiles,
+    ) -> Vec<PositionedToken> {
+        sources.add(file_name, lines.clone());
+
+        let token_strings = split_to_token_strings(lines.into_iter(), file_name);
+        let token_strings = strip_comments(token_strings);
+        let tokens =
+            token_strings.map(|(t, location)| (t.parse::<UxnToken>(), SourcePos::new(location)));
+
+        let mut output = Vec::new();
+        for t in validate_tokens(tokens) {
+            match t {
+                Ok((UxnToken::Include(path), pos)) => {
+                    output.extend(self.include(&path, &pos, file_name, sources));
+                }
+                t => output.push(t),
+            }
+        }
+        return output;
+    }
+
+    // the tokens of the file `path`, included at `pos` by the file `including_file`
+    fn include(
+        &mut self,
+        path: &str,
+        pos: &SourcePos,
+        including_file: &str,
+        sources: &mut SourceFiles,
+    ) -> Vec<PositionedToken> {
+        let resolved = match self.resolve(path, including_file) {
+            Some(resolved) => resolved,
+            None => {
+                return vec![Err((
+                    AsmError::IncludeNotFound {
+                        file_name: path.to_owned(),
+                    },
+                    pos.clone(),
+                ))];
+            }
+        };
+
+        let canonical = fs::canonicalize(&resolved).unwrap_or_else(|_| resolved.clone());
+        if self.stack.contains(&canonical) {
+            return vec![Err((
+                AsmError::IncludeCycle {
+                    file_name: path.to_owned(),
+                },
+                pos.clone(),
+            ))];
+        }
+
+        let file_name = resolved.display().to_string();
+        let lines = match fs::read_to_string(&resolved) {
+            Ok(contents) => contents.lines().map(|l| l.to_owned()).collect(),
+            Err(err) => {
+                return vec![Err((
+                    AsmError::IncludeRead {
+                        file_name,
+                        msg: err.to_string(),
+                    },
+                    pos.clone(),
+                ))];
+            }
+        };
+
+        self.stack.push(canonical);
+        let tokens = self.tokens_of_file(lines, &file_name, sources);
+        self.stack.pop();
+        return tokens;
+    }
+
+    fn resolve(&self, path: &str, including_file: &str) -> Option<PathBuf> {
+        let relative = match Path::new(including_file).parent() {
+            Some(dir) => dir.join(path),
+            None => PathBuf::from(path),
+        };
+
+        return iter::once(relative)
+            .chain(self.search_dirs.iter().map(|dir| dir.join(path)))
+            .find(|candidate| candidate.is_file());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    // a new directory holding `files`, each given as its path within the directory and its
+    // contents
+    fn test_dir(files: &[(&str, &str)]) -> PathBuf {
+        let mut dir = std::env::temp_dir();
+        dir.push(format!("test_includes_{}", Uuid::new_v4()));
+        for (path, contents) in files {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).expect("Failed to create test directory");
+            fs::write(&path, contents).expect("Failed to write test file");
+        }
+        return dir;
+    }
+
+    fn read(dir: &Path, main: &str, search_dirs: &[PathBuf]) -> Vec<PositionedToken> {
+        let main = dir.join(main);
+        let lines = fs::read_to_string(&main)
+            .unwrap()
+            .lines()
+            .map(|l| l.to_owned())
+            .collect();
+        let mut sources = SourceFiles::new();
+        return read_tokens(
+            lines,
+            &main.display().to_string(),
+            search_dirs,
+            &mut sources,
+        );
+    }
+
+    // the tokens, each with the name of the file it came from relative to `dir`
+    fn with_files(
+        dir: &Path,
+        tokens: Vec<PositionedToken>,
+    ) -> Vec<Result<(UxnToken, String), AsmError>> {
+        let relative = |pos: SourcePos| {
+            return Path::new(&pos.location.file)
+                .strip_prefix(dir)
+                .unwrap()
+                .display()
+                .to_string();
+        };
+        return tokens
+            .into_iter()
+            .map(|t| match t {
+                Ok((t, pos)) => Ok((t, relative(pos))),
+                Err((e, _)) => Err(e),
+            })
+            .collect();
+    }
+
+    #[test]
+    fn test_include() {
+        let dir = test_dir(&[
+            ("main.tal", "#01 ~lib/a.tal ( ~not-included.tal ) #04"),
+            ("lib/a.tal", "#02 ~b.tal"),
+            ("lib/b.tal", "#03"),
+            ("search/lib/b.tal", "#ff"),
+        ]);
+
+        let output = with_files(&dir, read(&dir, "main.tal", &[dir.join("search")]));
+        assert_eq!(
+            output,
+            vec![
+                Ok((UxnToken::LitByte(0x01), "main.tal".to_owned())),
+                Ok((UxnToken::LitByte(0x02), "lib/a.tal".to_owned())),
+                Ok((UxnToken::LitByte(0x03), "lib/b.tal".to_owned())),
+                Ok((UxnToken::LitByte(0x04), "main.tal".to_owned())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_include_search_dirs() {
+        let dir = test_dir(&[
+            ("main.tal", "~devices.tal ~util.tal"),
+            ("first/devices.tal", "#01"),
+            ("second/devices.tal", "#02"),
+            ("second/util.tal", "#03"),
+        ]);
+
+        let search_dirs = [dir.join("first"), dir.join("second")];
+        let output = with_files(&dir, read(&dir, "main.tal", &search_dirs));
+        assert_eq!(
+            output,
+            vec![
+                Ok((UxnToken::LitByte(0x01), "first/devices.tal".to_owned())),
+                Ok((UxnToken::LitByte(0x03), "second/util.tal".to_owned())),
+            ]
+        );
+
+        let output = with_files(&dir, read(&dir, "main.tal", &[]));
+        assert_eq!(
+            output,
+            vec![
+                Err(AsmError::IncludeNotFound {
+                    file_name: "devices.tal".to_owned()
+                }),
+                Err(AsmError::IncludeNotFound {
+                    file_name: "util.tal".to_owned()
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_include_cycle() {
+        let dir = test_dir(&[
+            ("main.tal", "#01 ~a.tal"),
+            ("a.tal", "#02 ~lib/../b.tal"),
+            ("b.tal", "#03 ~main.tal ~a.tal"),
+            ("lib/.keep", ""),
+        ]);
+
+        let output = read(&dir, "main.tal", &[]);
+        let errors = output
+            .iter()
+            .filter_map(|t| t.as_ref().err())
+            .map(|(e, pos)| (e, pos.location.line, pos.location.column))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errors,
+            vec![
+                (
+                    &AsmError::IncludeCycle {
+                        file_name: "main.tal".to_owned()
+                    },
+                    1,
+                    5
+                ),
+                (
+                    &AsmError::IncludeCycle {
+                        file_name: "a.tal".to_owned()
+                    },
+                    1,
+                    15
+                ),
+            ]
+        );
+        assert_eq!(output.iter().filter(|t| t.is_ok()).count(), 3);
+    }
+
+    // test that macros and errors from an included file are attributed to it, and that its
+    // unused labels aren't warned of
+    #[test]
+    fn test_include_diagnostics() {
+        let dir = test_dir(&[
+            ("main.tal", "|100 ~lib.tal\nEMIT BRK"),
+            ("lib.tal", "%EMIT { ;nowhere #18 DEO2 }\n@lib-routine JMP2r"),
+        ]);
+
+        let main = dir.join("main.tal");
+        let lines = fs::read_to_string(&main).unwrap();
+        let mut asm = super::super::Asm::assemble(
+            lines.lines().map(|l| l.to_owned()),
+            &main.display().to_string(),
+            &[],
+        );
+        asm.output(&mut std::io::Cursor::new(Vec::new()));
+
+        assert_eq!(
+            asm.take_diagnostics().to_string(),
+            format!(
+                "{}:1:9: error: undefined label: nowhere\n    \
+                %EMIT {{ ;nowhere #18 DEO2 }}\n            ^\n\
+                {}:2:1: note: in expansion of macro 'EMIT'\n\
+                1 error and 0 warnings",
+                dir.join("lib.tal").display(),
+                main.display()
+            )
+        );
+    }
+}
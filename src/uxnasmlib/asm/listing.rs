@@ -0,0 +1,183 @@
+use crate::sourcemap::{MacroExpansion, SourcePos};
+use std::fmt;
+
+// the most bytes shown on a line of a listing, with the rest of the bytes of a token that writes
+// more shown on the lines after
+const BYTES_PER_LINE: usize = 4;
+
+// a token of an assembled program
+#[derive(Debug, PartialEq)]
+pub struct ListingEntry {
+    // the program counter at the token
+    pub address: u16,
+    // the bytes the token wrote, if any
+    pub bytes: Vec<u8>,
+    pub pos: SourcePos,
+    // the token as it was written in the source
+    pub text: String,
+    // whether the token defines a label or sub-label
+    pub is_label: bool,
+}
+
+// a listing of an assembled program, with a line for each token giving its address, the bytes it
+// wrote and the token itself. Label definitions are set apart from the tokens following them,
+// and the tokens produced by a macro are indented under a line giving the macro's invocation.
+// For example:
+//
+// 0100               @main
+// 0100  80 01          #01
+// 0102                 EMIT
+// 0102  80 18            #18
+// 0104  17               DEO
+pub struct Listing {
+    // in the order they were assembled
+    entries: Vec<ListingEntry>,
+}
+
+impl Listing {
+    pub fn new() -> Self {
+        Listing {
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, entry: ListingEntry) {
+        self.entries.push(entry);
+    }
+}
+
+impl Default for Listing {
+    fn default() -> Self {
+        return Listing::new();
+    }
+}
+
+// write a line of a listing, with `text` indented to `depth`
+fn write_line(
+    f: &mut fmt::Formatter<'_>,
+    address: u16,
+    bytes: &[u8],
+    depth: usize,
+    text: &str,
+) -> fmt::Result {
+    let bytes = bytes
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let line = format!(
+        "{:04x}  {:bytes_width$}  {:indent$}{}",
+        address,
+        bytes,
+        "",
+        text,
+        bytes_width = BYTES_PER_LINE * 3 - 1,
+        indent = depth * 2
+    );
+    return writeln!(f, "{}", line.trim_end());
+}
+
+impl fmt::Display for Listing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // the macro invocations (innermost first) the previous token was produced by
+        let mut invocations: &[MacroExpansion] = &[];
+
+        for entry in &self.entries {
+            let expansions = &entry.pos.expansions;
+
+            // a line is given to each invocation this token was produced by that the previous
+            // one wasn't, outermost first
+            let shared = invocations
+                .iter()
+                .rev()
+                .zip(expansions.iter().rev())
+                .take_while(|(previous, current)| previous == current)
+                .count();
+            for (depth, expansion) in expansions.iter().rev().enumerate().skip(shared) {
+                write_line(f, entry.address, &[], depth + 1, &expansion.macro_name)?;
+            }
+            invocations = expansions;
+
+            let depth = if entry.is_label {
+                expansions.len()
+            } else {
+                expansions.len() + 1
+            };
+            let mut chunks = entry.bytes.chunks(BYTES_PER_LINE);
+            write_line(
+                f,
+                entry.address,
+                chunks.next().unwrap_or(&[]),
+                depth,
+                &entry.text,
+            )?;
+            for (i, chunk) in chunks.enumerate() {
+                let address = entry
+                    .address
+                    .wrapping_add(((i + 1) * BYTES_PER_LINE) as u16);
+                write_line(f, address, chunk, 0, "")?;
+            }
+        }
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sourcemap::SourceLocation;
+
+    fn at(line: usize, column: usize) -> SourcePos {
+        return SourcePos::new(SourceLocation {
+            file: "test.tal".to_owned(),
+            line,
+            column,
+        });
+    }
+
+    fn entry(address: u16, bytes: Vec<u8>, pos: SourcePos, text: &str) -> ListingEntry {
+        return ListingEntry {
+            address,
+            bytes,
+            pos,
+            text: text.to_owned(),
+            is_label: text.starts_with('@') || text.starts_with('&'),
+        };
+    }
+
+    #[test]
+    fn test_display() {
+        let emit = at(3, 1);
+        let inner = at(1, 12).expanded("INNER", &at(2, 10).expanded("OUTER", &emit));
+        let outer = at(2, 16).expanded("OUTER", &emit);
+
+        let mut listing = Listing::new();
+        listing.push(entry(0x100, vec![], at(4, 1), "|100"));
+        listing.push(entry(0x100, vec![], at(4, 6), "@main"));
+        listing.push(entry(0x100, vec![0x80, 0x01], at(4, 12), "#01"));
+        listing.push(entry(0x102, vec![0x80, 0x18], inner, "#18"));
+        listing.push(entry(0x104, vec![0x17], outer, "DEO"));
+        listing.push(entry(0x105, vec![], at(5, 1), "&loop"));
+        listing.push(entry(
+            0x105,
+            "hello".as_bytes().to_vec(),
+            at(5, 7),
+            "\"hello",
+        ));
+
+        assert_eq!(
+            listing.to_string(),
+            "\
+            0100                 |100\n\
+            0100               @main\n\
+            0100  80 01          #01\n\
+            0102                 OUTER\n\
+            0102                   INNER\n\
+            0102  80 18              #18\n\
+            0104  17               DEO\n\
+            0105               &loop\n\
+            0105  68 65 6c 6c    \"hello\n\
+            0109  6f\n"
+        );
+    }
+}
@@ -1,5 +1,6 @@
-use super::tokens::UxnToken;
-use super::AsmError;
+use super::tokens::{LabelRef, UxnToken};
+use super::{AsmError, PositionedToken};
+use crate::sourcemap::SourcePos;
 use std::collections::HashMap;
 use std::mem;
 
@@ -10,155 +11,222 @@ enum MacroState {
     },
     MacroDefinitionBody {
         macro_name: String,
-        macro_body: Vec<UxnToken>,
+        macro_body: Vec<(UxnToken, SourcePos)>,
+        // how many lambdas in the body are open, the body ending at the first '}' outside them
+        lambda_depth: usize,
     },
 }
 
+// the token a '{' that isn't the start of a macro body stands for: a call to (or rather, a jump
+// past) the lambda it opens, leaving the lambda's address on the return stack
+fn lambda_start() -> UxnToken {
+    return UxnToken::JsiAddress(LabelRef::Lambda);
+}
+
 const MAX_MACRO_DEPTH: u8 = 5;
 
+// expand the macro `macro_name`, invoked at `invoked_at`. The tokens of the expansion are
+// positioned in the macro's body, expanded through the invocation. If there is no such macro
+// the word is taken to be a subroutine call
 fn expand_macro(
     macro_name: &str,
-    macros: &HashMap<String, Vec<UxnToken>>,
+    invoked_at: &SourcePos,
+    macros: &HashMap<String, Vec<(UxnToken, SourcePos)>>,
     depth: u8,
-) -> Vec<Result<UxnToken, AsmError>> {
+) -> Vec<PositionedToken> {
     if depth >= MAX_MACRO_DEPTH {
-        return vec![Err(AsmError::MaxMacroCallDepthExceeded {
-            macro_name: macro_name.to_owned(),
-        })];
+        return vec![Err((
+            AsmError::MaxMacroCallDepthExceeded {
+                macro_name: macro_name.to_owned(),
+            },
+            invoked_at.clone(),
+        ))];
     }
 
     if let Some(macro_body) = macros.get(macro_name) {
         return macro_body
             .iter()
-            .cloned()
-            .flat_map(|e| match e {
-                UxnToken::MacroInvocation(ref macro_name) => {
-                    expand_macro(macro_name, macros, depth + 1)
-                }
-                _ => {
-                    vec![Ok(e)]
+            .flat_map(|(e, pos)| {
+                let pos = pos.expanded(macro_name, invoked_at);
+                match e {
+                    UxnToken::MacroInvocation(ref inner_macro_name) => {
+                        expand_macro(inner_macro_name, &pos, macros, depth + 1)
+                    }
+                    _ => {
+                        vec![Ok((e.clone(), pos))]
+                    }
                 }
             })
             .collect::<Vec<_>>();
     } else {
-        return vec![Err(AsmError::UnrecognisedMacro {
-            macro_name: macro_name.to_owned(),
-        })];
+        // a word that isn't a macro is a call to the label of that name
+        let label_ref = macro_name.parse::<LabelRef>().unwrap();
+        return vec![Ok((UxnToken::JsiAddress(label_ref), invoked_at.clone()))];
     }
 }
 
 // strips macro definitions out of token stream, and expands
-// macro invocations
-pub fn process_macros<I>(input: I) -> impl Iterator<Item = Result<UxnToken, AsmError>>
+// macro invocations. Lambdas are left for `get_labels` to match up, with each '{' opening one
+// replaced by the jump past it
+pub fn process_macros<I>(input: I) -> impl Iterator<Item = PositionedToken>
 where
-    I: Iterator<Item = Result<UxnToken, AsmError>>,
+    I: Iterator<Item = PositionedToken>,
 {
     let mut macros = HashMap::new();
     let mut state = MacroState::MainBody;
 
     input.flat_map(move |t| match t {
         Err(e) => vec![Err(e)],
-        Ok(UxnToken::MacroDefine(ref macro_name)) => match state {
+        Ok((UxnToken::MacroDefine(ref macro_name), ref pos)) => match state {
             MacroState::MainBody => {
+                // the second definition is still read, so that its body isn't taken to be part
+                // of the main body, but the first definition is the one kept
+                let mut output = vec![];
                 if macros.contains_key(macro_name) {
-                    return vec![Err(AsmError::DoubleMacroDefine {
-                        macro_name: macro_name.clone(),
-                    })];
+                    output.push(Err((
+                        AsmError::DoubleMacroDefine {
+                            macro_name: macro_name.clone(),
+                        },
+                        pos.clone(),
+                    )));
                 }
 
                 state = MacroState::MacroDefinitionHead {
                     macro_name: macro_name.clone(),
                 };
-                return vec![];
+                return output;
             }
             MacroState::MacroDefinitionHead { macro_name: ref _m } => {
+                // the unfinished definition is abandoned for this one
                 let macro_name = macro_name.clone();
-                return vec![Err(AsmError::MalformedMacroDefine { macro_name })];
+                state = MacroState::MacroDefinitionHead {
+                    macro_name: macro_name.clone(),
+                };
+                return vec![Err((
+                    AsmError::MalformedMacroDefine { macro_name },
+                    pos.clone(),
+                ))];
             }
             MacroState::MacroDefinitionBody {
                 macro_name: ref outer_macro_name,
-                macro_body: ref _b,
+                ..
             } => {
                 let inner_macro_name = macro_name.clone();
                 let outer_macro_name = outer_macro_name.clone();
-                return vec![Err(AsmError::MacroDefineWithinMacro {
-                    outer_macro_name,
-                    inner_macro_name,
-                })];
+                return vec![Err((
+                    AsmError::MacroDefineWithinMacro {
+                        outer_macro_name,
+                        inner_macro_name,
+                    },
+                    pos.clone(),
+                ))];
             }
         },
-        Ok(UxnToken::MacroStartDelimiter) => match state {
+        Ok((UxnToken::MacroStartDelimiter, ref pos)) => match state {
             MacroState::MainBody => {
-                return vec![Err(AsmError::MacroStartDelimiterMisplaced)];
+                return vec![Ok((lambda_start(), pos.clone()))];
             }
             MacroState::MacroDefinitionHead { ref macro_name } => {
                 state = MacroState::MacroDefinitionBody {
                     macro_name: macro_name.clone(),
                     macro_body: Vec::new(),
+                    lambda_depth: 0,
                 };
                 return vec![];
             }
             MacroState::MacroDefinitionBody {
-                macro_name: ref _name,
-                macro_body: ref _body,
+                ref mut macro_body,
+                ref mut lambda_depth,
+                ..
             } => {
-                return vec![Err(AsmError::MacroStartDelimiterMisplaced)];
+                macro_body.push((lambda_start(), pos.clone()));
+                *lambda_depth += 1;
+                return vec![];
             }
         },
-        Ok(UxnToken::MacroEndDelimiter) => match state {
+        Ok((UxnToken::MacroEndDelimiter, ref pos)) => match state {
             MacroState::MainBody => {
-                return vec![Err(AsmError::MacroEndDelimiterMisplaced)];
+                // the end of a lambda
+                return vec![t];
             }
             MacroState::MacroDefinitionHead { macro_name: _ } => {
-                return vec![Err(AsmError::MacroEndDelimiterMisplaced)];
+                state = MacroState::MainBody;
+                return vec![Err((AsmError::MacroEndDelimiterMisplaced, pos.clone()))];
             }
             MacroState::MacroDefinitionBody {
-                macro_name: _,
-                macro_body: _,
-            } => {
+                ref mut macro_body,
+                ref mut lambda_depth,
+                ..
+            } if *lambda_depth > 0 => {
+                macro_body.push((UxnToken::MacroEndDelimiter, pos.clone()));
+                *lambda_depth -= 1;
+                return vec![];
+            }
+            MacroState::MacroDefinitionBody { .. } => {
                 let old_state = mem::replace(&mut state, MacroState::MainBody);
                 if let MacroState::MacroDefinitionBody {
                     macro_name,
                     macro_body,
+                    ..
                 } = old_state
                 {
-                    macros.insert(macro_name.clone(), macro_body);
+                    macros.entry(macro_name).or_insert(macro_body);
                 }
 
                 return vec![];
             }
         },
-        Ok(UxnToken::MacroInvocation(ref macro_name)) => match state {
+        Ok((UxnToken::MacroInvocation(ref macro_name), ref pos)) => match state {
             MacroState::MainBody => {
-                return expand_macro(macro_name, &macros, 0);
+                return expand_macro(macro_name, pos, &macros, 0);
             }
-            MacroState::MacroDefinitionHead { ref macro_name } => {
-                return vec![Err(AsmError::MalformedMacroDefine {
-                    macro_name: macro_name.clone(),
-                })];
+            MacroState::MacroDefinitionHead {
+                macro_name: ref defined_macro_name,
+            } => {
+                // the unfinished definition is abandoned, and the invocation taken to be
+                // part of the main body
+                let mut output = vec![Err((
+                    AsmError::MalformedMacroDefine {
+                        macro_name: defined_macro_name.clone(),
+                    },
+                    pos.clone(),
+                ))];
+                state = MacroState::MainBody;
+                output.extend(expand_macro(macro_name, pos, &macros, 0));
+                return output;
             }
             MacroState::MacroDefinitionBody {
-                macro_name: _,
-                ref mut macro_body,
+                ref mut macro_body, ..
             } => {
                 macro_body.push(t.unwrap());
                 return vec![];
             }
         },
-        Ok(t) => match state {
+        Ok((t, pos)) => match state {
             MacroState::MainBody => {
-                return vec![Ok(t)];
+                return vec![Ok((t, pos))];
             }
             MacroState::MacroDefinitionHead { ref macro_name } => {
-                return vec![Err(AsmError::MalformedMacroDefine {
-                    macro_name: macro_name.clone(),
-                })];
+                // the unfinished definition is abandoned, and the token taken to be part of
+                // the main body
+                let error = Err((
+                    AsmError::MalformedMacroDefine {
+                        macro_name: macro_name.clone(),
+                    },
+                    pos.clone(),
+                ));
+                state = MacroState::MainBody;
+                return vec![error, Ok((t, pos))];
             }
             MacroState::MacroDefinitionBody {
-                macro_name: _,
                 ref mut macro_body,
+                ref mut lambda_depth,
+                ..
             } => {
-                macro_body.push(t);
+                if t.opens_lambda() {
+                    *lambda_depth += 1;
+                }
+                macro_body.push((t, pos));
                 return vec![];
             }
         },
@@ -167,7 +235,9 @@ where
 
 #[cfg(test)]
 mod tests {
+    use super::super::tests::{positioned, unpositioned};
     use super::*;
+    use crate::sourcemap::{MacroExpansion, SourceLocation};
 
     // test `process_macros` function; that it strips correctly defined,
     // but unused, macros from the input stream
@@ -203,7 +273,7 @@ mod tests {
             Ok(UxnToken::RawByte(0xff)),
         ];
 
-        let output = process_macros(input.into_iter()).collect::<Vec<_>>();
+        let output = unpositioned(process_macros(positioned(input)));
 
         assert_eq!(output, expected_output);
     }
@@ -227,7 +297,9 @@ mod tests {
             Ok(UxnToken::MacroEndDelimiter),
         ];
 
-        let output = process_macros(input.into_iter()).collect::<Result<Vec<_>, AsmError>>();
+        let output = process_macros(positioned(input))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|(e, _)| e);
 
         assert_eq!(
             output,
@@ -247,7 +319,9 @@ mod tests {
             Ok(UxnToken::MacroDefine("test_macro_b".to_owned())),
         ];
 
-        let output = process_macros(input.into_iter()).collect::<Result<Vec<_>, AsmError>>();
+        let output = process_macros(positioned(input))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|(e, _)| e);
 
         assert_eq!(
             output,
@@ -272,7 +346,9 @@ mod tests {
             Ok(UxnToken::MacroEndDelimiter),
         ];
 
-        let output = process_macros(input.into_iter()).collect::<Result<Vec<_>, AsmError>>();
+        let output = process_macros(positioned(input))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|(e, _)| e);
 
         assert_eq!(
             output,
@@ -283,51 +359,62 @@ mod tests {
         );
     }
 
-    // test that the opening curly bracket of a macro, when on
-    // its own in the main program body, results in an appropriate
-    // error
+    // test that an opening curly bracket on its own in the main program body opens a lambda,
+    // with the closing bracket left for `get_labels` to match to it
     #[test]
-    fn test_macro_start_delimiter_misplaced_in_main_body() {
+    fn test_lambda_in_main_body() {
         let input = vec![
             Ok(UxnToken::RawByte(0x99)),
             Ok(UxnToken::MacroStartDelimiter),
             Ok(UxnToken::RawByte(0xf9)),
+            Ok(UxnToken::MacroEndDelimiter),
         ];
 
-        let output = process_macros(input.into_iter()).collect::<Result<Vec<_>, AsmError>>();
+        let output = unpositioned(process_macros(positioned(input)));
 
-        assert_eq!(output, Err(AsmError::MacroStartDelimiterMisplaced));
+        assert_eq!(
+            output,
+            vec![
+                Ok(UxnToken::RawByte(0x99)),
+                Ok(UxnToken::JsiAddress(LabelRef::Lambda)),
+                Ok(UxnToken::RawByte(0xf9)),
+                Ok(UxnToken::MacroEndDelimiter),
+            ]
+        );
     }
 
+    // test that lambdas within a macro body are kept in the body, with the body ending at the
+    // first closing bracket outside of them
     #[test]
-    fn test_macro_start_delimiter_misplaced_in_macro_body() {
+    fn test_lambda_in_macro_body() {
         let input = vec![
             Ok(UxnToken::RawByte(0x99)),
             Ok(UxnToken::MacroDefine("test_macro".to_owned())),
             Ok(UxnToken::MacroStartDelimiter),
             Ok(UxnToken::RawByte(0xf9)),
+            Ok(UxnToken::JciAddress(LabelRef::Lambda)),
             Ok(UxnToken::MacroStartDelimiter),
-            Ok(UxnToken::RawByte(0xf9)),
+            Ok(UxnToken::RawByte(0xf8)),
             Ok(UxnToken::MacroEndDelimiter),
             Ok(UxnToken::MacroEndDelimiter),
-        ];
-
-        let output = process_macros(input.into_iter()).collect::<Result<Vec<_>, AsmError>>();
-
-        assert_eq!(output, Err(AsmError::MacroStartDelimiterMisplaced));
-    }
-
-    #[test]
-    fn test_macro_end_delimiter_misplaced_in_main_body() {
-        let input = vec![
-            Ok(UxnToken::RawByte(0x99)),
             Ok(UxnToken::MacroEndDelimiter),
-            Ok(UxnToken::RawByte(0xf9)),
+            Ok(UxnToken::MacroInvocation("test_macro".to_owned())),
         ];
 
-        let output = process_macros(input.into_iter()).collect::<Result<Vec<_>, AsmError>>();
+        let output = unpositioned(process_macros(positioned(input)));
 
-        assert_eq!(output, Err(AsmError::MacroEndDelimiterMisplaced));
+        assert_eq!(
+            output,
+            vec![
+                Ok(UxnToken::RawByte(0x99)),
+                Ok(UxnToken::RawByte(0xf9)),
+                Ok(UxnToken::JciAddress(LabelRef::Lambda)),
+                Ok(UxnToken::JsiAddress(LabelRef::Lambda)),
+                Ok(UxnToken::RawByte(0xf8)),
+                Ok(UxnToken::MacroEndDelimiter),
+                Ok(UxnToken::MacroEndDelimiter),
+            ]
+        );
     }
 
     #[test]
@@ -337,7 +424,9 @@ mod tests {
             Ok(UxnToken::MacroEndDelimiter),
         ];
 
-        let output = process_macros(input.into_iter()).collect::<Result<Vec<_>, AsmError>>();
+        let output = process_macros(positioned(input))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|(e, _)| e);
 
         assert_eq!(output, Err(AsmError::MacroEndDelimiterMisplaced));
     }
@@ -349,7 +438,9 @@ mod tests {
             Ok(UxnToken::RawByte(0x99)),
         ];
 
-        let output = process_macros(input.into_iter()).collect::<Result<Vec<_>, AsmError>>();
+        let output = process_macros(positioned(input))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|(e, _)| e);
         assert_eq!(
             output,
             Err(AsmError::MalformedMacroDefine {
@@ -358,6 +449,44 @@ mod tests {
         );
     }
 
+    // test that `process_macros` carries on after errors, with each reported once and the
+    // tokens around them kept
+    #[test]
+    fn test_error_recovery() {
+        let input = vec![
+            Ok(UxnToken::MacroDefine("incomplete".to_owned())),
+            Ok(UxnToken::RawByte(0x01)),
+            Ok(UxnToken::MacroDefine("empty".to_owned())),
+            Ok(UxnToken::MacroEndDelimiter),
+            Ok(UxnToken::MacroDefine("twice".to_owned())),
+            Ok(UxnToken::MacroStartDelimiter),
+            Ok(UxnToken::RawByte(0x02)),
+            Ok(UxnToken::MacroEndDelimiter),
+            Ok(UxnToken::MacroDefine("twice".to_owned())),
+            Ok(UxnToken::MacroStartDelimiter),
+            Ok(UxnToken::RawByte(0x03)),
+            Ok(UxnToken::MacroEndDelimiter),
+            Ok(UxnToken::MacroInvocation("twice".to_owned())),
+        ];
+
+        let output = unpositioned(process_macros(positioned(input)));
+
+        assert_eq!(
+            output,
+            vec![
+                Err(AsmError::MalformedMacroDefine {
+                    macro_name: "incomplete".to_owned()
+                }),
+                Ok(UxnToken::RawByte(0x01)),
+                Err(AsmError::MacroEndDelimiterMisplaced),
+                Err(AsmError::DoubleMacroDefine {
+                    macro_name: "twice".to_owned()
+                }),
+                Ok(UxnToken::RawByte(0x02)),
+            ]
+        );
+    }
+
     #[test]
     fn test_macro_invocation() {
         let input = vec![
@@ -386,7 +515,7 @@ mod tests {
             Ok(UxnToken::RawByte(0xff)),
         ];
 
-        let output = process_macros(input.into_iter()).collect::<Vec<_>>();
+        let output = unpositioned(process_macros(positioned(input)));
 
         assert_eq!(output, expected_output);
     }
@@ -457,11 +586,56 @@ mod tests {
             Ok(UxnToken::RawByte(0xdd)),
         ];
 
-        let output = process_macros(input.into_iter()).collect::<Vec<_>>();
+        let output = unpositioned(process_macros(positioned(input)));
 
         assert_eq!(output, expected_output);
     }
 
+    // tokens from a macro should be positioned in the macro's body, and say which invocations
+    // they were expanded through
+    #[test]
+    fn test_macro_invocation_positions() {
+        let at = |line, column| {
+            return SourcePos::new(SourceLocation {
+                file: "test.tal".to_owned(),
+                line,
+                column,
+            });
+        };
+
+        // %INNER { #01 }
+        // %OUTER { INNER }
+        // OUTER
+        let input = vec![
+            Ok((UxnToken::MacroDefine("INNER".to_owned()), at(1, 1))),
+            Ok((UxnToken::MacroStartDelimiter, at(1, 8))),
+            Ok((UxnToken::LitByte(0x01), at(1, 10))),
+            Ok((UxnToken::MacroEndDelimiter, at(1, 14))),
+            Ok((UxnToken::MacroDefine("OUTER".to_owned()), at(2, 1))),
+            Ok((UxnToken::MacroStartDelimiter, at(2, 8))),
+            Ok((UxnToken::MacroInvocation("INNER".to_owned()), at(2, 10))),
+            Ok((UxnToken::MacroEndDelimiter, at(2, 16))),
+            Ok((UxnToken::MacroInvocation("OUTER".to_owned()), at(3, 1))),
+        ];
+
+        let output = process_macros(input.into_iter()).collect::<Vec<_>>();
+
+        let expected_pos = SourcePos {
+            location: at(1, 10).location,
+            expansions: vec![
+                MacroExpansion {
+                    macro_name: "INNER".to_owned(),
+                    invoked_at: at(2, 10).location,
+                },
+                MacroExpansion {
+                    macro_name: "OUTER".to_owned(),
+                    invoked_at: at(3, 1).location,
+                },
+            ],
+        };
+        assert_eq!(output, vec![Ok((UxnToken::LitByte(0x01), expected_pos))]);
+    }
+
     #[test]
     fn test_nested_macro_invocation_beyond_max() {
         let input = vec![
@@ -478,7 +652,9 @@ mod tests {
             Ok(UxnToken::MacroInvocation("test_macro1".to_owned())),
         ];
 
-        let output = process_macros(input.into_iter()).collect::<Result<Vec<_>, AsmError>>();
+        let output = process_macros(positioned(input))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|(e, _)| e);
 
         assert_eq!(
             output,
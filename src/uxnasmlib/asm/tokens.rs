@@ -16,12 +16,18 @@ pub enum LabelRef {
     SubLabel {
         sub_label_name: String,
     },
+    // the lambda opened by the reference, until it is given a label of its own
+    Lambda,
 }
 
 impl FromStr for LabelRef {
     type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "{" {
+            return Ok(LabelRef::Lambda);
+        }
+
         let c1 = s.get(0..1);
 
         // attempt to parse as partial sub-label
@@ -58,13 +64,18 @@ pub enum UxnToken {
     PadRel(u16),
     LabelDefine(String),
     SubLabelDefine(String),
-    // Include,
+    Include(String),
     LitByte(u8),
     LitShort(u16),
     LitAddressZeroPage(LabelRef),
     LitAddressRel(LabelRef),
     LitAddressAbs(LabelRef),
     RawAbsAddr(LabelRef),
+    RawZeroPageAddr(LabelRef),
+    RawRelAddr(LabelRef),
+    JmiAddress(LabelRef),
+    JciAddress(LabelRef),
+    JsiAddress(LabelRef),
     MacroInvocation(String),
     RawByte(u8),
     RawShort(u16),
@@ -140,7 +151,112 @@ fn get_address_of_label(
                 sub_label_name: sub_label_name.clone(),
             });
         }
+        LabelRef::Lambda => {
+            return Err(GetBytesError::UndefinedLabel {
+                label_name: "{".to_owned(),
+            });
+        }
+    }
+}
+
+// the address of the label `label_ref`, which must be in the zero page
+fn get_zero_page_address_of_label(
+    label_ref: &LabelRef,
+    prog_state: &ProgState,
+) -> Result<u8, GetBytesError> {
+    let address = get_address_of_label(label_ref, prog_state)?;
+    if address > 0xff {
+        // not in zero-page
+        match label_ref {
+            LabelRef::Label { label_name } => {
+                return Err(GetBytesError::LabelNotInZeroPage {
+                    label_name: label_name.clone(),
+                });
+            }
+            LabelRef::SubLabel { sub_label_name } => {
+                let label_name = &prog_state.current_label;
+                return Err(GetBytesError::SubLabelNotInZeroPage {
+                    label_name: label_name.clone(),
+                    sub_label_name: sub_label_name.clone(),
+                });
+            }
+            LabelRef::FullSubLabel {
+                label_name,
+                sub_label_name,
+            } => {
+                return Err(GetBytesError::SubLabelNotInZeroPage {
+                    label_name: label_name.clone(),
+                    sub_label_name: sub_label_name.clone(),
+                });
+            }
+            // `get_address_of_label` has already failed for a lambda not yet given its label
+            LabelRef::Lambda => {
+                unreachable!("lambda references are resolved to λ labels before byte generation")
+            }
+        }
     }
+
+    let bytes = address.to_be_bytes();
+    return Ok(bytes[1]);
+}
+
+// the distance to the label `label_ref` from the address `from`, which must fit in a signed
+// byte
+fn get_relative_address_of_label(
+    label_ref: &LabelRef,
+    prog_state: &ProgState,
+    from: u16,
+) -> Result<i8, GetBytesError> {
+    let address = get_address_of_label(label_ref, prog_state)?;
+    let address: i32 = i32::from(address) - i32::from(from);
+    if address > i8::MAX.into() || address < i8::MIN.into() {
+        // more than one byte needed for rel address
+        match label_ref {
+            LabelRef::Label { label_name } => {
+                return Err(GetBytesError::RelLabelNotInRange {
+                    label_name: label_name.clone(),
+                });
+            }
+            LabelRef::SubLabel { sub_label_name } => {
+                let label_name = &prog_state.current_label;
+                return Err(GetBytesError::RelSubLabelNotInRange {
+                    label_name: label_name.clone(),
+                    sub_label_name: sub_label_name.clone(),
+                });
+            }
+            LabelRef::FullSubLabel {
+                label_name,
+                sub_label_name,
+            } => {
+                return Err(GetBytesError::RelSubLabelNotInRange {
+                    label_name: label_name.clone(),
+                    sub_label_name: sub_label_name.clone(),
+                });
+            }
+            // `get_address_of_label` has already failed for a lambda not yet given its label
+            LabelRef::Lambda => {
+                unreachable!("lambda references are resolved to λ labels before byte generation")
+            }
+        }
+    }
+
+    let address: i8 = address.try_into().unwrap();
+    return Ok(address);
+}
+
+// the bytes of the immediate jump instruction `instr` to the label `label_ref`, the instruction
+// being followed by the distance to the label from the end of the instruction. As the distance
+// is a short, and wraps around the end of memory, every label is in range
+fn immediate_jump_bytes(
+    instr: u8,
+    label_ref: &LabelRef,
+    prog_state: &ProgState,
+) -> Result<Vec<u8>, GetBytesError> {
+    let address = get_address_of_label(label_ref, prog_state)?;
+    let offset = address.wrapping_sub(prog_state.counter).wrapping_sub(3);
+    let bytes = offset.to_be_bytes();
+
+    return Ok(vec![instr, bytes[0], bytes[1]]);
 }
 
 impl UxnToken {
@@ -153,6 +269,7 @@ impl UxnToken {
             UxnToken::MacroInvocation(_) => return Err(GetBytesError::NotWritableToken),
             UxnToken::PadAbs(_) => return Err(GetBytesError::NotWritableToken),
             UxnToken::PadRel(_) => return Err(GetBytesError::NotWritableToken),
+            UxnToken::Include(_) => return Err(GetBytesError::NotWritableToken),
             UxnToken::RawByte(b) => return Ok(vec![*b]),
             UxnToken::RawShort(s) => {
                 let bytes = s.to_be_bytes();
@@ -167,68 +284,13 @@ impl UxnToken {
                 return Ok(vec![0xA0, bytes[0], bytes[1]]);
             }
             UxnToken::LitAddressZeroPage(label_ref) => {
-                let address = get_address_of_label(label_ref, prog_state)?;
-                if address > 0xff {
-                    // not in zero-page
-                    match label_ref {
-                        LabelRef::Label { label_name } => {
-                            return Err(GetBytesError::LabelNotInZeroPage {
-                                label_name: label_name.clone(),
-                            });
-                        }
-                        LabelRef::SubLabel { sub_label_name } => {
-                            let label_name = &prog_state.current_label;
-                            return Err(GetBytesError::SubLabelNotInZeroPage {
-                                label_name: label_name.clone(),
-                                sub_label_name: sub_label_name.clone(),
-                            });
-                        }
-                        LabelRef::FullSubLabel {
-                            label_name,
-                            sub_label_name,
-                        } => {
-                            return Err(GetBytesError::SubLabelNotInZeroPage {
-                                label_name: label_name.clone(),
-                                sub_label_name: sub_label_name.clone(),
-                            });
-                        }
-                    }
-                }
-
-                let bytes = address.to_be_bytes();
-                return Ok(vec![0x80, bytes[1]]);
+                let address = get_zero_page_address_of_label(label_ref, prog_state)?;
+                return Ok(vec![0x80, address]);
             }
             UxnToken::LitAddressRel(label_ref) => {
-                let address = get_address_of_label(label_ref, prog_state)?;
-                let address: i32 = i32::from(address) - i32::from(prog_state.counter) - 3;
-                if address > i8::MAX.into() || address < i8::MIN.into() {
-                    // more than one byte needed for rel address
-                    match label_ref {
-                        LabelRef::Label { label_name } => {
-                            return Err(GetBytesError::RelLabelNotInRange {
-                                label_name: label_name.clone(),
-                            });
-                        }
-                        LabelRef::SubLabel { sub_label_name } => {
-                            let label_name = &prog_state.current_label;
-                            return Err(GetBytesError::RelSubLabelNotInRange {
-                                label_name: label_name.clone(),
-                                sub_label_name: sub_label_name.clone(),
-                            });
-                        }
-                        LabelRef::FullSubLabel {
-                            label_name,
-                            sub_label_name,
-                        } => {
-                            return Err(GetBytesError::RelSubLabelNotInRange {
-                                label_name: label_name.clone(),
-                                sub_label_name: sub_label_name.clone(),
-                            });
-                        }
-                    }
-                }
-
-                let address: i8 = address.try_into().unwrap();
+                // the distance is from the end of the literal
+                let from = prog_state.counter.wrapping_add(3);
+                let address = get_relative_address_of_label(label_ref, prog_state, from)?;
                 let bytes = address.to_be_bytes();
 
                 return Ok(vec![0x80, bytes[0]]);
@@ -246,6 +308,28 @@ impl UxnToken {
                 let bytes = address.to_be_bytes();
                 return Ok(vec![bytes[0], bytes[1]]);
             }
+            UxnToken::RawZeroPageAddr(label_ref) => {
+                let address = get_zero_page_address_of_label(label_ref, prog_state)?;
+                return Ok(vec![address]);
+            }
+            UxnToken::RawRelAddr(label_ref) => {
+                // following the reference assembler, the distance is from two bytes on, as if
+                // the address were the operand of a literal
+                let from = prog_state.counter.wrapping_add(2);
+                let address = get_relative_address_of_label(label_ref, prog_state, from)?;
+                let bytes = address.to_be_bytes();
+
+                return Ok(vec![bytes[0]]);
+            }
+            UxnToken::JmiAddress(label_ref) => {
+                return immediate_jump_bytes(0x40, label_ref, prog_state);
+            }
+            UxnToken::JciAddress(label_ref) => {
+                return immediate_jump_bytes(0x20, label_ref, prog_state);
+            }
+            UxnToken::JsiAddress(label_ref) => {
+                return immediate_jump_bytes(0x60, label_ref, prog_state);
+            }
         }
     }
 
@@ -256,6 +340,7 @@ impl UxnToken {
             UxnToken::MacroStartDelimiter => return 0x0,
             UxnToken::MacroEndDelimiter => return 0x0,
             UxnToken::MacroInvocation(_) => return 0x0,
+            UxnToken::Include(_) => return 0x0,
             UxnToken::RawByte(_) => return 0x1,
             UxnToken::RawShort(_) => return 0x2,
             UxnToken::RawWord(w) => return w.len().try_into().unwrap(),
@@ -267,11 +352,48 @@ impl UxnToken {
             UxnToken::LabelDefine(_) => return 0x0,
             UxnToken::SubLabelDefine(_) => return 0x0,
             UxnToken::RawAbsAddr(_) => return 0x2,
+            UxnToken::RawZeroPageAddr(_) => return 0x1,
+            UxnToken::RawRelAddr(_) => return 0x1,
+            UxnToken::JmiAddress(_) => return 0x3,
+            UxnToken::JciAddress(_) => return 0x3,
+            UxnToken::JsiAddress(_) => return 0x3,
             UxnToken::PadAbs(_) => panic!(),
             UxnToken::PadRel(_) => panic!(),
         }
     }
 
+    // whether the token refers to (and so opens) a lambda
+    pub fn opens_lambda(&self) -> bool {
+        match self {
+            UxnToken::LitAddressZeroPage(LabelRef::Lambda)
+            | UxnToken::LitAddressRel(LabelRef::Lambda)
+            | UxnToken::LitAddressAbs(LabelRef::Lambda)
+            | UxnToken::RawAbsAddr(LabelRef::Lambda)
+            | UxnToken::RawZeroPageAddr(LabelRef::Lambda)
+            | UxnToken::RawRelAddr(LabelRef::Lambda)
+            | UxnToken::JmiAddress(LabelRef::Lambda)
+            | UxnToken::JciAddress(LabelRef::Lambda)
+            | UxnToken::JsiAddress(LabelRef::Lambda) => return true,
+            _ => return false,
+        }
+    }
+
+    // the label the token refers to, if it refers to one
+    pub fn label_ref_mut(&mut self) -> Option<&mut LabelRef> {
+        match self {
+            UxnToken::LitAddressZeroPage(label_ref)
+            | UxnToken::LitAddressRel(label_ref)
+            | UxnToken::LitAddressAbs(label_ref)
+            | UxnToken::RawAbsAddr(label_ref)
+            | UxnToken::RawZeroPageAddr(label_ref)
+            | UxnToken::RawRelAddr(label_ref)
+            | UxnToken::JmiAddress(label_ref)
+            | UxnToken::JciAddress(label_ref)
+            | UxnToken::JsiAddress(label_ref) => return Some(label_ref),
+            _ => return None,
+        }
+    }
+
     pub fn update_prog_counter(&self, prog_counter: u16) -> u16 {
         match self {
             UxnToken::PadAbs(n) => return *n,
@@ -525,6 +647,64 @@ impl FromStr for UxnToken {
             return Ok(UxnToken::RawAbsAddr(label_ref));
         }
 
+        if &s[0..1] == "-" {
+            if s.len() == 1 {
+                return Err(ParseError::RuneAbsentArg {
+                    rune: "-".to_owned(),
+                });
+            }
+
+            let label_ref = s[1..].parse::<LabelRef>().unwrap();
+
+            return Ok(UxnToken::RawZeroPageAddr(label_ref));
+        }
+
+        if &s[0..1] == "_" {
+            if s.len() == 1 {
+                return Err(ParseError::RuneAbsentArg {
+                    rune: "_".to_owned(),
+                });
+            }
+
+            let label_ref = s[1..].parse::<LabelRef>().unwrap();
+
+            return Ok(UxnToken::RawRelAddr(label_ref));
+        }
+
+        if &s[0..1] == "!" {
+            if s.len() == 1 {
+                return Err(ParseError::RuneAbsentArg {
+                    rune: "!".to_owned(),
+                });
+            }
+
+            let label_ref = s[1..].parse::<LabelRef>().unwrap();
+
+            return Ok(UxnToken::JmiAddress(label_ref));
+        }
+
+        if &s[0..1] == "?" {
+            if s.len() == 1 {
+                return Err(ParseError::RuneAbsentArg {
+                    rune: "?".to_owned(),
+                });
+            }
+
+            let label_ref = s[1..].parse::<LabelRef>().unwrap();
+
+            return Ok(UxnToken::JciAddress(label_ref));
+        }
+
+        if &s[0..1] == "~" {
+            if s.len() == 1 {
+                return Err(ParseError::RuneAbsentArg {
+                    rune: "~".to_owned(),
+                });
+            }
+
+            return Ok(UxnToken::Include((&s[1..]).to_owned()));
+        }
+
         return Ok(UxnToken::MacroInvocation(s.to_owned()));
     }
 }
@@ -560,6 +740,7 @@ mod tests {
                     sub_label_name: "sub_label".to_owned(),
                 },
             ),
+            ("{", LabelRef::Lambda),
         ];
 
         for (input, expected) in inputs.into_iter() {
@@ -579,6 +760,7 @@ mod tests {
             UxnToken::PadRel(0xff),
             UxnToken::LabelDefine("blah".to_owned()),
             UxnToken::SubLabelDefine("blah".to_owned()),
+            UxnToken::Include("blah.tal".to_owned()),
         ];
 
         let labels = HashMap::new();
@@ -684,6 +866,34 @@ mod tests {
                 UxnToken::RawAbsAddr("&sub_label2".parse::<LabelRef>().unwrap()),
                 vec![0x00, 0x76],
             ),
+            (
+                UxnToken::RawZeroPageAddr("test_label_zp".parse::<LabelRef>().unwrap()),
+                vec![0x12],
+            ),
+            (
+                UxnToken::RawZeroPageAddr("&sub_label2".parse::<LabelRef>().unwrap()),
+                vec![0x76],
+            ),
+            (
+                UxnToken::RawRelAddr("test_label_zp".parse::<LabelRef>().unwrap()),
+                vec![0x10],
+            ),
+            (
+                UxnToken::RawRelAddr("test_label_zp/sub_label".parse::<LabelRef>().unwrap()),
+                vec![0x13],
+            ),
+            (
+                UxnToken::JmiAddress("test_label".parse::<LabelRef>().unwrap()),
+                vec![0x40, 0x12, 0x31],
+            ),
+            (
+                UxnToken::JciAddress("&sub_label2".parse::<LabelRef>().unwrap()),
+                vec![0x20, 0x00, 0x73],
+            ),
+            (
+                UxnToken::JsiAddress("test_label2/sub_label".parse::<LabelRef>().unwrap()),
+                vec![0x60, 0x45, 0x73],
+            ),
             (
                 UxnToken::RawWord("hello world".as_bytes().iter().copied().collect()),
                 vec![
@@ -965,6 +1175,76 @@ mod tests {
         );
     }
 
+    // test `get_bytes` function for the raw zero page and relative addresses; that the same range
+    // checks are made as for the literal addresses
+    #[test]
+    fn test_get_bytes_raw_address_failed() {
+        let mut labels = HashMap::new();
+        labels.insert("test_label".to_owned(), Label::new(0x100));
+        labels
+            .get_mut("test_label")
+            .unwrap()
+            .sub_labels
+            .insert("sub_label".to_owned(), 0x00);
+        let mut prog_state = ProgState {
+            counter: 0x0,
+            labels: &labels,
+            current_label: "test_label".to_owned(),
+        };
+
+        let token = UxnToken::RawZeroPageAddr("test_label".parse::<LabelRef>().unwrap());
+        assert_eq!(
+            token.get_bytes(&prog_state),
+            Err(GetBytesError::LabelNotInZeroPage {
+                label_name: "test_label".to_owned()
+            })
+        );
+
+        // 0x7f bytes on from two bytes after the address is in range, but a byte further isn't
+        prog_state.counter = 0x100 - 0x7f - 2;
+        let token = UxnToken::RawRelAddr("test_label".parse::<LabelRef>().unwrap());
+        assert_eq!(token.get_bytes(&prog_state), Ok(vec![0x7f]));
+
+        prog_state.counter -= 1;
+        assert_eq!(
+            token.get_bytes(&prog_state),
+            Err(GetBytesError::RelLabelNotInRange {
+                label_name: "test_label".to_owned()
+            })
+        );
+
+        // likewise 0x80 bytes back is in range, but a byte further isn't
+        prog_state.counter = 0x80 - 2;
+        let token = UxnToken::RawRelAddr("&sub_label".parse::<LabelRef>().unwrap());
+        assert_eq!(token.get_bytes(&prog_state), Ok(vec![0x80]));
+
+        prog_state.counter += 1;
+        assert_eq!(
+            token.get_bytes(&prog_state),
+            Err(GetBytesError::RelSubLabelNotInRange {
+                label_name: "test_label".to_owned(),
+                sub_label_name: "sub_label".to_owned()
+            })
+        );
+    }
+
+    // test `get_bytes` function for the immediate jumps; that a jump back to an earlier label
+    // gives the offset wrapped around to a short
+    #[test]
+    fn test_get_bytes_immediate_jump_backwards() {
+        let mut labels = HashMap::new();
+        labels.insert("test_label".to_owned(), Label::new(0x0100));
+        let prog_state = ProgState {
+            counter: 0x0200,
+            labels: &labels,
+            current_label: "".to_owned(),
+        };
+
+        let token = UxnToken::JmiAddress("test_label".parse::<LabelRef>().unwrap());
+        let returned = token.get_bytes(&prog_state);
+        assert_eq!(returned, Ok(vec![0x40, 0xfe, 0xfd]));
+    }
+
     // test from_str for UxnToken with an input that should be parsed as a raw byte
     #[test]
     fn test_from_str_raw_byte() {
@@ -1109,11 +1389,45 @@ mod tests {
         assert_eq!(output, Ok(expected));
     }
 
+    // test from_str for UxnToken with inputs that should be parsed as raw zero page and relative
+    // addresses
+    #[test]
+    fn test_from_str_raw_zero_page_rel_address() {
+        let output = "-test_label".parse::<UxnToken>();
+        let expected = UxnToken::RawZeroPageAddr("test_label".parse::<LabelRef>().unwrap());
+        assert_eq!(output, Ok(expected));
+
+        let output = "_&test_sub_label".parse::<UxnToken>();
+        let expected = UxnToken::RawRelAddr("&test_sub_label".parse::<LabelRef>().unwrap());
+        assert_eq!(output, Ok(expected));
+    }
+
+    // test from_str for UxnToken with inputs that should be parsed as immediate jumps
+    #[test]
+    fn test_from_str_immediate_jumps() {
+        let output = "!test_label".parse::<UxnToken>();
+        let expected = UxnToken::JmiAddress("test_label".parse::<LabelRef>().unwrap());
+        assert_eq!(output, Ok(expected));
+
+        let output = "?&test_sub_label".parse::<UxnToken>();
+        let expected = UxnToken::JciAddress("&test_sub_label".parse::<LabelRef>().unwrap());
+        assert_eq!(output, Ok(expected));
+    }
+
+    // test from_str for UxnToken with an input that should be parsed as an include
+    #[test]
+    fn test_from_str_include() {
+        let input = "~lib/devices.tal";
+        let output = input.parse::<UxnToken>();
+        let expected = UxnToken::Include("lib/devices.tal".to_owned());
+        assert_eq!(output, Ok(expected));
+    }
+
     // test from_str for UxnToken with an input that should trigger an error because a rune has no
     // argument attached
     #[test]
     fn test_from_str_rune_absent() {
-        let inputs = ["|", "$", "#", "'", "@", ":", ".", ",", "\""];
+        let inputs = ["|", "$", "#", "'", "@", ":", ".", ",", "\"", "~", "!", "?", "-", "_"];
 
         for input in inputs {
             let output = input.parse::<UxnToken>();
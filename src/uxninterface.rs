@@ -1,10 +1,12 @@
 use std::fmt;
 use std::error::Error;
+use std::io;
 
 #[derive(Debug, PartialEq)]
 pub enum UxnStatus {
     Halt,
     Terminate,
+    BudgetExhausted,
 }
 
 #[derive(Debug, PartialEq)]
@@ -12,7 +14,9 @@ pub enum UxnError {
     OutOfRangeMemoryAddress,
     StackUnderflow,
     StackOverflow,
+    DivisionByZero,
     UnrecognisedDevice,
+    DeviceError(DeviceError),
 }
 
 impl fmt::Display for UxnError {
@@ -27,15 +31,111 @@ impl fmt::Display for UxnError {
             UxnError::StackOverflow => {
                 write!(f, "stack overflow encountered")
             },
+            UxnError::DivisionByZero => {
+                write!(f, "division by zero encountered")
+            },
             UxnError::UnrecognisedDevice => {
                 write!(f, "unrecognised device targeted for read/write")
             },
+            UxnError::DeviceError(err) => {
+                write!(f, "device error: {}", err)
+            },
         }
     }
 }
 
 impl Error for UxnError {}
 
+// an error raised by a device while it handles a read or write from the program, for example
+// because the program gave it an address that runs off the end of memory. The error is passed to
+// the uxn, which treats it as a fault of the instruction that made the access
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum DeviceError {
+    PortOutOfRange(u8),
+    OutOfRangeMemoryAddress,
+    InvalidFileName,
+    Io(io::ErrorKind),
+    // a read from a device that is being replayed from a recorded session didn't match the
+    // recording
+    ReplayMismatch,
+}
+
+impl fmt::Display for DeviceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeviceError::PortOutOfRange(port) => {
+                write!(f, "attempt to access port 0x{:x}, which is out of range", port)
+            },
+            DeviceError::OutOfRangeMemoryAddress => {
+                write!(f, "attempt to access out of range memory address")
+            },
+            DeviceError::InvalidFileName => {
+                write!(f, "file name is not valid utf8 or is not terminated")
+            },
+            DeviceError::Io(kind) => {
+                write!(f, "I/O error: {}", kind)
+            },
+            DeviceError::ReplayMismatch => {
+                write!(f, "read does not match the recorded session")
+            },
+        }
+    }
+}
+
+impl Error for DeviceError {}
+
+impl From<io::Error> for DeviceError {
+    fn from(err: io::Error) -> Self {
+        return DeviceError::Io(err.kind());
+    }
+}
+
+impl From<DeviceError> for UxnError {
+    fn from(err: DeviceError) -> Self {
+        return UxnError::DeviceError(err);
+    }
+}
+
+// a fault that stopped the uxn, because no handler was installed at the system vector to deal
+// with it. Holds enough of the state of the machine at the time of the fault to produce a crash
+// report
+#[derive(Debug, PartialEq)]
+pub struct UxnCrash {
+    pub error: UxnError,
+    pub address: u16,
+    pub instruction: u8,
+    pub working_stack: Vec<u8>,
+    pub return_stack: Vec<u8>,
+}
+
+fn write_stack(f: &mut fmt::Formatter<'_>, name: &str, stack: &[u8]) -> fmt::Result {
+    write!(f, "  <{}>", name)?;
+    for byte in stack {
+        write!(f, " {:02x}", byte)?;
+    }
+    return Ok(());
+}
+
+impl fmt::Display for UxnCrash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "uxn crashed: {}", self.error)?;
+        writeln!(f, "  pc:     0x{:04x}", self.address)?;
+        writeln!(f, "  opcode: 0x{:02x} ({})", self.instruction,
+            crate::ops::OpObject::from_byte(self.instruction))?;
+        write_stack(f, "wst", &self.working_stack)?;
+        writeln!(f)?;
+        return write_stack(f, "rst", &self.return_stack);
+    }
+}
+
+impl Error for UxnCrash {}
+
+impl From<UxnCrash> for UxnError {
+    fn from(crash: UxnCrash) -> Self {
+        return crash.error;
+    }
+}
+
 
 pub trait Uxn {
     fn read_next_byte_from_ram(&mut self) -> Result<u8, UxnError>;
@@ -51,5 +151,28 @@ pub trait Uxn {
 
 pub trait UxnWithDevices : Uxn {
     fn read_from_device(&mut self, device_address: u8) -> Result<u8, UxnError>;
-    fn write_to_device(&mut self, device_address: u8, val: u8);
+    fn write_to_device(&mut self, device_address: u8, val: u8) -> Result<(), UxnError>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crash_report() {
+        let crash = UxnCrash{
+            error: UxnError::StackUnderflow,
+            address: 0x0123,
+            instruction: 0xa2,
+            working_stack: vec![0x01, 0xab],
+            return_stack: vec![],
+        };
+
+        assert_eq!(crash.to_string(), concat!(
+            "uxn crashed: stack underflow encountered\n",
+            "  pc:     0x0123\n",
+            "  opcode: 0xa2 (POP2k)\n",
+            "  <wst> 01 ab\n",
+            "  <rst>"));
+    }
 }
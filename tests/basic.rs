@@ -20,7 +20,10 @@ fn push_and_debug() {
 
     fs::write(&tmp_file_path, &prog).expect("Failed to write test program");
 
-    let cli_options = uxnclilib::Cli{rom: tmp_file_path, input: Vec::new()};
+    let cli_options = uxnclilib::Cli{rom: tmp_file_path, input: Vec::new(), max_steps: None,
+        load_state: None, save_state_on_exit: None, stack_faults: uxnclilib::StackFaultPolicy::Error,
+        strict_devices: uxnclilib::DeviceAccessPolicy::Ignore,
+        trace: None, symbols: None, record: None, replay: None, gdb: None};
     let mut stdout_output = Vec::new();
     let stdin_input = Cursor::new("");
     let mut stderr_output = Vec::new();
@@ -131,7 +134,10 @@ fn console_test() {
 
     fs::write(&tmp_file_path, &prog).expect("Failed to write test program");
 
-    let cli_options = uxnclilib::Cli{rom: tmp_file_path, input: vec!{"first".to_string()}};
+    let cli_options = uxnclilib::Cli{rom: tmp_file_path, input: vec!{"first".to_string()}, max_steps: None,
+        load_state: None, save_state_on_exit: None, stack_faults: uxnclilib::StackFaultPolicy::Error,
+        strict_devices: uxnclilib::DeviceAccessPolicy::Ignore,
+        trace: None, symbols: None, record: None, replay: None, gdb: None};
     let mut stdout_output = Vec::new();
     let stdin_input = Cursor::new(" secondq");
     let mut stderr_output = Vec::new();
@@ -150,6 +156,144 @@ fn console_test() {
     assert_eq!(String::from_utf8(stderr_output).unwrap(), "Error test");
 }
 
+// run the console program from `console_test` until part of the name has been entered, save the
+// machine's state on exit, and then restore that state in a new run and finish entering the name
+#[test]
+fn save_and_load_state_test() {
+    // see `console_test` for the assembly of this program
+    let prog = vec![
+        0xa0, 0x01, 0x0c, 0x80, 0x10, 0x37, 0x80, 0x00, 0x80,
+        0x00, 0x11, 0x00, 0x80, 0x12, 0x16, 0x06, 0x80, 0x71,
+        0x08, 0xa0, 0x01, 0x26, 0x2d, 0x80, 0x01, 0x80, 0x00,
+        0x10, 0x18, 0x11, 0x80, 0x00, 0x10, 0x01, 0x80, 0x00,
+        0x11, 0x00, 0x02, 0x80, 0x00, 0x80, 0x3a, 0x13, 0xa0,
+        0x01, 0x67, 0x94, 0x80, 0x18, 0x17, 0x21, 0x94, 0x80,
+        0xf7, 0x0d, 0x22, 0x80, 0x01, 0x80, 0x28, 0x12, 0x18,
+        0x10, 0x80, 0x18, 0x17, 0x80, 0x20, 0x12, 0x01, 0x80,
+        0x1c, 0x13, 0x80, 0x19, 0x12, 0x80, 0x00, 0x10, 0x09,
+        0x80, 0xe5, 0x0d, 0xa0, 0x01, 0x6f, 0x94, 0x80, 0x19,
+        0x17, 0x21, 0x94, 0x80, 0xf7, 0x0d, 0x22, 0xa0, 0x01,
+        0x0f, 0x17, 0x00, 0x00, 0x48, 0x65, 0x6c, 0x6c, 0x6f,
+        0x2c, 0x20, 0x00, 0x45, 0x72, 0x72, 0x6f, 0x72, 0x20,
+        0x74, 0x65, 0x73, 0x74,];
+
+    let tmp_file_name = format!("save_and_load_state_test{}", Uuid::new_v4());
+    let mut tmp_file_path = std::env::temp_dir();
+    tmp_file_path.push(tmp_file_name);
+
+    fs::write(&tmp_file_path, &prog).expect("Failed to write test program");
+
+    let mut state_file_path = tmp_file_path.clone();
+    state_file_path.set_extension("state");
+
+    let cli_options = uxnclilib::Cli{rom: tmp_file_path.clone(), input: vec!{"first".to_string()}, max_steps: None,
+        load_state: None, save_state_on_exit: Some(state_file_path.clone()),
+        stack_faults: uxnclilib::StackFaultPolicy::Error,
+        strict_devices: uxnclilib::DeviceAccessPolicy::Ignore,
+        trace: None, symbols: None, record: None, replay: None, gdb: None};
+    let mut stdout_output = Vec::new();
+    let stdin_input = Cursor::new("");
+    let mut stderr_output = Vec::new();
+    let mut debug_output = Vec::new();
+    let config = uxnclilib::Config{
+        stdout_writer: &mut stdout_output,
+        stdin_reader: stdin_input,
+        stderr_writer: &mut stderr_output,
+        debug_writer: &mut debug_output};
+
+    uxnclilib::run(cli_options, config).expect("Failed to execute test program");
+    assert_eq!(String::from_utf8(stdout_output).unwrap(), "");
+
+    let cli_options = uxnclilib::Cli{rom: tmp_file_path, input: Vec::new(), max_steps: None,
+        load_state: Some(state_file_path), save_state_on_exit: None,
+        stack_faults: uxnclilib::StackFaultPolicy::Error,
+        strict_devices: uxnclilib::DeviceAccessPolicy::Ignore,
+        trace: None, symbols: None, record: None, replay: None, gdb: None};
+    let mut stdout_output = Vec::new();
+    let stdin_input = Cursor::new(" secondq");
+    let mut stderr_output = Vec::new();
+    let mut debug_output = Vec::new();
+    let config = uxnclilib::Config{
+        stdout_writer: &mut stdout_output,
+        stdin_reader: stdin_input,
+        stderr_writer: &mut stderr_output,
+        debug_writer: &mut debug_output};
+
+    uxnclilib::run(cli_options, config).expect("Failed to execute test program");
+
+    // the name entered before the state was saved should have been restored
+    assert_eq!(String::from_utf8(stdout_output).unwrap(), "Hello, first\n second");
+    assert_eq!(String::from_utf8(stderr_output).unwrap(), "Error test");
+}
+
+// run the console program from `console_test` while recording its input, and then replay the
+// recording with no input given, which should give the same output
+#[test]
+fn record_and_replay_test() {
+    // see `console_test` for the assembly of this program
+    let prog = vec![
+        0xa0, 0x01, 0x0c, 0x80, 0x10, 0x37, 0x80, 0x00, 0x80,
+        0x00, 0x11, 0x00, 0x80, 0x12, 0x16, 0x06, 0x80, 0x71,
+        0x08, 0xa0, 0x01, 0x26, 0x2d, 0x80, 0x01, 0x80, 0x00,
+        0x10, 0x18, 0x11, 0x80, 0x00, 0x10, 0x01, 0x80, 0x00,
+        0x11, 0x00, 0x02, 0x80, 0x00, 0x80, 0x3a, 0x13, 0xa0,
+        0x01, 0x67, 0x94, 0x80, 0x18, 0x17, 0x21, 0x94, 0x80,
+        0xf7, 0x0d, 0x22, 0x80, 0x01, 0x80, 0x28, 0x12, 0x18,
+        0x10, 0x80, 0x18, 0x17, 0x80, 0x20, 0x12, 0x01, 0x80,
+        0x1c, 0x13, 0x80, 0x19, 0x12, 0x80, 0x00, 0x10, 0x09,
+        0x80, 0xe5, 0x0d, 0xa0, 0x01, 0x6f, 0x94, 0x80, 0x19,
+        0x17, 0x21, 0x94, 0x80, 0xf7, 0x0d, 0x22, 0xa0, 0x01,
+        0x0f, 0x17, 0x00, 0x00, 0x48, 0x65, 0x6c, 0x6c, 0x6f,
+        0x2c, 0x20, 0x00, 0x45, 0x72, 0x72, 0x6f, 0x72, 0x20,
+        0x74, 0x65, 0x73, 0x74,];
+
+    let tmp_file_name = format!("record_and_replay_test{}", Uuid::new_v4());
+    let mut tmp_file_path = std::env::temp_dir();
+    tmp_file_path.push(tmp_file_name);
+
+    fs::write(&tmp_file_path, &prog).expect("Failed to write test program");
+
+    let mut log_file_path = tmp_file_path.clone();
+    log_file_path.set_extension("log");
+
+    let cli_options = uxnclilib::Cli{rom: tmp_file_path.clone(), input: vec!{"first".to_string()}, max_steps: None,
+        load_state: None, save_state_on_exit: None, stack_faults: uxnclilib::StackFaultPolicy::Error,
+        strict_devices: uxnclilib::DeviceAccessPolicy::Ignore,
+        trace: None, symbols: None, record: Some(log_file_path.clone()), replay: None, gdb: None};
+    let mut stdout_output = Vec::new();
+    let stdin_input = Cursor::new(" secondq");
+    let mut stderr_output = Vec::new();
+    let mut debug_output = Vec::new();
+    let config = uxnclilib::Config{
+        stdout_writer: &mut stdout_output,
+        stdin_reader: stdin_input,
+        stderr_writer: &mut stderr_output,
+        debug_writer: &mut debug_output};
+
+    uxnclilib::run(cli_options, config).expect("Failed to execute test program");
+    assert_eq!(String::from_utf8(stdout_output).unwrap(), "Hello, first\n second");
+
+    let cli_options = uxnclilib::Cli{rom: tmp_file_path, input: Vec::new(), max_steps: None,
+        load_state: None, save_state_on_exit: None, stack_faults: uxnclilib::StackFaultPolicy::Error,
+        strict_devices: uxnclilib::DeviceAccessPolicy::Ignore,
+        trace: None, symbols: None, record: None, replay: Some(log_file_path), gdb: None};
+    let mut stdout_output = Vec::new();
+    let stdin_input = Cursor::new("");
+    let mut stderr_output = Vec::new();
+    let mut debug_output = Vec::new();
+    let config = uxnclilib::Config{
+        stdout_writer: &mut stdout_output,
+        stdin_reader: stdin_input,
+        stderr_writer: &mut stderr_output,
+        debug_writer: &mut debug_output};
+
+    uxnclilib::run(cli_options, config).expect("Failed to execute test program");
+
+    // the input should all have come from the recording
+    assert_eq!(String::from_utf8(stdout_output).unwrap(), "Hello, first\n second");
+    assert_eq!(String::from_utf8(stderr_output).unwrap(), "Error test");
+}
+
 // test datetime device, printing out datetime and ensuring it is correct
 #[test]
 fn datetime_test() {
@@ -252,7 +396,10 @@ fn datetime_test() {
 
     fs::write(&tmp_file_path, &prog).expect("Failed to write test program");
 
-    let cli_options = uxnclilib::Cli{rom: tmp_file_path, input: Vec::new()};
+    let cli_options = uxnclilib::Cli{rom: tmp_file_path, input: Vec::new(), max_steps: None,
+        load_state: None, save_state_on_exit: None, stack_faults: uxnclilib::StackFaultPolicy::Error,
+        strict_devices: uxnclilib::DeviceAccessPolicy::Ignore,
+        trace: None, symbols: None, record: None, replay: None, gdb: None};
     let mut stdout_output = Vec::new();
     let stdin_input = Cursor::new("");
     let mut stderr_output = Vec::new();
@@ -269,3 +416,317 @@ fn datetime_test() {
     
     assert_eq!(String::from_utf8(stdout_output).unwrap(), expected);
 }
+
+// run a program that never completes with a limit on the number of steps, and
+// ensure it is stopped with an error rather than hanging
+#[test]
+fn max_steps_test() {
+    // this is the machine code for the following assembly:
+    // |100 &loop ,&loop JMP
+    let prog = vec![0x80, 0xfd, 0x0c];
+
+    let tmp_file_name = format!("max_steps_test{}", Uuid::new_v4());
+    let mut tmp_file_path = std::env::temp_dir();
+    tmp_file_path.push(tmp_file_name);
+
+    fs::write(&tmp_file_path, &prog).expect("Failed to write test program");
+
+    let cli_options = uxnclilib::Cli{rom: tmp_file_path, input: Vec::new(), max_steps: Some(1000),
+        load_state: None, save_state_on_exit: None, stack_faults: uxnclilib::StackFaultPolicy::Error,
+        strict_devices: uxnclilib::DeviceAccessPolicy::Ignore,
+        trace: None, symbols: None, record: None, replay: None, gdb: None};
+    let mut stdout_output = Vec::new();
+    let stdin_input = Cursor::new("");
+    let mut stderr_output = Vec::new();
+    let mut debug_output = Vec::new();
+    let config = uxnclilib::Config{
+        stdout_writer: &mut stdout_output,
+        stdin_reader: stdin_input,
+        stderr_writer: &mut stderr_output,
+        debug_writer: &mut debug_output};
+
+    let err = uxnclilib::run(cli_options, config).expect_err("Program should not have completed");
+    assert_eq!(err.to_string(), "vector 0x0100 did not complete within 1000 instructions");
+}
+
+#[test]
+fn crash_report_test() {
+    // this is the machine code for the following assembly:
+    // |100 #12 POP POP BRK
+    let prog = vec![0x80, 0x12, 0x02, 0x02, 0x00];
+
+    let tmp_file_name = format!("crash_report_test{}", Uuid::new_v4());
+    let mut tmp_file_path = std::env::temp_dir();
+    tmp_file_path.push(tmp_file_name);
+
+    fs::write(&tmp_file_path, &prog).expect("Failed to write test program");
+
+    let cli_options = uxnclilib::Cli{rom: tmp_file_path, input: Vec::new(), max_steps: None,
+        load_state: None, save_state_on_exit: None, stack_faults: uxnclilib::StackFaultPolicy::Error,
+        strict_devices: uxnclilib::DeviceAccessPolicy::Ignore,
+        trace: None, symbols: None, record: None, replay: None, gdb: None};
+    let mut stdout_output = Vec::new();
+    let stdin_input = Cursor::new("");
+    let mut stderr_output = Vec::new();
+    let mut debug_output = Vec::new();
+    let config = uxnclilib::Config{
+        stdout_writer: &mut stdout_output,
+        stdin_reader: stdin_input,
+        stderr_writer: &mut stderr_output,
+        debug_writer: &mut debug_output};
+
+    let err = uxnclilib::run(cli_options, config).expect_err("Program should have crashed");
+    assert_eq!(err.to_string(), "uxn crashed: stack underflow encountered\n  pc:     0x0103\n  opcode: 0x02 (POP)\n  <wst>\n  <rst>");
+}
+
+#[test]
+fn rom_too_large_test() {
+    // the largest rom fills all 16 banks of memory, less the page before the initial vector
+    let prog = vec![0x00; 0x10 * 0x10000 - 0x100 + 1];
+
+    let tmp_file_name = format!("rom_too_large_test{}", Uuid::new_v4());
+    let mut tmp_file_path = std::env::temp_dir();
+    tmp_file_path.push(tmp_file_name);
+
+    fs::write(&tmp_file_path, &prog).expect("Failed to write test program");
+
+    let cli_options = uxnclilib::Cli{rom: tmp_file_path.clone(), input: Vec::new(), max_steps: None,
+        load_state: None, save_state_on_exit: None, stack_faults: uxnclilib::StackFaultPolicy::Error,
+        strict_devices: uxnclilib::DeviceAccessPolicy::Ignore,
+        trace: None, symbols: None, record: None, replay: None, gdb: None};
+    let config = uxnclilib::Config{
+        stdout_writer: Vec::new(),
+        stdin_reader: Cursor::new(""),
+        stderr_writer: Vec::new(),
+        debug_writer: Vec::new()};
+
+    let err = uxnclilib::run(cli_options, config).expect_err("Rom should have been rejected");
+    assert_eq!(err.to_string(), format!(
+        "ROM {} is too large: 1048321 bytes (the maximum is 1048320 bytes)", tmp_file_path.display()));
+}
+
+#[test]
+fn trace_test() {
+    // this is the machine code for the following assembly:
+    // |100 #1234 #01 @add ADD BRK
+    let prog = vec![0xa0, 0x12, 0x34, 0x80, 0x01, 0x18, 0x00];
+
+    let tmp_file_name = format!("trace_test{}", Uuid::new_v4());
+    let mut tmp_file_path = std::env::temp_dir();
+    tmp_file_path.push(tmp_file_name);
+
+    fs::write(&tmp_file_path, &prog).expect("Failed to write test program");
+
+    // the symbol file, giving the address of `add`
+    let mut symbols_file_path = tmp_file_path.clone().into_os_string();
+    symbols_file_path.push(".sym");
+    fs::write(&symbols_file_path, [0x01, 0x05, b'a', b'd', b'd', 0x00])
+        .expect("Failed to write symbol file");
+
+    let trace_file_name = format!("trace_test_log{}", Uuid::new_v4());
+    let mut trace_file_path = std::env::temp_dir();
+    trace_file_path.push(trace_file_name);
+
+    let cli_options = uxnclilib::Cli{rom: tmp_file_path, input: Vec::new(), max_steps: None,
+        load_state: None, save_state_on_exit: None, stack_faults: uxnclilib::StackFaultPolicy::Error,
+        strict_devices: uxnclilib::DeviceAccessPolicy::Ignore,
+        trace: Some(trace_file_path.clone()), symbols: None, record: None, replay: None, gdb: None};
+    let config = uxnclilib::Config{
+        stdout_writer: Vec::new(),
+        stdin_reader: Cursor::new(""),
+        stderr_writer: Vec::new(),
+        debug_writer: Vec::new()};
+
+    uxnclilib::run(cli_options, config).expect("Program should have completed");
+
+    let trace = fs::read_to_string(&trace_file_path).expect("Failed to read trace");
+    assert_eq!(trace, "0100 LIT2    <wst> <rst>\n\
+        0103 LIT     <wst> 12 34 <rst>\n\
+        @add\n\
+        0105 ADD     <wst> 12 34 01 <rst>\n");
+}
+
+#[test]
+fn machine_test() {
+    use rusty_uxn::emulators::machine::{MachineBuilder, MachineBuildError, Device, DeviceError,
+        MainRamInterface, UxnStatus, INIT_VECTOR, SYSTEM_DEVICE_SLOT};
+    use std::rc::Rc;
+    use std::cell::RefCell;
+
+    struct MockDevice {
+        writes: Vec<(u8, u8)>,
+    }
+
+    impl Device for MockDevice {
+        fn write(&mut self, port: u8, val: u8, _main_ram: &mut dyn MainRamInterface) -> Result<(), DeviceError> {
+            self.writes.push((port, val));
+            return Ok(());
+        }
+
+        fn read(&mut self, port: u8) -> Result<u8, DeviceError> {
+            return Ok(0x50 + port);
+        }
+    }
+
+    // this is the machine code for the following assembly:
+    // |100 INC #0300 STA #99 #a0 DEO #a1 DEI BRK
+    let prog = vec![0x01, 0xa0, 0x03, 0x00, 0x15, 0x80, 0x99, 0x80, 0xa0, 0x17, 0x80, 0xa1, 0x16, 0x00];
+
+    let device = Rc::new(RefCell::new(MockDevice{writes: Vec::new()}));
+    let mut machine = MachineBuilder::new()
+        .rom(prog)
+        .device(0xa, Box::new(Rc::clone(&device)))
+        .build()
+        .expect("Failed to build machine");
+
+    // the program increments a value left on the stack by the host and stores it in ram
+    machine.push_to_working_stack(0x07).unwrap();
+    assert_eq!(machine.run_vector(INIT_VECTOR), Ok(UxnStatus::Halt));
+
+    assert_eq!(machine.read_ram(0x300), 0x08);
+    assert_eq!(device.borrow().writes, vec![(0x0, 0x99)]);
+    assert_eq!(machine.working_stack(), &[0x51]);
+    assert_eq!(machine.pop_from_working_stack(), Ok(0x51));
+
+    // the host can also modify the program
+    machine.write_ram(0x101, 0x80);
+    assert_eq!(machine.run_vector(0x101), Ok(UxnStatus::Halt));
+    assert_eq!(machine.working_stack(), &[0x03]);
+
+    // stepping back undoes only what the program did, not what the host changed
+    let mut machine = MachineBuilder::new()
+        .rom(vec![0x01, 0xa0, 0x03, 0x00, 0x15, 0x00])
+        .history_capacity(10)
+        .build()
+        .expect("Failed to build machine");
+    machine.push_to_working_stack(0x07).unwrap();
+    assert_eq!(machine.run_vector(INIT_VECTOR), Ok(UxnStatus::Halt));
+    machine.write_ram(0x301, 0xaa);
+    machine.push_to_return_stack(0xbb).unwrap();
+
+    assert_eq!(machine.step_back(1), 1);
+    assert_eq!(machine.read_ram(0x300), 0x00);
+    assert_eq!(machine.read_ram(0x301), 0xaa);
+    assert_eq!(machine.working_stack(), &[0x08, 0x03, 0x00]);
+    // the stack pointers go back to where they were before the instruction
+    assert!(machine.return_stack().is_empty());
+
+    assert_eq!(machine.step_back(2), 2);
+    assert_eq!(machine.working_stack(), &[0x07]);
+    assert_eq!(machine.read_ram(0x301), 0xaa);
+
+    let err = MachineBuilder::new()
+        .device(SYSTEM_DEVICE_SLOT, Box::new(MockDevice{writes: Vec::new()}))
+        .build()
+        .err();
+    assert_eq!(err, Some(MachineBuildError::SystemSlot));
+
+    let err = MachineBuilder::new()
+        .rom(vec![0x00; 0x10 * 0x10000])
+        .build()
+        .err();
+    assert_eq!(err, Some(MachineBuildError::RomTooLarge{size: 0x100000, max_size: 0xfff00}));
+}
+
+// drive the debugger with a script of commands, setting a breakpoint by label, stepping over a
+// subroutine call and modifying the program's memory
+#[test]
+fn debugger_test() {
+    use rusty_uxn::emulators::uxndbglib;
+
+    // this is the machine code for the following assembly:
+    // |100 #12 ;sub JSR2 ;sub JSR2 #34 BRK
+    // @sub #56 JMP2r
+    let prog = vec![0x80, 0x12, 0xa0, 0x01, 0x0d, 0x2e, 0xa0, 0x01, 0x0d, 0x2e,
+        0x80, 0x34, 0x00, 0x80, 0x56, 0x6c];
+
+    let tmp_file_name = format!("debugger_test{}", Uuid::new_v4());
+    let mut tmp_file_path = std::env::temp_dir();
+    tmp_file_path.push(tmp_file_name);
+
+    fs::write(&tmp_file_path, &prog).expect("Failed to write test program");
+
+    // the symbol file, giving the address of `sub`
+    let mut symbols_file_path = tmp_file_path.clone().into_os_string();
+    symbols_file_path.push(".sym");
+    fs::write(&symbols_file_path, [0x01, 0x0d, b's', b'u', b'b', 0x00])
+        .expect("Failed to write symbol file");
+
+    let cli_options = uxndbglib::Cli{rom: tmp_file_path, input: Vec::new(), symbols: None,
+        stack_faults: uxndbglib::StackFaultPolicy::Error,
+        strict_devices: uxndbglib::DeviceAccessPolicy::Ignore};
+    let commands = "b sub\ns 2\nn\nst\nd sub\ns 3\nn\nst\npoke 010b ff\ns\nst\nl 0109 2\nc\nq\n";
+    let mut output = Vec::new();
+    let config = uxndbglib::Config{
+        stdout_writer: Vec::new(),
+        command_reader: Cursor::new(commands),
+        stderr_writer: Vec::new(),
+        debug_writer: Vec::new(),
+        output_writer: &mut output};
+
+    uxndbglib::run(cli_options, config).expect("Failed to run debugger");
+
+    // the first call to `sub` stops at its breakpoint, and the second is stepped over
+    assert_eq!(String::from_utf8(output).unwrap(), "\
+        => 0100  80 12     LIT 12\n\
+        (uxndbg) breakpoint set at 010d\n\
+        (uxndbg) => 0105  2e        JSR2\n\
+        (uxndbg) breakpoint at 010d\n\
+        @sub\n\
+        =>*010d  80 56     LIT 56\n\
+        (uxndbg) <wst> 12\n\
+        <rst> 01 06\n\
+        (uxndbg) (uxndbg) => 0109  2e        JSR2\n\
+        (uxndbg) => 010a  80 34     LIT 34\n\
+        (uxndbg) <wst> 12 56 56\n\
+        <rst>\n\
+        (uxndbg) (uxndbg) => 010c  00        BRK\n\
+        (uxndbg) <wst> 12 56 56 ff\n\
+        <rst>\n\
+        (uxndbg)    0109  2e        JSR2\n   010a  80 ff     LIT ff\n\
+        (uxndbg) the program is waiting for console input (give it some with `input`)\n\
+        (uxndbg) ");
+}
+
+// the debugger stops with an error, rather than panicking, when its output can't be written, as
+// happens when it is piped into a program that exits early
+#[test]
+fn debugger_closed_output_test() {
+    use rusty_uxn::emulators::uxndbglib;
+    use std::io::{self, Write};
+
+    struct ClosedWriter {}
+
+    impl Write for ClosedWriter {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            return Err(io::Error::from(io::ErrorKind::BrokenPipe));
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            return Ok(());
+        }
+    }
+
+    // this is the machine code for the following assembly:
+    // |100 #12 BRK
+    let prog = vec![0x80, 0x12, 0x00];
+
+    let tmp_file_name = format!("debugger_closed_output_test{}", Uuid::new_v4());
+    let mut tmp_file_path = std::env::temp_dir();
+    tmp_file_path.push(tmp_file_name);
+
+    fs::write(&tmp_file_path, &prog).expect("Failed to write test program");
+
+    let cli_options = uxndbglib::Cli{rom: tmp_file_path, input: Vec::new(), symbols: None,
+        stack_faults: uxndbglib::StackFaultPolicy::Error,
+        strict_devices: uxndbglib::DeviceAccessPolicy::Ignore};
+    let config = uxndbglib::Config{
+        stdout_writer: Vec::new(),
+        command_reader: Cursor::new("s\nq\n"),
+        stderr_writer: Vec::new(),
+        debug_writer: Vec::new(),
+        output_writer: ClosedWriter{}};
+
+    let err = uxndbglib::run(cli_options, config).expect_err("Debugger ignored the closed output");
+    assert_eq!(err.downcast::<io::Error>().unwrap().kind(), io::ErrorKind::BrokenPipe);
+}